@@ -0,0 +1,182 @@
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::rc::Rc;
+
+/// Accumulates fields that may be narrower than a byte
+struct BitWriter {
+    msb_first: bool,
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+
+impl BitWriter {
+    fn new(msb_first: bool) -> BitWriter {
+        BitWriter {
+            msb_first,
+            bytes: Vec::with_capacity(8),
+            bit_len: 0,
+        }
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        let bit_idx = self.bit_len % 8;
+        if bit_idx == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            let shift = if self.msb_first { 7 - bit_idx } else { bit_idx };
+            *self.bytes.last_mut().unwrap() |= 1 << shift;
+        }
+        self.bit_len += 1;
+    }
+
+    fn push_field(&mut self, width: u64, value: u64) -> Result<(), Error> {
+        if width == 0 || width > 64 {
+            bail!(
+                "Invalid bit field width: {}, must be between 1 and 64",
+                width
+            );
+        }
+        if width < 64 && value >= (1 << width) {
+            bail!(
+                "The value {} does not fit in a bit field of width {}",
+                value,
+                width
+            );
+        }
+        if self.msb_first {
+            for i in (0..width).rev() {
+                self.push_bit(value & (1 << i) != 0);
+            }
+        } else {
+            for i in 0..width {
+                self.push_bit(value & (1 << i) != 0);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<u8>, Error> {
+        if self.bit_len % 8 != 0 {
+            bail!(
+                "The bit field widths add up to {} bits, which is not a multiple of 8",
+                self.bit_len
+            );
+        }
+        Ok(self.bytes)
+    }
+}
+
+#[derive(Debug)]
+struct Bits {
+    msb_first: bool,
+    fields: Vec<(DynUintFun, DynUintFun)>,
+}
+
+impl RunnableFunction<Vec<u8>> for Bits {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let mut writer = BitWriter::new(self.msb_first);
+        for (width, value) in self.fields.iter() {
+            let width = width.gen_value(ctx)?;
+            let value = value.gen_value(ctx)?;
+            writer.push_field(width, value)?;
+        }
+        writer.finish()
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(&bytes)
+    }
+}
+
+const WIDTH_PARAM: &str = "width";
+const VALUE_PARAM: &str = "value";
+
+fn create_bits(msb_first: bool, args: Arguments) -> CreateFunctionResult {
+    let args = args.get_required_varargs(VALUE_PARAM, 0, AnyFunction::require_uint)?;
+    if args.len() % 2 != 0 {
+        bail!(
+            "The bits function requires pairs of (width, value) arguments, but got {} arguments",
+            args.len()
+        );
+    }
+    let fields = args
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    Ok(AnyFunction::Bin(Rc::new(Bits { msb_first, fields })))
+}
+
+fn create_bits_msb(args: Arguments) -> CreateFunctionResult {
+    create_bits(true, args)
+}
+
+fn create_bits_lsb(args: Arguments) -> CreateFunctionResult {
+    create_bits(false, args)
+}
+
+pub const BITS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "bits",
+    description: "packs pairs of (width, value) arguments into binary, starting from the most significant bit of each byte. The widths must add up to a multiple of 8, and each value must fit within its width",
+    arguments: &[(WIDTH_PARAM, GenType::Uint), (VALUE_PARAM, GenType::Uint)],
+    variadic: true,
+    create_fn: &create_bits_msb,
+};
+
+pub const BITS_LSB_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "bits_lsb",
+    description: "packs pairs of (width, value) arguments into binary, starting from the least significant bit of each byte. The widths must add up to a multiple of 8, and each value must fit within its width",
+    arguments: &[(WIDTH_PARAM, GenType::Uint), (VALUE_PARAM, GenType::Uint)],
+    variadic: true,
+    create_fn: &create_bits_lsb,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{
+        assert_bin_output_is_expected, assert_compile_error, assert_runtime_error,
+    };
+
+    #[test]
+    fn fields_are_packed_msb_first() {
+        // the first byte of an IPv4 header: version 4 and header length 5
+        assert_bin_output_is_expected("bits(4, 4, 4, 5)", &[0x45]);
+        // TCP data offset, reserved bits, and flags with SYN and ACK set
+        assert_bin_output_is_expected("bits(4, 5, 3, 0, 9, 0x012)", &[0x50, 0x12]);
+        assert_bin_output_is_expected("bits(1, 1, 15, 3)", &[0x80, 0x03]);
+    }
+
+    #[test]
+    fn fields_are_packed_lsb_first() {
+        assert_bin_output_is_expected("bits_lsb(4, 4, 4, 5)", &[0x54]);
+        assert_bin_output_is_expected("bits_lsb(1, 1, 3, 2, 12, 0xABC)", &[0xC5, 0xAB]);
+    }
+
+    #[test]
+    fn bits_composes_with_concat() {
+        assert_bin_output_is_expected(
+            "concat([0xFF], bits(3, 7, 5, 0), [0x00])",
+            &[0xFF, 0xE0, 0x00],
+        );
+    }
+
+    #[test]
+    fn invalid_fields_return_errors() {
+        assert_compile_error(
+            "bits(4, 4, 4)",
+            "requires pairs of (width, value) arguments",
+        );
+        assert_runtime_error(
+            "bits(4, 4, 3, 5)",
+            "add up to 7 bits, which is not a multiple of 8",
+        );
+        assert_runtime_error(
+            "bits(4, 16, 4, 5)",
+            "The value 16 does not fit in a bit field of width 4",
+        );
+        assert_runtime_error("bits(0, 0, 8, 5)", "Invalid bit field width: 0");
+    }
+}
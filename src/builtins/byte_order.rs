@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 use std::rc::Rc;
 use crate::{
     AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput, DynFun,
-    GenType, ProgramContext, RunnableFunction,
+    DynUintFun, GenType, ProgramContext, RunnableFunction,
 };
 
 #[derive(Debug)]
@@ -126,9 +126,164 @@ make_num_to_binary!(
     BigEndian::write_f64
 );
 
+/// Numbers that can be encoded as binary using a specific number of bits
+pub(crate) trait FixedWidth: Copy {
+    /// appends the number to the output using the given width and byte order, or returns an error if the width is
+    /// not supported or the number doesn't fit
+    fn write_fixed_width<B: ByteOrder>(self, width_bits: u64, out: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+fn integer_width_bytes(width_bits: u64) -> Result<usize, Error> {
+    match width_bits {
+        8 | 16 | 24 | 32 | 64 => Ok(width_bits as usize / 8),
+        other => bail!(
+            "Invalid width: {} bits, must be one of 8, 16, 24, 32, or 64",
+            other
+        ),
+    }
+}
+
+fn extend_zeroed(out: &mut Vec<u8>, len: usize) -> &mut [u8] {
+    let start = out.len();
+    out.resize(start + len, 0);
+    &mut out[start..]
+}
+
+impl FixedWidth for u64 {
+    fn write_fixed_width<B: ByteOrder>(self, width_bits: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+        let nbytes = integer_width_bytes(width_bits)?;
+        if width_bits < 64 && self >= (1 << width_bits) {
+            bail!("The value {} does not fit in an unsigned {} bit integer", self, width_bits);
+        }
+        B::write_uint(extend_zeroed(out, nbytes), self, nbytes);
+        Ok(())
+    }
+}
+
+impl FixedWidth for i64 {
+    fn write_fixed_width<B: ByteOrder>(self, width_bits: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+        let nbytes = integer_width_bytes(width_bits)?;
+        let limit = 1i64.checked_shl(width_bits as u32 - 1).unwrap_or(0);
+        if width_bits < 64 && (self < -limit || self >= limit) {
+            bail!("The value {} does not fit in a signed {} bit integer", self, width_bits);
+        }
+        B::write_int(extend_zeroed(out, nbytes), self, nbytes);
+        Ok(())
+    }
+}
+
+impl FixedWidth for f64 {
+    fn write_fixed_width<B: ByteOrder>(self, width_bits: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+        match width_bits {
+            32 => {
+                if self.is_finite() && self.abs() > f64::from(f32::MAX) {
+                    bail!("The value {} does not fit in a 32 bit float", self);
+                }
+                B::write_f32(extend_zeroed(out, 4), self as f32);
+            }
+            64 => B::write_f64(extend_zeroed(out, 8), self),
+            other => bail!("Invalid width: {} bits, must be either 32 or 64", other),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FixedWidthToBinary<T, B: ByteOrder> {
+    width_bits: DynUintFun,
+    number: DynFun<T>,
+    _phantom: PhantomData<B>,
+}
+
+impl<T: FixedWidth + ::std::fmt::Debug, B: ByteOrder + ::std::fmt::Debug> RunnableFunction<Vec<u8>>
+    for FixedWidthToBinary<T, B>
+{
+    fn gen_value(&self, context: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let width_bits = self.width_bits.gen_value(context)?;
+        let num = self.number.gen_value(context)?;
+        let mut buffer = Vec::with_capacity(8);
+        num.write_fixed_width::<B>(width_bits, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(
+        &self,
+        context: &mut ProgramContext,
+        out: &mut DataGenOutput,
+    ) -> Result<(), Error> {
+        let buffer = self.gen_value(context)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+const WIDTH_ARG_NAME: &str = "width_bits";
+
+macro_rules! make_fixed_width_to_binary {
+    ($le_builtin_name:ident, $be_builtin_name:ident, $gen_type:expr, $convert_input:path, $widths:expr) => {
+        pub const $le_builtin_name: &BuiltinFunctionPrototype = {
+            fn create_le(args: Arguments) -> CreateFunctionResult {
+                let (width_bits, number) = args.require_2_args(WIDTH_ARG_NAME, AnyFunction::require_uint, ARG_NAME, $convert_input)?;
+                Ok(AnyFunction::Bin(Rc::new(FixedWidthToBinary::<_, LittleEndian> {
+                    width_bits,
+                    number,
+                    _phantom: PhantomData,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: LITTLE_ENDIAN_FUNCTION_NAME,
+                description: concat!("converts the input number into binary with little endian byte order, using the given number of bits, which must be one of ", $widths, ". Returns an error if the number does not fit"),
+                arguments: &[(WIDTH_ARG_NAME, GenType::Uint), (ARG_NAME, $gen_type)],
+                variadic: false,
+                create_fn: &create_le,
+            }
+        };
+
+        pub const $be_builtin_name: &BuiltinFunctionPrototype = {
+            fn create_be(args: Arguments) -> CreateFunctionResult {
+                let (width_bits, number) = args.require_2_args(WIDTH_ARG_NAME, AnyFunction::require_uint, ARG_NAME, $convert_input)?;
+                Ok(AnyFunction::Bin(Rc::new(FixedWidthToBinary::<_, BigEndian> {
+                    width_bits,
+                    number,
+                    _phantom: PhantomData,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: BIG_ENDIAN_FUNCTION_NAME,
+                description: concat!("converts the input number into binary with big endian byte order, using the given number of bits, which must be one of ", $widths, ". Returns an error if the number does not fit"),
+                arguments: &[(WIDTH_ARG_NAME, GenType::Uint), (ARG_NAME, $gen_type)],
+                variadic: false,
+                create_fn: &create_be,
+            }
+        };
+    };
+}
+
+make_fixed_width_to_binary!(
+    SIZED_UINT_LITTLE_ENDIAN,
+    SIZED_UINT_BIG_ENDIAN,
+    GenType::Uint,
+    AnyFunction::require_uint,
+    "8, 16, 24, 32, or 64"
+);
+make_fixed_width_to_binary!(
+    SIZED_INT_LITTLE_ENDIAN,
+    SIZED_INT_BIG_ENDIAN,
+    GenType::Int,
+    AnyFunction::require_int,
+    "8, 16, 24, 32, or 64"
+);
+make_fixed_width_to_binary!(
+    SIZED_DECIMAL_LITTLE_ENDIAN,
+    SIZED_DECIMAL_BIG_ENDIAN,
+    GenType::Decimal,
+    AnyFunction::require_decimal,
+    "32 or 64"
+);
+
 #[cfg(test)]
 mod test {
-    use crate::fun_test::assert_bin_output_is_expected;
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error};
 
     #[test]
     fn int_is_converted_to_big_endian_binary() {
@@ -171,4 +326,35 @@ mod test {
         let expected = &[0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x5E, 0x40];
         assert_bin_output_is_expected(program, expected);
     }
+
+    #[test]
+    fn uint_is_converted_using_the_given_width() {
+        assert_bin_output_is_expected("big_endian(16, 0x1234)", &[0x12, 0x34]);
+        assert_bin_output_is_expected("little_endian(16, 0x1234)", &[0x34, 0x12]);
+        assert_bin_output_is_expected("big_endian(24, 0x123456)", &[0x12, 0x34, 0x56]);
+        assert_bin_output_is_expected("little_endian(32, 1)", &[0x01, 0x00, 0x00, 0x00]);
+        assert_bin_output_is_expected("big_endian(8, 255)", &[0xFF]);
+    }
+
+    #[test]
+    fn int_is_converted_using_the_given_width() {
+        assert_bin_output_is_expected("big_endian(16, -2)", &[0xFF, 0xFE]);
+        assert_bin_output_is_expected("little_endian(24, -2)", &[0xFE, 0xFF, 0xFF]);
+        assert_bin_output_is_expected("big_endian(8, -128)", &[0x80]);
+    }
+
+    #[test]
+    fn decimal_is_converted_to_32_bit_float() {
+        assert_bin_output_is_expected("big_endian(32, 123.5)", &[0x42, 0xF7, 0x00, 0x00]);
+        assert_bin_output_is_expected("little_endian(32, 123.5)", &[0x00, 0x00, 0xF7, 0x42]);
+    }
+
+    #[test]
+    fn out_of_range_values_return_an_error() {
+        assert_runtime_error("big_endian(8, 256)", "The value 256 does not fit in an unsigned 8 bit integer");
+        assert_runtime_error("big_endian(16, +32768)", "The value 32768 does not fit in a signed 16 bit integer");
+        assert_runtime_error("little_endian(8, -129)", "The value -129 does not fit in a signed 8 bit integer");
+        assert_runtime_error("big_endian(32, 1000000000000000000000000000000000000000.0)", "does not fit in a 32 bit float");
+        assert_runtime_error("big_endian(12, 1)", "Invalid width: 12 bits");
+    }
 }
@@ -0,0 +1,511 @@
+use super::encoding::{
+    create_encode_items, create_encode_no_items, create_encode_tagged, create_encode_value, is_exact_single,
+    require_even_items, Variation, ITEMS_PARAM, KEYS_AND_VALUES_PARAM, VALUE_PARAM, VARIATION_RATE_PARAM,
+};
+use crate::IString;
+use crate::{AnyFunction, Arguments, BuiltinFunctionPrototype, ConstBin, CreateFunctionResult, GenType};
+use failure::Error;
+
+/// Returns the IEEE 754 half precision bits of the value, if it can be represented exactly. All NaNs are represented
+/// by the canonical quiet NaN
+fn to_half(value: f64) -> Option<u16> {
+    if value.is_nan() {
+        return Some(0x7E00);
+    }
+    let single = value as f32;
+    if f64::from(single) != value {
+        return None;
+    }
+    let bits = single.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127;
+    let mantissa = bits & 0x7F_FFFF;
+    if value.is_infinite() {
+        Some(sign | 0x7C00)
+    } else if value == 0.0 {
+        Some(sign)
+    } else if (-14..=15).contains(&exponent) && mantissa & 0x1FFF == 0 {
+        Some(sign | (((exponent + 15) as u16) << 10) | (mantissa >> 13) as u16)
+    } else if (-24..-14).contains(&exponent) {
+        // subnormal half precision numbers have an implicit exponent of -24 and no implicit leading bit
+        let shift = (-1 - exponent) as u32;
+        let significand = mantissa | 0x80_0000;
+        if significand & ((1 << shift) - 1) == 0 {
+            Some(sign | (significand >> shift) as u16)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NEGATIVE_INT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const INDEFINITE_LENGTH: u8 = 31;
+const BREAK: u8 = 0xFF;
+
+/// Appends the initial byte and argument of a data item. The shortest form is canonical, but any of the longer
+/// argument sizes are also valid
+fn write_head(major: u8, value: u64, variation: &mut Variation, out: &mut Vec<u8>) {
+    let sizes: &[usize] = match value {
+        0..=23 => &[0, 1, 2, 4, 8],
+        24..=0xFF => &[1, 2, 4, 8],
+        0x100..=0xFFFF => &[2, 4, 8],
+        0x1_0000..=0xFFFF_FFFF => &[4, 8],
+        _ => &[8],
+    };
+    let size = variation.choose(sizes);
+    let additional_info = match size {
+        0 => value as u8,
+        1 => 24,
+        2 => 25,
+        4 => 26,
+        _ => 27,
+    };
+    out.push((major << 5) | additional_info);
+    for i in (0..size).rev() {
+        out.push((value >> (i * 8)) as u8);
+    }
+}
+
+fn encode_uint(value: u64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_head(MAJOR_UINT, value, variation, out);
+    Ok(())
+}
+
+fn encode_int(value: i64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    if value < 0 {
+        write_head(MAJOR_NEGATIVE_INT, !value as u64, variation, out);
+    } else {
+        write_head(MAJOR_UINT, value as u64, variation, out);
+    }
+    Ok(())
+}
+
+fn encode_float(value: f64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    let half = to_half(value);
+    let sizes: &[usize] = match (half, is_exact_single(value)) {
+        (Some(_), _) => &[2, 4, 8],
+        (None, true) => &[4, 8],
+        (None, false) => &[8],
+    };
+    match variation.choose(sizes) {
+        2 => {
+            out.push(0xF9);
+            out.extend_from_slice(&half.unwrap_or(0x7E00).to_be_bytes());
+        }
+        4 => {
+            out.push(0xFA);
+            out.extend_from_slice(&(value as f32).to_bits().to_be_bytes());
+        }
+        _ => {
+            out.push(0xFB);
+            out.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Appends a byte or text string, which may be written as an indefinite length string made up of definite length
+/// chunks, split at random positions where `is_boundary` returns true
+fn write_string(
+    major: u8,
+    value: &[u8],
+    is_boundary: &dyn Fn(usize) -> bool,
+    variation: &mut Variation,
+    out: &mut Vec<u8>,
+) {
+    if !variation.vary() {
+        write_head(major, value.len() as u64, variation, out);
+        out.extend_from_slice(value);
+        return;
+    }
+    out.push((major << 5) | INDEFINITE_LENGTH);
+    let split_count = variation.gen_range_inclusive(0, 3);
+    let mut splits = (0..split_count)
+        .map(|_| variation.gen_range_inclusive(0, value.len()))
+        .filter(|&split| is_boundary(split))
+        .collect::<Vec<_>>();
+    splits.sort_unstable();
+    let mut start = 0;
+    for end in splits.into_iter().chain(Some(value.len())) {
+        write_head(major, (end - start) as u64, variation, out);
+        out.extend_from_slice(&value[start..end]);
+        start = end;
+    }
+    out.push(BREAK);
+}
+
+fn encode_text(value: IString, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_string(MAJOR_TEXT, value.as_bytes(), &|i| value.is_char_boundary(i), variation, out);
+    Ok(())
+}
+
+fn encode_bytes(value: Vec<u8>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_string(MAJOR_BYTES, value.as_slice(), &|_| true, variation, out);
+    Ok(())
+}
+
+fn write_container(major: u8, len: usize, items: Vec<Vec<u8>>, variation: &mut Variation, out: &mut Vec<u8>) {
+    let indefinite = variation.vary();
+    if indefinite {
+        out.push((major << 5) | INDEFINITE_LENGTH);
+    } else {
+        write_head(major, len as u64, variation, out);
+    }
+    for item in items {
+        out.extend(item);
+    }
+    if indefinite {
+        out.push(BREAK);
+    }
+}
+
+fn encode_array(items: Vec<Vec<u8>>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_container(MAJOR_ARRAY, items.len(), items, variation, out);
+    Ok(())
+}
+
+/// Canonical maps have their entries sorted by the bytes of the encoded keys, as required by the core deterministic
+/// encoding of RFC 8949. Otherwise, the entries are written in the order they were given
+fn encode_map(items: Vec<Vec<u8>>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    require_even_items("cbor_map", &items)?;
+    let mut entries = items.chunks(2).map(<[Vec<u8>]>::to_vec).collect::<Vec<_>>();
+    if variation.is_canonical() {
+        entries.sort_by(|a, b| a[0].cmp(&b[0]));
+    }
+    write_container(MAJOR_MAP, entries.len(), entries.concat(), variation, out);
+    Ok(())
+}
+
+fn encode_tag(tag: u64, item: Vec<u8>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_head(MAJOR_TAG, tag, variation, out);
+    out.extend(item);
+    Ok(())
+}
+
+fn encode_bool(value: bool, _variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    out.push(if value { 0xF5 } else { 0xF4 });
+    Ok(())
+}
+
+macro_rules! make_cbor_builtins {
+    ($proto_name:ident, $varied_proto_name:ident, $fun_name:expr, [$(($arg_name:expr, $arg_type:expr)),*], $variadic:expr, $create:ident($($create_arg:expr),*), $description:literal) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                $create(args, false, $($create_arg),*)
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!($description, ", using the canonical encoding"),
+                arguments: &[$(($arg_name, $arg_type)),*],
+                variadic: $variadic,
+                create_fn: &create,
+            }
+        };
+
+        pub const $varied_proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                $create(args, true, $($create_arg),*)
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!(
+                    $description,
+                    ". Each length, integer, and float is written using a randomly chosen longer encoding, and strings, arrays, and maps use indefinite lengths, with a probability of variation_rate"
+                ),
+                arguments: &[(VARIATION_RATE_PARAM, GenType::Decimal), $(($arg_name, $arg_type)),*],
+                variadic: $variadic,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_cbor_builtins!(
+    UINT_BUILTIN,
+    UINT_VARIED_BUILTIN,
+    "cbor_int",
+    [(VALUE_PARAM, GenType::Uint)],
+    false,
+    create_encode_value(AnyFunction::require_uint, encode_uint),
+    "encodes the value as a cbor unsigned integer"
+);
+make_cbor_builtins!(
+    INT_BUILTIN,
+    INT_VARIED_BUILTIN,
+    "cbor_int",
+    [(VALUE_PARAM, GenType::Int)],
+    false,
+    create_encode_value(AnyFunction::require_int, encode_int),
+    "encodes the value as a cbor unsigned or negative integer"
+);
+make_cbor_builtins!(
+    FLOAT_BUILTIN,
+    FLOAT_VARIED_BUILTIN,
+    "cbor_float",
+    [(VALUE_PARAM, GenType::Decimal)],
+    false,
+    create_encode_value(AnyFunction::require_decimal, encode_float),
+    "encodes the value as a cbor float. The canonical encoding is the shortest of half, single, or double precision that represents the value exactly"
+);
+make_cbor_builtins!(
+    TEXT_BUILTIN,
+    TEXT_VARIED_BUILTIN,
+    "cbor_text",
+    [(VALUE_PARAM, GenType::String)],
+    false,
+    create_encode_value(AnyFunction::require_string, encode_text),
+    "encodes the value as a cbor text string. Indefinite length strings are only ever split between characters"
+);
+make_cbor_builtins!(
+    BYTES_BUILTIN,
+    BYTES_VARIED_BUILTIN,
+    "cbor_bytes",
+    [(VALUE_PARAM, GenType::Bin)],
+    false,
+    create_encode_value(AnyFunction::require_bin, encode_bytes),
+    "encodes the value as a cbor byte string"
+);
+make_cbor_builtins!(
+    ARRAY_BUILTIN,
+    ARRAY_VARIED_BUILTIN,
+    "cbor_array",
+    [(ITEMS_PARAM, GenType::Bin)],
+    true,
+    create_encode_items(ITEMS_PARAM, encode_array),
+    "encodes a cbor array of the items, which must already be cbor encoded"
+);
+make_cbor_builtins!(
+    EMPTY_ARRAY_BUILTIN,
+    EMPTY_ARRAY_VARIED_BUILTIN,
+    "cbor_array",
+    [],
+    false,
+    create_encode_no_items(encode_array),
+    "encodes an empty cbor array"
+);
+make_cbor_builtins!(
+    MAP_BUILTIN,
+    MAP_VARIED_BUILTIN,
+    "cbor_map",
+    [(KEYS_AND_VALUES_PARAM, GenType::Bin)],
+    true,
+    create_encode_items(KEYS_AND_VALUES_PARAM, encode_map),
+    "encodes a cbor map from alternating keys and values, which must already be cbor encoded. The canonical encoding sorts the entries by their encoded keys"
+);
+make_cbor_builtins!(
+    EMPTY_MAP_BUILTIN,
+    EMPTY_MAP_VARIED_BUILTIN,
+    "cbor_map",
+    [],
+    false,
+    create_encode_no_items(encode_map),
+    "encodes an empty cbor map"
+);
+make_cbor_builtins!(
+    TAG_BUILTIN,
+    TAG_VARIED_BUILTIN,
+    "cbor_tag",
+    [("tag", GenType::Uint), (VALUE_PARAM, GenType::Bin)],
+    false,
+    create_encode_tagged("tag", AnyFunction::require_uint, encode_tag),
+    "encodes a cbor tag followed by the value, which must already be cbor encoded"
+);
+
+fn create_bool(args: Arguments) -> CreateFunctionResult {
+    create_encode_value(args, false, AnyFunction::require_boolean, encode_bool)
+}
+
+pub const BOOL_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "cbor_bool",
+    description: "encodes the value as a cbor true or false",
+    arguments: &[(VALUE_PARAM, GenType::Boolean)],
+    variadic: false,
+    create_fn: &create_bool,
+};
+
+fn create_null(_args: Arguments) -> CreateFunctionResult {
+    Ok(ConstBin::new(vec![0xF6]))
+}
+
+pub const NULL_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "cbor_null",
+    description: "encodes a cbor null",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_null,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error, run_program};
+
+    #[test]
+    fn canonical_values_are_encoded() {
+        // examples from RFC 8949 appendix A
+        assert_bin_output_is_expected("cbor_int(23)", &[0x17]);
+        assert_bin_output_is_expected("cbor_int(1000000)", &[0x1A, 0x00, 0x0F, 0x42, 0x40]);
+        assert_bin_output_is_expected("cbor_int(18446744073709551615)", &[0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_bin_output_is_expected("cbor_int(-1000)", &[0x39, 0x03, 0xE7]);
+        assert_bin_output_is_expected("cbor_float(1.5)", &[0xF9, 0x3E, 0x00]);
+        assert_bin_output_is_expected("cbor_float(100000.0)", &[0xFA, 0x47, 0xC3, 0x50, 0x00]);
+        assert_bin_output_is_expected("cbor_float(1.1)", &[0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]);
+        assert_bin_output_is_expected("cbor_float(0.00006103515625)", &[0xF9, 0x04, 0x00]);
+        assert_bin_output_is_expected("cbor_float(0.000000059604644775390625)", &[0xF9, 0x00, 0x01]);
+        assert_bin_output_is_expected("cbor_float(-4.0)", &[0xF9, 0xC4, 0x00]);
+        assert_bin_output_is_expected("cbor_text(\"\\u{6c34}\")", &[0x63, 0xE6, 0xB0, 0xB4]);
+        assert_bin_output_is_expected("cbor_bytes([1, 2, 3, 4])", &[0x44, 1, 2, 3, 4]);
+        assert_bin_output_is_expected("cbor_tag(1, cbor_int(1363896240))", &[0xC1, 0x1A, 0x51, 0x4B, 0x67, 0xB0]);
+        assert_bin_output_is_expected("cbor_array()", &[0x80]);
+        assert_bin_output_is_expected("cbor_array(cbor_int(1), cbor_array(cbor_int(2), cbor_int(3)))", &[0x82, 0x01, 0x82, 0x02, 0x03]);
+        assert_bin_output_is_expected("cbor_map(cbor_text(\"b\"), cbor_int(2), cbor_text(\"a\"), cbor_null())", &[0xA2, 0x61, 0x61, 0xF6, 0x61, 0x62, 0x02]);
+        assert_bin_output_is_expected("cbor_bool(true)", &[0xF5]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Value {
+        Uint(u64),
+        Negative(u64),
+        Bytes(Vec<u8>),
+        Text(String),
+        Array(Vec<Value>),
+        Map(Vec<(Value, Value)>),
+        Tag(u64, Box<Value>),
+        Float(f64),
+        Simple(u8),
+    }
+
+    fn half_to_f64(bits: u16) -> f64 {
+        let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exponent = i32::from((bits >> 10) & 0x1F);
+        let mantissa = f64::from(bits & 0x3FF);
+        sign * match exponent {
+            0 => mantissa * 2f64.powi(-24),
+            31 => f64::INFINITY,
+            _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+        }
+    }
+
+    /// Decodes a single data item, returning it along with the rest of the input. Indefinite length items are
+    /// decoded into the same values as definite length ones
+    fn decode(input: &[u8]) -> (Value, &[u8]) {
+        let major = input[0] >> 5;
+        let info = input[0] & 0x1F;
+        let (argument, mut rest) = match info {
+            24..=27 => {
+                let size = 1 << (info - 24);
+                let argument = input[1..=size].iter().fold(0, |value, &byte| (value << 8) | u64::from(byte));
+                (argument, &input[size + 1..])
+            }
+            _ => (u64::from(info), &input[1..]),
+        };
+        let indefinite = info == 31;
+        let value = match major {
+            0 => Value::Uint(argument),
+            1 => Value::Negative(argument),
+            2 | 3 if indefinite => {
+                let mut contents = Vec::new();
+                while rest[0] != 0xFF {
+                    assert_eq!(major, rest[0] >> 5, "chunks must have the same major type");
+                    let (chunk, remaining) = decode(rest);
+                    match chunk {
+                        Value::Bytes(bytes) => contents.extend(bytes),
+                        Value::Text(text) => contents.extend(text.into_bytes()),
+                        other => panic!("invalid chunk: {:?}", other),
+                    }
+                    rest = remaining;
+                }
+                rest = &rest[1..];
+                if major == 2 {
+                    Value::Bytes(contents)
+                } else {
+                    Value::Text(String::from_utf8(contents).unwrap())
+                }
+            }
+            2 | 3 => {
+                let (contents, remaining) = rest.split_at(argument as usize);
+                rest = remaining;
+                if major == 2 {
+                    Value::Bytes(contents.to_vec())
+                } else {
+                    Value::Text(std::str::from_utf8(contents).unwrap().to_owned())
+                }
+            }
+            4 | 5 => {
+                let per_entry = if major == 5 { 2 } else { 1 };
+                let mut items = Vec::new();
+                while (indefinite && rest[0] != 0xFF) || (!indefinite && items.len() < (argument * per_entry) as usize) {
+                    let (item, remaining) = decode(rest);
+                    items.push(item);
+                    rest = remaining;
+                }
+                if indefinite {
+                    rest = &rest[1..];
+                }
+                if major == 4 {
+                    Value::Array(items)
+                } else {
+                    let mut entries = Vec::new();
+                    let mut items = items.into_iter();
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        entries.push((key, value));
+                    }
+                    Value::Map(entries)
+                }
+            }
+            6 => {
+                let (item, remaining) = decode(rest);
+                rest = remaining;
+                Value::Tag(argument, Box::new(item))
+            }
+            _ => match info {
+                25 => Value::Float(half_to_f64(argument as u16)),
+                26 => Value::Float(f64::from(f32::from_bits(argument as u32))),
+                27 => Value::Float(f64::from_bits(argument)),
+                simple => Value::Simple(simple),
+            },
+        };
+        (value, rest)
+    }
+
+    #[test]
+    fn varied_values_decode_to_the_same_value() {
+        let values = [
+            "cbor_int(+0)",
+            "cbor_int(-500)",
+            "cbor_int(4294967296)",
+            "cbor_float(0.5)",
+            "cbor_float(100000.0)",
+            "cbor_float(0.1)",
+            "cbor_text(\"a\\u{e9}\\u{1f600}z\")",
+            "cbor_bytes([1, 2, 3])",
+            "cbor_tag(32, cbor_text(\"http://x\"))",
+            "cbor_array(cbor_int(1), cbor_array(), cbor_map(cbor_int(1), cbor_text(\"\")))",
+        ];
+        for value in values.iter() {
+            let canonical = run_program(1, value).unwrap();
+            let (expected, _) = decode(&canonical);
+            let varied_program = value.replace("(", "(1.0, ").replace("(1.0, )", "(1.0)");
+            for _ in 0..20 {
+                let varied = run_program(1, &varied_program).unwrap();
+                let (decoded, rest) = decode(&varied);
+                assert!(rest.is_empty());
+                assert_eq!(expected, decoded, "varied encoding: {:02X?}", varied);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_number_of_map_arguments_returns_error() {
+        assert_runtime_error("cbor_map(cbor_int(1))", "cbor_map requires an even number of arguments");
+    }
+}
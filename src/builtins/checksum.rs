@@ -0,0 +1,541 @@
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynBinFun, DynDecimalFun, DynFun, DynStringFun, DynUintFun, GenType, ProgramContext,
+    RunnableFunction,
+};
+use failure::Error;
+use sha2::Digest as Sha2Digest;
+use std::rc::Rc;
+
+const CRC32_POLY_REFLECTED: u32 = 0xEDB8_8320;
+
+/// The IEEE CRC-32 used by ethernet, zip, png, and gzip
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLY_REFLECTED
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Crc16Params {
+    poly: u16,
+    init: u16,
+    reflected: bool,
+    xor_out: u16,
+}
+
+const CRC16_VARIANTS: &[(&str, Crc16Params)] = &[
+    (
+        "ccitt-false",
+        Crc16Params {
+            poly: 0x1021,
+            init: 0xFFFF,
+            reflected: false,
+            xor_out: 0,
+        },
+    ),
+    (
+        "xmodem",
+        Crc16Params {
+            poly: 0x1021,
+            init: 0,
+            reflected: false,
+            xor_out: 0,
+        },
+    ),
+    (
+        "kermit",
+        Crc16Params {
+            poly: 0x1021,
+            init: 0,
+            reflected: true,
+            xor_out: 0,
+        },
+    ),
+    (
+        "x25",
+        Crc16Params {
+            poly: 0x1021,
+            init: 0xFFFF,
+            reflected: true,
+            xor_out: 0xFFFF,
+        },
+    ),
+    (
+        "arc",
+        Crc16Params {
+            poly: 0x8005,
+            init: 0,
+            reflected: true,
+            xor_out: 0,
+        },
+    ),
+    (
+        "modbus",
+        Crc16Params {
+            poly: 0x8005,
+            init: 0xFFFF,
+            reflected: true,
+            xor_out: 0,
+        },
+    ),
+];
+
+fn crc16_params(variant: &str) -> Result<Crc16Params, Error> {
+    CRC16_VARIANTS
+        .iter()
+        .find(|&&(name, _)| name.eq_ignore_ascii_case(variant))
+        .map(|&(_, params)| params)
+        .ok_or_else(|| {
+            let names = CRC16_VARIANTS
+                .iter()
+                .map(|&(name, _)| name)
+                .collect::<Vec<_>>();
+            format_err!(
+                "Invalid crc16 variant: '{}', must be one of: {}",
+                variant,
+                names.join(", ")
+            )
+        })
+}
+
+fn crc16(params: Crc16Params, data: &[u8]) -> u16 {
+    let mut crc = params.init;
+    if params.reflected {
+        let poly = params.poly.reverse_bits();
+        for byte in data {
+            crc ^= u16::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ poly
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    } else {
+        for byte in data {
+            crc ^= u16::from(*byte) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ params.poly
+                } else {
+                    crc << 1
+                };
+            }
+        }
+    }
+    crc ^ params.xor_out
+}
+
+const ADLER_MOD: u32 = 65521;
+
+/// The Adler-32 checksum used by zlib
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + u32::from(*byte)) % ADLER_MOD;
+        b = (b + a) % ADLER_MOD;
+    }
+    (b << 16) | a
+}
+
+/// The RFC 1071 checksum used by IPv4, ICMP, TCP, and UDP headers. An odd trailing byte is padded with a zero
+fn inet_checksum(data: &[u8]) -> u16 {
+    let mut sum = data.chunks(2).fold(0u64, |sum, word| {
+        let high = u64::from(word[0]) << 8;
+        let low = word.get(1).cloned().map(u64::from).unwrap_or(0);
+        sum + (high | low)
+    });
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[derive(Debug)]
+struct Checksum {
+    data: DynBinFun,
+    compute: fn(&[u8]) -> u64,
+}
+
+impl RunnableFunction<u64> for Checksum {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<u64, Error> {
+        let data = self.data.gen_value(ctx)?;
+        Ok((self.compute)(data.as_slice()))
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct Crc16 {
+    variant: DynStringFun,
+    data: DynBinFun,
+}
+
+impl RunnableFunction<u64> for Crc16 {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<u64, Error> {
+        let variant = self.variant.gen_value(ctx)?;
+        let params = crc16_params(&variant)?;
+        let data = self.data.gen_value(ctx)?;
+        Ok(u64::from(crc16(params, data.as_slice())))
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct HashDigest {
+    data: DynBinFun,
+    compute: fn(&[u8]) -> Vec<u8>,
+}
+
+impl RunnableFunction<Vec<u8>> for HashDigest {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let data = self.data.gen_value(ctx)?;
+        Ok((self.compute)(data.as_slice()))
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+fn md5_digest(data: &[u8]) -> Vec<u8> {
+    md5::compute(data).0.to_vec()
+}
+
+fn sha1_digest(data: &[u8]) -> Vec<u8> {
+    sha1::Sha1::from(data).digest().bytes().to_vec()
+}
+
+fn sha256_digest(data: &[u8]) -> Vec<u8> {
+    sha2::Sha256::digest(data).to_vec()
+}
+
+const DATA_PARAM: &str = "data";
+const VARIANT_PARAM: &str = "variant";
+
+fn create_checksum(args: Arguments, compute: fn(&[u8]) -> u64) -> CreateFunctionResult {
+    let data = args.required_arg(DATA_PARAM, 0, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Uint(Rc::new(Checksum { data, compute })))
+}
+
+fn create_digest(args: Arguments, compute: fn(&[u8]) -> Vec<u8>) -> CreateFunctionResult {
+    let data = args.required_arg(DATA_PARAM, 0, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(HashDigest { data, compute })))
+}
+
+fn create_crc32(args: Arguments) -> CreateFunctionResult {
+    create_checksum(args, |data| u64::from(crc32(data)))
+}
+
+fn create_default_crc16(args: Arguments) -> CreateFunctionResult {
+    create_checksum(args, |data| u64::from(crc16(CRC16_VARIANTS[0].1, data)))
+}
+
+fn create_crc16(args: Arguments) -> CreateFunctionResult {
+    let (variant, data) = args.require_2_args(
+        VARIANT_PARAM,
+        AnyFunction::require_string,
+        DATA_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::Uint(Rc::new(Crc16 { variant, data })))
+}
+
+fn create_adler32(args: Arguments) -> CreateFunctionResult {
+    create_checksum(args, |data| u64::from(adler32(data)))
+}
+
+fn create_inet_checksum(args: Arguments) -> CreateFunctionResult {
+    create_checksum(args, |data| u64::from(inet_checksum(data)))
+}
+
+fn create_md5(args: Arguments) -> CreateFunctionResult {
+    create_digest(args, md5_digest)
+}
+
+fn create_sha1(args: Arguments) -> CreateFunctionResult {
+    create_digest(args, sha1_digest)
+}
+
+fn create_sha256(args: Arguments) -> CreateFunctionResult {
+    create_digest(args, sha256_digest)
+}
+
+pub const CRC32_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "crc32",
+    description: "computes the IEEE CRC-32 (as used by ethernet, zip, and png) of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_crc32,
+};
+
+pub const CRC16_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "crc16",
+    description: "computes the CRC-16/CCITT-FALSE of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_default_crc16,
+};
+
+pub const CRC16_VARIANT_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "crc16",
+    description: "computes a CRC-16 of the given binary. The variant must be one of: 'ccitt-false', 'xmodem', 'kermit', 'x25', 'arc', or 'modbus'",
+    arguments: &[(VARIANT_PARAM, GenType::String), (DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_crc16,
+};
+
+pub const ADLER32_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "adler32",
+    description: "computes the Adler-32 checksum (as used by zlib) of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_adler32,
+};
+
+pub const INET_CHECKSUM_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "inet_checksum",
+    description: "computes the 16 bit internet checksum (as used by IPv4, ICMP, TCP, and UDP) of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_inet_checksum,
+};
+
+pub const MD5_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "md5",
+    description: "computes the 16 byte MD5 digest of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_md5,
+};
+
+pub const SHA1_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "sha1",
+    description: "computes the 20 byte SHA-1 digest of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_sha1,
+};
+
+pub const SHA256_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "sha256",
+    description: "computes the 32 byte SHA-256 digest of the given binary",
+    arguments: &[(DATA_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_sha256,
+};
+
+#[derive(Debug)]
+struct CorruptChecksum<T> {
+    rate: DynDecimalFun,
+    checksum: DynFun<T>,
+}
+
+impl RunnableFunction<u64> for CorruptChecksum<u64> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<u64, Error> {
+        let rate = self.rate.gen_value(ctx)?;
+        let checksum = self.checksum.gen_value(ctx)?;
+        if ctx.gen_chance(rate) {
+            // every checksum is at least 16 bits wide, so flipping one of the low 16 bits is always wrong
+            // without changing the width
+            Ok(checksum ^ (1 << ctx.gen_range_exclusive(0, 16)))
+        } else {
+            Ok(checksum)
+        }
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for CorruptChecksum<Vec<u8>> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let rate = self.rate.gen_value(ctx)?;
+        let mut checksum = self.checksum.gen_value(ctx)?;
+        if !checksum.is_empty() && ctx.gen_chance(rate) {
+            let idx = ctx.gen_range_exclusive(0, checksum.len());
+            checksum[idx] ^= 1 << ctx.gen_range_exclusive(0, 8);
+        }
+        Ok(checksum)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+const RATE_PARAM: &str = "rate";
+const CHECKSUM_PARAM: &str = "checksum";
+
+fn create_corrupt_uint_checksum(args: Arguments) -> CreateFunctionResult {
+    let (rate, checksum): (DynDecimalFun, DynUintFun) = args.require_2_args(
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        CHECKSUM_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::Uint(Rc::new(CorruptChecksum {
+        rate,
+        checksum,
+    })))
+}
+
+fn create_corrupt_bin_checksum(args: Arguments) -> CreateFunctionResult {
+    let (rate, checksum) = args.require_2_args(
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        CHECKSUM_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(CorruptChecksum {
+        rate,
+        checksum,
+    })))
+}
+
+pub const CORRUPT_UINT_CHECKSUM_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "corrupt_checksum",
+    description: "with the given probability, returns a deliberately wrong checksum by flipping one of its low 16 bits. Otherwise the checksum is returned unchanged",
+    arguments: &[(RATE_PARAM, GenType::Decimal), (CHECKSUM_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_corrupt_uint_checksum,
+};
+
+pub const CORRUPT_BIN_CHECKSUM_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "corrupt_checksum",
+    description: "with the given probability, returns a deliberately wrong digest by flipping a single bit. Otherwise the digest is returned unchanged",
+    arguments: &[(RATE_PARAM, GenType::Decimal), (CHECKSUM_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_corrupt_bin_checksum,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_bin_output_is_expected, run_program, test_program_success};
+
+    const CHECK_INPUT: &[u8] = b"123456789";
+
+    #[test]
+    fn crc_check_values_are_correct() {
+        assert_eq!(0xCBF4_3926, crc32(CHECK_INPUT));
+        let expected = &[
+            ("ccitt-false", 0x29B1),
+            ("xmodem", 0x31C3),
+            ("kermit", 0x2189),
+            ("x25", 0x906E),
+            ("arc", 0xBB3D),
+            ("modbus", 0x4B37),
+        ];
+        for &(variant, check) in expected {
+            let params = crc16_params(variant).unwrap();
+            assert_eq!(check, crc16(params, CHECK_INPUT), "variant: {}", variant);
+        }
+        assert!(crc16_params("crc-17").is_err());
+    }
+
+    #[test]
+    fn checksums_are_computed_over_binary() {
+        test_program_success(
+            1,
+            "crc32(string_bytes(\"utf-8\", \"123456789\"))",
+            "3421780262",
+        );
+        test_program_success(
+            1,
+            "crc16(\"modbus\", string_bytes(\"utf-8\", \"123456789\"))",
+            "19255",
+        );
+        test_program_success(
+            1,
+            "adler32(string_bytes(\"utf-8\", \"Wikipedia\"))",
+            "300286872",
+        );
+        // an IPv4 header with the checksum field zeroed
+        let ipv4_header = "[0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7]";
+        test_program_success(1, &format!("inet_checksum({})", ipv4_header), "47201");
+        assert_eq!(!0x0102u16, inet_checksum(&[0x01, 0x02]));
+        assert_eq!(!0x0100u16, inet_checksum(&[0x01]));
+    }
+
+    #[test]
+    fn digests_are_computed_over_binary() {
+        assert_bin_output_is_expected(
+            "md5([])",
+            &[
+                0xd4, 0x1d, 0x8c, 0xd9, 0x8f, 0x00, 0xb2, 0x04, 0xe9, 0x80, 0x09, 0x98, 0xec, 0xf8,
+                0x42, 0x7e,
+            ],
+        );
+        assert_bin_output_is_expected(
+            "sha1(string_bytes(\"utf-8\", \"abc\"))",
+            &[
+                0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+                0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d,
+            ],
+        );
+        assert_bin_output_is_expected(
+            "sha256(string_bytes(\"utf-8\", \"abc\"))",
+            &[
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ],
+        );
+    }
+
+    #[test]
+    fn checksum_over_mapped_value_matches_the_bytes_written() {
+        let program = "uuid_v4_bin() { id -> concat(id, big_endian(32, crc32(id))) }";
+        let output = run_program(20, program).unwrap();
+        assert_eq!(20 * 20, output.len());
+        for record in output.chunks(20) {
+            let expected = crc32(&record[..16]);
+            let actual = u32::from(record[16]) << 24
+                | u32::from(record[17]) << 16
+                | u32::from(record[18]) << 8
+                | u32::from(record[19]);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn corrupt_checksum_returns_wrong_values_at_the_given_rate() {
+        assert_eq!(
+            run_program(10, "crc32([0x01])").unwrap(),
+            run_program(10, "corrupt_checksum(0.0, crc32([0x01]))").unwrap()
+        );
+        let output = run_program(
+            50,
+            "corrupt_checksum(1.0, sha1([])) { d -> concat(d, sha1([])) }",
+        )
+        .unwrap();
+        for record in output.chunks(40) {
+            let differing_bits: u32 = record[..20]
+                .iter()
+                .zip(record[20..].iter())
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum();
+            assert_eq!(1, differing_bits);
+        }
+    }
+}
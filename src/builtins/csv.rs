@@ -0,0 +1,700 @@
+use super::from_file::RandFileReader;
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quoting {
+    Minimal,
+    All,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Dialect {
+    delimiter: char,
+    quote: Option<char>,
+    escape: Option<char>,
+    quoting: Quoting,
+    line_ending: &'static str,
+    ragged_rate: f64,
+}
+
+const RFC_4180: Dialect = Dialect {
+    delimiter: ',',
+    quote: Some('"'),
+    escape: None,
+    quoting: Quoting::Minimal,
+    line_ending: "\r\n",
+    ragged_rate: 0.0,
+};
+
+fn base_dialect(name: &str) -> Result<Dialect, Error> {
+    let dialect = match name {
+        "rfc4180" | "excel" => RFC_4180,
+        "excel-tab" => Dialect {
+            delimiter: '\t',
+            ..RFC_4180
+        },
+        "unix" => Dialect {
+            quoting: Quoting::All,
+            line_ending: "\n",
+            ..RFC_4180
+        },
+        "tsv" => Dialect {
+            delimiter: '\t',
+            quote: None,
+            escape: Some('\\'),
+            quoting: Quoting::None,
+            line_ending: "\n",
+            ..RFC_4180
+        },
+        other => bail!(
+            "Invalid csv dialect: '{}', must start with one of: 'rfc4180', 'excel', 'excel-tab', 'unix', or 'tsv'",
+            other
+        ),
+    };
+    Ok(dialect)
+}
+
+fn parse_dialect_char(key: &str, value: &str) -> Result<Option<char>, Error> {
+    let c = match value {
+        "none" => return Ok(None),
+        "tab" => '\t',
+        "space" => ' ',
+        _ => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => bail!(
+                    "Invalid csv {}: '{}', must be a single character, 'tab', 'space', or 'none'",
+                    key,
+                    value
+                ),
+            }
+        }
+    };
+    if c == '\r' || c == '\n' {
+        bail!("Invalid csv {}: line breaks cannot be used", key);
+    }
+    Ok(Some(c))
+}
+
+/// Parses a dialect like "rfc4180 delimiter=; quoting=all", which is a base dialect name followed by any number of
+/// space separated overrides
+fn parse_dialect(spec: &str) -> Result<Dialect, Error> {
+    let mut parts = spec.split(' ').filter(|part| !part.is_empty());
+    let mut dialect = base_dialect(parts.next().unwrap_or(""))?;
+    for part in parts {
+        let mut key_value = part.splitn(2, '=');
+        let key = key_value.next().unwrap_or("");
+        let value = key_value.next().ok_or_else(|| {
+            format_err!(
+                "Invalid csv dialect option: '{}', must be of the form key=value",
+                part
+            )
+        })?;
+        match key {
+            "delimiter" => {
+                dialect.delimiter = parse_dialect_char(key, value)?
+                    .ok_or_else(|| format_err!("The csv delimiter cannot be 'none'"))?;
+            }
+            "quote" => dialect.quote = parse_dialect_char(key, value)?,
+            "escape" => dialect.escape = parse_dialect_char(key, value)?,
+            "quoting" => {
+                dialect.quoting = match value {
+                    "minimal" => Quoting::Minimal,
+                    "all" => Quoting::All,
+                    "none" => Quoting::None,
+                    other => bail!(
+                        "Invalid csv quoting: '{}', must be one of 'minimal', 'all', or 'none'",
+                        other
+                    ),
+                };
+            }
+            "line_ending" => {
+                dialect.line_ending = match value {
+                    "crlf" => "\r\n",
+                    "lf" => "\n",
+                    "cr" => "\r",
+                    other => bail!(
+                        "Invalid csv line_ending: '{}', must be one of 'crlf', 'lf', or 'cr'",
+                        other
+                    ),
+                };
+            }
+            "ragged" => {
+                dialect.ragged_rate = match value.parse::<f64>() {
+                    Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+                    _ => bail!("Invalid csv ragged rate: '{}', must be a decimal between 0 and 1", value),
+                };
+            }
+            other => bail!(
+                "Invalid csv dialect option: '{}', must be one of 'delimiter', 'quote', 'escape', 'quoting', 'line_ending', or 'ragged'",
+                other
+            ),
+        }
+    }
+    if dialect.quote.is_some() && dialect.quote == Some(dialect.delimiter) {
+        bail!("The csv quote and delimiter must be different characters");
+    }
+    Ok(dialect)
+}
+
+impl Dialect {
+    fn is_special(&self, c: char) -> bool {
+        c == self.delimiter
+            || c == '\r'
+            || c == '\n'
+            || Some(c) == self.quote
+            || Some(c) == self.escape
+    }
+
+    fn write_field(&self, field: &str, only_field: bool, out: &mut String) -> Result<(), Error> {
+        let quote = match (self.quoting, self.quote) {
+            (Quoting::None, _) | (_, None) => None,
+            (Quoting::All, Some(quote)) => Some(quote),
+            (Quoting::Minimal, Some(quote)) => {
+                // an empty field on its own would be indistinguishable from a blank line
+                let needs_quotes =
+                    field.chars().any(|c| self.is_special(c)) || (only_field && field.is_empty());
+                if needs_quotes {
+                    Some(quote)
+                } else {
+                    None
+                }
+            }
+        };
+
+        match quote {
+            Some(quote) => {
+                out.push(quote);
+                for c in field.chars() {
+                    if c == quote || Some(c) == self.escape {
+                        out.push(self.escape.unwrap_or(quote));
+                    }
+                    out.push(c);
+                }
+                out.push(quote);
+            }
+            None => {
+                if self.escape.is_none() {
+                    if let Some(c) = field.chars().find(|c| self.is_special(*c)) {
+                        bail!(
+                            "The csv field {:?} contains the special character {:?}, but the dialect has no way to quote or escape it",
+                            field,
+                            c
+                        );
+                    }
+                }
+                for c in field.chars() {
+                    match self.escape {
+                        Some(escape) if self.is_special(c) => {
+                            out.push(escape);
+                            match c {
+                                '\t' => out.push('t'),
+                                '\r' => out.push('r'),
+                                '\n' => out.push('n'),
+                                other => out.push(other),
+                            }
+                        }
+                        _ => out.push(c),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Randomly changes the number of fields in a row, either by dropping some from the end or by repeating some
+fn make_ragged(fields: &mut Vec<IString>, ctx: &mut ProgramContext) {
+    if fields.len() > 1 && ctx.gen_chance(0.5) {
+        let new_len = ctx.gen_range_exclusive(1, fields.len());
+        fields.truncate(new_len);
+    } else {
+        let extra = ctx.gen_range_inclusive(1, fields.len().max(1));
+        for _ in 0..extra {
+            let idx = ctx.gen_range_exclusive(0, fields.len().max(1));
+            let field = fields.get(idx).cloned().unwrap_or_else(|| "".into());
+            fields.push(field);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CsvRow {
+    dialect: DynStringFun,
+    fields: Vec<DynStringFun>,
+}
+
+impl RunnableFunction<IString> for CsvRow {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let dialect = self.dialect.gen_value(ctx)?;
+        let dialect = parse_dialect(&dialect)?;
+
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in self.fields.iter() {
+            fields.push(field.gen_value(ctx)?);
+        }
+        if ctx.gen_chance(dialect.ragged_rate) {
+            make_ragged(&mut fields, ctx);
+        }
+
+        let mut row = String::new();
+        let only_field = fields.len() == 1;
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                row.push(dialect.delimiter);
+            }
+            dialect.write_field(field, only_field, &mut row)?;
+        }
+        row.push_str(dialect.line_ending);
+        Ok(row.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Parses delimited text with optional double quotes, where quotes inside a quoted field are doubled
+pub(crate) fn parse_csv(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            row.push(std::mem::take(&mut field));
+            rows.push(std::mem::take(&mut row));
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Parses a single record, which is the first row of the text
+fn parse_record(text: &str) -> Vec<String> {
+    parse_csv(text, ',').into_iter().next().unwrap_or_else(|| vec![String::new()])
+}
+
+/// Reads and parses the header, which is the first record of the file. The records are found the same way as the rows
+/// that are selected from the file, so a quoted column name may contain a delimiter, quotes, or a newline
+fn read_header(path: &str) -> Result<Vec<String>, Error> {
+    let mut reader = RandFileReader::create_records(path)?;
+    if reader.region_count() == 0 {
+        bail!("The csv file '{}' is empty, so it has no header", path);
+    }
+    let mut buffer = Vec::with_capacity(512);
+    let record = trim_line_ending(reader.read_region(0, &mut buffer)?);
+    let header = ::std::str::from_utf8(record).map_err(|err| {
+        format_err!("The csv file '{}' contains invalid utf-8: {}", path, err)
+    })?;
+    Ok(parse_record(header))
+}
+
+fn trim_line_ending(record: &[u8]) -> &[u8] {
+    if record.ends_with(b"\r") {
+        &record[..record.len() - 1]
+    } else {
+        record
+    }
+}
+
+struct CsvDatasets {
+    read_buffer: Vec<u8>,
+    readers: HashMap<IString, RandFileReader>,
+}
+
+impl CsvDatasets {
+    fn read_random_row(&mut self, ctx: &mut ProgramContext, path: IString) -> Result<IString, Error> {
+        let CsvDatasets {
+            ref mut read_buffer,
+            ref mut readers,
+        } = *self;
+        if !readers.contains_key(&path) {
+            let reader = RandFileReader::create_records(&path)?;
+            readers.insert(path.clone(), reader);
+        }
+        let reader = readers.get_mut(&path).unwrap();
+
+        // the first record is always the header, so it's never selected
+        let record_count = reader.region_count();
+        if record_count < 2 {
+            bail!("The csv file '{}' does not have any rows after the header", path);
+        }
+        let record_idx = ctx.gen_range_exclusive(1, record_count);
+        let record = trim_line_ending(reader.read_region(record_idx, read_buffer)?);
+        let row = ::std::str::from_utf8(record).map_err(|err| {
+            format_err!("The csv file '{}' contains invalid utf-8: {}", path, err)
+        })?;
+        Ok(row.into())
+    }
+}
+
+/// Selects a random row from a csv file, which is meant to be used with a mapper so that several columns can be
+/// taken from the same row
+struct CsvRowFromFile {
+    file_path_gen: DynStringFun,
+    datasets: RefCell<CsvDatasets>,
+}
+
+impl Debug for CsvRowFromFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CsvRowFromFile")
+            .field("file_path", &self.file_path_gen)
+            .finish()
+    }
+}
+
+impl RunnableFunction<IString> for CsvRowFromFile {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let path = self.file_path_gen.gen_value(ctx)?;
+        self.datasets.borrow_mut().read_random_row(ctx, path)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+struct CsvColumn {
+    file_path_gen: DynStringFun,
+    row_gen: DynStringFun,
+    column_gen: DynStringFun,
+    headers: RefCell<HashMap<IString, Rc<Vec<String>>>>,
+}
+
+impl Debug for CsvColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CsvColumn")
+            .field("file_path", &self.file_path_gen)
+            .field("row", &self.row_gen)
+            .field("column", &self.column_gen)
+            .finish()
+    }
+}
+
+impl CsvColumn {
+    fn get_header(&self, path: IString) -> Result<Rc<Vec<String>>, Error> {
+        let mut headers = self.headers.borrow_mut();
+        if let Some(header) = headers.get(&path) {
+            return Ok(header.clone());
+        }
+        let header = Rc::new(read_header(&path)?);
+        headers.insert(path, header.clone());
+        Ok(header)
+    }
+}
+
+impl RunnableFunction<IString> for CsvColumn {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let path = self.file_path_gen.gen_value(ctx)?;
+        let row = self.row_gen.gen_value(ctx)?;
+        let column = self.column_gen.gen_value(ctx)?;
+
+        let header = self.get_header(path.clone())?;
+        let index = header.iter().position(|name| *name == *column).ok_or_else(|| {
+            format_err!(
+                "The csv file '{}' has no column named '{}', the columns are: {:?}",
+                path,
+                column,
+                header
+            )
+        })?;
+        column_at(&row, index)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct CsvColumnAt {
+    row: DynStringFun,
+    index: DynUintFun,
+}
+
+impl RunnableFunction<IString> for CsvColumnAt {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let row = self.row.gen_value(ctx)?;
+        let index = self.index.gen_value(ctx)?;
+        column_at(&row, index as usize)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+fn column_at(row: &str, index: usize) -> Result<IString, Error> {
+    let mut fields = parse_record(row);
+    if index >= fields.len() {
+        bail!(
+            "The csv row has {} columns, so there is no column at index {}: '{}'",
+            fields.len(),
+            index,
+            row
+        );
+    }
+    Ok(fields.swap_remove(index).into())
+}
+
+const DIALECT_PARAM: &str = "dialect";
+const FIELDS_PARAM: &str = "fields";
+const FILEPATH_PARAM: &str = "filepath";
+const ROW_PARAM: &str = "row";
+const COLUMN_PARAM: &str = "column";
+const INDEX_PARAM: &str = "index";
+
+fn create_csv_row(args: Arguments) -> CreateFunctionResult {
+    let dialect = args.required_arg(DIALECT_PARAM, 0, AnyFunction::require_string)?;
+    let fields = args.get_required_varargs(FIELDS_PARAM, 1, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(CsvRow { dialect, fields })))
+}
+
+pub const CSV_ROW_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "csv_row",
+    description: "formats the fields as a single delimited record, including the line ending. The dialect is one of 'rfc4180', 'excel', 'excel-tab', 'unix', or 'tsv', optionally followed by space separated overrides: 'delimiter=<char>', 'quote=<char>', 'escape=<char>', 'quoting=<minimal|all|none>' (fields with special characters are an error if they can neither be quoted nor escaped), 'line_ending=<crlf|lf|cr>', or 'ragged=<rate>'. Characters may also be given as 'tab', 'space', or 'none'. For example: 'rfc4180 delimiter=; quoting=all'",
+    arguments: &[(DIALECT_PARAM, GenType::String), (FIELDS_PARAM, GenType::String)],
+    variadic: true,
+    create_fn: &create_csv_row,
+};
+
+fn create_select_csv_row(args: Arguments) -> CreateFunctionResult {
+    let file_path_gen = args.required_arg(FILEPATH_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(CsvRowFromFile {
+        file_path_gen,
+        datasets: RefCell::new(CsvDatasets {
+            read_buffer: Vec::with_capacity(512),
+            readers: HashMap::new(),
+        }),
+    })))
+}
+
+pub const SELECT_CSV_ROW_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "select_csv_row",
+    description: "selects a random row from the given comma separated file, skipping the header row. Fields may be quoted with double quotes, and quoted fields may contain line breaks. The row is returned without its line ending. Use it with a mapper, like `select_csv_row(path) { row -> ... }`, and get columns from the row using `csv_column`, so that every column comes from the same row",
+    arguments: &[(FILEPATH_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_select_csv_row,
+};
+
+fn create_csv_column(args: Arguments) -> CreateFunctionResult {
+    let (file_path_gen, row_gen, column_gen) = args.require_3_args(
+        FILEPATH_PARAM,
+        AnyFunction::require_string,
+        ROW_PARAM,
+        AnyFunction::require_string,
+        COLUMN_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(CsvColumn {
+        file_path_gen,
+        row_gen,
+        column_gen,
+        headers: RefCell::new(HashMap::new()),
+    })))
+}
+
+pub const CSV_COLUMN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "csv_column",
+    description: "returns the unquoted value of the named column from a row of the given csv file, using the header row of the file to find the column",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (ROW_PARAM, GenType::String),
+        (COLUMN_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_csv_column,
+};
+
+fn create_csv_column_at(args: Arguments) -> CreateFunctionResult {
+    let (row, index) = args.require_2_args(
+        ROW_PARAM,
+        AnyFunction::require_string,
+        INDEX_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::String(Rc::new(CsvColumnAt { row, index })))
+}
+
+pub const CSV_COLUMN_AT_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "csv_column",
+    description: "returns the unquoted value of the column at the given zero-based index from a comma separated row",
+    arguments: &[(ROW_PARAM, GenType::String), (INDEX_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_csv_column_at,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_compile_error, assert_runtime_error, run_program, test_program_success};
+
+    #[test]
+    fn fields_are_quoted_only_when_required() {
+        test_program_success(
+            1,
+            r#"csv_row("rfc4180", "a", "b,c", "say \"hi\"", "line\nbreak")"#,
+            "a,\"b,c\",\"say \"\"hi\"\"\",\"line\nbreak\"\r\n",
+        );
+        test_program_success(1, r#"csv_row("rfc4180", "")"#, "\"\"\r\n");
+        test_program_success(1, r#"csv_row("rfc4180", "", "")"#, ",\r\n");
+    }
+
+    #[test]
+    fn dialect_options_are_applied() {
+        test_program_success(1, r#"csv_row("unix", "a", "b")"#, "\"a\",\"b\"\n");
+        test_program_success(
+            1,
+            r#"csv_row("rfc4180 delimiter=; quote=' quoting=minimal line_ending=lf", "a;b", "it's", "c,d")"#,
+            "'a;b';'it''s';c,d\n",
+        );
+        test_program_success(
+            1,
+            r#"csv_row("rfc4180 escape=\\ quoting=all", "say \"hi\"", "back\\slash")"#,
+            "\"say \\\"hi\\\"\",\"back\\\\slash\"\r\n",
+        );
+        test_program_success(
+            1,
+            r#"csv_row("tsv", "a\tb", "c\nd", "e")"#,
+            "a\\tb\tc\\nd\te\n",
+        );
+        test_program_success(
+            1,
+            r#"csv_row("excel-tab quoting=none escape=none", "a,b", "c")"#,
+            "a,b\tc\r\n",
+        );
+    }
+
+    #[test]
+    fn rows_keep_the_same_number_of_columns() {
+        let output = run_program(50, r#"csv_row("unix", "a", "b", "c")"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().all(|line| line == "\"a\",\"b\",\"c\""));
+    }
+
+    #[test]
+    fn ragged_rows_have_different_numbers_of_columns() {
+        let output = run_program(100, r#"csv_row("unix ragged=0.5", "a", "b", "c")"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let counts: Vec<usize> = output.lines().map(|line| line.split(',').count()).collect();
+        assert!(counts.iter().any(|&count| count < 3));
+        assert!(counts.iter().any(|&count| count > 3));
+        assert!(counts.iter().filter(|&&count| count == 3).count() > 20);
+    }
+
+    #[test]
+    fn invalid_dialects_return_errors() {
+        assert_runtime_error(r#"csv_row("json", "a")"#, "Invalid csv dialect: 'json'");
+        assert_runtime_error(
+            r#"csv_row("rfc4180 delimiter=ab", "a")"#,
+            "Invalid csv delimiter: 'ab'",
+        );
+        assert_runtime_error(
+            r#"csv_row("rfc4180 quoting", "a")"#,
+            "must be of the form key=value",
+        );
+        assert_runtime_error(
+            r#"csv_row("rfc4180 quote=,", "a")"#,
+            "must be different characters",
+        );
+        assert_runtime_error(
+            r#"csv_row("rfc4180 quoting=none", "a,b")"#,
+            "no way to quote or escape it",
+        );
+        assert_runtime_error(
+            r#"csv_row("rfc4180 quote=none", "a\nb")"#,
+            "no way to quote or escape it",
+        );
+        assert!(parse_dialect("rfc4180 quoting=sometimes").is_err());
+        for rate in &["nan", "5", "-1", "inf", "half"] {
+            let error = parse_dialect(&format!("unix ragged={}", rate)).unwrap_err();
+            assert!(error.to_string().contains("Invalid csv ragged rate"), "unexpected error: {}", error);
+        }
+        assert_eq!(1.0, parse_dialect("unix ragged=1").unwrap().ragged_rate);
+        assert_eq!(RFC_4180, parse_dialect("excel").unwrap());
+        // a row needs at least one field, rather than the dialect being mistaken for a file to select a row from
+        assert_compile_error(r#"csv_row("rfc4180")"#, "No such method");
+    }
+
+    #[test]
+    fn columns_from_a_csv_row_come_from_the_same_row() {
+        let program = r##"select_csv_row("test-data/cities.csv") { row ->
+            concat(csv_column("test-data/cities.csv", row, "city"), "|", csv_column(row, 2), "#")
+        }"##;
+        let output = run_program(100, program).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "Springfield|62701",
+            "Portland, East|97201",
+            "Austin|73301",
+            "Point 5\" Bay|04011",
+            "The \"Dalles\"|97058",
+            "Multi\nLine|10001",
+        ];
+        let actual: Vec<&str> = output.split_terminator('#').collect();
+        assert_eq!(100, actual.len());
+        for row in actual.iter() {
+            assert!(expected.contains(row), "unexpected row: {:?}", row);
+        }
+        for row in expected.iter() {
+            assert!(actual.contains(row), "row was never selected: {:?}", row);
+        }
+    }
+
+    #[test]
+    fn missing_csv_columns_return_errors() {
+        assert_runtime_error(
+            r#"select_csv_row("test-data/cities.csv") { row -> csv_column("test-data/cities.csv", row, "country") }"#,
+            "has no column named 'country'",
+        );
+        assert_runtime_error(r#"csv_column("a,b", 2)"#, "there is no column at index 2");
+        test_program_success(1, r#"csv_column("a,\"b,c\"", 1)"#, "b,c");
+    }
+
+    #[test]
+    fn header_is_read_like_the_other_records() {
+        // the header has a quote within an unquoted name, followed by a quoted name that contains a newline
+        let program = r##"select_csv_row("test-data/quoted-header.csv") { row -> concat(
+            csv_column("test-data/quoted-header.csv", row, "size 5\""), "|",
+            csv_column("test-data/quoted-header.csv", row, "multi\nline"), "|",
+            csv_column("test-data/quoted-header.csv", row, "name"))
+        }"##;
+        test_program_success(1, program, "10|x|Smith");
+    }
+}
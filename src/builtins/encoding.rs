@@ -0,0 +1,211 @@
+use crate::{
+    AnyFunction, Arguments, CreateFunctionResult, DataGenOutput, DynBinFun, DynDecimalFun, DynFun, ProgramContext,
+    RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// Decides whether to use one of the alternative encodings for each part of a value. This is shared by the cbor and
+/// messagepack builtins, which both have several valid encodings for most values
+pub(super) struct Variation<'a> {
+    ctx: &'a mut ProgramContext,
+    rate: f64,
+}
+
+impl<'a> Variation<'a> {
+    /// whether only the canonical encoding is ever used
+    pub(super) fn is_canonical(&self) -> bool {
+        self.rate <= 0.0
+    }
+
+    pub(super) fn vary(&mut self) -> bool {
+        self.ctx.gen_chance(self.rate)
+    }
+
+    /// returns the first option unless the encoding should be varied, in which case any of them may be returned
+    pub(super) fn choose<T: Copy>(&mut self, options: &[T]) -> T {
+        if self.vary() {
+            self.ctx.choose(options)
+        } else {
+            options[0]
+        }
+    }
+
+    pub(super) fn gen_range_inclusive(&mut self, min: usize, max: usize) -> usize {
+        self.ctx.gen_range_inclusive(min, max)
+    }
+}
+
+fn gen_variation_rate(rate: Option<&DynDecimalFun>, ctx: &mut ProgramContext) -> Result<f64, Error> {
+    match rate {
+        Some(fun) => fun.gen_value(ctx),
+        None => Ok(0.0),
+    }
+}
+
+pub(super) type EncodeFn<T> = fn(T, &mut Variation, &mut Vec<u8>) -> Result<(), Error>;
+pub(super) type EncodeItemsFn = fn(Vec<Vec<u8>>, &mut Variation, &mut Vec<u8>) -> Result<(), Error>;
+pub(super) type EncodeTaggedFn<T> = fn(T, Vec<u8>, &mut Variation, &mut Vec<u8>) -> Result<(), Error>;
+
+#[derive(Debug)]
+struct EncodeValue<T> {
+    variation_rate: Option<DynDecimalFun>,
+    value: DynFun<T>,
+    encode: EncodeFn<T>,
+}
+
+impl<T: Debug> RunnableFunction<Vec<u8>> for EncodeValue<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let rate = gen_variation_rate(self.variation_rate.as_ref(), ctx)?;
+        let value = self.value.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(16);
+        (self.encode)(value, &mut Variation { ctx, rate }, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+#[derive(Debug)]
+struct EncodeItems {
+    variation_rate: Option<DynDecimalFun>,
+    items: Vec<DynBinFun>,
+    encode: EncodeItemsFn,
+}
+
+impl RunnableFunction<Vec<u8>> for EncodeItems {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let rate = gen_variation_rate(self.variation_rate.as_ref(), ctx)?;
+        let mut items = Vec::with_capacity(self.items.len());
+        for item in self.items.iter() {
+            items.push(item.gen_value(ctx)?);
+        }
+        let mut buffer = Vec::with_capacity(items.iter().map(Vec::len).sum::<usize>() + 10);
+        (self.encode)(items, &mut Variation { ctx, rate }, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+#[derive(Debug)]
+struct EncodeTagged<T> {
+    variation_rate: Option<DynDecimalFun>,
+    tag: DynFun<T>,
+    item: DynBinFun,
+    encode: EncodeTaggedFn<T>,
+}
+
+impl<T: Debug> RunnableFunction<Vec<u8>> for EncodeTagged<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let rate = gen_variation_rate(self.variation_rate.as_ref(), ctx)?;
+        let tag = self.tag.gen_value(ctx)?;
+        let item = self.item.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(item.len() + 10);
+        (self.encode)(tag, item, &mut Variation { ctx, rate }, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+pub(super) const VARIATION_RATE_PARAM: &str = "variation_rate";
+pub(super) const VALUE_PARAM: &str = "value";
+pub(super) const ITEMS_PARAM: &str = "items";
+pub(super) const KEYS_AND_VALUES_PARAM: &str = "keys_and_values";
+
+/// Returns the variation rate if this is the varied version of a builtin, along with the position of the argument
+/// after it
+fn variation_rate_arg(args: &Arguments, varied: bool) -> Result<(Option<DynDecimalFun>, usize), Error> {
+    if varied {
+        let rate = args.required_arg(VARIATION_RATE_PARAM, 0, AnyFunction::require_decimal)?;
+        Ok((Some(rate), 1))
+    } else {
+        Ok((None, 0))
+    }
+}
+
+pub(super) fn create_encode_value<T: Debug + 'static>(
+    args: Arguments,
+    varied: bool,
+    convert: fn(AnyFunction) -> Result<DynFun<T>, Error>,
+    encode: EncodeFn<T>,
+) -> CreateFunctionResult {
+    let (variation_rate, position) = variation_rate_arg(&args, varied)?;
+    let value = args.required_arg(VALUE_PARAM, position, convert)?;
+    Ok(AnyFunction::Bin(Rc::new(EncodeValue {
+        variation_rate,
+        value,
+        encode,
+    })))
+}
+
+pub(super) fn create_encode_items(
+    args: Arguments,
+    varied: bool,
+    name: &str,
+    encode: EncodeItemsFn,
+) -> CreateFunctionResult {
+    let (variation_rate, position) = variation_rate_arg(&args, varied)?;
+    let items = args.get_required_varargs(name, position, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(EncodeItems {
+        variation_rate,
+        items,
+        encode,
+    })))
+}
+
+/// Variadic functions require at least one argument, so empty arrays and maps have their own prototypes
+pub(super) fn create_encode_no_items(
+    args: Arguments,
+    varied: bool,
+    encode: EncodeItemsFn,
+) -> CreateFunctionResult {
+    let (variation_rate, _) = variation_rate_arg(&args, varied)?;
+    Ok(AnyFunction::Bin(Rc::new(EncodeItems {
+        variation_rate,
+        items: Vec::new(),
+        encode,
+    })))
+}
+
+pub(super) fn create_encode_tagged<T: Debug + 'static>(
+    args: Arguments,
+    varied: bool,
+    tag_name: &str,
+    convert: fn(AnyFunction) -> Result<DynFun<T>, Error>,
+    encode: EncodeTaggedFn<T>,
+) -> CreateFunctionResult {
+    let (variation_rate, position) = variation_rate_arg(&args, varied)?;
+    let tag = args.required_arg(tag_name, position, convert)?;
+    let item = args.required_arg(VALUE_PARAM, position + 1, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(EncodeTagged {
+        variation_rate,
+        tag,
+        item,
+        encode,
+    })))
+}
+
+pub(super) fn require_even_items(function_name: &str, items: &[Vec<u8>]) -> Result<(), Error> {
+    if items.len() % 2 != 0 {
+        bail!(
+            "{} requires an even number of arguments, alternating between keys and values, but got {}",
+            function_name,
+            items.len()
+        );
+    }
+    Ok(())
+}
+
+/// Whether the value can be represented exactly as a single precision float
+pub(super) fn is_exact_single(value: f64) -> bool {
+    value.is_nan() || f64::from(value as f32) == value
+}
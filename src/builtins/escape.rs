@@ -0,0 +1,662 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Decides whether to use one of the alternative escapes for a given character
+struct Variation<'a> {
+    ctx: &'a mut ProgramContext,
+    rate: f64,
+}
+
+impl<'a> Variation<'a> {
+    fn vary(&mut self) -> bool {
+        self.ctx.gen_chance(self.rate)
+    }
+}
+
+type EscapeFn = fn(&str, &mut Variation, &mut String);
+
+fn write_hex_escape(prefix: &str, value: u32, digits: usize, uppercase: bool, out: &mut String) {
+    if uppercase {
+        let _ = write!(out, "{}{:0width$X}", prefix, value, width = digits);
+    } else {
+        let _ = write!(out, "{}{:0width$x}", prefix, value, width = digits);
+    }
+}
+
+fn escape_json(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push('"');
+    for c in input.chars() {
+        if variation.vary() {
+            if c == '/' {
+                out.push_str("\\/");
+            } else {
+                let uppercase = variation.vary();
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units).iter() {
+                    write_hex_escape("\\u", u32::from(*unit), 4, uppercase, out);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => write_hex_escape("\\u", c as u32, 4, false, out),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn is_xml_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x9 | 0xA | 0xD | 0x20..=0xD7FF | 0xE000..=0xFFFD | 0x10000..=0x10FFFF
+    )
+}
+
+fn write_xml_char_reference(c: char, variation: &mut Variation, out: &mut String) {
+    if variation.vary() {
+        let _ = write!(out, "&#{};", c as u32);
+    } else {
+        let uppercase = variation.vary();
+        write_hex_escape("&#x", c as u32, 1, uppercase, out);
+        out.push(';');
+    }
+}
+
+fn escape_xml_text(input: &str, variation: &mut Variation, out: &mut String) {
+    for c in input.chars() {
+        // characters that aren't allowed in xml at all can't be escaped either, so they're passed through as is
+        if is_xml_char(c) && variation.vary() {
+            write_xml_char_reference(c, variation, out);
+            continue;
+        }
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            // a literal carriage return would get normalized to a newline by the parser
+            '\r' => out.push_str("&#13;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn write_xml_attribute(quote: char, input: &str, variation: &mut Variation, out: &mut String) {
+    out.push(quote);
+    for c in input.chars() {
+        if is_xml_char(c) && variation.vary() {
+            write_xml_char_reference(c, variation, out);
+            continue;
+        }
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if quote == '"' => out.push_str("&quot;"),
+            '\'' if quote == '\'' => out.push_str("&apos;"),
+            // literal whitespace characters would get normalized to spaces by the parser
+            '\t' => out.push_str("&#9;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+}
+
+fn escape_xml_attribute(input: &str, variation: &mut Variation, out: &mut String) {
+    write_xml_attribute('"', input, variation, out);
+}
+
+fn escape_xml_single_quoted_attribute(input: &str, variation: &mut Variation, out: &mut String) {
+    write_xml_attribute('\'', input, variation, out);
+}
+
+fn escape_xml_cdata(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push_str("<![CDATA[");
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        out.push(c);
+        // a cdata section can't contain its own terminator, so it gets split across two sections. Sections may also
+        // be split anywhere else without changing the text
+        let is_terminator = c == ']' && out.ends_with("]]") && chars.peek() == Some(&'>');
+        if is_terminator || (chars.peek().is_some() && variation.vary()) {
+            out.push_str("]]><![CDATA[");
+        }
+    }
+    out.push_str("]]>");
+}
+
+fn escape_xml_comment(input: &str, _: &mut Variation, out: &mut String) {
+    out.push_str("<!--");
+    for c in input.chars() {
+        // comments can't contain a double hyphen, or end with a hyphen
+        if c == '-' && out.ends_with('-') {
+            out.push(' ');
+        }
+        out.push(c);
+    }
+    if out.ends_with('-') {
+        out.push(' ');
+    }
+    out.push_str("-->");
+}
+
+fn escape_csv_field(input: &str, variation: &mut Variation, out: &mut String) {
+    let needs_quotes = input.contains([',', '"', '\r', '\n']);
+    // quoting a field that doesn't need it is always allowed
+    if needs_quotes || variation.vary() {
+        out.push('"');
+        for c in input.chars() {
+            if c == '"' {
+                out.push_str("\"\"");
+            } else {
+                out.push(c);
+            }
+        }
+        out.push('"');
+    } else {
+        out.push_str(input);
+    }
+}
+
+fn escape_shell_single_quote(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push('\'');
+    for c in input.chars() {
+        if c == '\'' {
+            if variation.vary() {
+                out.push_str("'\"'\"'");
+            } else {
+                out.push_str("'\\''");
+            }
+        } else {
+            if variation.vary() {
+                // closing and immediately re-opening the quotes concatenates the two strings
+                out.push_str("''");
+            }
+            out.push(c);
+        }
+    }
+    out.push('\'');
+}
+
+fn escape_shell_double_quote(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push('"');
+    for c in input.chars() {
+        if c != '\'' && variation.vary() {
+            // switch to single quotes for just this one character
+            out.push_str("\"'");
+            out.push(c);
+            out.push_str("'\"");
+            continue;
+        }
+        match c {
+            '"' | '\\' | '$' | '`' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn escape_json5(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push('\'');
+    for c in input.chars() {
+        if variation.vary() {
+            let uppercase = variation.vary();
+            match c {
+                // an escaped line terminator is a line continuation, which adds nothing to the string value
+                _ if variation.vary() => {
+                    out.push_str("\\\n");
+                    write_json5_char(c, uppercase, out);
+                }
+                c if (c as u32) < 0x100 => write_hex_escape("\\x", c as u32, 2, uppercase, out),
+                c => {
+                    let mut units = [0u16; 2];
+                    for unit in c.encode_utf16(&mut units).iter() {
+                        write_hex_escape("\\u", u32::from(*unit), 4, uppercase, out);
+                    }
+                }
+            }
+            continue;
+        }
+        write_json5_char(c, false, out);
+    }
+    out.push('\'');
+}
+
+/// Writes a character of a single quoted json5 string, using the shortest escape if one is required
+fn write_json5_char(c: char, uppercase: bool, out: &mut String) {
+    match c {
+        '\'' => out.push_str("\\'"),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if (c as u32) < 0x20 => write_hex_escape("\\x", c as u32, 2, uppercase, out),
+        c => out.push(c),
+    }
+}
+
+fn escape_sql_string(input: &str, _: &mut Variation, out: &mut String) {
+    out.push('\'');
+    for c in input.chars() {
+        if c == '\'' {
+            out.push_str("''");
+        } else {
+            out.push(c);
+        }
+    }
+    out.push('\'');
+}
+
+fn write_c_octal_escapes(c: char, out: &mut String) {
+    let mut buffer = [0u8; 4];
+    for byte in c.encode_utf8(&mut buffer).bytes() {
+        // octal escapes always use all three digits so they can't consume a digit that follows them
+        let _ = write!(out, "\\{:03o}", byte);
+    }
+}
+
+fn escape_c_string(input: &str, variation: &mut Variation, out: &mut String) {
+    out.push('"');
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if variation.vary() {
+            // hex escapes consume every hex digit that follows them, so they can only be used when the next
+            // character isn't one
+            let next_is_hex = chars.peek().map(|n| n.is_ascii_hexdigit()).unwrap_or(false);
+            if c.is_ascii() && !next_is_hex && variation.vary() {
+                let uppercase = variation.vary();
+                write_hex_escape("\\x", c as u32, 2, uppercase, out);
+            } else if c == '?' {
+                out.push_str("\\?");
+            } else {
+                write_c_octal_escapes(c, out);
+            }
+            continue;
+        }
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == ' ' || c.is_ascii_graphic() => out.push(c),
+            c => write_c_octal_escapes(c, out),
+        }
+    }
+    out.push('"');
+}
+
+fn is_url_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' || c == '~'
+}
+
+fn escape_url_component(input: &str, variation: &mut Variation, out: &mut String) {
+    for c in input.chars() {
+        // percent encoding an unreserved character is allowed, and it's equivalent to the literal character
+        if is_url_unreserved(c) && !variation.vary() {
+            out.push(c);
+            continue;
+        }
+        let mut buffer = [0u8; 4];
+        for byte in c.encode_utf8(&mut buffer).bytes() {
+            let uppercase = !variation.vary();
+            write_hex_escape("%", u32::from(byte), 2, uppercase, out);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Escape {
+    variation_rate: Option<DynDecimalFun>,
+    string: DynStringFun,
+    escape: EscapeFn,
+}
+
+impl RunnableFunction<IString> for Escape {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let rate = match self.variation_rate.as_ref() {
+            Some(fun) => fun.gen_value(ctx)?,
+            None => 0.0,
+        };
+        let input = self.string.gen_value(ctx)?;
+        let mut escaped = String::with_capacity(input.len() + 2);
+        let mut variation = Variation { ctx, rate };
+        (self.escape)(&input, &mut variation, &mut escaped);
+        Ok(escaped.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const STRING_PARAM: &str = "string";
+const VARIATION_RATE_PARAM: &str = "variation_rate";
+
+macro_rules! make_escape_builtins {
+    ($proto_name:ident, $varied_proto_name:ident, $fun_name:expr, $escape_fn:path, $description:literal) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let string = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+                Ok(AnyFunction::String(Rc::new(Escape {
+                    variation_rate: None,
+                    string,
+                    escape: $escape_fn,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: $description,
+                arguments: &[(STRING_PARAM, GenType::String)],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+
+        pub const $varied_proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let (variation_rate, string) = args.require_2_args(
+                    VARIATION_RATE_PARAM,
+                    AnyFunction::require_decimal,
+                    STRING_PARAM,
+                    AnyFunction::require_string,
+                )?;
+                Ok(AnyFunction::String(Rc::new(Escape {
+                    variation_rate: Some(variation_rate),
+                    string,
+                    escape: $escape_fn,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!(
+                    $description,
+                    ". Each character is written using a randomly chosen equivalent escape with a probability of variation_rate"
+                ),
+                arguments: &[
+                    (VARIATION_RATE_PARAM, GenType::Decimal),
+                    (STRING_PARAM, GenType::String),
+                ],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_escape_builtins!(
+    JSON_STRING_BUILTIN,
+    JSON_STRING_VARIED_BUILTIN,
+    "json_string",
+    escape_json,
+    "formats the string as a double quoted json string, escaping any characters that require it"
+);
+make_escape_builtins!(
+    JSON5_STRING_BUILTIN,
+    JSON5_STRING_VARIED_BUILTIN,
+    "json5_string",
+    escape_json5,
+    "formats the string as a single quoted json5 string, escaping any characters that require it"
+);
+make_escape_builtins!(
+    XML_TEXT_BUILTIN,
+    XML_TEXT_VARIED_BUILTIN,
+    "xml_text",
+    escape_xml_text,
+    "escapes the string for use as xml text content"
+);
+make_escape_builtins!(
+    XML_ATTRIBUTE_BUILTIN,
+    XML_ATTRIBUTE_VARIED_BUILTIN,
+    "xml_attribute",
+    escape_xml_attribute,
+    "formats the string as a double quoted xml attribute value, escaping any characters that require it"
+);
+make_escape_builtins!(
+    XML_SINGLE_QUOTED_ATTRIBUTE_BUILTIN,
+    XML_SINGLE_QUOTED_ATTRIBUTE_VARIED_BUILTIN,
+    "xml_single_quoted_attribute",
+    escape_xml_single_quoted_attribute,
+    "formats the string as a single quoted xml attribute value, escaping any characters that require it"
+);
+make_escape_builtins!(
+    XML_CDATA_BUILTIN,
+    XML_CDATA_VARIED_BUILTIN,
+    "xml_cdata",
+    escape_xml_cdata,
+    "wraps the string in an xml CDATA section, splitting it into two sections wherever it contains ']]>'"
+);
+make_escape_builtins!(
+    CSV_FIELD_BUILTIN,
+    CSV_FIELD_VARIED_BUILTIN,
+    "csv_field",
+    escape_csv_field,
+    "formats the string as an RFC 4180 csv field, which is quoted only if it contains a comma, double quote, or line break"
+);
+make_escape_builtins!(
+    SHELL_SINGLE_QUOTE_BUILTIN,
+    SHELL_SINGLE_QUOTE_VARIED_BUILTIN,
+    "shell_single_quote",
+    escape_shell_single_quote,
+    "formats the string as a single quoted posix shell word"
+);
+make_escape_builtins!(
+    SHELL_DOUBLE_QUOTE_BUILTIN,
+    SHELL_DOUBLE_QUOTE_VARIED_BUILTIN,
+    "shell_double_quote",
+    escape_shell_double_quote,
+    "formats the string as a double quoted posix shell word, escaping any characters that would otherwise be expanded"
+);
+make_escape_builtins!(
+    C_STRING_BUILTIN,
+    C_STRING_VARIED_BUILTIN,
+    "c_string",
+    escape_c_string,
+    "formats the string as a C string literal. Non-ascii characters are written as octal escapes of their utf-8 bytes"
+);
+make_escape_builtins!(
+    URL_COMPONENT_BUILTIN,
+    URL_COMPONENT_VARIED_BUILTIN,
+    "url_component",
+    escape_url_component,
+    "percent encodes every character of the string except for the unreserved characters from RFC 3986"
+);
+
+/// There's only one valid way to write a comment, so there's no variation
+pub const XML_COMMENT_BUILTIN: &BuiltinFunctionPrototype = {
+    fn create(args: Arguments) -> CreateFunctionResult {
+        let string = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+        Ok(AnyFunction::String(Rc::new(Escape {
+            variation_rate: None,
+            string,
+            escape: escape_xml_comment,
+        })))
+    }
+
+    &BuiltinFunctionPrototype {
+        function_name: "xml_comment",
+        description: "wraps the string in an xml comment, inserting a space wherever it contains '--' or ends with '-'",
+        arguments: &[(STRING_PARAM, GenType::String)],
+        variadic: false,
+        create_fn: &create,
+    }
+};
+
+/// Standard SQL only has one way to escape a quote, so there's no variation
+pub const SQL_STRING_BUILTIN: &BuiltinFunctionPrototype = {
+    fn create(args: Arguments) -> CreateFunctionResult {
+        let string = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+        Ok(AnyFunction::String(Rc::new(Escape {
+            variation_rate: None,
+            string,
+            escape: escape_sql_string,
+        })))
+    }
+
+    &BuiltinFunctionPrototype {
+        function_name: "sql_string",
+        description: "formats the string as a standard SQL string literal, with embedded single quotes doubled",
+        arguments: &[(STRING_PARAM, GenType::String)],
+        variadic: false,
+        create_fn: &create,
+    }
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{run_program, test_program_success};
+
+    fn unescape_json(input: &str) -> String {
+        let inner = &input[1..input.len() - 1];
+        let mut units = Vec::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buf = [0u16; 2];
+                units.extend_from_slice(c.encode_utf16(&mut buf));
+                continue;
+            }
+            let unit = match chars.next().unwrap() {
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    u16::from_str_radix(&hex, 16).unwrap()
+                }
+                'n' => 0x0A,
+                'r' => 0x0D,
+                't' => 0x09,
+                'b' => 0x08,
+                'f' => 0x0C,
+                other => other as u16,
+            };
+            units.push(unit);
+        }
+        String::from_utf16(&units).unwrap()
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        test_program_success(
+            1,
+            r#"json_string("a \"quoted\" \\ value\n\u{1}")"#,
+            r#""a \"quoted\" \\ value\n\u0001""#,
+        );
+        test_program_success(
+            1,
+            r#"json5_string("it's \"quoted\"\n\u{1}")"#,
+            r#"'it\'s "quoted"\n\x01'"#,
+        );
+    }
+
+    #[test]
+    fn varied_json_strings_decode_to_the_original() {
+        let input = "a/\"\\\n\u{1}\u{e9}\u{1F600}z";
+        let program = format!("json_string(0.5, \"{}\")", input.escape_default());
+        let output = run_program(1, &program).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_ne!(output, "\"a/\\\"\\\\\\n\\u0001\u{e9}\u{1F600}z\"");
+        assert_eq!(input, unescape_json(&output));
+    }
+
+    #[test]
+    fn xml_text_and_attributes_are_escaped() {
+        test_program_success(
+            1,
+            r#"xml_text("a < b && c > d")"#,
+            "a &lt; b &amp;&amp; c &gt; d",
+        );
+        test_program_success(
+            1,
+            r#"xml_attribute("say \"hi\"\n<tab>\t")"#,
+            "\"say &quot;hi&quot;&#10;&lt;tab&gt;&#9;\"",
+        );
+    }
+
+    #[test]
+    fn xml_sections_can_not_be_terminated_early() {
+        test_program_success(1, r#"xml_single_quoted_attribute("it's \"ok\"")"#, "'it&apos;s \"ok\"'");
+        test_program_success(1, r#"xml_cdata("a]]>b")"#, "<![CDATA[a]]]]><![CDATA[>b]]>");
+        test_program_success(1, r#"xml_comment("a--b-")"#, "<!--a- -b- -->");
+        test_program_success(1, r#"xml_comment("---")"#, "<!-- - - - -->");
+    }
+
+    #[test]
+    fn csv_fields_are_only_quoted_when_required() {
+        test_program_success(1, r#"csv_field("plain")"#, "plain");
+        test_program_success(1, r#"csv_field("a,b")"#, "\"a,b\"");
+        test_program_success(1, r#"csv_field("say \"hi\"")"#, "\"say \"\"hi\"\"\"");
+        test_program_success(1, r#"csv_field(1.0, "plain")"#, "\"plain\"");
+    }
+
+    #[test]
+    fn shell_words_are_quoted() {
+        test_program_success(
+            1,
+            r#"shell_single_quote("it's $HOME")"#,
+            r#"'it'\''s $HOME'"#,
+        );
+        test_program_success(
+            1,
+            r#"shell_double_quote("it's \"$HOME\" `pwd` \\")"#,
+            r#""it's \"\$HOME\" \`pwd\` \\""#,
+        );
+    }
+
+    #[test]
+    fn sql_and_c_strings_are_escaped() {
+        test_program_success(1, r#"sql_string("it's")"#, "'it''s'");
+        test_program_success(
+            1,
+            r#"c_string("tab\t\"q\" \u{e9}\u{7F}")"#,
+            r#""tab\t\"q\" \303\251\177""#,
+        );
+    }
+
+    #[test]
+    fn c_hex_escapes_are_never_followed_by_hex_digits() {
+        let mut variation_ctx = ProgramContext::from_seed([3; 16], crate::verbosity::NORMAL);
+        let mut variation = Variation {
+            ctx: &mut variation_ctx,
+            rate: 1.0,
+        };
+        for _ in 0..50 {
+            let mut out = String::new();
+            escape_c_string("abc", &mut variation, &mut out);
+            assert!(
+                !out.contains("\\x61b") && !out.contains("\\x62c"),
+                "invalid escape: {}",
+                out
+            );
+        }
+    }
+
+    #[test]
+    fn url_components_are_percent_encoded() {
+        test_program_success(
+            1,
+            r#"url_component("a b/c?d=\u{e9}~")"#,
+            "a%20b%2Fc%3Fd%3D%C3%A9~",
+        );
+        test_program_success(1, r#"url_component(1.0, "")"#, "");
+    }
+}
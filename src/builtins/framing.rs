@@ -0,0 +1,347 @@
+use super::byte_order::FixedWidth;
+use super::varint::{write_ber_length, write_uleb128};
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynBinFun, DynIntFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use byteorder::{BigEndian, LittleEndian};
+use failure::Error;
+use std::rc::Rc;
+
+fn write_with_endianness(
+    endianness: &str,
+    width_bits: u64,
+    value: u64,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    match endianness {
+        "big" => value.write_fixed_width::<BigEndian>(width_bits, out),
+        "little" => value.write_fixed_width::<LittleEndian>(width_bits, out),
+        other => bail!(
+            "Invalid endianness: '{}', must be either 'big' or 'little'",
+            other
+        ),
+    }
+}
+
+fn max_for_width(width_bits: u64) -> u64 {
+    if width_bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << width_bits) - 1
+    }
+}
+
+/// Adds the offset to the true length, saturating at zero and at the largest length that fits in the width
+fn lie_about_length(true_length: u64, offset: i64, width_bits: u64) -> u64 {
+    let adjusted = if offset < 0 {
+        true_length.saturating_sub(offset.unsigned_abs())
+    } else {
+        true_length.saturating_add(offset as u64)
+    };
+    adjusted.min(max_for_width(width_bits))
+}
+
+#[derive(Debug)]
+struct LengthPrefixed {
+    width_bits: DynUintFun,
+    endianness: DynStringFun,
+    length_offset: Option<DynIntFun>,
+    body: DynBinFun,
+}
+
+impl RunnableFunction<Vec<u8>> for LengthPrefixed {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let width_bits = self.width_bits.gen_value(ctx)?;
+        let endianness = self.endianness.gen_value(ctx)?;
+        let offset = match self.length_offset.as_ref() {
+            Some(fun) => Some(fun.gen_value(ctx)?),
+            None => None,
+        };
+        let body = self.body.gen_value(ctx)?;
+
+        let length = match offset {
+            Some(offset) => lie_about_length(body.len() as u64, offset, width_bits),
+            None => body.len() as u64,
+        };
+        let mut framed = Vec::with_capacity(body.len() + 8);
+        write_with_endianness(&endianness, width_bits, length, &mut framed)?;
+        framed.extend_from_slice(body.as_slice());
+        Ok(framed)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct Tlv {
+    type_width_bits: DynUintFun,
+    length_width_bits: DynUintFun,
+    endianness: DynStringFun,
+    tag: DynUintFun,
+    value: DynBinFun,
+}
+
+impl RunnableFunction<Vec<u8>> for Tlv {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let type_width_bits = self.type_width_bits.gen_value(ctx)?;
+        let length_width_bits = self.length_width_bits.gen_value(ctx)?;
+        let endianness = self.endianness.gen_value(ctx)?;
+        let tag = self.tag.gen_value(ctx)?;
+        let value = self.value.gen_value(ctx)?;
+
+        let mut framed = Vec::with_capacity(value.len() + 16);
+        write_with_endianness(&endianness, type_width_bits, tag, &mut framed)?;
+        write_with_endianness(
+            &endianness,
+            length_width_bits,
+            value.len() as u64,
+            &mut framed,
+        )?;
+        framed.extend_from_slice(value.as_slice());
+        Ok(framed)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct BerTlv {
+    tag: DynUintFun,
+    value: DynBinFun,
+}
+
+impl RunnableFunction<Vec<u8>> for BerTlv {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let tag = self.tag.gen_value(ctx)?;
+        if tag > 0xFF {
+            bail!("Invalid BER tag: {}, must be a single identifier byte", tag);
+        }
+        let value = self.value.gen_value(ctx)?;
+
+        let mut framed = Vec::with_capacity(value.len() + 10);
+        framed.push(tag as u8);
+        write_ber_length(value.len() as u64, &mut framed)?;
+        framed.extend_from_slice(value.as_slice());
+        Ok(framed)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct VarintPrefixed(DynBinFun);
+
+impl RunnableFunction<Vec<u8>> for VarintPrefixed {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let body = self.0.gen_value(ctx)?;
+        let mut framed = Vec::with_capacity(body.len() + 10);
+        write_uleb128(body.len() as u64, &mut framed)?;
+        framed.extend_from_slice(body.as_slice());
+        Ok(framed)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+const WIDTH_PARAM: &str = "width_bits";
+const ENDIANNESS_PARAM: &str = "endianness";
+const OFFSET_PARAM: &str = "length_offset";
+const BODY_PARAM: &str = "body";
+const TYPE_WIDTH_PARAM: &str = "type_width_bits";
+const LENGTH_WIDTH_PARAM: &str = "length_width_bits";
+const TAG_PARAM: &str = "tag";
+const VALUE_PARAM: &str = "value";
+
+fn create_length_prefixed(args: Arguments) -> CreateFunctionResult {
+    let (width_bits, endianness, body) = args.require_3_args(
+        WIDTH_PARAM,
+        AnyFunction::require_uint,
+        ENDIANNESS_PARAM,
+        AnyFunction::require_string,
+        BODY_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(LengthPrefixed {
+        width_bits,
+        endianness,
+        length_offset: None,
+        body,
+    })))
+}
+
+fn create_lying_length_prefixed(args: Arguments) -> CreateFunctionResult {
+    let width_bits = args.required_arg(WIDTH_PARAM, 0, AnyFunction::require_uint)?;
+    let endianness = args.required_arg(ENDIANNESS_PARAM, 1, AnyFunction::require_string)?;
+    let length_offset = args.required_arg(OFFSET_PARAM, 2, AnyFunction::require_int)?;
+    let body = args.required_arg(BODY_PARAM, 3, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(LengthPrefixed {
+        width_bits,
+        endianness,
+        length_offset: Some(length_offset),
+        body,
+    })))
+}
+
+fn create_tlv(args: Arguments) -> CreateFunctionResult {
+    let type_width_bits = args.required_arg(TYPE_WIDTH_PARAM, 0, AnyFunction::require_uint)?;
+    let length_width_bits = args.required_arg(LENGTH_WIDTH_PARAM, 1, AnyFunction::require_uint)?;
+    let endianness = args.required_arg(ENDIANNESS_PARAM, 2, AnyFunction::require_string)?;
+    let tag = args.required_arg(TAG_PARAM, 3, AnyFunction::require_uint)?;
+    let value = args.required_arg(VALUE_PARAM, 4, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(Tlv {
+        type_width_bits,
+        length_width_bits,
+        endianness,
+        tag,
+        value,
+    })))
+}
+
+fn create_ber_tlv(args: Arguments) -> CreateFunctionResult {
+    let (tag, value) = args.require_2_args(
+        TAG_PARAM,
+        AnyFunction::require_uint,
+        VALUE_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(BerTlv { tag, value })))
+}
+
+fn create_varint_prefixed(args: Arguments) -> CreateFunctionResult {
+    let body = args.required_arg(BODY_PARAM, 0, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(VarintPrefixed(body))))
+}
+
+pub const LENGTH_PREFIXED_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "length_prefixed",
+    description: "generates the body once and prefixes it with its length in bytes, as an unsigned integer of the given width (8, 16, 24, 32, or 64) and endianness ('big' or 'little'). Strings should be converted using string_bytes first, so that the length matches the encoding",
+    arguments: &[
+        (WIDTH_PARAM, GenType::Uint),
+        (ENDIANNESS_PARAM, GenType::String),
+        (BODY_PARAM, GenType::Bin),
+    ],
+    variadic: false,
+    create_fn: &create_length_prefixed,
+};
+
+pub const LYING_LENGTH_PREFIXED_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "lying_length_prefixed",
+    description: "like length_prefixed, except that length_offset is added to the length that gets written, for generating invalid frames. The written length is clamped between 0 and the largest value that fits in the width",
+    arguments: &[
+        (WIDTH_PARAM, GenType::Uint),
+        (ENDIANNESS_PARAM, GenType::String),
+        (OFFSET_PARAM, GenType::Int),
+        (BODY_PARAM, GenType::Bin),
+    ],
+    variadic: false,
+    create_fn: &create_lying_length_prefixed,
+};
+
+pub const TLV_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "tlv",
+    description: "generates a type-length-value triple, with the tag and the length of the value written as unsigned integers of the given widths and endianness ('big' or 'little')",
+    arguments: &[
+        (TYPE_WIDTH_PARAM, GenType::Uint),
+        (LENGTH_WIDTH_PARAM, GenType::Uint),
+        (ENDIANNESS_PARAM, GenType::String),
+        (TAG_PARAM, GenType::Uint),
+        (VALUE_PARAM, GenType::Bin),
+    ],
+    variadic: false,
+    create_fn: &create_tlv,
+};
+
+pub const BER_TLV_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ber_tlv",
+    description: "generates an ASN.1 BER/DER encoded element, with a single identifier byte followed by the definite length of the value and the value itself",
+    arguments: &[(TAG_PARAM, GenType::Uint), (VALUE_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_ber_tlv,
+};
+
+pub const VARINT_PREFIXED_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "varint_prefixed",
+    description: "prefixes the body with its length encoded as an unsigned LEB128 varint, as in protobuf length delimited fields",
+    arguments: &[(BODY_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_varint_prefixed,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error};
+
+    #[test]
+    fn body_is_prefixed_with_its_length() {
+        assert_bin_output_is_expected("length_prefixed(16, \"big\", [1, 2, 3])", &[0, 3, 1, 2, 3]);
+        assert_bin_output_is_expected(
+            "length_prefixed(32, \"little\", [1, 2])",
+            &[2, 0, 0, 0, 1, 2],
+        );
+        // the length counts the encoded bytes rather than the utf-8 bytes
+        assert_bin_output_is_expected(
+            "length_prefixed(8, \"big\", string_bytes(\"utf-16be\", \"ab\"))",
+            &[4, 0, 0x61, 0, 0x62],
+        );
+        assert_bin_output_is_expected("varint_prefixed(repeat(200, [7]))", &{
+            let mut expected = vec![0xC8, 0x01];
+            expected.extend_from_slice(&[7; 200]);
+            expected
+        });
+    }
+
+    #[test]
+    fn frames_can_be_nested() {
+        let program = "length_prefixed(8, \"big\", concat(length_prefixed(8, \"big\", [0xAA]), tlv(8, 16, \"big\", 9, [0xBB])))";
+        assert_bin_output_is_expected(program, &[6, 1, 0xAA, 9, 0, 1, 0xBB]);
+    }
+
+    #[test]
+    fn body_is_only_generated_once() {
+        let program = "length_prefixed(8, \"big\", repeat(uint(1, 20), [0x55]))";
+        for _ in 0..10 {
+            let output = crate::fun_test::run_program(1, program).unwrap();
+            assert_eq!(output[0] as usize, output.len() - 1);
+        }
+    }
+
+    #[test]
+    fn lying_length_prefix_is_clamped_to_width() {
+        assert_bin_output_is_expected("lying_length_prefixed(8, \"big\", -1, [1, 2])", &[1, 1, 2]);
+        assert_bin_output_is_expected("lying_length_prefixed(8, \"big\", -5, [1, 2])", &[0, 1, 2]);
+        assert_bin_output_is_expected(
+            "lying_length_prefixed(8, \"big\", +1000, [1, 2])",
+            &[0xFF, 1, 2],
+        );
+    }
+
+    #[test]
+    fn ber_tlv_uses_long_form_lengths() {
+        assert_bin_output_is_expected("ber_tlv(0x04, [1, 2])", &[0x04, 2, 1, 2]);
+        let mut expected = vec![0x30, 0x81, 0x80];
+        expected.extend_from_slice(&[0; 128]);
+        assert_bin_output_is_expected("ber_tlv(0x30, repeat(128, [0]))", &expected);
+    }
+
+    #[test]
+    fn invalid_frames_return_errors() {
+        assert_runtime_error(
+            "length_prefixed(8, \"middle\", [1])",
+            "Invalid endianness: 'middle'",
+        );
+        assert_runtime_error(
+            "length_prefixed(8, \"big\", repeat(256, [1]))",
+            "The value 256 does not fit in an unsigned 8 bit integer",
+        );
+        assert_runtime_error("ber_tlv(256, [1])", "Invalid BER tag: 256");
+    }
+}
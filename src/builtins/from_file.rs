@@ -4,15 +4,16 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::fs::File;
-use std::io::{self, Read, Seek, SeekFrom};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
 use std::rc::Rc;
 use crate::{
     AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
-    DynStringFun, GenType, ProgramContext, RunnableFunction,
+    DynBinFun, DynBooleanFun, DynStringFun, GenType, ProgramContext, RunnableFunction,
 };
 use crate::IString;
 
-struct RandFileReader {
+/// Reads random regions of a file, using an index of the offsets of every delimiter in the file
+pub(crate) struct RandFileReader {
     file: File,
     file_len: u64,
     delimiter: IString,
@@ -32,18 +33,51 @@ impl RandFileReader {
         })
     }
 
+    /// Creates a reader where each region is a newline terminated record. Newlines that appear within a quoted
+    /// csv field do not end a record, so that quoted fields may span multiple lines
+    pub(crate) fn create_records(path: &str) -> Result<RandFileReader, Error> {
+        let mut file = File::open(path)
+            .map_err(|err| format_err!("Failed to open the file '{}': {}", path, err))?;
+        let file_len = file.metadata()?.len();
+        let region_offsets = find_record_offsets(&mut file)?;
+        Ok(RandFileReader {
+            file,
+            file_len,
+            region_offsets,
+            delimiter: "\n".into(),
+        })
+    }
+
+    /// The number of regions in the file, not counting the empty region after a delimiter at the very end
+    pub(crate) fn region_count(&self) -> usize {
+        let trailing_delimiter = self
+            .region_offsets
+            .last()
+            .map(|offset| offset + self.delimiter.len() as u64 == self.file_len)
+            .unwrap_or(self.file_len == 0);
+        self.region_offsets.len() + 1 - trailing_delimiter as usize
+    }
+
     fn read_random_region<'a>(
         &mut self,
         rng: &mut ProgramContext,
         buffer: &'a mut Vec<u8>,
-    ) -> Result<&'a str, Error> {
+    ) -> Result<&'a [u8], Error> {
+        let region_idx = rng.gen_range_inclusive(0, self.region_offsets.len());
+        self.read_region(region_idx, buffer)
+    }
+
+    pub(crate) fn read_region<'a>(
+        &mut self,
+        region_idx: usize,
+        buffer: &'a mut Vec<u8>,
+    ) -> Result<&'a [u8], Error> {
         let RandFileReader {
             ref mut file,
             ref file_len,
             ref region_offsets,
             ref delimiter,
         } = *self;
-        let region_idx = rng.gen_range_inclusive(0, region_offsets.len());
         let region_start = if region_idx == 0 {
             0
         } else {
@@ -52,11 +86,9 @@ impl RandFileReader {
             region_offsets[region_idx - 1] + delimiter.len() as u64
         };
 
-        let nread = if region_offsets[region_idx.min(region_offsets.len() - 1)] == region_start {
-            0
-        } else if region_idx < (region_offsets.len() - 1) {
+        let nread = if region_idx < region_offsets.len() {
             // there's another region after this one, so we'll stop there
-            region_offsets[region_idx] - region_start // - delimiter.len() as u64
+            region_offsets[region_idx] - region_start
         } else {
             *file_len - region_start // we'll just read to the end of the file
         };
@@ -68,8 +100,7 @@ impl RandFileReader {
         let buf_slice = &mut buffer[0..nread as usize];
         file.seek(SeekFrom::Start(region_start))?;
         file.read_exact(buf_slice)?;
-
-        ::std::str::from_utf8(buf_slice).map_err(Into::into)
+        Ok(buf_slice)
     }
 }
 
@@ -91,7 +122,7 @@ impl SelectFromFileInner {
         ctx: &mut ProgramContext,
         filename: IString,
         delimiter: IString,
-    ) -> Result<&str, Error> {
+    ) -> Result<&[u8], Error> {
         let SelectFromFileInner {
             ref mut read_buffer,
             ref mut readers,
@@ -124,19 +155,41 @@ impl Debug for SelectFromFile {
     }
 }
 
-impl RunnableFunction<IString> for SelectFromFile {
-    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
-        let SelectFromFile {
-            ref file_path_gen,
-            ref delimiter_gen,
-            ref inner,
-        } = *self;
+impl SelectFromFile {
+    pub fn new(path: DynStringFun, delimiter: DynStringFun) -> DynStringFun {
+        SelectFromFile::create(path, delimiter)
+    }
 
-        let path = file_path_gen.gen_value(ctx)?;
-        let delimiter = delimiter_gen.gen_value(ctx)?;
+    pub fn new_bin(path: DynStringFun, delimiter: DynStringFun) -> DynBinFun {
+        SelectFromFile::create(path, delimiter)
+    }
 
-        let mut cell = inner.borrow_mut();
-        cell.read(ctx, path, delimiter).map(Into::into)
+    fn create(path: DynStringFun, delimiter: DynStringFun) -> Rc<SelectFromFile> {
+        Rc::new(SelectFromFile {
+            file_path_gen: path,
+            delimiter_gen: delimiter,
+            inner: RefCell::new(SelectFromFileInner::new()),
+        })
+    }
+
+    fn with_region<F, R>(&self, ctx: &mut ProgramContext, fun: F) -> Result<R, Error>
+    where
+        F: FnOnce(&str, &[u8]) -> Result<R, Error>,
+    {
+        let path = self.file_path_gen.gen_value(ctx)?;
+        let delimiter = self.delimiter_gen.gen_value(ctx)?;
+
+        let mut cell = self.inner.borrow_mut();
+        let region = cell.read(ctx, path.clone(), delimiter)?;
+        fun(&path, region)
+    }
+}
+
+impl RunnableFunction<IString> for SelectFromFile {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        self.with_region(ctx, |path, region| {
+            to_utf8(path, region, SELECT_FROM_FILE_BIN_BUILTIN).map(Into::into)
+        })
     }
 
     fn write_value(
@@ -144,29 +197,40 @@ impl RunnableFunction<IString> for SelectFromFile {
         ctx: &mut ProgramContext,
         output: &mut DataGenOutput,
     ) -> Result<(), Error> {
-        let SelectFromFile {
-            ref file_path_gen,
-            ref delimiter_gen,
-            ref inner,
-        } = *self;
+        self.with_region(ctx, |path, region| {
+            let str_value = to_utf8(path, region, SELECT_FROM_FILE_BIN_BUILTIN)?;
+            output.write_string(str_value)
+        })
+    }
+}
 
-        let path = file_path_gen.gen_value(ctx)?;
-        let delimiter = delimiter_gen.gen_value(ctx)?;
+impl RunnableFunction<Vec<u8>> for SelectFromFile {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        self.with_region(ctx, |_, region| Ok(region.to_vec()))
+    }
 
-        let mut cell = inner.borrow_mut();
-        let str_value = cell.read(ctx, path, delimiter)?;
-        output.write_string(str_value).map_err(Into::into)
+    fn write_value(
+        &self,
+        ctx: &mut ProgramContext,
+        output: &mut DataGenOutput,
+    ) -> Result<(), Error> {
+        self.with_region(ctx, |_, region| output.write_bytes(region))
     }
 }
 
-impl SelectFromFile {
-    pub fn new(path: DynStringFun, delimiter: DynStringFun) -> DynStringFun {
-        Rc::new(SelectFromFile {
-            file_path_gen: path,
-            delimiter_gen: delimiter,
-            inner: RefCell::new(SelectFromFileInner::new()),
-        })
-    }
+fn to_utf8<'a>(
+    path: &str,
+    bytes: &'a [u8],
+    bin_function: &BuiltinFunctionPrototype,
+) -> Result<&'a str, Error> {
+    ::std::str::from_utf8(bytes).map_err(|err| {
+        format_err!(
+            "The file '{}' contains invalid utf-8: {}. Use `{}` to read it as binary",
+            path,
+            err,
+            bin_function.function_name
+        )
+    })
 }
 
 fn is_region_start(buffer: &[u8], idx: usize, delimiter: &[u8]) -> bool {
@@ -223,9 +287,284 @@ fn find_region_offsets(file: &mut File, delimiter: &str) -> Result<Vec<u64>, io:
     Ok(result)
 }
 
+struct SequentialReader {
+    reader: BufReader<File>,
+    delimiter: IString,
+}
+
+impl SequentialReader {
+    fn create(path: &str, delimiter: IString) -> Result<SequentialReader, Error> {
+        if delimiter.is_empty() {
+            bail!("Cannot read records from '{}' using an empty delimiter", path);
+        }
+        let file = File::open(path)
+            .map_err(|err| format_err!("Failed to open the file '{}': {}", path, err))?;
+        Ok(SequentialReader {
+            reader: BufReader::new(file),
+            delimiter,
+        })
+    }
+
+    /// Reads the next record into the buffer, without its trailing delimiter. Returns false once the end of
+    /// the file has been reached. A delimiter at the very end of the file does not start another record.
+    fn read_next(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Error> {
+        let SequentialReader {
+            ref mut reader,
+            ref delimiter,
+        } = *self;
+        let delimiter = delimiter.as_bytes();
+        let last_byte = delimiter[delimiter.len() - 1];
+
+        buffer.clear();
+        loop {
+            if reader.read_until(last_byte, buffer)? == 0 {
+                return Ok(!buffer.is_empty());
+            }
+            if buffer.ends_with(delimiter) {
+                let record_len = buffer.len() - delimiter.len();
+                buffer.truncate(record_len);
+                return Ok(true);
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        self.reader.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+struct NextRecordInner {
+    read_buffer: Vec<u8>,
+    readers: HashMap<(IString, IString), SequentialReader>,
+}
+
+impl NextRecordInner {
+    fn new() -> NextRecordInner {
+        NextRecordInner {
+            read_buffer: Vec::with_capacity(512),
+            readers: HashMap::new(),
+        }
+    }
+
+    fn read(&mut self, filename: IString, delimiter: IString, wrap: bool) -> Result<&[u8], Error> {
+        let NextRecordInner {
+            ref mut read_buffer,
+            ref mut readers,
+        } = *self;
+        let key = (filename, delimiter);
+        if !readers.contains_key(&key) {
+            let reader = SequentialReader::create(&key.0, key.1.clone())?;
+            readers.insert(key.clone(), reader);
+        }
+        let reader = readers.get_mut(&key).unwrap();
+
+        if !reader.read_next(read_buffer)? {
+            if !wrap {
+                bail!("Reached the end of the file '{}'", key.0);
+            }
+            reader.rewind()?;
+            if !reader.read_next(read_buffer)? {
+                bail!("Cannot read records from '{}' because the file is empty", key.0);
+            }
+        }
+        Ok(&read_buffer[..])
+    }
+}
+
+/// Reads the records of a file in order, one per invocation. Each function keeps its own position in each file,
+/// so that two calls can walk the same or different files independently.
+pub struct NextRecord {
+    file_path_gen: DynStringFun,
+    /// `None` means read lines, which accepts either `\n` or `\r\n` line endings
+    delimiter_gen: Option<DynStringFun>,
+    wrap_gen: Option<DynBooleanFun>,
+    inner: RefCell<NextRecordInner>,
+}
+
+impl Debug for NextRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NextRecord")
+            .field("file_path", &self.file_path_gen)
+            .field("delimiter", &self.delimiter_gen)
+            .field("wrap", &self.wrap_gen)
+            .finish()
+    }
+}
+
+impl NextRecord {
+    fn create(
+        path: DynStringFun,
+        delimiter: Option<DynStringFun>,
+        wrap: Option<DynBooleanFun>,
+    ) -> Rc<NextRecord> {
+        Rc::new(NextRecord {
+            file_path_gen: path,
+            delimiter_gen: delimiter,
+            wrap_gen: wrap,
+            inner: RefCell::new(NextRecordInner::new()),
+        })
+    }
+
+    fn with_record<F, R>(&self, ctx: &mut ProgramContext, fun: F) -> Result<R, Error>
+    where
+        F: FnOnce(&str, &[u8]) -> Result<R, Error>,
+    {
+        let path = self.file_path_gen.gen_value(ctx)?;
+        let delimiter = match self.delimiter_gen {
+            Some(ref delimiter_gen) => delimiter_gen.gen_value(ctx)?,
+            None => IString::from("\n"),
+        };
+        let wrap = match self.wrap_gen {
+            Some(ref wrap_gen) => wrap_gen.gen_value(ctx)?,
+            None => true,
+        };
+
+        let mut cell = self.inner.borrow_mut();
+        let mut record = cell.read(path.clone(), delimiter, wrap)?;
+        if self.delimiter_gen.is_none() && record.ends_with(b"\r") {
+            record = &record[..record.len() - 1];
+        }
+        fun(&path, record)
+    }
+
+    fn bin_function(&self) -> &'static BuiltinFunctionPrototype {
+        if self.delimiter_gen.is_none() {
+            NEXT_LINE_BIN_BUILTIN
+        } else {
+            NEXT_RECORD_BIN_BUILTIN
+        }
+    }
+}
+
+impl RunnableFunction<IString> for NextRecord {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        self.with_record(ctx, |path, record| {
+            to_utf8(path, record, self.bin_function()).map(Into::into)
+        })
+    }
+
+    fn write_value(
+        &self,
+        ctx: &mut ProgramContext,
+        output: &mut DataGenOutput,
+    ) -> Result<(), Error> {
+        self.with_record(ctx, |path, record| {
+            let str_value = to_utf8(path, record, self.bin_function())?;
+            output.write_string(str_value)
+        })
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for NextRecord {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        self.with_record(ctx, |_, record| Ok(record.to_vec()))
+    }
+
+    fn write_value(
+        &self,
+        ctx: &mut ProgramContext,
+        output: &mut DataGenOutput,
+    ) -> Result<(), Error> {
+        self.with_record(ctx, |_, record| output.write_bytes(record))
+    }
+}
+
+pub struct FileBytes {
+    file_path_gen: DynStringFun,
+    contents: RefCell<HashMap<IString, Rc<Vec<u8>>>>,
+}
+
+impl Debug for FileBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileBytes")
+            .field("file_path", &self.file_path_gen)
+            .finish()
+    }
+}
+
+impl FileBytes {
+    fn get_contents(&self, ctx: &mut ProgramContext) -> Result<Rc<Vec<u8>>, Error> {
+        let path = self.file_path_gen.gen_value(ctx)?;
+        let mut contents = self.contents.borrow_mut();
+        if let Some(bytes) = contents.get(&path) {
+            return Ok(bytes.clone());
+        }
+        let bytes = ::std::fs::read(&*path)
+            .map_err(|err| format_err!("Failed to read the file '{}': {}", path, err))?;
+        let bytes = Rc::new(bytes);
+        contents.insert(path, bytes.clone());
+        Ok(bytes)
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for FileBytes {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        self.get_contents(ctx).map(|bytes| (*bytes).clone())
+    }
+
+    fn write_value(
+        &self,
+        ctx: &mut ProgramContext,
+        output: &mut DataGenOutput,
+    ) -> Result<(), Error> {
+        let bytes = self.get_contents(ctx)?;
+        output.write_bytes(&bytes[..])
+    }
+}
+
+fn find_record_offsets(file: &mut File) -> Result<Vec<u64>, io::Error> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut result = Vec::with_capacity(32);
+    let mut buffer = [0; 8192];
+    // follows the same rules as `parse_csv`, where a quote only starts a quoted field at the start of a field
+    let mut in_quotes = false;
+    let mut at_field_start = true;
+    // a quote within a quoted field either ends the field or is the first half of an escaped quote, which
+    // depends on the next byte, and that may be in the next buffer
+    let mut pending_quote = false;
+    let mut index_adder = 0;
+    loop {
+        let nread = do_read(file, &mut buffer[..])?;
+        if nread == 0 {
+            break;
+        }
+        for (i, byte) in buffer[..nread].iter().enumerate() {
+            if pending_quote {
+                pending_quote = false;
+                if *byte == b'"' {
+                    at_field_start = false;
+                    continue;
+                }
+                in_quotes = false;
+            }
+            if in_quotes {
+                if *byte == b'"' {
+                    pending_quote = true;
+                } else {
+                    at_field_start = false;
+                }
+                continue;
+            }
+            match *byte {
+                b'"' if at_field_start => in_quotes = true,
+                b'\n' => {
+                    result.push(i as u64 + index_adder);
+                    at_field_start = true;
+                }
+                b',' | b'\r' => at_field_start = true,
+                _ => at_field_start = false,
+            }
+        }
+        index_adder += nread as u64;
+    }
+    Ok(result)
+}
+
 
 const FILEPATH_PARAM: &str = "filepath";
 const DELIMITER_PARAM: &str = "delimiter";
+const WRAP_PARAM: &str = "wrap";
 
 fn create_file_fun(args: Arguments) -> CreateFunctionResult {
     let (filepath, delimiter) = args.require_2_args(
@@ -239,6 +578,63 @@ fn create_file_fun(args: Arguments) -> CreateFunctionResult {
     )))
 }
 
+fn create_file_bin_fun(args: Arguments) -> CreateFunctionResult {
+    let (filepath, delimiter) = args.require_2_args(
+        FILEPATH_PARAM,
+        AnyFunction::require_string,
+        DELIMITER_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::Bin(SelectFromFile::new_bin(filepath, delimiter)))
+}
+
+fn create_next_record(args: Arguments, read_lines: bool, binary: bool) -> CreateFunctionResult {
+    let filepath = args.required_arg(FILEPATH_PARAM, 0, AnyFunction::require_string)?;
+    let mut wrap_position = 1;
+    let delimiter = if read_lines {
+        None
+    } else {
+        wrap_position += 1;
+        Some(args.required_arg(DELIMITER_PARAM, 1, AnyFunction::require_string)?)
+    };
+    let wrap = if args.get_arg_type(wrap_position).is_some() {
+        Some(args.required_arg(WRAP_PARAM, wrap_position, AnyFunction::require_boolean)?)
+    } else {
+        None
+    };
+
+    let fun = NextRecord::create(filepath, delimiter, wrap);
+    if binary {
+        Ok(AnyFunction::Bin(fun))
+    } else {
+        Ok(AnyFunction::String(fun))
+    }
+}
+
+fn create_next_line_fun(args: Arguments) -> CreateFunctionResult {
+    create_next_record(args, true, false)
+}
+
+fn create_next_line_bin_fun(args: Arguments) -> CreateFunctionResult {
+    create_next_record(args, true, true)
+}
+
+fn create_next_record_fun(args: Arguments) -> CreateFunctionResult {
+    create_next_record(args, false, false)
+}
+
+fn create_next_record_bin_fun(args: Arguments) -> CreateFunctionResult {
+    create_next_record(args, false, true)
+}
+
+fn create_file_bytes_fun(args: Arguments) -> CreateFunctionResult {
+    let filepath = args.required_arg(FILEPATH_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::Bin(Rc::new(FileBytes {
+        file_path_gen: filepath,
+        contents: RefCell::new(HashMap::new()),
+    })))
+}
+
 fn create_words_fun(_: Arguments) -> CreateFunctionResult {
     use std::path::Path;
     use crate::ConstString;
@@ -272,6 +668,115 @@ pub const SELECT_FROM_FILE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunction
     create_fn: &create_file_fun,
 };
 
+pub const SELECT_FROM_FILE_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "select_from_file_bin",
+    description: "Selects random regions from the given file as binary, using the given delimiter. Unlike select_from_file, the file does not need to be valid utf-8",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (DELIMITER_PARAM, GenType::String)
+    ],
+    variadic: false,
+    create_fn: &create_file_bin_fun,
+};
+
+pub const NEXT_LINE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_line",
+    description: "Reads the lines of the given file in order, one per invocation, wrapping around to the first line after the last one. Both \\n and \\r\\n line endings are accepted",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_next_line_fun,
+};
+
+pub const NEXT_LINE_WRAP_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_line",
+    description: "Reads the lines of the given file in order, one per invocation. After the last line, wraps around to the first if `wrap` is true, or else returns an error",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (WRAP_PARAM, GenType::Boolean),
+    ],
+    variadic: false,
+    create_fn: &create_next_line_fun,
+};
+
+pub const NEXT_LINE_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_line_bin",
+    description: "Reads the lines of the given file in order as binary, wrapping around to the first line after the last one. The file does not need to be valid utf-8",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_next_line_bin_fun,
+};
+
+pub const NEXT_LINE_BIN_WRAP_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_line_bin",
+    description: "Reads the lines of the given file in order as binary. After the last line, wraps around to the first if `wrap` is true, or else returns an error",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (WRAP_PARAM, GenType::Boolean),
+    ],
+    variadic: false,
+    create_fn: &create_next_line_bin_fun,
+};
+
+pub const NEXT_RECORD_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_record",
+    description: "Reads the regions of the given file that are separated by the delimiter in order, one per invocation, wrapping around to the first region after the last one",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (DELIMITER_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_next_record_fun,
+};
+
+pub const NEXT_RECORD_WRAP_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_record",
+    description: "Reads the regions of the given file that are separated by the delimiter in order. After the last region, wraps around to the first if `wrap` is true, or else returns an error",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (DELIMITER_PARAM, GenType::String),
+        (WRAP_PARAM, GenType::Boolean),
+    ],
+    variadic: false,
+    create_fn: &create_next_record_fun,
+};
+
+pub const NEXT_RECORD_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_record_bin",
+    description: "Reads the delimited regions of the given file in order as binary, wrapping around to the first region after the last one. The file does not need to be valid utf-8",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (DELIMITER_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_next_record_bin_fun,
+};
+
+pub const NEXT_RECORD_BIN_WRAP_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "next_record_bin",
+    description: "Reads the delimited regions of the given file in order as binary. After the last region, wraps around to the first if `wrap` is true, or else returns an error",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+        (DELIMITER_PARAM, GenType::String),
+        (WRAP_PARAM, GenType::Boolean),
+    ],
+    variadic: false,
+    create_fn: &create_next_record_bin_fun,
+};
+
+pub const FILE_BYTES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "file_bytes",
+    description: "Returns the entire contents of the given file as binary",
+    arguments: &[
+        (FILEPATH_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_file_bytes_fun,
+};
+
 pub const WORDS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
     function_name: "words",
     description: "Selects a random word from the unix words file (/usr/share/dict/words or /usr/dict/words)",
@@ -283,7 +788,7 @@ pub const WORDS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ConstString;
+    use crate::{ConstBoolean, ConstString};
     use crate::verbosity;
 
     const RAND_SEED: &[u8; 16] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
@@ -319,4 +824,103 @@ mod test {
             assert!(expected.contains(&&*actual), "Expected: '{:?}' did not contain actual: '{:?}'", expected, actual);
         }
     }
+
+    #[test]
+    fn select_from_file_reads_the_whole_last_region() {
+        let mut reader = RandFileReader::create("test-data/simple-words.txt".into(), "\n".into()).unwrap();
+        let mut buffer = Vec::new();
+        let regions: Vec<Vec<u8>> = (0..reader.region_count())
+            .map(|i| reader.read_region(i, &mut buffer).unwrap().to_vec())
+            .collect();
+        let expected: Vec<&[u8]> = vec![b"foo", b"bar", b"baz", b"", b"qux"];
+        assert_eq!(expected, regions);
+
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let path_gen = ConstString::new("test-data/simple-words.txt").require_string().unwrap();
+        let delim = ConstString::new("\n").require_string().unwrap();
+        let subject = SelectFromFile::new(path_gen, delim);
+        let selected: Vec<IString> = (0..50).map(|_| subject.gen_value(&mut rng).unwrap()).collect();
+        assert!(selected.iter().any(|value| &**value == "qux"), "the last region was never selected");
+    }
+
+    #[test]
+    fn select_from_file_returns_empty_values_for_an_empty_file() {
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let path_gen = ConstString::new("test-data/empty.txt").require_string().unwrap();
+        let delim = ConstString::new("\n").require_string().unwrap();
+        let subject = SelectFromFile::new(path_gen, delim);
+        for _ in 0..5 {
+            assert_eq!("", &*subject.gen_value(&mut rng).unwrap());
+        }
+    }
+
+    fn next_record_fun(path: &str, delimiter: Option<&str>, wrap: bool) -> Rc<NextRecord> {
+        let path_gen = ConstString::new(path).require_string().unwrap();
+        let delim = delimiter.map(|d| ConstString::new(d).require_string().unwrap());
+        let wrap = ConstBoolean::new(wrap).require_boolean().unwrap();
+        NextRecord::create(path_gen, delim, Some(wrap))
+    }
+
+    fn next_strings(subject: &Rc<NextRecord>, ctx: &mut ProgramContext, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                let value: IString = subject.gen_value(ctx).expect("failed to gen value");
+                value.to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn next_line_reads_lines_in_order_and_wraps_around() {
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let expected = vec!["foo", "bar", "baz", "", "qux", "foo", "bar"];
+        for path in &["test-data/simple-words.txt", "test-data/crlf-words.txt"] {
+            let subject = next_record_fun(path, None, true);
+            assert_eq!(expected, next_strings(&subject, &mut rng, expected.len()));
+        }
+    }
+
+    #[test]
+    fn next_record_returns_error_at_end_of_file_when_not_wrapping() {
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let subject = next_record_fun("test-data/crlf-words.txt", Some("\r\n"), false);
+        let expected = vec!["foo", "bar", "baz", "", "qux"];
+        assert_eq!(expected, next_strings(&subject, &mut rng, expected.len()));
+
+        let result: Result<IString, Error> = subject.gen_value(&mut rng);
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("Reached the end of the file"), "unexpected error: {}", message);
+    }
+
+    #[test]
+    fn binary_variants_read_files_that_are_not_utf8() {
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let path = "test-data/latin1-words.txt";
+        let subject = next_record_fun(path, Some("\n"), true);
+        let first: Vec<u8> = subject.gen_value(&mut rng).unwrap();
+        let second: Vec<u8> = subject.gen_value(&mut rng).unwrap();
+        assert_eq!(b"caf\xe9".to_vec(), first);
+        assert_eq!(b"na\xefve".to_vec(), second);
+
+        let text_result: Result<IString, Error> = subject.gen_value(&mut rng);
+        let message = text_result.unwrap_err().to_string();
+        assert!(message.contains("next_record_bin"), "unexpected error: {}", message);
+
+        let path_gen = ConstString::new(path).require_string().unwrap();
+        let delim = ConstString::new("\n").require_string().unwrap();
+        let select = SelectFromFile::new_bin(path_gen, delim);
+        for _ in 0..10 {
+            let actual = select.gen_value(&mut rng).unwrap();
+            assert!(actual.is_empty() || actual == first || actual == second, "unexpected region: {:?}", actual);
+        }
+    }
+
+    #[test]
+    fn file_bytes_returns_the_whole_file() {
+        let mut rng = ProgramContext::from_seed(*RAND_SEED, verbosity::NORMAL);
+        let args = Arguments::new(vec![ConstString::new("test-data/crlf-words.txt")]);
+        let subject = create_file_bytes_fun(args).unwrap().require_bin().unwrap();
+        let actual = subject.gen_value(&mut rng).unwrap();
+        assert_eq!(b"foo\r\nbar\r\nbaz\r\n\r\nqux".to_vec(), actual);
+    }
 }
@@ -0,0 +1,1084 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput, DynStringFun, DynUintFun,
+    GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+const DEFAULT_MAX_DEPTH: u64 = 16;
+/// The number of extra repetitions that may be generated for repetitions without an upper bound, like `*element`
+const DEFAULT_EXTRA_REPEATS: u32 = 4;
+/// The probability that a case-insensitive ABNF string is written with randomly mixed case
+const MIXED_CASE_RATE: f64 = 0.25;
+const WEIGHT_ANNOTATION: &str = "@weight";
+
+/// The core rules from RFC 5234 appendix B, which may be used by any ABNF grammar without defining them
+const ABNF_CORE_RULES: &str = r#"
+ALPHA = %x41-5A / %x61-7A
+BIT = "0" / "1"
+CHAR = %x01-7F
+CR = %x0D
+CRLF = CR LF
+CTL = %x00-1F / %x7F
+DIGIT = %x30-39
+DQUOTE = %x22
+HEXDIG = DIGIT / "A" / "B" / "C" / "D" / "E" / "F"
+HTAB = %x09
+LF = %x0A
+LWSP = *(WSP / CRLF WSP)
+OCTET = %x00-FF
+SP = %x20
+VCHAR = %x21-7E
+WSP = SP / HTAB
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Element {
+    Literal { text: String, case_sensitive: bool },
+    Range(u32, u32),
+    Rule(usize),
+    Sequence(Vec<Element>),
+    /// alternatives with their weights
+    Alternation(Vec<(u64, Element)>),
+    Repeat { min: u32, max: Option<u32>, element: Box<Element> },
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    element: Option<Element>,
+    /// whether the rule came from the file, rather than from the ABNF core rules
+    user_defined: bool,
+    line: usize,
+}
+
+/// Collects the rules of a grammar as they're parsed, since rules may be referenced before they're defined
+struct GrammarBuilder {
+    rules: Vec<Rule>,
+    indices: HashMap<String, usize>,
+    case_sensitive_names: bool,
+}
+
+impl GrammarBuilder {
+    fn new(case_sensitive_names: bool) -> GrammarBuilder {
+        GrammarBuilder {
+            rules: Vec::new(),
+            indices: HashMap::new(),
+            case_sensitive_names,
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.case_sensitive_names {
+            name.to_owned()
+        } else {
+            name.to_lowercase()
+        }
+    }
+
+    fn rule_index(&mut self, name: &str, line: usize) -> usize {
+        let key = self.key(name);
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
+        self.rules.push(Rule {
+            name: name.to_owned(),
+            element: None,
+            user_defined: false,
+            line,
+        });
+        self.indices.insert(key, self.rules.len() - 1);
+        self.rules.len() - 1
+    }
+
+    fn define(&mut self, name: &str, element: Element, incremental: bool, user_defined: bool, line: usize) -> Result<(), Error> {
+        let index = self.rule_index(name, line);
+        let rule = &mut self.rules[index];
+        match rule.element.take() {
+            Some(existing) if incremental => {
+                let mut alternatives = match existing {
+                    Element::Alternation(alternatives) => alternatives,
+                    other => vec![(1, other)],
+                };
+                match element {
+                    Element::Alternation(more) => alternatives.extend(more),
+                    other => alternatives.push((1, other)),
+                }
+                rule.element = Some(Element::Alternation(alternatives));
+            }
+            Some(_) if rule.user_defined && user_defined => {
+                bail!("line {}: the rule '{}' is already defined on line {}", line, name, rule.line)
+            }
+            None if incremental => bail!("line {}: the rule '{}' is extended with '=/' before it's defined", line, name),
+            _ => {
+                rule.element = Some(element);
+                rule.line = line;
+            }
+        }
+        rule.user_defined |= user_defined;
+        Ok(())
+    }
+
+    fn build(self) -> Result<Grammar, Error> {
+        if let Some(rule) = self.rules.iter().find(|rule| rule.element.is_none()) {
+            bail!("line {}: the rule '{}' is used but never defined", rule.line, rule.name);
+        }
+        let mut grammar = Grammar {
+            rules: self.rules,
+            indices: self.indices,
+            case_sensitive_names: self.case_sensitive_names,
+            heights: Vec::new(),
+        };
+        grammar.heights = grammar.find_heights();
+        Ok(grammar)
+    }
+}
+
+/// A parsed grammar, where every rule has been defined
+#[derive(Debug)]
+struct Grammar {
+    rules: Vec<Rule>,
+    indices: HashMap<String, usize>,
+    case_sensitive_names: bool,
+    /// the minimum number of nested rule references needed to finish generating each rule, or None for rules that
+    /// can only recurse forever
+    heights: Vec<Option<u32>>,
+}
+
+impl Grammar {
+    fn rule(&self, name: &str) -> Option<usize> {
+        if self.case_sensitive_names {
+            self.indices.get(name).cloned()
+        } else {
+            self.indices.get(&name.to_lowercase()).cloned()
+        }
+    }
+
+    fn height(&self, element: &Element, heights: &[Option<u32>]) -> Option<u32> {
+        match element {
+            Element::Literal { .. } | Element::Range(..) => Some(0),
+            Element::Rule(index) => heights[*index].map(|height| height + 1),
+            Element::Sequence(elements) => elements.iter().try_fold(0, |max, element| {
+                self.height(element, heights).map(|height| height.max(max))
+            }),
+            Element::Alternation(alternatives) => alternatives
+                .iter()
+                .filter_map(|(_, element)| self.height(element, heights))
+                .min(),
+            Element::Repeat { min: 0, .. } => Some(0),
+            Element::Repeat { element, .. } => self.height(element, heights),
+        }
+    }
+
+    fn find_heights(&self) -> Vec<Option<u32>> {
+        let mut heights = vec![None; self.rules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, rule) in self.rules.iter().enumerate() {
+                let height = self.height(rule.element.as_ref().unwrap(), &heights);
+                if height != heights[index] {
+                    heights[index] = height;
+                    changed = true;
+                }
+            }
+        }
+        heights
+    }
+
+    fn fits(&self, element: &Element, budget: u32) -> bool {
+        self.height(element, &self.heights).map(|height| height <= budget).unwrap_or(false)
+    }
+
+    fn gen(&self, element: &Element, ctx: &mut ProgramContext, depth: u32, max_depth: u32, out: &mut String) -> Result<(), Error> {
+        let budget = max_depth.saturating_sub(depth);
+        match element {
+            Element::Literal { text, case_sensitive } => {
+                if !*case_sensitive && ctx.gen_chance(MIXED_CASE_RATE) {
+                    for c in text.chars() {
+                        if ctx.gen_chance(0.5) {
+                            out.push(c.to_ascii_uppercase());
+                        } else {
+                            out.push(c.to_ascii_lowercase());
+                        }
+                    }
+                } else {
+                    out.push_str(text);
+                }
+            }
+            Element::Range(min, max) => {
+                let value = ctx.gen_range_inclusive(*min, *max);
+                // ranges may span the surrogates, which aren't valid characters
+                out.push(std::char::from_u32(value).unwrap_or('\u{FFFD}'));
+            }
+            Element::Rule(index) => {
+                let element = self.rules[*index].element.as_ref().unwrap();
+                self.gen(element, ctx, depth + 1, max_depth, out)?;
+            }
+            Element::Sequence(elements) => {
+                for element in elements.iter() {
+                    self.gen(element, ctx, depth, max_depth, out)?;
+                }
+            }
+            Element::Alternation(alternatives) => {
+                // prefer the alternatives that can finish within the depth limit, or else the ones that finish soonest
+                let mut candidates = alternatives
+                    .iter()
+                    .filter(|(_, element)| self.fits(element, budget))
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    let min_height = self.height(element, &self.heights);
+                    candidates = alternatives
+                        .iter()
+                        .filter(|(_, element)| self.height(element, &self.heights) == min_height)
+                        .collect();
+                }
+                let total = candidates.iter().map(|(weight, _)| *weight).sum::<u64>();
+                let index = if total == 0 {
+                    ctx.gen_range_exclusive(0, candidates.len())
+                } else {
+                    let mut target = ctx.gen_range_exclusive(0, total);
+                    candidates
+                        .iter()
+                        .position(|(weight, _)| {
+                            let found = target < *weight;
+                            target = target.saturating_sub(*weight);
+                            found
+                        })
+                        .unwrap()
+                };
+                self.gen(&candidates[index].1, ctx, depth, max_depth, out)?;
+            }
+            Element::Repeat { min, max, element } => {
+                let count = if self.fits(element, budget) {
+                    let max = max.unwrap_or(min + DEFAULT_EXTRA_REPEATS).max(*min);
+                    ctx.gen_range_inclusive(*min, max)
+                } else {
+                    *min
+                };
+                for _ in 0..count {
+                    self.gen(element, ctx, depth, max_depth, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_weight(comment: &str, line: usize) -> Result<Option<u64>, Error> {
+    let comment = comment.trim();
+    if !comment.starts_with(WEIGHT_ANNOTATION) {
+        return Ok(None);
+    }
+    let weight = comment[WEIGHT_ANNOTATION.len()..].trim();
+    weight
+        .parse::<u64>()
+        .map(Some)
+        .map_err(|_| format_err!("line {}: invalid weight '{}', must be a non-negative integer", line, weight))
+}
+
+fn code_point(value: u32, line: usize) -> Result<char, Error> {
+    std::char::from_u32(value).ok_or_else(|| format_err!("line {}: invalid character value: {:#x}", line, value))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Defined { incremental: bool },
+    Alternative,
+    Open(char),
+    Close(char),
+    Repeat(u32, Option<u32>),
+    Element(Element),
+    Weight(u64),
+    // ebnf only
+    Concatenate,
+    Terminator,
+    Integer(u32),
+    Postfix(char),
+}
+
+#[derive(Debug)]
+struct Lexeme {
+    token: Token,
+    line: usize,
+    /// whether the token is the first thing on its line, which is how ABNF rules are separated
+    line_start: bool,
+}
+
+/// Splits text into lexemes, keeping track of lines. The closures for each syntax consume a single token
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    lexemes: Vec<Lexeme>,
+}
+
+impl Lexer {
+    fn new(text: &str) -> Lexer {
+        Lexer {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+            lexemes: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            taken.push(c);
+            self.next();
+        }
+        taken
+    }
+
+    fn take_until(&mut self, end: &str, what: &str) -> Result<String, Error> {
+        let line = self.line;
+        let end = end.chars().collect::<Vec<_>>();
+        let mut taken = String::new();
+        loop {
+            if self.chars[self.pos..].starts_with(&end) {
+                self.pos += end.len();
+                return Ok(taken);
+            }
+            match self.next() {
+                Some(c) => taken.push(c),
+                None => bail!("line {}: unterminated {}", line, what),
+            }
+        }
+    }
+
+    fn is_line_start(&self) -> bool {
+        self.pos == 0 || self.chars[self.pos - 1] == '\n'
+    }
+
+    fn push(&mut self, token: Token, line: usize, line_start: bool) {
+        self.lexemes.push(Lexeme { token, line, line_start });
+    }
+
+    fn number(&mut self, radix: u32, line: usize) -> Result<u32, Error> {
+        let digits = self.take_while(|c| c.is_digit(radix));
+        u32::from_str_radix(&digits, radix).map_err(|_| format_err!("line {}: invalid number '{}'", line, digits))
+    }
+}
+
+fn abnf_num_val(lexer: &mut Lexer, line: usize) -> Result<Element, Error> {
+    let radix = match lexer.next().map(|c| c.to_ascii_lowercase()) {
+        Some('x') => 16,
+        Some('d') => 10,
+        Some('b') => 2,
+        other => bail!("line {}: invalid numeric value base: {:?}", line, other),
+    };
+    let first = lexer.number(radix, line)?;
+    match lexer.peek() {
+        Some('-') => {
+            lexer.next();
+            let last = lexer.number(radix, line)?;
+            if last < first || last > 0x10FFFF {
+                bail!("line {}: invalid character range: {:#x}-{:#x}", line, first, last);
+            }
+            Ok(Element::Range(first, last))
+        }
+        _ => {
+            let mut text = code_point(first, line)?.to_string();
+            while lexer.peek() == Some('.') {
+                lexer.next();
+                let value = lexer.number(radix, line)?;
+                text.push(code_point(value, line)?);
+            }
+            Ok(Element::Literal {
+                text,
+                case_sensitive: true,
+            })
+        }
+    }
+}
+
+fn abnf_tokens(text: &str) -> Result<Vec<Lexeme>, Error> {
+    let mut lexer = Lexer::new(text);
+    while let Some(c) = lexer.peek() {
+        let line = lexer.line;
+        let line_start = lexer.is_line_start();
+        let token = match c {
+            ' ' | '\t' | '\r' | '\n' => {
+                lexer.next();
+                continue;
+            }
+            ';' => {
+                lexer.next();
+                let comment = lexer.take_while(|c| c != '\n');
+                match parse_weight(&comment, line)? {
+                    Some(weight) => Token::Weight(weight),
+                    None => continue,
+                }
+            }
+            '=' => {
+                lexer.next();
+                let incremental = lexer.peek() == Some('/');
+                if incremental {
+                    lexer.next();
+                }
+                Token::Defined { incremental }
+            }
+            '/' => {
+                lexer.next();
+                Token::Alternative
+            }
+            '(' | '[' => {
+                lexer.next();
+                Token::Open(c)
+            }
+            ')' | ']' => {
+                lexer.next();
+                Token::Close(c)
+            }
+            '"' => {
+                lexer.next();
+                let text = lexer.take_until("\"", "string")?;
+                Token::Element(Element::Literal {
+                    text,
+                    case_sensitive: false,
+                })
+            }
+            '%' => {
+                lexer.next();
+                match (lexer.peek().map(|c| c.to_ascii_lowercase()), lexer.peek_at(1)) {
+                    (Some(modifier @ 's'), Some('"')) | (Some(modifier @ 'i'), Some('"')) => {
+                        lexer.pos += 2;
+                        let text = lexer.take_until("\"", "string")?;
+                        Token::Element(Element::Literal {
+                            text,
+                            case_sensitive: modifier == 's',
+                        })
+                    }
+                    _ => Token::Element(abnf_num_val(&mut lexer, line)?),
+                }
+            }
+            '<' => {
+                lexer.next();
+                let prose = lexer.take_until(">", "prose value")?;
+                bail!(
+                    "line {}: prose values like <{}> can't be generated, so replace it with a rule that defines it",
+                    line,
+                    prose
+                );
+            }
+            '*' | '0'..='9' => {
+                let min = lexer.take_while(|c| c.is_ascii_digit());
+                let min = if min.is_empty() { 0 } else { min.parse::<u32>()? };
+                if lexer.peek() == Some('*') {
+                    lexer.next();
+                    let max = lexer.take_while(|c| c.is_ascii_digit());
+                    let max = if max.is_empty() { None } else { Some(max.parse::<u32>()?) };
+                    Token::Repeat(min, max)
+                } else {
+                    Token::Repeat(min, Some(min))
+                }
+            }
+            c if c.is_ascii_alphabetic() => Token::Name(lexer.take_while(|c| c.is_ascii_alphanumeric() || c == '-')),
+            other => bail!("line {}: unexpected character '{}'", line, other),
+        };
+        lexer.push(token, line, line_start);
+    }
+    Ok(lexer.lexemes)
+}
+
+fn ebnf_tokens(text: &str) -> Result<Vec<Lexeme>, Error> {
+    let mut lexer = Lexer::new(text);
+    while let Some(c) = lexer.peek() {
+        let line = lexer.line;
+        let line_start = lexer.is_line_start();
+        let token = match c {
+            c if c.is_whitespace() => {
+                lexer.next();
+                continue;
+            }
+            '(' if lexer.peek_at(1) == Some('*') => {
+                lexer.pos += 2;
+                let comment = lexer.take_until("*)", "comment")?;
+                match parse_weight(&comment, line)? {
+                    Some(weight) => Token::Weight(weight),
+                    None => continue,
+                }
+            }
+            ':' if lexer.peek_at(1) == Some(':') && lexer.peek_at(2) == Some('=') => {
+                lexer.pos += 3;
+                Token::Defined { incremental: false }
+            }
+            '=' => {
+                lexer.next();
+                Token::Defined { incremental: false }
+            }
+            '|' | '/' | '!' => {
+                lexer.next();
+                Token::Alternative
+            }
+            ',' => {
+                lexer.next();
+                Token::Concatenate
+            }
+            ';' | '.' => {
+                lexer.next();
+                Token::Terminator
+            }
+            '(' | '[' | '{' => {
+                lexer.next();
+                Token::Open(c)
+            }
+            ')' | ']' | '}' => {
+                lexer.next();
+                Token::Close(c)
+            }
+            '?' | '*' | '+' => {
+                lexer.next();
+                Token::Postfix(c)
+            }
+            '"' | '\'' => {
+                lexer.next();
+                let text = lexer.take_until(&c.to_string(), "string")?;
+                Token::Element(Element::Literal {
+                    text,
+                    case_sensitive: true,
+                })
+            }
+            '#' if lexer.peek_at(1) == Some('x') => {
+                lexer.pos += 2;
+                let value = lexer.number(16, line)?;
+                Token::Element(Element::Literal {
+                    text: code_point(value, line)?.to_string(),
+                    case_sensitive: true,
+                })
+            }
+            '-' => bail!("line {}: exceptions using '-' are not supported", line),
+            '0'..='9' => Token::Integer(lexer.number(10, line)?),
+            c if c.is_alphabetic() || c == '_' => {
+                Token::Name(lexer.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-'))
+            }
+            other => bail!("line {}: unexpected character '{}'", line, other),
+        };
+        lexer.push(token, line, line_start);
+    }
+    Ok(lexer.lexemes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Syntax {
+    Abnf,
+    Ebnf,
+}
+
+/// A recursive descent parser for both syntaxes, which only differ in how rules are separated, how elements are
+/// concatenated, and how repetitions are written
+struct Parser<'a> {
+    syntax: Syntax,
+    lexemes: &'a [Lexeme],
+    pos: usize,
+    builder: &'a mut GrammarBuilder,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.lexemes.get(self.pos).map(|lexeme| &lexeme.token)
+    }
+
+    fn line(&self) -> usize {
+        self.lexemes
+            .get(self.pos)
+            .or_else(|| self.lexemes.last())
+            .map(|lexeme| lexeme.line)
+            .unwrap_or(1)
+    }
+
+    /// Whether the next tokens start a new rule definition
+    fn at_rule_start(&self) -> bool {
+        match (self.lexemes.get(self.pos), self.lexemes.get(self.pos + 1)) {
+            (Some(name), Some(defined)) => match (&name.token, &defined.token) {
+                (Token::Name(_), Token::Defined { .. }) => self.syntax == Syntax::Ebnf || name.line_start,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, Error> {
+        match self.peek() {
+            Some(token) => bail!("line {}: expected {}, but found {:?}", self.line(), expected, token),
+            None => bail!("line {}: expected {}, but the grammar ended", self.line(), expected),
+        }
+    }
+
+    fn parse_rules(&mut self, user_defined: bool) -> Result<(), Error> {
+        while self.pos < self.lexemes.len() {
+            let line = self.line();
+            let name = match self.peek() {
+                Some(Token::Name(name)) if self.at_rule_start() => name,
+                _ => return self.error("a rule definition"),
+            };
+            let incremental = match self.lexemes[self.pos + 1].token {
+                Token::Defined { incremental } => incremental,
+                _ => unreachable!(),
+            };
+            self.pos += 2;
+            let element = self.alternation()?;
+            if self.syntax == Syntax::Ebnf && self.peek() == Some(&Token::Terminator) {
+                self.pos += 1;
+            }
+            self.builder.define(name, element, incremental, user_defined, line)?;
+        }
+        Ok(())
+    }
+
+    fn alternation(&mut self) -> Result<Element, Error> {
+        let mut alternatives = Vec::new();
+        loop {
+            let element = self.concatenation()?;
+            let weight = match self.peek() {
+                Some(Token::Weight(weight)) => {
+                    self.pos += 1;
+                    *weight
+                }
+                _ => 1,
+            };
+            alternatives.push((weight, element));
+            if self.peek() == Some(&Token::Alternative) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap().1)
+        } else {
+            Ok(Element::Alternation(alternatives))
+        }
+    }
+
+    fn concatenation(&mut self) -> Result<Element, Error> {
+        let mut elements = Vec::new();
+        loop {
+            if self.at_rule_start() {
+                break;
+            }
+            match self.peek() {
+                Some(Token::Name(_)) | Some(Token::Open(_)) | Some(Token::Element(_)) | Some(Token::Repeat(..))
+                | Some(Token::Integer(_)) => elements.push(self.repetition()?),
+                Some(Token::Concatenate) if self.syntax == Syntax::Ebnf && !elements.is_empty() => {
+                    self.pos += 1;
+                    if !self.starts_element() {
+                        return self.error("an element after ','");
+                    }
+                }
+                _ => break,
+            }
+        }
+        match elements.len() {
+            0 => self.error("an element"),
+            1 => Ok(elements.pop().unwrap()),
+            _ => Ok(Element::Sequence(elements)),
+        }
+    }
+
+    fn starts_element(&self) -> bool {
+        match self.peek() {
+            Some(Token::Name(_)) | Some(Token::Open(_)) | Some(Token::Element(_)) | Some(Token::Repeat(..))
+            | Some(Token::Integer(_)) => !self.at_rule_start(),
+            _ => false,
+        }
+    }
+
+    fn repetition(&mut self) -> Result<Element, Error> {
+        let (min, max) = match self.peek() {
+            Some(Token::Repeat(min, max)) if self.syntax == Syntax::Abnf => {
+                self.pos += 1;
+                (*min, *max)
+            }
+            Some(Token::Integer(count)) if self.syntax == Syntax::Ebnf => {
+                self.pos += 1;
+                if self.peek() != Some(&Token::Postfix('*')) {
+                    return self.error("'*' after a repetition count");
+                }
+                self.pos += 1;
+                (*count, Some(*count))
+            }
+            _ => (1, Some(1)),
+        };
+        if max.map(|max| max < min).unwrap_or(false) {
+            bail!("line {}: invalid repetition, the maximum is less than the minimum", self.line());
+        }
+        let mut element = self.element()?;
+        if self.syntax == Syntax::Ebnf {
+            while let Some(Token::Postfix(c)) = self.peek() {
+                self.pos += 1;
+                let (min, max) = match c {
+                    '?' => (0, Some(1)),
+                    '*' => (0, None),
+                    _ => (1, None),
+                };
+                element = Element::Repeat {
+                    min,
+                    max,
+                    element: Box::new(element),
+                };
+            }
+        }
+        if (min, max) == (1, Some(1)) {
+            Ok(element)
+        } else {
+            Ok(Element::Repeat {
+                min,
+                max,
+                element: Box::new(element),
+            })
+        }
+    }
+
+    fn element(&mut self) -> Result<Element, Error> {
+        let line = self.line();
+        match self.peek() {
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                Ok(Element::Rule(self.builder.rule_index(name, line)))
+            }
+            Some(Token::Element(element)) => {
+                self.pos += 1;
+                Ok(element.clone())
+            }
+            Some(Token::Open(open)) => {
+                self.pos += 1;
+                let inner = self.alternation()?;
+                let close = match open {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                if self.peek() != Some(&Token::Close(close)) {
+                    return self.error(&format!("'{}'", close));
+                }
+                self.pos += 1;
+                let (min, max) = match open {
+                    '(' => return Ok(inner),
+                    '[' => (0, Some(1)),
+                    _ => (0, None),
+                };
+                Ok(Element::Repeat {
+                    min,
+                    max,
+                    element: Box::new(inner),
+                })
+            }
+            _ => self.error("an element"),
+        }
+    }
+}
+
+fn parse_grammar(syntax: Syntax, text: &str) -> Result<Grammar, Error> {
+    let mut builder = GrammarBuilder::new(syntax == Syntax::Ebnf);
+    if syntax == Syntax::Abnf {
+        let core = abnf_tokens(ABNF_CORE_RULES)?;
+        Parser {
+            syntax,
+            lexemes: &core,
+            pos: 0,
+            builder: &mut builder,
+        }
+        .parse_rules(false)?;
+    }
+    let lexemes = match syntax {
+        Syntax::Abnf => abnf_tokens(text)?,
+        Syntax::Ebnf => ebnf_tokens(text)?,
+    };
+    Parser {
+        syntax,
+        lexemes: &lexemes,
+        pos: 0,
+        builder: &mut builder,
+    }
+    .parse_rules(true)?;
+    builder.build()
+}
+
+#[derive(Debug)]
+struct FromGrammar {
+    syntax: Syntax,
+    path: DynStringFun,
+    rule: DynStringFun,
+    max_depth: Option<DynUintFun>,
+    grammars: RefCell<HashMap<IString, Rc<Grammar>>>,
+}
+
+impl FromGrammar {
+    fn get_grammar(&self, path: IString) -> Result<Rc<Grammar>, Error> {
+        let mut grammars = self.grammars.borrow_mut();
+        if let Some(grammar) = grammars.get(&path) {
+            return Ok(grammar.clone());
+        }
+        let text = fs::read_to_string(&*path).map_err(|e| format_err!("Failed to read grammar '{}': {}", path, e))?;
+        let grammar = parse_grammar(self.syntax, &text).map_err(|e| format_err!("Invalid grammar '{}': {}", path, e))?;
+        let grammar = Rc::new(grammar);
+        grammars.insert(path, grammar.clone());
+        Ok(grammar)
+    }
+}
+
+impl RunnableFunction<IString> for FromGrammar {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let path = self.path.gen_value(ctx)?;
+        let rule_name = self.rule.gen_value(ctx)?;
+        let max_depth = match self.max_depth.as_ref() {
+            Some(fun) => fun.gen_value(ctx)?,
+            None => DEFAULT_MAX_DEPTH,
+        };
+        let grammar = self.get_grammar(path.clone())?;
+        let rule = grammar
+            .rule(&rule_name)
+            .ok_or_else(|| format_err!("The grammar '{}' does not define a rule named '{}'", path, rule_name))?;
+        if grammar.heights[rule].is_none() {
+            bail!("The rule '{}' in the grammar '{}' can only recurse forever", rule_name, path);
+        }
+        let mut out = String::new();
+        let max_depth = max_depth.min(u64::from(u32::MAX)) as u32;
+        grammar.gen(&Element::Rule(rule), ctx, 0, max_depth, &mut out)?;
+        Ok(out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const PATH_PARAM: &str = "grammar_path";
+const RULE_PARAM: &str = "rule";
+const MAX_DEPTH_PARAM: &str = "max_depth";
+
+macro_rules! make_grammar_builtins {
+    ($proto_name:ident, $depth_proto_name:ident, $fun_name:expr, $syntax:expr, $description:literal) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let (path, rule) =
+                    args.require_2_args(PATH_PARAM, AnyFunction::require_string, RULE_PARAM, AnyFunction::require_string)?;
+                Ok(AnyFunction::String(Rc::new(FromGrammar {
+                    syntax: $syntax,
+                    path,
+                    rule,
+                    max_depth: None,
+                    grammars: RefCell::new(HashMap::new()),
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!($description, ", using a max_depth of 16"),
+                arguments: &[(PATH_PARAM, GenType::String), (RULE_PARAM, GenType::String)],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+
+        pub const $depth_proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let (path, rule, max_depth) = args.require_3_args(
+                    PATH_PARAM,
+                    AnyFunction::require_string,
+                    RULE_PARAM,
+                    AnyFunction::require_string,
+                    MAX_DEPTH_PARAM,
+                    AnyFunction::require_uint,
+                )?;
+                Ok(AnyFunction::String(Rc::new(FromGrammar {
+                    syntax: $syntax,
+                    path,
+                    rule,
+                    max_depth: Some(max_depth),
+                    grammars: RefCell::new(HashMap::new()),
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!(
+                    $description,
+                    ". Once rules are nested max_depth deep, only the alternatives and repetitions that finish soonest are chosen"
+                ),
+                arguments: &[
+                    (PATH_PARAM, GenType::String),
+                    (RULE_PARAM, GenType::String),
+                    (MAX_DEPTH_PARAM, GenType::Uint),
+                ],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_grammar_builtins!(
+    FROM_ABNF_BUILTIN,
+    FROM_ABNF_DEPTH_BUILTIN,
+    "from_abnf",
+    Syntax::Abnf,
+    "generates strings that match the named rule of the ABNF grammar (RFC 5234 and RFC 7405) in the given file. The core rules like ALPHA, DIGIT, and CRLF are always available, prose values are not supported, and case-insensitive strings are sometimes written in mixed case. Alternatives are chosen with equal weight, unless they're followed by a comment like `;@weight 5`"
+);
+make_grammar_builtins!(
+    FROM_EBNF_BUILTIN,
+    FROM_EBNF_DEPTH_BUILTIN,
+    "from_ebnf",
+    Syntax::Ebnf,
+    "generates strings that match the named rule of the EBNF grammar in the given file. Rules are defined with `=` or `::=` and optionally end with `;` or `.`, alternatives are separated by `|`, elements by `,` or whitespace, and elements may be grouped with `()`, `[]` for optional, or `{}` for zero or more, followed by `?`, `*`, or `+`, or preceded by `n *` for exactly n. Strings use single or double quotes, and characters may be written as `#xNN`. Alternatives are chosen with equal weight, unless they're followed by a comment like `(* @weight 5 *)`"
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_runtime_error, create_context, run_program};
+    use regex::Regex;
+
+    fn run_lines(program: &str, iterations: u64) -> Vec<String> {
+        let output = run_program(iterations, &format!("concat({}, \"\\n\")", program)).expect("failed to run program");
+        String::from_utf8(output).unwrap().lines().map(String::from).collect()
+    }
+
+    fn generate(syntax: Syntax, grammar: &str, rule: &str, max_depth: u32, iterations: usize) -> Vec<String> {
+        let grammar = parse_grammar(syntax, grammar).expect("failed to parse grammar");
+        let rule = grammar.rule(rule).expect("no such rule");
+        let mut ctx = create_context();
+        (0..iterations)
+            .map(|_| {
+                let mut out = String::new();
+                grammar.gen(&Element::Rule(rule), &mut ctx, 0, max_depth, &mut out).unwrap();
+                out
+            })
+            .collect()
+    }
+
+    fn parse_error(syntax: Syntax, grammar: &str) -> String {
+        parse_grammar(syntax, grammar).expect_err("expected an error").to_string()
+    }
+
+    #[test]
+    fn abnf_uris_are_generated() {
+        let uri = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:[^ #?]*(\?[^ #]*)?(#[^ ]*)?$").unwrap();
+        for line in run_lines(r#"from_abnf("test-data/uri.abnf", "URI")"#, 100) {
+            assert!(uri.is_match(&line), "invalid uri: '{}'", line);
+        }
+        for line in run_lines(r#"from_abnf("test-data/uri.abnf", "ipv4ADDRESS")"#, 100) {
+            let octets = line.split('.').map(|octet| octet.parse::<u8>()).collect::<Result<Vec<_>, _>>();
+            assert_eq!(4, octets.map(|octets| octets.len()).unwrap_or(0), "invalid address: '{}'", line);
+        }
+    }
+
+    #[test]
+    fn alternatives_are_weighted_by_annotations() {
+        let schemes = run_lines(r#"from_abnf("test-data/uri.abnf", "scheme")"#, 300);
+        let common = schemes.iter().filter(|scheme| *scheme == "http" || *scheme == "https").count();
+        assert!(common > 230 && common < 300, "common schemes: {}", common);
+
+        let values = generate(Syntax::Abnf, "a = %s\"x\" ;@weight 0\n  / %s\"y\"\n", "a", 16, 50);
+        assert!(values.iter().all(|value| value == "y"));
+        let values = generate(Syntax::Ebnf, "a = 'x' (* @weight 0 *) | 'y' ;", "a", 16, 50);
+        assert!(values.iter().all(|value| value == "y"));
+    }
+
+    #[test]
+    fn recursion_is_limited_by_max_depth() {
+        let grammar = "nested = \"(\" nested \")\" / \"(\" nested nested \")\" / \"x\"\n";
+        for max_depth in 0..6 {
+            for value in generate(Syntax::Abnf, grammar, "nested", max_depth, 50) {
+                let depth = value.chars().filter(|c| *c == '(').count() - value.chars().filter(|c| *c == ')').count();
+                assert_eq!(0, depth);
+                let mut nesting = 0;
+                let mut max_nesting = 0;
+                for c in value.chars() {
+                    match c {
+                        '(' => nesting += 1,
+                        ')' => nesting -= 1,
+                        _ => {}
+                    }
+                    max_nesting = max_nesting.max(nesting);
+                }
+                assert!(max_nesting <= max_depth.max(1), "too deep for {}: {}", max_depth, value);
+            }
+        }
+        let error = parse_grammar(Syntax::Abnf, "forever = \"a\" forever\n").unwrap();
+        assert_eq!(None, error.heights[error.rule("forever").unwrap()]);
+    }
+
+    #[test]
+    fn abnf_syntax_is_parsed() {
+        let grammar = r#"
+greeting = %i"hello" SP name 1*3"!" [ CRLF ]
+name     = 2ALPHA
+         / %d49.50.51 ; a comment
+name     =/ %b1000001-1011010 ; A-Z
+"#;
+        let pattern = Regex::new(r"^[hH][eE][lL][lL][oO] ([a-zA-Z]{2}|123|[A-Z])!{1,3}(\r\n)?$").unwrap();
+        let values = generate(Syntax::Abnf, grammar, "GREETING", 16, 200);
+        for value in values.iter() {
+            assert!(pattern.is_match(value), "invalid greeting: '{:?}'", value);
+        }
+        assert!(values.iter().any(|value| value.starts_with("hello")));
+        assert!(values.iter().any(|value| !value.starts_with("hello")));
+        assert!(values.iter().any(|value| value.contains("123")));
+    }
+
+    #[test]
+    fn ebnf_syntax_is_parsed() {
+        let grammar = r#"
+(* comments are ignored *)
+list ::= "[" items? "]"
+items ::= item ("," item)*
+item = 'a' | "b" | 2 * #x43 | { 'd' }+ ;
+"#;
+        let pattern = Regex::new(r"^\[((a|b|CC|d*)(,(a|b|CC|d*))*)?\]$").unwrap();
+        for value in generate(Syntax::Ebnf, grammar, "list", 16, 100) {
+            assert!(pattern.is_match(&value), "invalid list: '{}'", value);
+        }
+        for line in run_lines(r#"from_ebnf("test-data/arithmetic.ebnf", "expression", 6)"#, 50) {
+            assert!(line.chars().all(|c| "0123456789.+-*/()".contains(c)), "invalid expression: {}", line);
+        }
+    }
+
+    #[test]
+    fn invalid_grammars_return_errors() {
+        assert!(parse_error(Syntax::Abnf, "a = b\n").contains("line 1: the rule 'b' is used but never defined"));
+        assert!(parse_error(Syntax::Abnf, "a = \"x\"\n\na = \"y\"\n").contains("line 3: the rule 'a' is already defined on line 1"));
+        assert!(parse_error(Syntax::Abnf, "a = <anything>\n").contains("prose values like <anything> can't be generated"));
+        assert!(parse_error(Syntax::Abnf, "a = \"x\" ;@weight lots\n").contains("invalid weight 'lots'"));
+        assert!(parse_error(Syntax::Abnf, "a = 3*2\"x\"\n").contains("the maximum is less than the minimum"));
+        assert!(parse_error(Syntax::Ebnf, "a = 'x' - 'y';").contains("exceptions using '-' are not supported"));
+        assert!(parse_error(Syntax::Ebnf, "a = ('x' ;").contains("expected ')'"));
+        // the core rules may be redefined
+        let values = generate(Syntax::Abnf, "a = DIGIT\nDIGIT = %s\"x\"\n", "a", 16, 5);
+        assert!(values.iter().all(|value| value == "x"));
+
+        assert_runtime_error(
+            r#"from_abnf("test-data/uri.abnf", "missing")"#,
+            "The grammar 'test-data/uri.abnf' does not define a rule named 'missing'",
+        );
+        assert_runtime_error(r#"from_ebnf("test-data/uri.abnf", "URI")"#, "Invalid grammar 'test-data/uri.abnf': line 5: unexpected character '%'");
+    }
+}
@@ -0,0 +1,274 @@
+use crate::json::JsonValue;
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Whether the text can be written without quotes. The special characters are a union of what various ini parsers
+/// treat specially, including inline comments. Empty strings are quoted to tell them apart from null
+fn can_be_unquoted(text: &str, specials: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+        && !text.ends_with(char::is_whitespace)
+        && !text.chars().any(|c| c.is_control() || specials.contains(c))
+}
+
+/// Whether ini parsers would read the text as a boolean or a number if it wasn't quoted, so a string value with this
+/// text must always be quoted to stay a string
+fn looks_like_bool_or_number(text: &str) -> bool {
+    let lowercase = text.to_lowercase();
+    ["true", "false", "yes", "no", "on", "off"].contains(&lowercase.as_str()) || text.parse::<f64>().is_ok()
+}
+
+fn write_quoted(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\x{:02x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Arrays that contain other arrays or objects are written as if they were objects with the indexes as keys
+fn indexed_members(items: &[JsonValue]) -> Vec<(String, JsonValue)> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (i.to_string(), item.clone()))
+        .collect()
+}
+
+struct IniWriter<'a> {
+    ctx: &'a mut ProgramContext,
+    out: String,
+    delimiter: char,
+}
+
+impl<'a> IniWriter<'a> {
+    fn maybe_comment(&mut self) {
+        if self.ctx.gen_chance(0.05) {
+            let comment = self.ctx.choose(&["; comment", ";", "# note", "#"]);
+            self.out.push_str(comment);
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str, specials: &str) {
+        if can_be_unquoted(text, specials) && self.ctx.gen_chance(0.8) {
+            self.out.push_str(text);
+        } else {
+            write_quoted(text, &mut self.out);
+        }
+    }
+
+    /// Writes the entries of a section, followed by a separate section for every nested object
+    fn section(&mut self, path: &[String], members: &[(String, JsonValue)]) {
+        let mut subsections = Vec::new();
+        for (key, value) in members.iter() {
+            match value {
+                JsonValue::Object(inner) => subsections.push((key.clone(), inner.clone())),
+                JsonValue::Array(items) if items.iter().any(JsonValue::is_container) => {
+                    subsections.push((key.clone(), indexed_members(items)));
+                }
+                JsonValue::Array(items) => {
+                    for item in items.iter() {
+                        self.entry(key, "[]", item);
+                    }
+                }
+                other => self.entry(key, "", other),
+            }
+        }
+
+        for (key, inner) in subsections {
+            let mut subsection_path = path.to_vec();
+            subsection_path.push(key);
+            self.header(&subsection_path);
+            self.section(&subsection_path, &inner);
+        }
+    }
+
+    fn header(&mut self, path: &[String]) {
+        if !self.out.is_empty() && self.ctx.gen_chance(0.8) {
+            self.out.push('\n');
+        }
+        self.maybe_comment();
+        self.out.push('[');
+        for (i, name) in path.iter().enumerate() {
+            if i > 0 {
+                self.out.push('.');
+            }
+            self.text(name, "[].;#");
+        }
+        self.out.push_str("]\n");
+    }
+
+    fn entry(&mut self, key: &str, key_suffix: &str, value: &JsonValue) {
+        self.maybe_comment();
+        self.text(key, "[]=:;#");
+        self.out.push_str(key_suffix);
+        let spacing = self.ctx.choose(&["", " ", " "]);
+        let _ = write!(self.out, "{}{}{}", spacing, self.delimiter, spacing);
+        match value {
+            JsonValue::Boolean(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(number) => self.out.push_str(number),
+            JsonValue::String(string) if looks_like_bool_or_number(string) => write_quoted(string, &mut self.out),
+            JsonValue::String(string) => self.text(string, ";#"),
+            // everything else is only written by section, so the only thing left is null, which is an empty value
+            _ => {}
+        }
+        self.out.push('\n');
+    }
+}
+
+#[derive(Debug)]
+struct ToIni {
+    json: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ToIni {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let json = self.json.gen_value(ctx)?;
+        let value = JsonValue::parse(&json)?;
+        let members = match value {
+            JsonValue::Object(members) => members,
+            other => bail!("to_ini requires a json object, but got a {}", other.type_name()),
+        };
+        let delimiter = ctx.choose(&['=', '=', ':']);
+        let mut writer = IniWriter {
+            ctx,
+            out: String::new(),
+            delimiter,
+        };
+        writer.section(&[], &members);
+        Ok(writer.out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const JSON_PARAM: &str = "json";
+
+fn create_to_ini(args: Arguments) -> CreateFunctionResult {
+    let json = args.required_arg(JSON_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ToIni { json })))
+}
+
+pub const TO_INI_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_ini",
+    description: "converts a json object into an ini file. Nested objects become sections named by their dotted path, arrays of scalars are written as repeated 'key[]' entries, and arrays containing objects or arrays are treated as objects with the indexes as keys. Null is written as an empty value, and keys, section names, and values that would be ambiguous are double quoted using backslash escapes. Every document randomly uses either '=' or ':' between keys and values",
+    arguments: &[(JSON_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_to_ini,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_runtime_error, run_program};
+
+    fn unquote(text: &str) -> String {
+        let text = text.trim();
+        if !text.starts_with('"') {
+            return text.to_owned();
+        }
+        let mut unquoted = String::new();
+        let mut chars = text[1..text.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => unquoted.push('\n'),
+                    Some('t') => unquoted.push('\t'),
+                    Some(other) => unquoted.push(other),
+                    None => {}
+                }
+            } else {
+                unquoted.push(c);
+            }
+        }
+        unquoted
+    }
+
+    /// Reads the (section, key, value) entries from an ini file, assuming that quoted text doesn't contain delimiters
+    fn read_entries(ini: &str) -> Vec<(String, String, String)> {
+        let mut section = String::new();
+        let mut entries = Vec::new();
+        for line in ini.lines() {
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                section = line[1..line.len() - 1]
+                    .split('.')
+                    .map(unquote)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                continue;
+            }
+            let delimiter = line.find(['=', ':']).unwrap();
+            let key = line[..delimiter].trim();
+            let key = match key.find("[]") {
+                Some(suffix) => format!("{}[]", unquote(&key[..suffix])),
+                None => unquote(key),
+            };
+            entries.push((section.clone(), key, unquote(&line[delimiter + 1..])));
+        }
+        entries
+    }
+
+    #[test]
+    fn nested_values_are_written_as_sections() {
+        let json = r#"{"name": "dgen", "empty": null, "tags": ["a", "b"], "server": {"host": " local host", "port": 8080, "tls": {"cert": "a;b"}}, "items": [{"id": 1}], "n": "x\ny"}"#;
+        let program = format!("concat(to_ini({:?}), \"\\u{{0}}\")", json);
+        let output = String::from_utf8(run_program(50, &program).unwrap()).unwrap();
+        let entry = |section: &str, key: &str, value: &str| (section.to_owned(), key.to_owned(), value.to_owned());
+        let expected = vec![
+            entry("", "name", "dgen"),
+            entry("", "empty", ""),
+            entry("", "tags[]", "a"),
+            entry("", "tags[]", "b"),
+            entry("", "n", "x\ny"),
+            entry("server", "host", " local host"),
+            entry("server", "port", "8080"),
+            entry("server.tls", "cert", "a;b"),
+            entry("items.0", "id", "1"),
+        ];
+        for document in output.split_terminator('\u{0}') {
+            assert_eq!(expected, read_entries(document), "document:\n{}", document);
+        }
+    }
+
+    #[test]
+    fn strings_that_look_like_other_values_are_always_quoted() {
+        let json = r#"{"enabled": "true", "debug": true, "port": "8080", "timeout": 30, "ratio": "-1.5e3", "mode": "Off"}"#;
+        let program = format!("concat(to_ini({:?}), \"\\u{{0}}\")", json);
+        let output = String::from_utf8(run_program(50, &program).unwrap()).unwrap();
+        for document in output.split_terminator('\u{0}') {
+            let values: Vec<&str> = document
+                .lines()
+                .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with('#'))
+                .map(|line| line[line.find(['=', ':']).unwrap() + 1..].trim())
+                .collect();
+            let expected = vec!["\"true\"", "true", "\"8080\"", "30", "\"-1.5e3\"", "\"Off\""];
+            assert_eq!(expected, values, "document:\n{}", document);
+        }
+    }
+
+    #[test]
+    fn non_object_documents_return_errors() {
+        assert_runtime_error(r#"to_ini("\"a\"")"#, "to_ini requires a json object, but got a string");
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InvalidUtf8Kind {
+    Truncated,
+    Overlong,
+    Surrogate,
+    AboveMax,
+    Continuation,
+    InvalidByte,
+}
+
+const ALL_KINDS: &[InvalidUtf8Kind] = &[
+    InvalidUtf8Kind::Truncated,
+    InvalidUtf8Kind::Overlong,
+    InvalidUtf8Kind::Surrogate,
+    InvalidUtf8Kind::AboveMax,
+    InvalidUtf8Kind::Continuation,
+    InvalidUtf8Kind::InvalidByte,
+];
+
+impl InvalidUtf8Kind {
+    fn from_str(kind: &str) -> Result<InvalidUtf8Kind, Error> {
+        match kind {
+            "truncated" => Ok(InvalidUtf8Kind::Truncated),
+            "overlong" => Ok(InvalidUtf8Kind::Overlong),
+            "surrogate" => Ok(InvalidUtf8Kind::Surrogate),
+            "above_max" => Ok(InvalidUtf8Kind::AboveMax),
+            "continuation" => Ok(InvalidUtf8Kind::Continuation),
+            "invalid_byte" => Ok(InvalidUtf8Kind::InvalidByte),
+            other => bail!("Invalid kind of invalid utf-8: '{}', must be one of 'truncated', 'overlong', 'surrogate', 'above_max', 'continuation', or 'invalid_byte'", other),
+        }
+    }
+
+    fn random(ctx: &mut ProgramContext) -> InvalidUtf8Kind {
+        ALL_KINDS[ctx.gen_range_exclusive(0, ALL_KINDS.len())]
+    }
+
+    fn gen_sequence(self, ctx: &mut ProgramContext, out: &mut Vec<u8>) {
+        match self {
+            InvalidUtf8Kind::Truncated => {
+                // a valid multi-byte sequence that's missing at least one of its trailing bytes
+                let len = ctx.gen_range_inclusive(2, 4);
+                let codepoint = match len {
+                    2 => ctx.gen_range_inclusive(0x80, 0x7FF),
+                    3 => ctx.gen_range_inclusive(0x800, 0xFFFF),
+                    _ => ctx.gen_range_inclusive(0x10000, 0x10FFFF),
+                };
+                let start = out.len();
+                encode_utf8_unchecked(codepoint, len, out);
+                let keep = ctx.gen_range_exclusive(1, len);
+                out.truncate(start + keep);
+            }
+            InvalidUtf8Kind::Overlong => {
+                // a codepoint encoded using more bytes than necessary, like the infamous 0xC0 0xAF for '/'
+                let len = ctx.gen_range_inclusive(2, 4);
+                let codepoint = match len {
+                    2 => ctx.gen_range_exclusive(0, 0x80),
+                    3 => ctx.gen_range_exclusive(0, 0x800),
+                    _ => ctx.gen_range_exclusive(0, 0x10000),
+                };
+                encode_utf8_unchecked(codepoint, len, out);
+            }
+            InvalidUtf8Kind::Surrogate => {
+                let codepoint = ctx.gen_range_inclusive(0xD800, 0xDFFF);
+                encode_utf8_unchecked(codepoint, 3, out);
+            }
+            InvalidUtf8Kind::AboveMax => {
+                // includes the 5 and 6 byte forms from the original utf-8 specification
+                let len = ctx.gen_range_inclusive(4, 6);
+                let codepoint = match len {
+                    4 => ctx.gen_range_inclusive(0x110000, 0x1F_FFFF),
+                    5 => ctx.gen_range_inclusive(0x20_0000, 0x3FF_FFFF),
+                    _ => ctx.gen_range_inclusive(0x400_0000, 0x7FFF_FFFF),
+                };
+                encode_utf8_unchecked(codepoint, len, out);
+            }
+            InvalidUtf8Kind::Continuation => {
+                let count = ctx.gen_range_inclusive(1, 3);
+                for _ in 0..count {
+                    out.push(ctx.gen_range_inclusive(0x80, 0xBF));
+                }
+            }
+            InvalidUtf8Kind::InvalidByte => {
+                // bytes that can never appear anywhere in valid utf-8
+                let byte = match ctx.gen_range_exclusive(0, 13) {
+                    0 => 0xC0,
+                    1 => 0xC1,
+                    n => 0xF3 + n as u8,
+                };
+                out.push(byte);
+            }
+        }
+    }
+}
+
+/// Encodes the codepoint using the utf-8 bit layout with exactly `len` bytes, without checking whether the
+/// result is valid
+fn encode_utf8_unchecked(codepoint: u32, len: usize, out: &mut Vec<u8>) {
+    if len == 1 {
+        out.push(codepoint as u8);
+        return;
+    }
+    let prefix = !(0xFFu8 >> len);
+    let shift = 6 * (len - 1) as u32;
+    out.push(prefix | (codepoint >> shift) as u8);
+    for i in (0..len - 1).rev() {
+        out.push(0x80 | ((codepoint >> (6 * i as u32)) & 0x3F) as u8);
+    }
+}
+
+#[derive(Debug)]
+struct InvalidUtf8 {
+    kind: Option<DynStringFun>,
+}
+
+impl RunnableFunction<Vec<u8>> for InvalidUtf8 {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let kind = match self.kind {
+            Some(ref kind) => InvalidUtf8Kind::from_str(&kind.gen_value(ctx)?)?,
+            None => InvalidUtf8Kind::random(ctx),
+        };
+        let mut bytes = Vec::with_capacity(6);
+        kind.gen_sequence(ctx, &mut bytes);
+        Ok(bytes)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct MixInvalidUtf8 {
+    rate: DynDecimalFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<Vec<u8>> for MixInvalidUtf8 {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let rate = self.rate.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+        let mut bytes = Vec::with_capacity(value.len() * 2);
+        let mut buffer = [0u8; 4];
+        for c in value.chars() {
+            if ctx.gen_chance(rate) {
+                InvalidUtf8Kind::random(ctx).gen_sequence(ctx, &mut bytes);
+            }
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        }
+        if ctx.gen_chance(rate) {
+            InvalidUtf8Kind::random(ctx).gen_sequence(ctx, &mut bytes);
+        }
+        Ok(bytes)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+fn push_utf16_unit(unit: u16, big_endian: bool, out: &mut Vec<u8>) {
+    let bytes = if big_endian {
+        unit.to_be_bytes()
+    } else {
+        unit.to_le_bytes()
+    };
+    out.extend_from_slice(&bytes);
+}
+
+#[derive(Debug)]
+struct Utf16LoneSurrogates {
+    encoding: DynStringFun,
+    rate: DynDecimalFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<Vec<u8>> for Utf16LoneSurrogates {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let encoding = self.encoding.gen_value(ctx)?;
+        let big_endian = match &*encoding {
+            "utf-16be" => true,
+            "utf-16le" => false,
+            other => bail!(
+                "Invalid encoding: '{}', must be either 'utf-16be' or 'utf-16le'",
+                other
+            ),
+        };
+        let rate = self.rate.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+
+        let mut bytes = Vec::with_capacity(value.len() * 2);
+        let mut buffer = [0u16; 2];
+        for c in value.chars() {
+            if ctx.gen_chance(rate) {
+                let surrogate = ctx.gen_range_inclusive(0xD800, 0xDFFF);
+                push_utf16_unit(surrogate, big_endian, &mut bytes);
+            }
+            for unit in c.encode_utf16(&mut buffer).iter() {
+                push_utf16_unit(*unit, big_endian, &mut bytes);
+            }
+        }
+        if ctx.gen_chance(rate) {
+            let surrogate = ctx.gen_range_inclusive(0xD800, 0xDFFF);
+            push_utf16_unit(surrogate, big_endian, &mut bytes);
+        }
+        Ok(bytes)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+/// Encodes strings using CESU-8, where supplementary characters are encoded as a utf-16 surrogate pair, with each
+/// surrogate encoded separately as 3 bytes. Modified utf-8 (as used by Java) is the same thing, except that NUL is
+/// encoded as the overlong 2 byte sequence 0xC0 0x80.
+#[derive(Debug)]
+struct SurrogatePairEncoding {
+    modified_utf8: bool,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<Vec<u8>> for SurrogatePairEncoding {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let value = self.wrapped.gen_value(ctx)?;
+        let mut bytes = Vec::with_capacity(value.len() * 2);
+        let mut buffer = [0u16; 2];
+        for c in value.chars() {
+            if c == '\0' && self.modified_utf8 {
+                encode_utf8_unchecked(0, 2, &mut bytes);
+                continue;
+            }
+            let codepoint = c as u32;
+            if codepoint < 0x10000 {
+                let len = c.len_utf8();
+                encode_utf8_unchecked(codepoint, len, &mut bytes);
+            } else {
+                for unit in c.encode_utf16(&mut buffer).iter() {
+                    encode_utf8_unchecked(u32::from(*unit), 3, &mut bytes);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+const KIND_PARAM: &str = "kind";
+const RATE_PARAM: &str = "rate";
+const STRING_PARAM: &str = "string";
+
+fn create_invalid_utf8(args: Arguments) -> CreateFunctionResult {
+    let kind = args.required_arg(KIND_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::Bin(Rc::new(InvalidUtf8 { kind: Some(kind) })))
+}
+
+fn create_any_invalid_utf8(_: Arguments) -> CreateFunctionResult {
+    Ok(AnyFunction::Bin(Rc::new(InvalidUtf8 { kind: None })))
+}
+
+fn create_mix_invalid_utf8(args: Arguments) -> CreateFunctionResult {
+    let (rate, wrapped) = args.require_2_args(
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(MixInvalidUtf8 { rate, wrapped })))
+}
+
+fn create_utf16_lone_surrogates(args: Arguments) -> CreateFunctionResult {
+    let (encoding, rate, wrapped) = args.require_3_args(
+        "encoding",
+        AnyFunction::require_string,
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(Utf16LoneSurrogates {
+        encoding,
+        rate,
+        wrapped,
+    })))
+}
+
+fn create_cesu8_bytes(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::Bin(Rc::new(SurrogatePairEncoding {
+        modified_utf8: false,
+        wrapped,
+    })))
+}
+
+fn create_modified_utf8_bytes(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::Bin(Rc::new(SurrogatePairEncoding {
+        modified_utf8: true,
+        wrapped,
+    })))
+}
+
+pub const INVALID_UTF8_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "invalid_utf8",
+    description: "generates a single invalid utf-8 sequence of the given kind, which must be one of 'truncated', 'overlong', 'surrogate', 'above_max', 'continuation', or 'invalid_byte'",
+    arguments: &[(KIND_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_invalid_utf8,
+};
+
+pub const ANY_INVALID_UTF8_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "invalid_utf8",
+    description: "generates a single invalid utf-8 sequence of a randomly selected kind",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_any_invalid_utf8,
+};
+
+pub const MIX_INVALID_UTF8_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mix_invalid_utf8",
+    description: "encodes the string as utf-8, inserting random invalid sequences between characters. Each position gets an invalid sequence with the given probability between 0 and 1",
+    arguments: &[(RATE_PARAM, GenType::Decimal), (STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_mix_invalid_utf8,
+};
+
+pub const UTF16_LONE_SURROGATES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "utf16_lone_surrogates",
+    description: "encodes the string as either 'utf-16be' or 'utf-16le', inserting unpaired surrogates between characters. Each position gets a lone surrogate with the given probability between 0 and 1",
+    arguments: &[
+        ("encoding", GenType::String),
+        (RATE_PARAM, GenType::Decimal),
+        (STRING_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_utf16_lone_surrogates,
+};
+
+pub const CESU8_BYTES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "cesu8_bytes",
+    description: "encodes the string as CESU-8, where characters outside the basic multilingual plane are encoded as a pair of 3 byte surrogates",
+    arguments: &[(STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_cesu8_bytes,
+};
+
+pub const MODIFIED_UTF8_BYTES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "modified_utf8_bytes",
+    description: "encodes the string as modified utf-8 (as used by Java), which is CESU-8 with NUL encoded as 0xC0 0x80",
+    arguments: &[(STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_modified_utf8_bytes,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{
+        assert_bin_output_is_expected, assert_runtime_error, run_program, test_program_success,
+    };
+
+    #[test]
+    fn every_kind_of_invalid_utf8_is_invalid() {
+        for kind in &[
+            "truncated",
+            "overlong",
+            "surrogate",
+            "above_max",
+            "continuation",
+            "invalid_byte",
+        ] {
+            let program = format!(r#"invalid_utf8("{}")"#, kind);
+            for _ in 0..5 {
+                let output = run_program(1, &program).expect("failed to run program");
+                assert!(
+                    ::std::str::from_utf8(&output).is_err(),
+                    "kind: {}, output: {:?}",
+                    kind,
+                    output
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_returns_error_for_unknown_kind() {
+        assert_runtime_error(r#"invalid_utf8("bogus")"#, "Invalid kind of invalid utf-8: 'bogus'");
+    }
+
+    #[test]
+    fn mix_invalid_utf8_keeps_the_original_text() {
+        test_program_success(1, r#"mix_invalid_utf8(0.0, "foo")"#, "foo");
+        let output = run_program(1, r#"mix_invalid_utf8(1.0, "foo")"#).unwrap();
+        assert!(::std::str::from_utf8(&output).is_err());
+        let lossy = String::from_utf8_lossy(&output);
+        let ascii: String = lossy.chars().filter(|c| c.is_ascii()).collect();
+        assert_eq!("foo", ascii);
+    }
+
+    #[test]
+    fn utf16_lone_surrogates_are_inserted() {
+        assert_bin_output_is_expected(
+            r#"utf16_lone_surrogates("utf-16be", 0.0, "a\u{1F600}")"#,
+            &[0x00, 0x61, 0xD8, 0x3D, 0xDE, 0x00],
+        );
+        let output = run_program(1, r#"utf16_lone_surrogates("utf-16le", 1.0, "ab")"#).unwrap();
+        let units: Vec<u16> = output
+            .chunks(2)
+            .map(|pair| u16::from(pair[0]) | (u16::from(pair[1]) << 8))
+            .collect();
+        assert_eq!(5, units.len());
+        assert!(String::from_utf16(&units).is_err());
+    }
+
+    #[test]
+    fn cesu8_and_modified_utf8_encode_supplementary_characters_as_surrogates() {
+        assert_bin_output_is_expected(
+            r#"cesu8_bytes("a\u{0}\u{1F600}")"#,
+            &[0x61, 0x00, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80],
+        );
+        assert_bin_output_is_expected(
+            r#"modified_utf8_bytes("a\u{0}\u{1F600}")"#,
+            &[0x61, 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80],
+        );
+    }
+}
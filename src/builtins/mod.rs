@@ -1,14 +1,39 @@
 mod bin_length;
+mod bits;
 mod byte_order;
+mod cbor;
+mod checksum;
 mod concat;
+mod csv;
+mod encoding;
 mod env;
+mod escape;
+mod framing;
 mod from_file;
+mod grammar;
+mod ini;
+mod malformed;
+mod msgpack;
+mod mutate;
+mod network;
+mod number_format;
 mod numeric;
+mod protobuf;
 mod repeat_delim;
+mod schema;
 mod select;
 mod sequence;
+mod stdin;
 mod strings;
 mod to_string;
+mod toml;
+mod unicode;
+mod uuid;
+mod varint;
+mod yaml;
+
+pub(crate) use self::csv::parse_csv;
+pub(crate) use self::stdin::{reads_input, reads_stdin, EndOfInput};
 
 use crate::interpreter::Module;
 use crate::BuiltinFunctionPrototype;
@@ -21,10 +46,18 @@ const BUILTIN_FNS: &'static [&'static BuiltinFunctionPrototype] = &[
     self::byte_order::INT_BIG_ENDIAN,
     self::byte_order::DECIMAL_LITTLE_ENDIAN,
     self::byte_order::DECIMAL_BIG_ENDIAN,
+    self::byte_order::SIZED_UINT_LITTLE_ENDIAN,
+    self::byte_order::SIZED_UINT_BIG_ENDIAN,
+    self::byte_order::SIZED_INT_LITTLE_ENDIAN,
+    self::byte_order::SIZED_INT_BIG_ENDIAN,
+    self::byte_order::SIZED_DECIMAL_LITTLE_ENDIAN,
+    self::byte_order::SIZED_DECIMAL_BIG_ENDIAN,
     self::env::ENV_VAR,
     self::strings::STRING_GEN_BUILTIN,
     self::strings::STRING_LENGTH_BUILTIN,
     self::strings::STRING_ENCODE_BUILTIN,
+    self::strings::TO_UPPERCASE_BUILTIN,
+    self::strings::TO_LOWERCASE_BUILTIN,
     self::concat::CONCAT_BUILTIN,
     self::concat::CONCAT_BIN_BUILTIN,
     self::select::SELECT_STRING_BUILTIN,
@@ -46,6 +79,22 @@ const BUILTIN_FNS: &'static [&'static BuiltinFunctionPrototype] = &[
     self::numeric::DECIMAL_BUILTIN,
     self::from_file::WORDS_BUILTIN,
     self::from_file::SELECT_FROM_FILE_BUILTIN,
+    self::from_file::SELECT_FROM_FILE_BIN_BUILTIN,
+    self::from_file::NEXT_LINE_BUILTIN,
+    self::from_file::NEXT_LINE_WRAP_BUILTIN,
+    self::from_file::NEXT_LINE_BIN_BUILTIN,
+    self::from_file::NEXT_LINE_BIN_WRAP_BUILTIN,
+    self::from_file::NEXT_RECORD_BUILTIN,
+    self::from_file::NEXT_RECORD_WRAP_BUILTIN,
+    self::from_file::NEXT_RECORD_BIN_BUILTIN,
+    self::from_file::NEXT_RECORD_BIN_WRAP_BUILTIN,
+    self::from_file::FILE_BYTES_BUILTIN,
+    self::stdin::STDIN_LINE_BUILTIN,
+    self::stdin::STDIN_RECORD_BUILTIN,
+    self::stdin::STDIN_BYTES_BUILTIN,
+    self::stdin::PIPE_LINE_BUILTIN,
+    self::stdin::PIPE_RECORD_BUILTIN,
+    self::stdin::PIPE_BYTES_BUILTIN,
     self::to_string::BOOLEAN_TO_STRING_BUILTIN,
     self::to_string::DECIMAL_TO_STRING_BUILTIN,
     self::to_string::INT_TO_STRING_BUILTIN,
@@ -60,6 +109,180 @@ const BUILTIN_FNS: &'static [&'static BuiltinFunctionPrototype] = &[
     self::sequence::INT_SEQ,
     self::sequence::DECIMAL_WRAPPING_SEQ,
     self::sequence::DECIMAL_SEQ,
+    self::uuid::UUID_V4_BIN_BUILTIN,
+    self::uuid::UUID_V7_BIN_BUILTIN,
+    self::uuid::ULID_BIN_BUILTIN,
+    self::uuid::NIL_UUID_BIN_BUILTIN,
+    self::uuid::MAX_UUID_BIN_BUILTIN,
+    self::uuid::UUID_STRING_BUILTIN,
+    self::uuid::ULID_STRING_BUILTIN,
+    self::network::IPV4_BIN_BUILTIN,
+    self::network::IPV4_RANGE_BIN_BUILTIN,
+    self::network::IPV6_BIN_BUILTIN,
+    self::network::IPV6_RANGE_BIN_BUILTIN,
+    self::network::IPV4_CIDR_BUILTIN,
+    self::network::IPV6_CIDR_BUILTIN,
+    self::network::IPV4_STRING_BUILTIN,
+    self::network::IPV6_STRING_BUILTIN,
+    self::network::MAC_BIN_BUILTIN,
+    self::network::MAC_STRING_BUILTIN,
+    self::network::HOST_PORT_BUILTIN,
+    self::network::PORT_BIN_BUILTIN,
+    self::unicode::COMBINING_MARKS_BUILTIN,
+    self::unicode::NORMALIZE_BUILTIN,
+    self::unicode::INSERT_ZERO_WIDTH_BUILTIN,
+    self::unicode::HOMOGLYPHS_BUILTIN,
+    self::unicode::TO_FULLWIDTH_BUILTIN,
+    self::unicode::TO_HALFWIDTH_BUILTIN,
+    self::malformed::INVALID_UTF8_BUILTIN,
+    self::malformed::ANY_INVALID_UTF8_BUILTIN,
+    self::malformed::MIX_INVALID_UTF8_BUILTIN,
+    self::malformed::UTF16_LONE_SURROGATES_BUILTIN,
+    self::malformed::CESU8_BYTES_BUILTIN,
+    self::malformed::MODIFIED_UTF8_BYTES_BUILTIN,
+    self::mutate::MUTATE_BIN_BUILTIN,
+    self::mutate::MUTATE_STRING_BUILTIN,
+    self::mutate::MUTATE_FILE_BUILTIN,
+    self::numeric::EDGE_UINT_BUILTIN,
+    self::numeric::EDGE_INT_BUILTIN,
+    self::numeric::EDGE_DECIMAL_BUILTIN,
+    self::numeric::UNBOUNDED_EDGE_DECIMAL_BUILTIN,
+    self::number_format::UINT_TO_RADIX_BUILTIN,
+    self::number_format::INT_TO_RADIX_BUILTIN,
+    self::number_format::TO_FIXED_BUILTIN,
+    self::number_format::TO_SCIENTIFIC_BUILTIN,
+    self::number_format::TO_SCIENTIFIC_WITH_STYLE_BUILTIN,
+    self::number_format::TO_SCIENTIFIC_WITH_PRECISION_BUILTIN,
+    self::number_format::EXPLICIT_SIGN_BUILTIN,
+    self::number_format::ZERO_PAD_BUILTIN,
+    self::number_format::GROUP_DIGITS_BUILTIN,
+    self::number_format::UINT_EQUIVALENT_FORM_BUILTIN,
+    self::number_format::INT_EQUIVALENT_FORM_BUILTIN,
+    self::number_format::DECIMAL_EQUIVALENT_FORM_BUILTIN,
+    self::varint::ULEB128_BUILTIN,
+    self::varint::SLEB128_BUILTIN,
+    self::varint::UINT_PROTOBUF_VARINT_BUILTIN,
+    self::varint::INT_PROTOBUF_VARINT_BUILTIN,
+    self::varint::ZIGZAG_VARINT_BUILTIN,
+    self::varint::QUIC_VARINT_BUILTIN,
+    self::varint::SIZED_QUIC_VARINT_BUILTIN,
+    self::varint::BER_LENGTH_BUILTIN,
+    self::varint::SIZED_BER_LENGTH_BUILTIN,
+    self::bits::BITS_BUILTIN,
+    self::bits::BITS_LSB_BUILTIN,
+    self::checksum::CRC32_BUILTIN,
+    self::checksum::CRC16_BUILTIN,
+    self::checksum::CRC16_VARIANT_BUILTIN,
+    self::checksum::ADLER32_BUILTIN,
+    self::checksum::INET_CHECKSUM_BUILTIN,
+    self::checksum::MD5_BUILTIN,
+    self::checksum::SHA1_BUILTIN,
+    self::checksum::SHA256_BUILTIN,
+    self::checksum::CORRUPT_UINT_CHECKSUM_BUILTIN,
+    self::checksum::CORRUPT_BIN_CHECKSUM_BUILTIN,
+    self::framing::LENGTH_PREFIXED_BUILTIN,
+    self::framing::LYING_LENGTH_PREFIXED_BUILTIN,
+    self::framing::TLV_BUILTIN,
+    self::framing::BER_TLV_BUILTIN,
+    self::framing::VARINT_PREFIXED_BUILTIN,
+    self::protobuf::TAG_BUILTIN,
+    self::protobuf::UINT_VARINT_FIELD_BUILTIN,
+    self::protobuf::INT_VARINT_FIELD_BUILTIN,
+    self::protobuf::BOOLEAN_VARINT_FIELD_BUILTIN,
+    self::protobuf::SINT_FIELD_BUILTIN,
+    self::protobuf::UINT_FIXED32_FIELD_BUILTIN,
+    self::protobuf::INT_FIXED32_FIELD_BUILTIN,
+    self::protobuf::DECIMAL_FIXED32_FIELD_BUILTIN,
+    self::protobuf::UINT_FIXED64_FIELD_BUILTIN,
+    self::protobuf::INT_FIXED64_FIELD_BUILTIN,
+    self::protobuf::DECIMAL_FIXED64_FIELD_BUILTIN,
+    self::protobuf::BIN_BYTES_FIELD_BUILTIN,
+    self::protobuf::STRING_BYTES_FIELD_BUILTIN,
+    self::protobuf::PACKED_FIELD_BUILTIN,
+    self::protobuf::MESSAGE_BUILTIN,
+    self::escape::JSON_STRING_BUILTIN,
+    self::escape::JSON_STRING_VARIED_BUILTIN,
+    self::escape::JSON5_STRING_BUILTIN,
+    self::escape::JSON5_STRING_VARIED_BUILTIN,
+    self::escape::XML_TEXT_BUILTIN,
+    self::escape::XML_TEXT_VARIED_BUILTIN,
+    self::escape::XML_ATTRIBUTE_BUILTIN,
+    self::escape::XML_ATTRIBUTE_VARIED_BUILTIN,
+    self::escape::XML_SINGLE_QUOTED_ATTRIBUTE_BUILTIN,
+    self::escape::XML_SINGLE_QUOTED_ATTRIBUTE_VARIED_BUILTIN,
+    self::escape::XML_CDATA_BUILTIN,
+    self::escape::XML_CDATA_VARIED_BUILTIN,
+    self::escape::XML_COMMENT_BUILTIN,
+    self::escape::CSV_FIELD_BUILTIN,
+    self::escape::CSV_FIELD_VARIED_BUILTIN,
+    self::escape::SHELL_SINGLE_QUOTE_BUILTIN,
+    self::escape::SHELL_SINGLE_QUOTE_VARIED_BUILTIN,
+    self::escape::SHELL_DOUBLE_QUOTE_BUILTIN,
+    self::escape::SHELL_DOUBLE_QUOTE_VARIED_BUILTIN,
+    self::escape::SQL_STRING_BUILTIN,
+    self::escape::C_STRING_BUILTIN,
+    self::escape::C_STRING_VARIED_BUILTIN,
+    self::escape::URL_COMPONENT_BUILTIN,
+    self::escape::URL_COMPONENT_VARIED_BUILTIN,
+    self::csv::CSV_ROW_BUILTIN,
+    self::csv::SELECT_CSV_ROW_BUILTIN,
+    self::csv::CSV_COLUMN_BUILTIN,
+    self::csv::CSV_COLUMN_AT_BUILTIN,
+    self::yaml::TO_YAML_BUILTIN,
+    self::toml::TO_TOML_BUILTIN,
+    self::ini::TO_INI_BUILTIN,
+    self::cbor::UINT_BUILTIN,
+    self::cbor::UINT_VARIED_BUILTIN,
+    self::cbor::INT_BUILTIN,
+    self::cbor::INT_VARIED_BUILTIN,
+    self::cbor::FLOAT_BUILTIN,
+    self::cbor::FLOAT_VARIED_BUILTIN,
+    self::cbor::TEXT_BUILTIN,
+    self::cbor::TEXT_VARIED_BUILTIN,
+    self::cbor::BYTES_BUILTIN,
+    self::cbor::BYTES_VARIED_BUILTIN,
+    self::cbor::ARRAY_BUILTIN,
+    self::cbor::ARRAY_VARIED_BUILTIN,
+    self::cbor::EMPTY_ARRAY_BUILTIN,
+    self::cbor::EMPTY_ARRAY_VARIED_BUILTIN,
+    self::cbor::MAP_BUILTIN,
+    self::cbor::MAP_VARIED_BUILTIN,
+    self::cbor::EMPTY_MAP_BUILTIN,
+    self::cbor::EMPTY_MAP_VARIED_BUILTIN,
+    self::cbor::TAG_BUILTIN,
+    self::cbor::TAG_VARIED_BUILTIN,
+    self::cbor::BOOL_BUILTIN,
+    self::cbor::NULL_BUILTIN,
+    self::msgpack::UINT_BUILTIN,
+    self::msgpack::UINT_VARIED_BUILTIN,
+    self::msgpack::INT_BUILTIN,
+    self::msgpack::INT_VARIED_BUILTIN,
+    self::msgpack::FLOAT_BUILTIN,
+    self::msgpack::FLOAT_VARIED_BUILTIN,
+    self::msgpack::STR_BUILTIN,
+    self::msgpack::STR_VARIED_BUILTIN,
+    self::msgpack::BIN_BUILTIN,
+    self::msgpack::BIN_VARIED_BUILTIN,
+    self::msgpack::ARRAY_BUILTIN,
+    self::msgpack::ARRAY_VARIED_BUILTIN,
+    self::msgpack::EMPTY_ARRAY_BUILTIN,
+    self::msgpack::EMPTY_ARRAY_VARIED_BUILTIN,
+    self::msgpack::MAP_BUILTIN,
+    self::msgpack::MAP_VARIED_BUILTIN,
+    self::msgpack::EMPTY_MAP_BUILTIN,
+    self::msgpack::EMPTY_MAP_VARIED_BUILTIN,
+    self::msgpack::EXT_BUILTIN,
+    self::msgpack::EXT_VARIED_BUILTIN,
+    self::msgpack::BOOL_BUILTIN,
+    self::msgpack::NIL_BUILTIN,
+    self::schema::FROM_SCHEMA_BUILTIN,
+    self::schema::FROM_SCHEMA_POINTER_BUILTIN,
+    self::schema::FROM_SCHEMA_VIOLATING_BUILTIN,
+    self::schema::FROM_SCHEMA_POINTER_VIOLATING_BUILTIN,
+    self::grammar::FROM_ABNF_BUILTIN,
+    self::grammar::FROM_ABNF_DEPTH_BUILTIN,
+    self::grammar::FROM_EBNF_BUILTIN,
+    self::grammar::FROM_EBNF_DEPTH_BUILTIN,
 ];
 
 
@@ -0,0 +1,423 @@
+use super::encoding::{
+    create_encode_items, create_encode_no_items, create_encode_tagged, create_encode_value, is_exact_single,
+    require_even_items, Variation, ITEMS_PARAM, KEYS_AND_VALUES_PARAM, VALUE_PARAM, VARIATION_RATE_PARAM,
+};
+use crate::IString;
+use crate::{AnyFunction, Arguments, BuiltinFunctionPrototype, ConstBin, CreateFunctionResult, GenType};
+use failure::Error;
+
+/// A format is the first byte and the number of big endian bytes that follow it. Fix formats have no following
+/// bytes, and store the value in the first byte instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Fix(u8),
+    Sized(u8, usize),
+}
+
+fn write_format(format: Format, value: u64, out: &mut Vec<u8>) {
+    match format {
+        Format::Fix(first_byte) => out.push(first_byte | value as u8),
+        Format::Sized(first_byte, size) => {
+            out.push(first_byte);
+            for i in (0..size).rev() {
+                out.push((value >> (i * 8)) as u8);
+            }
+        }
+    }
+}
+
+fn encode_uint(value: u64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    // the canonical format is always first, and values that fit in a signed format may also use those
+    let candidates = [
+        (0x7F, Format::Fix(0x00)),
+        (0xFF, Format::Sized(0xCC, 1)),
+        (0xFFFF, Format::Sized(0xCD, 2)),
+        (0xFFFF_FFFF, Format::Sized(0xCE, 4)),
+        (u64::MAX, Format::Sized(0xCF, 8)),
+        (0x7F, Format::Sized(0xD0, 1)),
+        (0x7FFF, Format::Sized(0xD1, 2)),
+        (0x7FFF_FFFF, Format::Sized(0xD2, 4)),
+        (i64::MAX as u64, Format::Sized(0xD3, 8)),
+    ];
+    let formats = candidates
+        .iter()
+        .filter(|(max, _)| value <= *max)
+        .map(|(_, format)| *format)
+        .collect::<Vec<_>>();
+    write_format(variation.choose(&formats), value, out);
+    Ok(())
+}
+
+fn encode_int(value: i64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    if value >= 0 {
+        return encode_uint(value as u64, variation, out);
+    }
+    let candidates = [
+        (-32, Format::Fix(0x00)),
+        (i64::from(i8::MIN), Format::Sized(0xD0, 1)),
+        (i64::from(i16::MIN), Format::Sized(0xD1, 2)),
+        (i64::from(i32::MIN), Format::Sized(0xD2, 4)),
+        (i64::MIN, Format::Sized(0xD3, 8)),
+    ];
+    let formats = candidates
+        .iter()
+        .filter(|(min, _)| value >= *min)
+        .map(|(_, format)| *format)
+        .collect::<Vec<_>>();
+    // negative fixints are the two's complement of the value, which always has the top three bits set
+    write_format(variation.choose(&formats), value as u64, out);
+    Ok(())
+}
+
+fn encode_float(value: f64, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    let single = is_exact_single(value) && variation.choose(&[true, false]);
+    if single {
+        out.push(0xCA);
+        out.extend_from_slice(&(value as f32).to_bits().to_be_bytes());
+    } else {
+        out.push(0xCB);
+        out.extend_from_slice(&value.to_bits().to_be_bytes());
+    }
+    Ok(())
+}
+
+/// Appends the header of a string, binary, array, or map using one of the formats that can hold the length. The fix
+/// format, if there is one, is only allowed up to the given maximum length
+fn write_length(
+    kind: &str,
+    len: usize,
+    fix: Option<(u8, usize)>,
+    sized: &[(u8, usize)],
+    variation: &mut Variation,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let mut formats = Vec::with_capacity(4);
+    if let Some((first_byte, max)) = fix {
+        if len <= max {
+            formats.push(Format::Fix(first_byte));
+        }
+    }
+    for &(first_byte, size) in sized.iter() {
+        if (len as u64) < 1 << (size * 8) {
+            formats.push(Format::Sized(first_byte, size));
+        }
+    }
+    if formats.is_empty() {
+        bail!("The {} length {} is too large for messagepack", kind, len);
+    }
+    write_format(variation.choose(&formats), len as u64, out);
+    Ok(())
+}
+
+fn encode_str(value: IString, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    let sized = [(0xD9, 1), (0xDA, 2), (0xDB, 4)];
+    write_length("str", value.len(), Some((0xA0, 31)), &sized, variation, out)?;
+    out.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn encode_bin(value: Vec<u8>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    let sized = [(0xC4, 1), (0xC5, 2), (0xC6, 4)];
+    write_length("bin", value.len(), None, &sized, variation, out)?;
+    out.extend(value);
+    Ok(())
+}
+
+fn encode_array(items: Vec<Vec<u8>>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    let sized = [(0xDC, 2), (0xDD, 4)];
+    write_length("array", items.len(), Some((0x90, 15)), &sized, variation, out)?;
+    for item in items {
+        out.extend(item);
+    }
+    Ok(())
+}
+
+fn encode_map(items: Vec<Vec<u8>>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    require_even_items("msgpack_map", &items)?;
+    let sized = [(0xDE, 2), (0xDF, 4)];
+    write_length("map", items.len() / 2, Some((0x80, 15)), &sized, variation, out)?;
+    for item in items {
+        out.extend(item);
+    }
+    Ok(())
+}
+
+/// Data with one of the fixed sizes can use either a fixext format, which is canonical, or an ext format with an
+/// explicit length
+fn encode_ext(ext_type: i64, data: Vec<u8>, variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    if ext_type < i64::from(i8::MIN) || ext_type > i64::from(i8::MAX) {
+        bail!(
+            "Invalid messagepack extension type: {}, must be between -128 and 127",
+            ext_type
+        );
+    }
+    let fixext = match data.len() {
+        1 => Some(0xD4),
+        2 => Some(0xD5),
+        4 => Some(0xD6),
+        8 => Some(0xD7),
+        16 => Some(0xD8),
+        _ => None,
+    };
+    match fixext {
+        Some(first_byte) if variation.choose(&[true, false]) => out.push(first_byte),
+        _ => {
+            let sized = [(0xC7, 1), (0xC8, 2), (0xC9, 4)];
+            write_length("ext", data.len(), None, &sized, variation, out)?;
+        }
+    }
+    out.push(ext_type as u8);
+    out.extend(data);
+    Ok(())
+}
+
+fn encode_bool(value: bool, _variation: &mut Variation, out: &mut Vec<u8>) -> Result<(), Error> {
+    out.push(if value { 0xC3 } else { 0xC2 });
+    Ok(())
+}
+
+macro_rules! make_msgpack_builtins {
+    ($proto_name:ident, $varied_proto_name:ident, $fun_name:expr, [$(($arg_name:expr, $arg_type:expr)),*], $variadic:expr, $create:ident($($create_arg:expr),*), $description:literal) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                $create(args, false, $($create_arg),*)
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!($description, ", using the smallest format"),
+                arguments: &[$(($arg_name, $arg_type)),*],
+                variadic: $variadic,
+                create_fn: &create,
+            }
+        };
+
+        pub const $varied_proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                $create(args, true, $($create_arg),*)
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: concat!(
+                    $description,
+                    ". A randomly chosen larger format is used with a probability of variation_rate"
+                ),
+                arguments: &[(VARIATION_RATE_PARAM, GenType::Decimal), $(($arg_name, $arg_type)),*],
+                variadic: $variadic,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_msgpack_builtins!(
+    UINT_BUILTIN,
+    UINT_VARIED_BUILTIN,
+    "msgpack_int",
+    [(VALUE_PARAM, GenType::Uint)],
+    false,
+    create_encode_value(AnyFunction::require_uint, encode_uint),
+    "encodes the value as a messagepack integer. Larger formats include the signed int formats for values that fit in them"
+);
+make_msgpack_builtins!(
+    INT_BUILTIN,
+    INT_VARIED_BUILTIN,
+    "msgpack_int",
+    [(VALUE_PARAM, GenType::Int)],
+    false,
+    create_encode_value(AnyFunction::require_int, encode_int),
+    "encodes the value as a messagepack integer"
+);
+make_msgpack_builtins!(
+    FLOAT_BUILTIN,
+    FLOAT_VARIED_BUILTIN,
+    "msgpack_float",
+    [(VALUE_PARAM, GenType::Decimal)],
+    false,
+    create_encode_value(AnyFunction::require_decimal, encode_float),
+    "encodes the value as a messagepack float 32 if it can be represented exactly, or a float 64 otherwise"
+);
+make_msgpack_builtins!(
+    STR_BUILTIN,
+    STR_VARIED_BUILTIN,
+    "msgpack_str",
+    [(VALUE_PARAM, GenType::String)],
+    false,
+    create_encode_value(AnyFunction::require_string, encode_str),
+    "encodes the value as a messagepack str, which is one of fixstr, str 8, str 16, or str 32"
+);
+make_msgpack_builtins!(
+    BIN_BUILTIN,
+    BIN_VARIED_BUILTIN,
+    "msgpack_bin",
+    [(VALUE_PARAM, GenType::Bin)],
+    false,
+    create_encode_value(AnyFunction::require_bin, encode_bin),
+    "encodes the value as a messagepack bin"
+);
+make_msgpack_builtins!(
+    ARRAY_BUILTIN,
+    ARRAY_VARIED_BUILTIN,
+    "msgpack_array",
+    [(ITEMS_PARAM, GenType::Bin)],
+    true,
+    create_encode_items(ITEMS_PARAM, encode_array),
+    "encodes a messagepack array of the items, which must already be messagepack encoded"
+);
+make_msgpack_builtins!(
+    EMPTY_ARRAY_BUILTIN,
+    EMPTY_ARRAY_VARIED_BUILTIN,
+    "msgpack_array",
+    [],
+    false,
+    create_encode_no_items(encode_array),
+    "encodes an empty messagepack array"
+);
+make_msgpack_builtins!(
+    MAP_BUILTIN,
+    MAP_VARIED_BUILTIN,
+    "msgpack_map",
+    [(KEYS_AND_VALUES_PARAM, GenType::Bin)],
+    true,
+    create_encode_items(KEYS_AND_VALUES_PARAM, encode_map),
+    "encodes a messagepack map from alternating keys and values, which must already be messagepack encoded"
+);
+make_msgpack_builtins!(
+    EMPTY_MAP_BUILTIN,
+    EMPTY_MAP_VARIED_BUILTIN,
+    "msgpack_map",
+    [],
+    false,
+    create_encode_no_items(encode_map),
+    "encodes an empty messagepack map"
+);
+make_msgpack_builtins!(
+    EXT_BUILTIN,
+    EXT_VARIED_BUILTIN,
+    "msgpack_ext",
+    [("type", GenType::Int), (VALUE_PARAM, GenType::Bin)],
+    false,
+    create_encode_tagged("type", AnyFunction::require_int, encode_ext),
+    "encodes a messagepack extension with the given type, between -128 and 127, and data"
+);
+
+fn create_bool(args: Arguments) -> CreateFunctionResult {
+    create_encode_value(args, false, AnyFunction::require_boolean, encode_bool)
+}
+
+pub const BOOL_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "msgpack_bool",
+    description: "encodes the value as a messagepack true or false",
+    arguments: &[(VALUE_PARAM, GenType::Boolean)],
+    variadic: false,
+    create_fn: &create_bool,
+};
+
+fn create_nil(_args: Arguments) -> CreateFunctionResult {
+    Ok(ConstBin::new(vec![0xC0]))
+}
+
+pub const NIL_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "msgpack_nil",
+    description: "encodes a messagepack nil",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_nil,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error, run_program};
+
+    #[test]
+    fn smallest_formats_are_used() {
+        assert_bin_output_is_expected("msgpack_int(127)", &[0x7F]);
+        assert_bin_output_is_expected("msgpack_int(128)", &[0xCC, 0x80]);
+        assert_bin_output_is_expected("msgpack_int(65536)", &[0xCE, 0, 1, 0, 0]);
+        assert_bin_output_is_expected("msgpack_int(-32)", &[0xE0]);
+        assert_bin_output_is_expected("msgpack_int(-33)", &[0xD0, 0xDF]);
+        assert_bin_output_is_expected("msgpack_int(-129)", &[0xD1, 0xFF, 0x7F]);
+        assert_bin_output_is_expected("msgpack_float(1.5)", &[0xCA, 0x3F, 0xC0, 0, 0]);
+        assert_bin_output_is_expected("msgpack_float(0.1)", &[0xCB, 0x3F, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]);
+        assert_bin_output_is_expected("msgpack_str(\"abc\")", &[0xA3, b'a', b'b', b'c']);
+        assert_bin_output_is_expected("msgpack_str(repeat(32, \"a\"))", &{
+            let mut expected = vec![0xD9, 32];
+            expected.extend_from_slice(&[b'a'; 32]);
+            expected
+        });
+        assert_bin_output_is_expected("msgpack_bin([1, 2])", &[0xC4, 2, 1, 2]);
+        assert_bin_output_is_expected("msgpack_array()", &[0x90]);
+        assert_bin_output_is_expected("msgpack_array(msgpack_nil(), msgpack_bool(false))", &[0x92, 0xC0, 0xC2]);
+        assert_bin_output_is_expected("msgpack_map(msgpack_str(\"a\"), msgpack_int(1))", &[0x81, 0xA1, b'a', 0x01]);
+        assert_bin_output_is_expected("msgpack_ext(-1, [0, 0, 0, 1])", &[0xD6, 0xFF, 0, 0, 0, 1]);
+        assert_bin_output_is_expected("msgpack_ext(+5, [1, 2, 3])", &[0xC7, 3, 5, 1, 2, 3]);
+    }
+
+    /// Decodes a value, returning its type, its integer or float value or length, and the rest of the input
+    fn decode(input: &[u8]) -> (&'static str, f64, &[u8]) {
+        let read = |size: usize| input[1..=size].iter().fold(0u64, |value, &byte| (value << 8) | u64::from(byte));
+        let (kind, value, header_len) = match input[0] {
+            0x00..=0x7F => ("int", f64::from(input[0]), 1),
+            0xE0..=0xFF => ("int", f64::from(input[0] as i8), 1),
+            0xCC..=0xCF => {
+                let size = 1 << (input[0] - 0xCC);
+                ("int", read(size) as f64, size + 1)
+            }
+            0xD0..=0xD3 => {
+                let size = 1 << (input[0] - 0xD0);
+                let shift = 64 - size * 8;
+                ("int", ((read(size) << shift) as i64 >> shift) as f64, size + 1)
+            }
+            0xCA => ("float", f64::from(f32::from_bits(read(4) as u32)), 5),
+            0xCB => ("float", f64::from_bits(read(8)), 9),
+            0xA0..=0xBF => ("str", f64::from(input[0] & 0x1F), 1),
+            0xD9..=0xDB => {
+                let size = 1 << (input[0] - 0xD9);
+                ("str", read(size) as f64, size + 1)
+            }
+            0xC4..=0xC6 => {
+                let size = 1 << (input[0] - 0xC4);
+                ("bin", read(size) as f64, size + 1)
+            }
+            0x90..=0x9F => ("array", f64::from(input[0] & 0x0F), 1),
+            0xDC => ("array", read(2) as f64, 3),
+            0xDD => ("array", read(4) as f64, 5),
+            other => panic!("unexpected first byte: {:02X}", other),
+        };
+        let rest = &input[header_len..];
+        match kind {
+            "str" | "bin" => (kind, value, &rest[value as usize..]),
+            _ => (kind, value, rest),
+        }
+    }
+
+    #[test]
+    fn varied_formats_decode_to_the_same_value() {
+        let values = [
+            ("msgpack_int(+0)", "int", 0.0),
+            ("msgpack_int(-20)", "int", -20.0),
+            ("msgpack_int(-1000)", "int", -1000.0),
+            ("msgpack_int(200)", "int", 200.0),
+            ("msgpack_int(9223372036854775807)", "int", 9223372036854775807.0),
+            ("msgpack_float(2.5)", "float", 2.5),
+            ("msgpack_str(\"hello\")", "str", 5.0),
+            ("msgpack_bin([1, 2, 3])", "bin", 3.0),
+            ("msgpack_array(msgpack_int(1), msgpack_int(2))", "array", 2.0),
+        ];
+        for (program, expected_kind, expected_value) in values.iter() {
+            let varied_program = program.replacen("(", "(1.0, ", 1);
+            for _ in 0..20 {
+                let output = run_program(1, &varied_program).unwrap();
+                let (kind, value, _) = decode(&output);
+                assert_eq!((*expected_kind, *expected_value), (kind, value), "output: {:02X?}", output);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_values_return_errors() {
+        assert_runtime_error("msgpack_ext(+128, [1])", "Invalid messagepack extension type: 128");
+        assert_runtime_error("msgpack_map(msgpack_nil())", "msgpack_map requires an even number of arguments");
+    }
+}
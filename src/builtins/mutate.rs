@@ -0,0 +1,342 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynBinFun, DynDecimalFun, DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::fs::File;
+use std::io::Read;
+use std::rc::Rc;
+
+/// An element of a sequence that can be mutated. Bin values are mutated one byte at a time, and Strings are
+/// mutated one character at a time so that the result is always valid utf-8.
+trait Mutable: Copy {
+    fn random(ctx: &mut ProgramContext) -> Self;
+    fn flip_bit(self, ctx: &mut ProgramContext) -> Self;
+    fn interesting(ctx: &mut ProgramContext) -> Self;
+}
+
+const INTERESTING_BYTES: &[u8] = &[0x00, 0x01, 0x7F, 0x80, 0xFE, 0xFF];
+
+impl Mutable for u8 {
+    fn random(ctx: &mut ProgramContext) -> u8 {
+        ctx.gen_value()
+    }
+    fn flip_bit(self, ctx: &mut ProgramContext) -> u8 {
+        self ^ (1 << ctx.gen_range_exclusive(0, 8))
+    }
+    fn interesting(ctx: &mut ProgramContext) -> u8 {
+        INTERESTING_BYTES[ctx.gen_range_exclusive(0, INTERESTING_BYTES.len())]
+    }
+}
+
+const INTERESTING_CHARS: &[char] = &[
+    '\u{0}', '\u{7F}', '\u{85}', '\u{FEFF}', '\u{FFFD}', '\u{FFFF}', '\u{10FFFF}', '\n', '\r', '"',
+    '\'', '\\', '%',
+];
+
+impl Mutable for char {
+    fn random(ctx: &mut ProgramContext) -> char {
+        // mostly printable ascii, with the occasional character from anywhere in the basic multilingual plane
+        let codepoint = if ctx.gen_chance(0.75) {
+            ctx.gen_range_inclusive(0x20, 0x7E)
+        } else {
+            ctx.gen_range_inclusive(0x80, 0xFFFD)
+        };
+        ::std::char::from_u32(codepoint).unwrap_or('\u{FFFD}')
+    }
+    fn flip_bit(self, ctx: &mut ProgramContext) -> char {
+        let bit_count = if (self as u32) < 0x80 { 7 } else { 16 };
+        let flipped = self as u32 ^ (1 << ctx.gen_range_exclusive(0, bit_count));
+        // flipping a bit may land in the surrogate range, in which case the character is left alone
+        ::std::char::from_u32(flipped).unwrap_or(self)
+    }
+    fn interesting(ctx: &mut ProgramContext) -> char {
+        INTERESTING_CHARS[ctx.gen_range_exclusive(0, INTERESTING_CHARS.len())]
+    }
+}
+
+const MAX_CHUNK_LEN: usize = 16;
+
+/// The mutation operators, along with their relative weights. Truncation is much less likely than the others,
+/// since it tends to throw away most of the value.
+const OPERATORS: &[(Operator, u32)] = &[
+    (Operator::FlipBit, 4),
+    (Operator::Insert, 3),
+    (Operator::Delete, 3),
+    (Operator::Duplicate, 2),
+    (Operator::ShuffleChunk, 2),
+    (Operator::Interesting, 4),
+    (Operator::Truncate, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    FlipBit,
+    Insert,
+    Delete,
+    Duplicate,
+    ShuffleChunk,
+    Interesting,
+    Truncate,
+}
+
+impl Operator {
+    fn random(ctx: &mut ProgramContext) -> Operator {
+        let total: u32 = OPERATORS.iter().map(|&(_, weight)| weight).sum();
+        let mut n = ctx.gen_range_exclusive(0, total);
+        for &(operator, weight) in OPERATORS.iter() {
+            if n < weight {
+                return operator;
+            }
+            n -= weight;
+        }
+        unreachable!()
+    }
+
+    fn apply<T: Mutable>(self, values: &mut Vec<T>, ctx: &mut ProgramContext) {
+        if values.is_empty() {
+            values.push(T::random(ctx));
+            return;
+        }
+        let len = values.len();
+        let idx = ctx.gen_range_exclusive(0, len);
+        match self {
+            Operator::FlipBit => {
+                values[idx] = values[idx].flip_bit(ctx);
+            }
+            Operator::Insert => {
+                let insert_idx = ctx.gen_range_inclusive(0, len);
+                values.insert(insert_idx, T::random(ctx));
+            }
+            Operator::Delete => {
+                values.remove(idx);
+            }
+            Operator::Duplicate => {
+                let chunk_len = ctx.gen_range_inclusive(1, MAX_CHUNK_LEN.min(len - idx));
+                let chunk: Vec<T> = values[idx..(idx + chunk_len)].to_vec();
+                let insert_idx = idx + chunk_len;
+                values.splice(insert_idx..insert_idx, chunk);
+            }
+            Operator::ShuffleChunk => {
+                let chunk_len = ctx.gen_range_inclusive(1, MAX_CHUNK_LEN.min(len - idx));
+                for i in (1..chunk_len).rev() {
+                    let j = ctx.gen_range_inclusive(0, i);
+                    values.swap(idx + i, idx + j);
+                }
+            }
+            Operator::Interesting => {
+                values[idx] = T::interesting(ctx);
+            }
+            Operator::Truncate => {
+                values.truncate(idx);
+            }
+        }
+    }
+}
+
+/// Applies random mutations to the values. The number of mutations is proportional to the length of the input,
+/// with the rate being the expected number of mutations per element.
+fn mutate<T: Mutable>(values: &mut Vec<T>, rate: f64, ctx: &mut ProgramContext) -> Result<(), Error> {
+    if rate < 0.0 || !rate.is_finite() {
+        bail!("Invalid mutation rate: {}, must be a positive number", rate);
+    }
+    let expected = values.len().max(1) as f64 * rate;
+    let mut count = expected.trunc() as u64;
+    if ctx.gen_chance(expected.fract()) {
+        count += 1;
+    }
+    for _ in 0..count {
+        Operator::random(ctx).apply(values, ctx);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct MutateBin {
+    value: DynBinFun,
+    rate: DynDecimalFun,
+}
+
+impl RunnableFunction<Vec<u8>> for MutateBin {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let mut value = self.value.gen_value(ctx)?;
+        let rate = self.rate.gen_value(ctx)?;
+        mutate(&mut value, rate, ctx)?;
+        Ok(value)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct MutateString {
+    value: DynStringFun,
+    rate: DynDecimalFun,
+}
+
+impl RunnableFunction<IString> for MutateString {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let mut chars: Vec<char> = self.value.gen_value(ctx)?.chars().collect();
+        let rate = self.rate.gen_value(ctx)?;
+        mutate(&mut chars, rate, ctx)?;
+        Ok(chars.into_iter().collect::<String>().into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+struct MutateFile {
+    path: DynStringFun,
+    rate: DynDecimalFun,
+    files: RefCell<HashMap<IString, Vec<u8>>>,
+}
+
+impl Debug for MutateFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MutateFile")
+            .field("path", &self.path)
+            .field("rate", &self.rate)
+            .finish()
+    }
+}
+
+impl MutateFile {
+    fn read_file(&self, path: IString) -> Result<Vec<u8>, Error> {
+        let mut files = self.files.borrow_mut();
+        if !files.contains_key(&path) {
+            let mut content = Vec::new();
+            File::open(&*path)
+                .and_then(|mut file| file.read_to_end(&mut content))
+                .map_err(|e| format_err!("Failed to read seed file '{}': {}", path, e))?;
+            files.insert(path.clone(), content);
+        }
+        Ok(files[&path].clone())
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for MutateFile {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let path = self.path.gen_value(ctx)?;
+        let rate = self.rate.gen_value(ctx)?;
+        let mut value = self.read_file(path)?;
+        mutate(&mut value, rate, ctx)?;
+        Ok(value)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(value.as_slice())
+    }
+}
+
+const VALUE_PARAM: &str = "value";
+const RATE_PARAM: &str = "rate";
+const PATH_PARAM: &str = "filepath";
+
+fn create_mutate_bin(args: Arguments) -> CreateFunctionResult {
+    let (value, rate) = args.require_2_args(
+        VALUE_PARAM,
+        AnyFunction::require_bin,
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(MutateBin { value, rate })))
+}
+
+fn create_mutate_string(args: Arguments) -> CreateFunctionResult {
+    let (value, rate) = args.require_2_args(
+        VALUE_PARAM,
+        AnyFunction::require_string,
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::String(Rc::new(MutateString { value, rate })))
+}
+
+fn create_mutate_file(args: Arguments) -> CreateFunctionResult {
+    let (path, rate) = args.require_2_args(
+        PATH_PARAM,
+        AnyFunction::require_string,
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(MutateFile {
+        path,
+        rate,
+        files: RefCell::new(HashMap::new()),
+    })))
+}
+
+pub const MUTATE_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mutate",
+    description: "applies random mutations to the binary value: bit flips, byte insertions, deletions, and duplications, chunk shuffling, interesting byte substitutions, and truncation. The rate is the expected number of mutations per byte",
+    arguments: &[(VALUE_PARAM, GenType::Bin), (RATE_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_mutate_bin,
+};
+
+pub const MUTATE_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mutate",
+    description: "applies random mutations to the characters of the string, the same as for binary values. The result is always valid unicode. The rate is the expected number of mutations per character",
+    arguments: &[(VALUE_PARAM, GenType::String), (RATE_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_mutate_string,
+};
+
+pub const MUTATE_FILE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mutate_file",
+    description: "reads the given seed file and applies random mutations to its contents. The file is only read once. The rate is the expected number of mutations per byte",
+    arguments: &[(PATH_PARAM, GenType::String), (RATE_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_mutate_file,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{
+        assert_bin_output_is_expected, assert_runtime_error, run_program, test_program_success,
+    };
+
+    #[test]
+    fn mutate_with_zero_rate_returns_the_input() {
+        assert_bin_output_is_expected("mutate([1, 2, 3], 0.0)", &[1, 2, 3]);
+        test_program_success(1, r#"mutate("foo", 0.0)"#, "foo");
+    }
+
+    #[test]
+    fn mutate_changes_binary_values() {
+        let program = "mutate([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15], 0.5)";
+        let output = run_program(1, program).unwrap();
+        assert_ne!((0..16).collect::<Vec<u8>>(), output);
+    }
+
+    #[test]
+    fn mutate_is_reproducible_under_the_same_seed() {
+        let program = r#"mutate("the quick brown fox jumps over the lazy dog", 0.3)"#;
+        let first = run_program(20, program).unwrap();
+        let second = run_program(20, program).unwrap();
+        assert_eq!(first, second);
+        assert!(String::from_utf8(first).is_ok());
+    }
+
+    #[test]
+    fn mutate_file_mutates_the_file_contents() {
+        let original = ::std::fs::read("test-data/simple-words.txt").unwrap();
+        let output = run_program(1, r#"mutate_file("test-data/simple-words.txt", 0.0)"#).unwrap();
+        assert_eq!(original, output);
+        let output = run_program(1, r#"mutate_file("test-data/simple-words.txt", 1.0)"#).unwrap();
+        assert_ne!(original, output);
+    }
+
+    #[test]
+    fn mutate_returns_error_for_negative_rate() {
+        assert_runtime_error("mutate([1, 2, 3], -1.0)", "Invalid mutation rate: -1");
+    }
+}
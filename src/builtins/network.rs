@@ -0,0 +1,835 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynBinFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use byteorder::{BigEndian, ByteOrder};
+use failure::Error;
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IpVersion {
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    fn bit_len(self) -> u32 {
+        match self {
+            IpVersion::V4 => 32,
+            IpVersion::V6 => 128,
+        }
+    }
+
+    fn byte_len(self) -> usize {
+        (self.bit_len() / 8) as usize
+    }
+
+    fn max_value(self) -> u128 {
+        match self {
+            IpVersion::V4 => u128::from(u32::MAX),
+            IpVersion::V6 => u128::MAX,
+        }
+    }
+
+    fn parse_address(self, address: &str) -> Result<u128, Error> {
+        let trimmed = address.trim();
+        match self {
+            IpVersion::V4 => trimmed
+                .parse::<Ipv4Addr>()
+                .map(|addr| u128::from(u32::from(addr)))
+                .map_err(|_| format_err!("Invalid IPv4 address: '{}'", address)),
+            IpVersion::V6 => trimmed
+                .parse::<Ipv6Addr>()
+                .map(u128::from)
+                .map_err(|_| format_err!("Invalid IPv6 address: '{}'", address)),
+        }
+    }
+
+    fn to_bytes(self, address: u128) -> Vec<u8> {
+        match self {
+            IpVersion::V4 => {
+                let mut buffer = vec![0; self.byte_len()];
+                BigEndian::write_u32(buffer.as_mut_slice(), address as u32);
+                buffer
+            }
+            IpVersion::V6 => address.to_be_bytes().to_vec(),
+        }
+    }
+
+    fn read_bytes(self, bytes: &[u8]) -> Result<u128, Error> {
+        if bytes.len() != self.byte_len() {
+            bail!(
+                "Invalid binary value with length {}, an IP{:?} address must be exactly {} bytes",
+                bytes.len(),
+                self,
+                self.byte_len()
+            );
+        }
+        let value = match self {
+            IpVersion::V4 => u128::from(BigEndian::read_u32(bytes)),
+            IpVersion::V6 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(bytes);
+                u128::from_be_bytes(octets)
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// returns a mask with the lowest `host_bits` bits set
+fn host_mask(host_bits: u32) -> u128 {
+    if host_bits >= 128 {
+        u128::MAX
+    } else {
+        (1u128 << host_bits) - 1
+    }
+}
+
+/// Parses a block in CIDR notation (e.g. `10.0.0.0/8`) into the first and last addresses and the prefix length.
+/// The prefix length may be omitted, in which case the block contains only the single address.
+fn parse_cidr(version: IpVersion, cidr: &str) -> Result<(u128, u128, u32), Error> {
+    let mut parts = cidr.splitn(2, '/');
+    let address = version.parse_address(parts.next().unwrap_or(""))?;
+    let prefix_len = match parts.next() {
+        Some(len) => len
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format_err!("Invalid prefix length in CIDR block: '{}'", cidr))?,
+        None => version.bit_len(),
+    };
+    if prefix_len > version.bit_len() {
+        bail!(
+            "Invalid prefix length in CIDR block: '{}', the maximum is {}",
+            cidr,
+            version.bit_len()
+        );
+    }
+    let mask = host_mask(version.bit_len() - prefix_len);
+    let first = address & !mask & version.max_value();
+    Ok((first, first | mask, prefix_len))
+}
+
+fn gen_u128(ctx: &mut ProgramContext) -> u128 {
+    let high: u64 = ctx.gen_value();
+    let low: u64 = ctx.gen_value();
+    (u128::from(high) << 64) | u128::from(low)
+}
+
+fn gen_u128_inclusive(ctx: &mut ProgramContext, min: u128, max: u128) -> u128 {
+    let (min, max) = if min <= max { (min, max) } else { (max, min) };
+    let span = max - min;
+    if span == u128::MAX {
+        return gen_u128(ctx);
+    }
+    let mask = host_mask(128 - span.leading_zeros());
+    loop {
+        let candidate = gen_u128(ctx) & mask;
+        if candidate <= span {
+            return min + candidate;
+        }
+    }
+}
+
+#[derive(Debug)]
+enum AddressRange {
+    Cidr(DynStringFun),
+    Inclusive(DynStringFun, DynStringFun),
+}
+
+#[derive(Debug)]
+struct RandomAddress {
+    version: IpVersion,
+    range: AddressRange,
+}
+
+impl RandomAddress {
+    fn gen_address(&self, ctx: &mut ProgramContext) -> Result<u128, Error> {
+        let (first, last) = match self.range {
+            AddressRange::Cidr(ref cidr) => {
+                let cidr = cidr.gen_value(ctx)?;
+                let (first, last, _) = parse_cidr(self.version, &cidr)?;
+                (first, last)
+            }
+            AddressRange::Inclusive(ref first, ref last) => {
+                let first = first.gen_value(ctx)?;
+                let last = last.gen_value(ctx)?;
+                (
+                    self.version.parse_address(&first)?,
+                    self.version.parse_address(&last)?,
+                )
+            }
+        };
+        Ok(gen_u128_inclusive(ctx, first, last))
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for RandomAddress {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let address = self.gen_address(ctx)?;
+        Ok(self.version.to_bytes(address))
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+#[derive(Debug)]
+struct RandomCidrBlock {
+    version: IpVersion,
+    within: DynStringFun,
+    prefix_len: DynUintFun,
+}
+
+impl RunnableFunction<IString> for RandomCidrBlock {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let within = self.within.gen_value(ctx)?;
+        let prefix_len = self.prefix_len.gen_value(ctx)?;
+        let (first, last, within_prefix_len) = parse_cidr(self.version, &within)?;
+        if prefix_len < u64::from(within_prefix_len) || prefix_len > u64::from(self.version.bit_len()) {
+            bail!(
+                "Invalid prefix length: {}, must be between {} and {} in order to fit within '{}'",
+                prefix_len,
+                within_prefix_len,
+                self.version.bit_len(),
+                within
+            );
+        }
+        let mask = host_mask(self.version.bit_len() - prefix_len as u32);
+        let network = gen_u128_inclusive(ctx, first, last) & !mask;
+        let address = match self.version {
+            IpVersion::V4 => format_ipv4_dotted(network as u32),
+            IpVersion::V6 => format_ipv6_with_style("compressed", network, ctx)?,
+        };
+        Ok(format!("{}/{}", address, prefix_len).into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+fn octets(address: u32) -> [u8; 4] {
+    let mut octets = [0u8; 4];
+    BigEndian::write_u32(&mut octets[..], address);
+    octets
+}
+
+fn format_ipv4_dotted(address: u32) -> String {
+    let o = octets(address);
+    format!("{}.{}.{}.{}", o[0], o[1], o[2], o[3])
+}
+
+fn format_octal_octet(octet: u8) -> String {
+    if octet == 0 {
+        "0".to_owned()
+    } else {
+        format!("0{:o}", octet)
+    }
+}
+
+fn format_ipv4(style: &str, address: u32, ctx: &mut ProgramContext) -> Result<String, Error> {
+    let o = octets(address);
+    let formatted = match style {
+        "dotted" => format_ipv4_dotted(address),
+        "leading_zeros" => format!("{:03}.{:03}.{:03}.{:03}", o[0], o[1], o[2], o[3]),
+        "octal" => o
+            .iter()
+            .map(|octet| format_octal_octet(*octet))
+            .collect::<Vec<_>>()
+            .join("."),
+        "hex" => format!("0x{:x}.0x{:x}.0x{:x}.0x{:x}", o[0], o[1], o[2], o[3]),
+        "integer" => address.to_string(),
+        "hex_integer" => format!("0x{:x}", address),
+        "three_part" => format!("{}.{}.{}", o[0], o[1], address & 0xFFFF),
+        "two_part" => format!("{}.{}", o[0], address & 0x00FF_FFFF),
+        "mixed" => {
+            let mut parts = Vec::with_capacity(4);
+            for octet in o.iter() {
+                let part = match ctx.gen_range_exclusive(0, 3) {
+                    0 => octet.to_string(),
+                    1 => format_octal_octet(*octet),
+                    _ => format!("0x{:x}", octet),
+                };
+                parts.push(part);
+            }
+            parts.join(".")
+        }
+        other => bail!("Invalid IPv4 format: '{}', must be one of 'dotted', 'leading_zeros', 'octal', 'hex', 'integer', 'hex_integer', 'three_part', 'two_part', or 'mixed'", other),
+    };
+    Ok(formatted)
+}
+
+fn ipv6_groups(address: u128) -> [u16; 8] {
+    let mut groups = [0u16; 8];
+    for (i, group) in groups.iter_mut().enumerate() {
+        *group = (address >> (112 - i * 16)) as u16;
+    }
+    groups
+}
+
+/// Describes one of the many valid ways to spell an IPv6 address
+#[derive(Debug, Clone)]
+struct Ipv6Style {
+    /// the start index and length of the run of zero groups that will be replaced by `::`
+    compress: Option<(usize, usize)>,
+    /// whether the last 32 bits are written as a dotted quad
+    embedded_ipv4: bool,
+    /// the minimum number of hex digits used for each group
+    group_widths: [usize; 8],
+    uppercase: [bool; 8],
+}
+
+impl Ipv6Style {
+    fn uncompressed(group_width: usize) -> Ipv6Style {
+        Ipv6Style {
+            compress: None,
+            embedded_ipv4: false,
+            group_widths: [group_width; 8],
+            uppercase: [false; 8],
+        }
+    }
+
+    fn compressed() -> Ipv6Style {
+        Ipv6Style::uncompressed(1)
+    }
+}
+
+fn zero_runs(groups: &[u16]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < groups.len() {
+        if groups[i] == 0 {
+            let start = i;
+            while i < groups.len() && groups[i] == 0 {
+                i += 1;
+            }
+            runs.push((start, i - start));
+        } else {
+            i += 1;
+        }
+    }
+    runs
+}
+
+/// finds the run of zero groups to compress as recommended by RFC 5952: the longest run of at least two groups,
+/// choosing the first one if there's a tie
+fn canonical_zero_run(groups: &[u16]) -> Option<(usize, usize)> {
+    zero_runs(groups)
+        .into_iter()
+        .filter(|&(_, len)| len > 1)
+        .fold(None, |best: Option<(usize, usize)>, run| match best {
+            Some(b) if b.1 >= run.1 => Some(b),
+            _ => Some(run),
+        })
+}
+
+fn format_ipv6(groups: &[u16; 8], style: Ipv6Style) -> String {
+    let hex_group_count = if style.embedded_ipv4 { 6 } else { 8 };
+    let mut result = String::with_capacity(48);
+    let mut i = 0;
+    while i < hex_group_count {
+        if let Some((start, len)) = style.compress {
+            if i == start {
+                result.push_str("::");
+                i += len;
+                continue;
+            }
+        }
+        if i > 0 && !result.ends_with(':') {
+            result.push(':');
+        }
+        let width = style.group_widths[i];
+        if style.uppercase[i] {
+            write!(result, "{:0width$X}", groups[i], width = width).unwrap();
+        } else {
+            write!(result, "{:0width$x}", groups[i], width = width).unwrap();
+        }
+        i += 1;
+    }
+    if style.embedded_ipv4 {
+        if !result.ends_with(':') {
+            result.push(':');
+        }
+        let ipv4 = (u32::from(groups[6]) << 16) | u32::from(groups[7]);
+        result.push_str(&format_ipv4_dotted(ipv4));
+    }
+    result
+}
+
+fn random_ipv6_style(groups: &[u16; 8], ctx: &mut ProgramContext) -> Ipv6Style {
+    let mut style = Ipv6Style::compressed();
+    style.embedded_ipv4 = ctx.gen_range_exclusive(0, 4) == 0;
+    let hex_group_count = if style.embedded_ipv4 { 6 } else { 8 };
+    let runs = zero_runs(&groups[..hex_group_count]);
+    if !runs.is_empty() && ctx.gen_range_exclusive(0, 4) > 0 {
+        let run = runs[ctx.gen_range_exclusive(0, runs.len())];
+        style.compress = Some(run);
+    }
+    for i in 0..8 {
+        style.group_widths[i] = ctx.gen_range_inclusive(1, 4);
+        style.uppercase[i] = ctx.gen_value();
+    }
+    style
+}
+
+fn format_ipv6_with_style(style: &str, address: u128, ctx: &mut ProgramContext) -> Result<String, Error> {
+    let groups = ipv6_groups(address);
+    let style = match style {
+        "compressed" => Ipv6Style {
+            compress: canonical_zero_run(&groups),
+            ..Ipv6Style::compressed()
+        },
+        "full" => Ipv6Style::uncompressed(4),
+        "expanded" => Ipv6Style::uncompressed(1),
+        "ipv4_embedded" => Ipv6Style {
+            compress: canonical_zero_run(&groups[..6]),
+            embedded_ipv4: true,
+            ..Ipv6Style::compressed()
+        },
+        "random" => random_ipv6_style(&groups, ctx),
+        other => bail!("Invalid IPv6 format: '{}', must be one of 'compressed', 'full', 'expanded', 'ipv4_embedded', or 'random'", other),
+    };
+    Ok(format_ipv6(&groups, style))
+}
+
+#[derive(Debug)]
+struct FormatAddress {
+    version: IpVersion,
+    style: DynStringFun,
+    address: DynBinFun,
+}
+
+impl RunnableFunction<IString> for FormatAddress {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let style = self.style.gen_value(ctx)?;
+        let bytes = self.address.gen_value(ctx)?;
+        let address = self.version.read_bytes(bytes.as_slice())?;
+        let formatted = match self.version {
+            IpVersion::V4 => format_ipv4(&style, address as u32, ctx)?,
+            IpVersion::V6 => format_ipv6_with_style(&style, address, ctx)?,
+        };
+        Ok(formatted.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const MAC_LEN: usize = 6;
+
+#[derive(Debug)]
+struct RandomMac;
+
+impl RunnableFunction<Vec<u8>> for RandomMac {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0; MAC_LEN];
+        for byte in bytes.iter_mut() {
+            *byte = ctx.gen_value();
+        }
+        Ok(bytes)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+fn format_mac(style: &str, bytes: &[u8]) -> Result<String, Error> {
+    if bytes.len() != MAC_LEN {
+        bail!(
+            "Invalid binary value with length {}, a MAC address must be exactly {} bytes",
+            bytes.len(),
+            MAC_LEN
+        );
+    }
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let formatted = match style {
+        "colon" => hex.join(":"),
+        "hyphen" => hex.join("-"),
+        "dot" => hex
+            .chunks(2)
+            .map(|pair| pair.concat())
+            .collect::<Vec<_>>()
+            .join("."),
+        "bare" => hex.concat(),
+        other => bail!(
+            "Invalid MAC address format: '{}', must be one of 'colon', 'hyphen', 'dot', or 'bare'",
+            other
+        ),
+    };
+    Ok(formatted)
+}
+
+#[derive(Debug)]
+struct FormatMac {
+    style: DynStringFun,
+    mac: DynBinFun,
+}
+
+impl RunnableFunction<IString> for FormatMac {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let style = self.style.gen_value(ctx)?;
+        let bytes = self.mac.gen_value(ctx)?;
+        format_mac(&style, bytes.as_slice()).map(Into::into)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+fn require_port(port: u64) -> Result<u16, Error> {
+    if port > u64::from(u16::MAX) {
+        bail!("Invalid port: {}, must be between 0 and 65535", port);
+    }
+    Ok(port as u16)
+}
+
+#[derive(Debug)]
+struct HostPort {
+    host: DynStringFun,
+    port: DynUintFun,
+}
+
+impl RunnableFunction<IString> for HostPort {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let host = self.host.gen_value(ctx)?;
+        let port = require_port(self.port.gen_value(ctx)?)?;
+        // IPv6 addresses must be surrounded by square brackets so that the port can be distinguished
+        let formatted = if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        };
+        Ok(formatted.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct PortBin {
+    port: DynUintFun,
+}
+
+impl RunnableFunction<Vec<u8>> for PortBin {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let port = require_port(self.port.gen_value(ctx)?)?;
+        let mut buffer = vec![0; 2];
+        BigEndian::write_u16(buffer.as_mut_slice(), port);
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_value(ctx)?;
+        out.write_bytes(bytes.as_slice())
+    }
+}
+
+const CIDR_PARAM: &str = "cidr";
+const FIRST_PARAM: &str = "first_inclusive";
+const LAST_PARAM: &str = "last_inclusive";
+const PREFIX_LEN_PARAM: &str = "prefix_length";
+const STYLE_PARAM: &str = "style";
+const ADDRESS_PARAM: &str = "address";
+const MAC_PARAM: &str = "mac";
+const HOST_PARAM: &str = "host";
+const PORT_PARAM: &str = "port";
+
+fn create_cidr_address(version: IpVersion, args: Arguments) -> CreateFunctionResult {
+    let cidr = args.required_arg(CIDR_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::Bin(Rc::new(RandomAddress {
+        version,
+        range: AddressRange::Cidr(cidr),
+    })))
+}
+
+fn create_range_address(version: IpVersion, args: Arguments) -> CreateFunctionResult {
+    let (first, last) = args.require_2_args(
+        FIRST_PARAM,
+        AnyFunction::require_string,
+        LAST_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(RandomAddress {
+        version,
+        range: AddressRange::Inclusive(first, last),
+    })))
+}
+
+fn create_cidr_block(version: IpVersion, args: Arguments) -> CreateFunctionResult {
+    let (within, prefix_len) = args.require_2_args(
+        CIDR_PARAM,
+        AnyFunction::require_string,
+        PREFIX_LEN_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::String(Rc::new(RandomCidrBlock {
+        version,
+        within,
+        prefix_len,
+    })))
+}
+
+fn create_format_address(version: IpVersion, args: Arguments) -> CreateFunctionResult {
+    let (style, address) = args.require_2_args(
+        STYLE_PARAM,
+        AnyFunction::require_string,
+        ADDRESS_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::String(Rc::new(FormatAddress {
+        version,
+        style,
+        address,
+    })))
+}
+
+fn create_ipv4_bin(args: Arguments) -> CreateFunctionResult {
+    create_cidr_address(IpVersion::V4, args)
+}
+fn create_ipv6_bin(args: Arguments) -> CreateFunctionResult {
+    create_cidr_address(IpVersion::V6, args)
+}
+fn create_ipv4_range_bin(args: Arguments) -> CreateFunctionResult {
+    create_range_address(IpVersion::V4, args)
+}
+fn create_ipv6_range_bin(args: Arguments) -> CreateFunctionResult {
+    create_range_address(IpVersion::V6, args)
+}
+fn create_ipv4_cidr(args: Arguments) -> CreateFunctionResult {
+    create_cidr_block(IpVersion::V4, args)
+}
+fn create_ipv6_cidr(args: Arguments) -> CreateFunctionResult {
+    create_cidr_block(IpVersion::V6, args)
+}
+fn create_ipv4_string(args: Arguments) -> CreateFunctionResult {
+    create_format_address(IpVersion::V4, args)
+}
+fn create_ipv6_string(args: Arguments) -> CreateFunctionResult {
+    create_format_address(IpVersion::V6, args)
+}
+
+fn create_mac_bin(_: Arguments) -> CreateFunctionResult {
+    Ok(AnyFunction::Bin(Rc::new(RandomMac)))
+}
+
+fn create_mac_string(args: Arguments) -> CreateFunctionResult {
+    let (style, mac) = args.require_2_args(
+        STYLE_PARAM,
+        AnyFunction::require_string,
+        MAC_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::String(Rc::new(FormatMac { style, mac })))
+}
+
+fn create_host_port(args: Arguments) -> CreateFunctionResult {
+    let (host, port) = args.require_2_args(
+        HOST_PARAM,
+        AnyFunction::require_string,
+        PORT_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::String(Rc::new(HostPort { host, port })))
+}
+
+fn create_port_bin(args: Arguments) -> CreateFunctionResult {
+    let port = args.required_arg(PORT_PARAM, 0, AnyFunction::require_uint)?;
+    Ok(AnyFunction::Bin(Rc::new(PortBin { port })))
+}
+
+pub const IPV4_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv4_bin",
+    description: "generates a random IPv4 address within the given CIDR block (e.g. '10.0.0.0/8'), as 4 bytes in network byte order",
+    arguments: &[(CIDR_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_ipv4_bin,
+};
+
+pub const IPV4_RANGE_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv4_bin",
+    description: "generates a random IPv4 address between the two given addresses (inclusive), as 4 bytes in network byte order",
+    arguments: &[(FIRST_PARAM, GenType::String), (LAST_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_ipv4_range_bin,
+};
+
+pub const IPV6_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv6_bin",
+    description: "generates a random IPv6 address within the given CIDR block (e.g. '2001:db8::/32'), as 16 bytes in network byte order",
+    arguments: &[(CIDR_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_ipv6_bin,
+};
+
+pub const IPV6_RANGE_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv6_bin",
+    description: "generates a random IPv6 address between the two given addresses (inclusive), as 16 bytes in network byte order",
+    arguments: &[(FIRST_PARAM, GenType::String), (LAST_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_ipv6_range_bin,
+};
+
+pub const IPV4_CIDR_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv4_cidr",
+    description: "generates a random IPv4 CIDR block with the given prefix length that fits within the given CIDR block",
+    arguments: &[(CIDR_PARAM, GenType::String), (PREFIX_LEN_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_ipv4_cidr,
+};
+
+pub const IPV6_CIDR_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv6_cidr",
+    description: "generates a random IPv6 CIDR block with the given prefix length that fits within the given CIDR block",
+    arguments: &[(CIDR_PARAM, GenType::String), (PREFIX_LEN_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_ipv6_cidr,
+};
+
+pub const IPV4_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv4_string",
+    description: "formats a 4 byte IPv4 address as a string. The style must be one of 'dotted', 'leading_zeros', 'octal', 'hex', 'integer', 'hex_integer', 'three_part', 'two_part', or 'mixed'",
+    arguments: &[(STYLE_PARAM, GenType::String), (ADDRESS_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_ipv4_string,
+};
+
+pub const IPV6_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ipv6_string",
+    description: "formats a 16 byte IPv6 address as a string. The style must be one of 'compressed', 'full', 'expanded', 'ipv4_embedded', or 'random'",
+    arguments: &[(STYLE_PARAM, GenType::String), (ADDRESS_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_ipv6_string,
+};
+
+pub const MAC_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mac_address_bin",
+    description: "generates a random 6 byte MAC address",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_mac_bin,
+};
+
+pub const MAC_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "mac_address_string",
+    description: "formats a 6 byte MAC address as lowercase hex. The style must be one of 'colon', 'hyphen', 'dot', or 'bare'",
+    arguments: &[(STYLE_PARAM, GenType::String), (MAC_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_mac_string,
+};
+
+pub const HOST_PORT_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "host_port",
+    description: "formats a host and port as 'host:port', surrounding IPv6 addresses with square brackets. Returns an error if the port is greater than 65535",
+    arguments: &[(HOST_PARAM, GenType::String), (PORT_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_host_port,
+};
+
+pub const PORT_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "port_bin",
+    description: "converts the port into 2 bytes in network byte order. Returns an error if the port is greater than 65535",
+    arguments: &[(PORT_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_port_bin,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{
+        assert_bin_output_is_expected, assert_runtime_error, run_program, test_program_success,
+    };
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn ipv4_address_is_generated_within_cidr_block() {
+        let program = r#"ipv4_bin("192.168.4.0/30")"#;
+        let output = run_program(50, program).expect("failed to run program");
+        for address in output.chunks(4) {
+            assert_eq!(&address[0..3], &[192, 168, 4]);
+            assert!(address[3] < 4);
+        }
+    }
+
+    #[test]
+    fn ipv4_address_is_formatted_in_several_styles() {
+        let program = r#"
+            def addr() = [192, 168, 1, 10];
+            concat(
+                ipv4_string("dotted", addr()), " ",
+                ipv4_string("leading_zeros", addr()), " ",
+                ipv4_string("octal", addr()), " ",
+                ipv4_string("hex", addr()), " ",
+                ipv4_string("integer", addr()), " ",
+                ipv4_string("three_part", addr())
+            )
+        "#;
+        let expected = "192.168.1.10 192.168.001.010 0300.0250.01.012 0xc0.0xa8.0x1.0xa 3232235786 192.168.266";
+        test_program_success(1, program, expected);
+    }
+
+    #[test]
+    fn ipv6_address_is_formatted_in_several_styles() {
+        let program = r#"
+            def addr() = ipv6_bin("2001:db8::ffff:c000:201");
+            concat(
+                ipv6_string("compressed", addr()), " ",
+                ipv6_string("full", addr()), " ",
+                ipv6_string("expanded", addr()), " ",
+                ipv6_string("ipv4_embedded", addr())
+            )
+        "#;
+        let expected = "2001:db8::ffff:c000:201 2001:0db8:0000:0000:0000:ffff:c000:0201 2001:db8:0:0:0:ffff:c000:201 2001:db8::ffff:192.0.2.1";
+        test_program_success(1, program, expected);
+    }
+
+    #[test]
+    fn random_ipv6_styles_all_parse_to_the_same_address() {
+        let program = r#"trailing_newline(ipv6_string("random", ipv6_bin("fe80::/10")))"#;
+        let output = run_program(100, program).expect("failed to run program");
+        let output = String::from_utf8(output).unwrap();
+        for line in output.lines() {
+            let parsed = line.parse::<Ipv6Addr>();
+            assert!(parsed.is_ok(), "invalid IPv6 address: '{}'", line);
+            assert_eq!(0xfe80, parsed.unwrap().segments()[0] & 0xffc0);
+        }
+    }
+
+    #[test]
+    fn cidr_block_is_generated_within_range() {
+        let program = r#"ipv4_cidr("10.1.0.0/16", 24)"#;
+        let output = run_program(1, program).expect("failed to run program");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("10.1.") && output.ends_with(".0/24"), "actual: '{}'", output);
+    }
+
+    #[test]
+    fn mac_address_is_formatted_in_several_styles() {
+        let program = r#"
+            def mac() = [0x00, 0x1A, 0x2b, 0x3C, 0x4d, 0x5E];
+            concat(mac_address_string("colon", mac()), " ", mac_address_string("hyphen", mac()), " ", mac_address_string("dot", mac()))
+        "#;
+        let expected = "00:1a:2b:3c:4d:5e 00-1a-2b-3c-4d-5e 001a.2b3c.4d5e";
+        test_program_success(1, program, expected);
+    }
+
+    #[test]
+    fn host_port_surrounds_ipv6_addresses_with_brackets() {
+        let program = r#"concat(host_port("::1", 8080), " ", host_port("localhost", 80))"#;
+        test_program_success(1, program, "[::1]:8080 localhost:80");
+    }
+
+    #[test]
+    fn port_bin_returns_error_for_invalid_port() {
+        assert_bin_output_is_expected("port_bin(443)", &[0x01, 0xBB]);
+        assert_runtime_error("port_bin(65536)", "Invalid port: 65536");
+    }
+}
@@ -0,0 +1,707 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+const RADIX_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+fn format_radix(mut magnitude: u128, radix: u32) -> String {
+    if magnitude == 0 {
+        return "0".to_owned();
+    }
+    let mut digits = Vec::with_capacity(128);
+    while magnitude > 0 {
+        digits.push(RADIX_DIGITS[(magnitude % u128::from(radix)) as usize]);
+        magnitude /= u128::from(radix);
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+#[derive(Debug)]
+struct ToRadix<T> {
+    radix: DynUintFun,
+    prefix: DynStringFun,
+    value: DynFun<T>,
+}
+
+impl<T: Into<i128> + Debug> RunnableFunction<IString> for ToRadix<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let radix = self.radix.gen_value(ctx)?;
+        if !(2..=36).contains(&radix) {
+            bail!("Invalid radix: {}, must be between 2 and 36", radix);
+        }
+        let prefix = self.prefix.gen_value(ctx)?;
+        let value: i128 = self.value.gen_value(ctx)?.into();
+        let sign = if value < 0 { "-" } else { "" };
+        let digits = format_radix(value.unsigned_abs(), radix as u32);
+        Ok(format!("{}{}{}", sign, prefix, digits).into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct ToFixed {
+    precision: DynUintFun,
+    value: DynDecimalFun,
+}
+
+impl RunnableFunction<IString> for ToFixed {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let precision = self.precision.gen_value(ctx)? as usize;
+        let value = self.value.gen_value(ctx)?;
+        Ok(format!("{:.*}", precision, value).into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Describes how the exponent of a number in scientific notation is written. Parsed from a pattern like `E+00`,
+/// where the first character is the exponent marker, an optional `+` means that positive exponents get an
+/// explicit sign, and the number of zeros is the minimum number of exponent digits.
+#[derive(Debug, Clone, PartialEq)]
+struct ExponentStyle {
+    marker: char,
+    explicit_sign: bool,
+    min_digits: usize,
+}
+
+impl ExponentStyle {
+    fn parse(pattern: &str) -> Result<ExponentStyle, Error> {
+        let mut chars = pattern.chars().peekable();
+        let marker = match chars.next() {
+            Some(c @ 'e') | Some(c @ 'E') => c,
+            _ => bail!(
+                "Invalid exponent style: '{}', must start with either 'e' or 'E'",
+                pattern
+            ),
+        };
+        let explicit_sign = chars.peek() == Some(&'+');
+        if explicit_sign {
+            chars.next();
+        }
+        let mut min_digits = 0;
+        for c in chars {
+            if c != '0' {
+                bail!("Invalid exponent style: '{}', expected a pattern like 'e', 'E+', or 'e+00'", pattern);
+            }
+            min_digits += 1;
+        }
+        Ok(ExponentStyle {
+            marker,
+            explicit_sign,
+            min_digits,
+        })
+    }
+
+    fn default() -> ExponentStyle {
+        ExponentStyle {
+            marker: 'e',
+            explicit_sign: false,
+            min_digits: 0,
+        }
+    }
+
+    /// reformats the exponent of a number that was formatted using rust's `LowerExp`
+    fn apply(&self, formatted: &str) -> String {
+        let split_at = match formatted.find('e') {
+            Some(idx) => idx,
+            None => return formatted.to_owned(), // NaN and inf don't have an exponent
+        };
+        let (mantissa, exponent) = formatted.split_at(split_at);
+        let exponent = &exponent[1..];
+        let (sign, digits) = if let Some(digits) = exponent.strip_prefix('-') {
+            ("-", digits)
+        } else if self.explicit_sign {
+            ("+", exponent)
+        } else {
+            ("", exponent)
+        };
+        format!(
+            "{}{}{}{:0>width$}",
+            mantissa,
+            self.marker,
+            sign,
+            digits,
+            width = self.min_digits
+        )
+    }
+}
+
+#[derive(Debug)]
+struct ToScientific {
+    precision: Option<DynUintFun>,
+    exponent_style: Option<DynStringFun>,
+    value: DynDecimalFun,
+}
+
+impl RunnableFunction<IString> for ToScientific {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let precision = match self.precision {
+            Some(ref precision) => Some(precision.gen_value(ctx)? as usize),
+            None => None,
+        };
+        let style = match self.exponent_style {
+            Some(ref style) => ExponentStyle::parse(&style.gen_value(ctx)?)?,
+            None => ExponentStyle::default(),
+        };
+        let value = self.value.gen_value(ctx)?;
+        let formatted = match precision {
+            Some(precision) => format!("{:.*e}", precision, value),
+            None => format!("{:e}", value),
+        };
+        Ok(style.apply(&formatted).into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// The parts of a formatted number, so that signs, padding, and separators can be added to numbers that have
+/// already been converted to strings by any of the other functions
+struct NumberParts<'a> {
+    sign: &'a str,
+    prefix: &'a str,
+    integer_digits: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> NumberParts<'a> {
+    fn split(number: &'a str) -> NumberParts<'a> {
+        let sign_len = if number.starts_with('-') || number.starts_with('+') {
+            1
+        } else {
+            0
+        };
+        let (sign, unsigned) = number.split_at(sign_len);
+        let prefix_len = match unsigned.get(0..2) {
+            Some("0x") | Some("0X") | Some("0o") | Some("0O") | Some("0b") | Some("0B") => 2,
+            _ => 0,
+        };
+        let (prefix, digits) = unsigned.split_at(prefix_len);
+        let digits_len = digits
+            .find(|c: char| {
+                if prefix_len > 0 {
+                    !c.is_ascii_alphanumeric()
+                } else {
+                    !c.is_ascii_digit()
+                }
+            })
+            .unwrap_or(digits.len());
+        let (integer_digits, rest) = digits.split_at(digits_len);
+        NumberParts {
+            sign,
+            prefix,
+            integer_digits,
+            rest,
+        }
+    }
+}
+
+/// splits the ascii digits into groups of the given size, starting from the right, and joins them with the separator
+fn group_from_right(digits: &str, group_size: usize, separator: &str) -> String {
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .rchunks(group_size)
+        .rev()
+        .map(|group| ::std::str::from_utf8(group).unwrap())
+        .collect();
+    groups.join(separator)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumberTransform {
+    ExplicitSign,
+    ZeroPad,
+    GroupDigits,
+}
+
+#[derive(Debug)]
+struct TransformNumber {
+    transform: NumberTransform,
+    separator: Option<DynStringFun>,
+    size: Option<DynUintFun>,
+    number: DynStringFun,
+}
+
+impl RunnableFunction<IString> for TransformNumber {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let separator = match self.separator {
+            Some(ref separator) => separator.gen_value(ctx)?,
+            None => IString::default(),
+        };
+        let size = match self.size {
+            Some(ref size) => size.gen_value(ctx)? as usize,
+            None => 0,
+        };
+        let number = self.number.gen_value(ctx)?;
+        let parts = NumberParts::split(&number);
+        let transformed = match self.transform {
+            NumberTransform::ExplicitSign => {
+                if parts.sign.is_empty() {
+                    format!("+{}", number)
+                } else {
+                    number.to_string()
+                }
+            }
+            NumberTransform::ZeroPad => format!(
+                "{}{}{:0>width$}{}",
+                parts.sign,
+                parts.prefix,
+                parts.integer_digits,
+                parts.rest,
+                width = size
+            ),
+            NumberTransform::GroupDigits => {
+                if size == 0 {
+                    bail!("Invalid group size: 0, must be at least 1");
+                }
+                let grouped = group_from_right(parts.integer_digits, size, &separator);
+                format!("{}{}{}{}", parts.sign, parts.prefix, grouped, parts.rest)
+            }
+        };
+        Ok(transformed.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Numbers that can be written in several different ways that all parse to the same value
+trait EquivalentForms: Copy {
+    fn random_form(self, ctx: &mut ProgramContext) -> String;
+}
+
+fn random_exponent_style(ctx: &mut ProgramContext) -> ExponentStyle {
+    ExponentStyle {
+        marker: if ctx.gen_value() { 'e' } else { 'E' },
+        explicit_sign: ctx.gen_value(),
+        min_digits: ctx.gen_range_inclusive(0, 3),
+    }
+}
+
+fn integer_form(value: i128, ctx: &mut ProgramContext) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    match ctx.gen_range_exclusive(0, 8) {
+        0 => value.to_string(),
+        1 if value >= 0 => format!("+{}", value),
+        2 => {
+            let zeros = ctx.gen_range_inclusive(1, 3);
+            format!("{}{}{}", sign, "0".repeat(zeros), magnitude)
+        }
+        3 => {
+            let grouped = group_from_right(&magnitude.to_string(), 3, "_");
+            format!("{}{}", sign, grouped)
+        }
+        4 => {
+            let digits = format_radix(magnitude, 16);
+            match ctx.gen_range_exclusive(0, 3) {
+                0 => format!("{}0x{}", sign, digits),
+                1 => format!("{}0x{}", sign, digits.to_uppercase()),
+                _ => format!("{}0X{}", sign, digits.to_uppercase()),
+            }
+        }
+        5 => format!("{}0o{}", sign, format_radix(magnitude, 8)),
+        6 => format!("{}0b{}", sign, format_radix(magnitude, 2)),
+        _ => {
+            // scientific notation, which is exact since the mantissa includes every significant digit
+            let digits = magnitude.to_string();
+            let significant = digits.trim_end_matches('0');
+            let significant = if significant.is_empty() { "0" } else { significant };
+            let mantissa = if significant.len() > 1 {
+                format!("{}.{}", &significant[..1], &significant[1..])
+            } else {
+                significant.to_owned()
+            };
+            let formatted = format!("{}{}e{}", sign, mantissa, digits.len() - 1);
+            random_exponent_style(ctx).apply(&formatted)
+        }
+    }
+}
+
+impl EquivalentForms for u64 {
+    fn random_form(self, ctx: &mut ProgramContext) -> String {
+        integer_form(i128::from(self), ctx)
+    }
+}
+
+impl EquivalentForms for i64 {
+    fn random_form(self, ctx: &mut ProgramContext) -> String {
+        integer_form(i128::from(self), ctx)
+    }
+}
+
+impl EquivalentForms for f64 {
+    fn random_form(self, ctx: &mut ProgramContext) -> String {
+        if !self.is_finite() {
+            return self.to_string();
+        }
+        // both the Display and LowerExp implementations produce the shortest representation that round trips
+        let plain = self.to_string();
+        let formatted = match ctx.gen_range_exclusive(0, 4) {
+            0 => plain,
+            1 => {
+                let zeros = "0".repeat(ctx.gen_range_inclusive(1, 3));
+                if plain.contains('.') {
+                    format!("{}{}", plain, zeros)
+                } else {
+                    format!("{}.{}", plain, zeros)
+                }
+            }
+            2 => {
+                let parts = NumberParts::split(&plain);
+                let zeros = "0".repeat(ctx.gen_range_inclusive(1, 3));
+                format!("{}{}{}{}", parts.sign, zeros, parts.integer_digits, parts.rest)
+            }
+            _ => random_exponent_style(ctx).apply(&format!("{:e}", self)),
+        };
+        if !formatted.starts_with('-') && ctx.gen_chance(0.25) {
+            format!("+{}", formatted)
+        } else {
+            formatted
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EquivalentForm<T> {
+    value: DynFun<T>,
+}
+
+impl<T: EquivalentForms + Debug> RunnableFunction<IString> for EquivalentForm<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let value = self.value.gen_value(ctx)?;
+        Ok(value.random_form(ctx).into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const RADIX_PARAM: &str = "radix";
+const PREFIX_PARAM: &str = "prefix";
+const PRECISION_PARAM: &str = "precision";
+const EXPONENT_STYLE_PARAM: &str = "exponent_style";
+const SEPARATOR_PARAM: &str = "separator";
+const GROUP_SIZE_PARAM: &str = "group_size";
+const WIDTH_PARAM: &str = "width";
+const VALUE_PARAM: &str = "value";
+const NUMBER_PARAM: &str = "number";
+
+fn create_uint_to_radix(args: Arguments) -> CreateFunctionResult {
+    let (radix, prefix, value) = args.require_3_args(
+        RADIX_PARAM,
+        AnyFunction::require_uint,
+        PREFIX_PARAM,
+        AnyFunction::require_string,
+        VALUE_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ToRadix {
+        radix,
+        prefix,
+        value,
+    })))
+}
+
+fn create_int_to_radix(args: Arguments) -> CreateFunctionResult {
+    let (radix, prefix, value) = args.require_3_args(
+        RADIX_PARAM,
+        AnyFunction::require_uint,
+        PREFIX_PARAM,
+        AnyFunction::require_string,
+        VALUE_PARAM,
+        AnyFunction::require_int,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ToRadix {
+        radix,
+        prefix,
+        value,
+    })))
+}
+
+fn create_to_fixed(args: Arguments) -> CreateFunctionResult {
+    let (precision, value) = args.require_2_args(
+        PRECISION_PARAM,
+        AnyFunction::require_uint,
+        VALUE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ToFixed { precision, value })))
+}
+
+fn create_to_scientific(args: Arguments) -> CreateFunctionResult {
+    let value = args.required_arg(VALUE_PARAM, 0, AnyFunction::require_decimal)?;
+    Ok(AnyFunction::String(Rc::new(ToScientific {
+        precision: None,
+        exponent_style: None,
+        value,
+    })))
+}
+
+fn create_to_scientific_with_style(args: Arguments) -> CreateFunctionResult {
+    let (exponent_style, value) = args.require_2_args(
+        EXPONENT_STYLE_PARAM,
+        AnyFunction::require_string,
+        VALUE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ToScientific {
+        precision: None,
+        exponent_style: Some(exponent_style),
+        value,
+    })))
+}
+
+fn create_to_scientific_with_precision(args: Arguments) -> CreateFunctionResult {
+    let (precision, exponent_style, value) = args.require_3_args(
+        PRECISION_PARAM,
+        AnyFunction::require_uint,
+        EXPONENT_STYLE_PARAM,
+        AnyFunction::require_string,
+        VALUE_PARAM,
+        AnyFunction::require_decimal,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ToScientific {
+        precision: Some(precision),
+        exponent_style: Some(exponent_style),
+        value,
+    })))
+}
+
+fn create_explicit_sign(args: Arguments) -> CreateFunctionResult {
+    let number = args.required_arg(NUMBER_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(TransformNumber {
+        transform: NumberTransform::ExplicitSign,
+        separator: None,
+        size: None,
+        number,
+    })))
+}
+
+fn create_zero_pad(args: Arguments) -> CreateFunctionResult {
+    let (width, number) = args.require_2_args(
+        WIDTH_PARAM,
+        AnyFunction::require_uint,
+        NUMBER_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(TransformNumber {
+        transform: NumberTransform::ZeroPad,
+        separator: None,
+        size: Some(width),
+        number,
+    })))
+}
+
+fn create_group_digits(args: Arguments) -> CreateFunctionResult {
+    let (separator, group_size, number) = args.require_3_args(
+        SEPARATOR_PARAM,
+        AnyFunction::require_string,
+        GROUP_SIZE_PARAM,
+        AnyFunction::require_uint,
+        NUMBER_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(TransformNumber {
+        transform: NumberTransform::GroupDigits,
+        separator: Some(separator),
+        size: Some(group_size),
+        number,
+    })))
+}
+
+fn create_equivalent_form(args: Arguments) -> CreateFunctionResult {
+    match args.require_any(VALUE_PARAM, 0)? {
+        AnyFunction::Uint(value) => Ok(AnyFunction::String(Rc::new(EquivalentForm { value }))),
+        AnyFunction::Int(value) => Ok(AnyFunction::String(Rc::new(EquivalentForm { value }))),
+        AnyFunction::Decimal(value) => Ok(AnyFunction::String(Rc::new(EquivalentForm { value }))),
+        other => Err(format_err!(
+            "Invalid argument to equivalent_form function: {:?}",
+            other
+        )),
+    }
+}
+
+pub const UINT_TO_RADIX_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_radix",
+    description: "formats the number using the given radix (between 2 and 36) with lowercase digits, putting the prefix (for example '0x') before the digits",
+    arguments: &[
+        (RADIX_PARAM, GenType::Uint),
+        (PREFIX_PARAM, GenType::String),
+        (VALUE_PARAM, GenType::Uint),
+    ],
+    variadic: false,
+    create_fn: &create_uint_to_radix,
+};
+
+pub const INT_TO_RADIX_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_radix",
+    description: "formats the number using the given radix (between 2 and 36) with lowercase digits, putting the sign and then the prefix (for example '-0x') before the digits",
+    arguments: &[
+        (RADIX_PARAM, GenType::Uint),
+        (PREFIX_PARAM, GenType::String),
+        (VALUE_PARAM, GenType::Int),
+    ],
+    variadic: false,
+    create_fn: &create_int_to_radix,
+};
+
+pub const TO_FIXED_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_fixed",
+    description: "formats the decimal with exactly the given number of digits after the decimal point",
+    arguments: &[(PRECISION_PARAM, GenType::Uint), (VALUE_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_to_fixed,
+};
+
+pub const TO_SCIENTIFIC_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_scientific",
+    description: "formats the decimal in scientific notation using the fewest digits that represent the exact value, for example: 1e3",
+    arguments: &[(VALUE_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_to_scientific,
+};
+
+pub const TO_SCIENTIFIC_WITH_STYLE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_scientific",
+    description: "formats the decimal in scientific notation using the fewest digits that represent the exact value. The exponent style is a pattern like 'e', 'E+', or 'E+00', where '+' adds an explicit sign and the number of zeros is the minimum number of exponent digits",
+    arguments: &[
+        (EXPONENT_STYLE_PARAM, GenType::String),
+        (VALUE_PARAM, GenType::Decimal),
+    ],
+    variadic: false,
+    create_fn: &create_to_scientific_with_style,
+};
+
+pub const TO_SCIENTIFIC_WITH_PRECISION_BUILTIN: &BuiltinFunctionPrototype =
+    &BuiltinFunctionPrototype {
+        function_name: "to_scientific",
+        description: "formats the decimal in scientific notation with exactly the given number of digits after the decimal point. The exponent style is a pattern like 'e', 'E+', or 'E+00', where '+' adds an explicit sign and the number of zeros is the minimum number of exponent digits",
+        arguments: &[
+            (PRECISION_PARAM, GenType::Uint),
+            (EXPONENT_STYLE_PARAM, GenType::String),
+            (VALUE_PARAM, GenType::Decimal),
+        ],
+        variadic: false,
+        create_fn: &create_to_scientific_with_precision,
+    };
+
+pub const EXPLICIT_SIGN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "explicit_sign",
+    description: "adds a '+' to the start of a formatted number, unless it already starts with a sign",
+    arguments: &[(NUMBER_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_explicit_sign,
+};
+
+pub const ZERO_PAD_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "zero_pad",
+    description: "pads the integer digits of a formatted number with leading zeros until there are at least the given number of them. Zeros are placed after any sign or radix prefix",
+    arguments: &[(WIDTH_PARAM, GenType::Uint), (NUMBER_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_zero_pad,
+};
+
+pub const GROUP_DIGITS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "group_digits",
+    description: "inserts the separator between groups of the given size in the integer digits of a formatted number, for example: group_digits(\",\", 3, \"1000000\") returns 1,000,000",
+    arguments: &[
+        (SEPARATOR_PARAM, GenType::String),
+        (GROUP_SIZE_PARAM, GenType::Uint),
+        (NUMBER_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_group_digits,
+};
+
+macro_rules! make_equivalent_form {
+    ($proto_name:ident, $gen_type:expr) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+            function_name: "equivalent_form",
+            description: "formats the number using a randomly selected representation that still parses to exactly the same value, such as scientific notation, leading zeros, an explicit sign, or a hex, octal, or binary literal for integers",
+            arguments: &[(VALUE_PARAM, $gen_type)],
+            variadic: false,
+            create_fn: &create_equivalent_form,
+        };
+    };
+}
+
+make_equivalent_form!(UINT_EQUIVALENT_FORM_BUILTIN, GenType::Uint);
+make_equivalent_form!(INT_EQUIVALENT_FORM_BUILTIN, GenType::Int);
+make_equivalent_form!(DECIMAL_EQUIVALENT_FORM_BUILTIN, GenType::Decimal);
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_runtime_error, run_program, test_program_success};
+
+    #[test]
+    fn numbers_are_formatted_with_radix() {
+        let program = r#"concat(to_radix(16, "0x", 1000), " ", to_radix(8, "0o", 8), " ", to_radix(2, "", 5), " ", to_radix(16, "0x", -255), " ", to_uppercase(to_radix(16, "", 48879)))"#;
+        test_program_success(1, program, "0x3e8 0o10 101 -0xff BEEF");
+    }
+
+    #[test]
+    fn to_radix_returns_error_for_invalid_radix() {
+        assert_runtime_error(r#"to_radix(37, "", 5)"#, "Invalid radix: 37");
+    }
+
+    #[test]
+    fn decimals_are_formatted_in_fixed_and_scientific_notation() {
+        let program = r#"concat(to_fixed(3, 1000.0), " ", to_scientific(1000.0), " ", to_scientific("E+00", 1000.0), " ", to_scientific(2, "e", 0.00125), " ", to_scientific("e+", 1.5))"#;
+        test_program_success(1, program, "1000.000 1e3 1E+03 1.25e-3 1.5e+0");
+    }
+
+    #[test]
+    fn formatted_numbers_are_signed_padded_and_grouped() {
+        let program = r#"concat(explicit_sign("1000"), " ", explicit_sign("-1"), " ", zero_pad(6, "-42.5"), " ", zero_pad(4, to_radix(16, "0x", 255)), " ", group_digits("_", 3, "1000"), " ", group_digits(",", 3, "-1234567.891"), " ", group_digits("_", 4, "0xdeadbeef"))"#;
+        test_program_success(
+            1,
+            program,
+            "+1000 -1 -000042.5 0x00ff 1_000 -1,234,567.891 0xdead_beef",
+        );
+    }
+
+    #[test]
+    fn equivalent_forms_parse_to_the_same_value() {
+        let output = run_program(200, r#"trailing_newline(equivalent_form(1000.0))"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for line in output.lines() {
+            let parsed: f64 = line.parse().expect(line);
+            assert_eq!(1000.0, parsed, "form: {}", line);
+        }
+
+        let output = run_program(200, r#"trailing_newline(equivalent_form(-1200))"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for line in output.lines() {
+            let negative = line.starts_with('-');
+            let unsigned = line.trim_start_matches('-').replace('_', "");
+            let radix = match unsigned.get(0..2) {
+                Some("0x") | Some("0X") => 16,
+                Some("0o") => 8,
+                Some("0b") => 2,
+                _ => 10,
+            };
+            let magnitude = if radix != 10 {
+                i64::from_str_radix(&unsigned[2..], radix).unwrap()
+            } else {
+                unsigned.parse::<f64>().expect(line) as i64
+            };
+            assert!(negative, "form: {}", line);
+            assert_eq!(1200, magnitude, "form: {}", line);
+        }
+    }
+}
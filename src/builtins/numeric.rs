@@ -2,8 +2,8 @@ use failure::Error;
 use std::fmt::Debug;
 use std::rc::Rc;
 use crate::{
-    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput, DynFun,
-    GenType, OutputType, ProgramContext, RunnableFunction,
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynFun, GenType, OutputType, ProgramContext, RunnableFunction,
 };
 
 use rand::distributions::uniform::SampleUniform;
@@ -14,12 +14,23 @@ struct NumericGen<T> {
     max_inclusive: DynFun<T>,
 }
 
+impl<T: Debug + PartialOrd + Copy> NumericGen<T> {
+    fn gen_bounds(&self, ctx: &mut ProgramContext) -> Result<(T, T), Error> {
+        let min = self.min_inclusive.gen_value(ctx)?;
+        let max = self.max_inclusive.gen_value(ctx)?;
+        if min <= max {
+            Ok((min, max))
+        } else {
+            Ok((max, min))
+        }
+    }
+}
+
 impl<T: Debug + PartialOrd + Copy + SampleUniform + OutputType> RunnableFunction<T>
     for NumericGen<T>
 {
     fn gen_value(&self, ctx: &mut ProgramContext) -> Result<T, Error> {
-        let min = self.min_inclusive.gen_value(ctx)?;
-        let max = self.max_inclusive.gen_value(ctx)?;
+        let (min, max) = self.gen_bounds(ctx)?;
         let result = ctx.gen_range_inclusive(min, max);
         Ok(result)
     }
@@ -83,3 +94,333 @@ make_numeric_builtin!(
     AnyFunction::require_decimal,
     "decimal"
 );
+
+/// Numeric types that have a set of "interesting" values that are more likely than others to expose bugs
+trait EdgeValues: Sized + Copy {
+    /// returns all the edge values that are within the given bounds
+    fn edge_values(min: Self, max: Self) -> Vec<Self>;
+
+    /// returns false for infinite or NaN values, which can't be used as bounds
+    fn is_finite(self) -> bool {
+        true
+    }
+}
+
+/// Returns the bounds, zero, one, negative one, and each power of two plus or minus one, as long as they're within
+/// the bounds
+fn integer_edge_values(min: i128, max: i128) -> Vec<i128> {
+    let mut values = vec![min, min + 1, max - 1, max, 0, 1, -1];
+    for bit in 1..=64 {
+        let power = 1i128 << bit;
+        values.extend_from_slice(&[
+            power - 1,
+            power,
+            power + 1,
+            -power - 1,
+            -power,
+            -power + 1,
+        ]);
+    }
+    values.retain(|value| *value >= min && *value <= max);
+    values.sort();
+    values.dedup();
+    values
+}
+
+impl EdgeValues for u64 {
+    fn edge_values(min: u64, max: u64) -> Vec<u64> {
+        integer_edge_values(i128::from(min), i128::from(max))
+            .into_iter()
+            .map(|value| value as u64)
+            .collect()
+    }
+}
+
+impl EdgeValues for i64 {
+    fn edge_values(min: i64, max: i64) -> Vec<i64> {
+        integer_edge_values(i128::from(min), i128::from(max))
+            .into_iter()
+            .map(|value| value as i64)
+            .collect()
+    }
+}
+
+/// returns the next representable value that's greater than the given one
+fn next_up(value: f64) -> f64 {
+    if value.is_nan() || value == f64::INFINITY {
+        return value;
+    }
+    if value == 0.0 {
+        return f64::from_bits(1);
+    }
+    let bits = value.to_bits();
+    if value > 0.0 {
+        f64::from_bits(bits + 1)
+    } else {
+        f64::from_bits(bits - 1)
+    }
+}
+
+/// returns the next representable value that's less than the given one
+fn next_down(value: f64) -> f64 {
+    -next_up(-value)
+}
+
+const SMALLEST_SUBNORMAL: f64 = 5e-324;
+const LARGEST_SUBNORMAL: f64 = 2.225_073_858_507_201e-308;
+
+/// finite decimal values that are interesting regardless of the bounds
+const DECIMAL_EDGES: &[f64] = &[
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    0.5,
+    -0.5,
+    f64::EPSILON,
+    1.0 + f64::EPSILON,
+    f64::MIN_POSITIVE,
+    -f64::MIN_POSITIVE,
+    SMALLEST_SUBNORMAL,
+    -SMALLEST_SUBNORMAL,
+    LARGEST_SUBNORMAL,
+    -LARGEST_SUBNORMAL,
+    255.0,
+    256.0,
+    65535.0,
+    65536.0,
+    2147483647.0,
+    -2147483648.0,
+    4294967295.0,
+    4294967296.0,
+    // the largest range of integers that can all be represented exactly
+    9007199254740991.0,
+    9007199254740992.0,
+    -9007199254740991.0,
+    -9007199254740992.0,
+    9.223_372_036_854_776e18,
+    -9.223_372_036_854_776e18,
+    1.844_674_407_370_955_2e19,
+    f64::MAX,
+    f64::MIN,
+];
+
+impl EdgeValues for f64 {
+    fn edge_values(min: f64, max: f64) -> Vec<f64> {
+        let mut values = vec![min, next_up(min), next_down(max), max];
+        values.extend_from_slice(DECIMAL_EDGES);
+        values.retain(|value| *value >= min && *value <= max);
+        values
+    }
+
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+}
+
+#[derive(Debug)]
+struct EdgeGen<T> {
+    bounds: NumericGen<T>,
+    edge_probability: DynDecimalFun,
+}
+
+impl<T: Debug + PartialOrd + Copy + SampleUniform + OutputType + EdgeValues> RunnableFunction<T>
+    for EdgeGen<T>
+{
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<T, Error> {
+        let (min, max) = self.bounds.gen_bounds(ctx)?;
+        if !min.is_finite() || !max.is_finite() {
+            bail!(
+                "Invalid bounds, min: {:?}, max: {:?}, both bounds must be finite",
+                min,
+                max
+            );
+        }
+        let edge_probability = self.edge_probability.gen_value(ctx)?;
+        if ctx.gen_chance(edge_probability) {
+            let edges = T::edge_values(min, max);
+            Ok(edges[ctx.gen_range_exclusive(0, edges.len())])
+        } else {
+            Ok(ctx.gen_range_inclusive(min, max))
+        }
+    }
+
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// non-finite values, which can only be generated when there are no bounds
+const NON_FINITE_DECIMALS: &[f64] = &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+
+#[derive(Debug)]
+struct UnboundedEdgeDecimal {
+    edge_probability: DynDecimalFun,
+}
+
+impl RunnableFunction<f64> for UnboundedEdgeDecimal {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<f64, Error> {
+        let edge_probability = self.edge_probability.gen_value(ctx)?;
+        if ctx.gen_chance(edge_probability) {
+            let index = ctx.gen_range_exclusive(0, DECIMAL_EDGES.len() + NON_FINITE_DECIMALS.len());
+            Ok(DECIMAL_EDGES
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| NON_FINITE_DECIMALS[index - DECIMAL_EDGES.len()]))
+        } else {
+            // random bits give a wide spread of magnitudes, unlike a uniform distribution across the whole range
+            let mut value = f64::from_bits(ctx.gen_value());
+            while !value.is_finite() {
+                value = f64::from_bits(ctx.gen_value());
+            }
+            Ok(value)
+        }
+    }
+
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const EDGE_PROBABILITY_PARAM: &str = "edge_probability";
+
+macro_rules! make_edge_builtin {
+    ($proto_name:ident, $create_fn_name:ident, $gen_type:expr, $any_fun_path:path, $convert_fun:path, $fun_name:expr) => {
+
+        fn $create_fn_name(args: Arguments) -> CreateFunctionResult {
+            let (min_inclusive, max_inclusive, edge_probability) = args.require_3_args(
+                MIN_PARAM,
+                $convert_fun,
+                MAX_PARAM,
+                $convert_fun,
+                EDGE_PROBABILITY_PARAM,
+                AnyFunction::require_decimal,
+            )?;
+            let fun = EdgeGen {
+                bounds: NumericGen {
+                    min_inclusive,
+                    max_inclusive,
+                },
+                edge_probability,
+            };
+            Ok($any_fun_path(Rc::new(fun)))
+        }
+
+        pub const $proto_name: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+            function_name: $fun_name,
+            description: "Generates a number between the given minimum and maximum (both inclusive). With the given probability (between 0 and 1), the number will be a boundary value such as the min or max, zero, or a power of two plus or minus one",
+            arguments: &[
+                (MIN_PARAM, $gen_type),
+                (MAX_PARAM, $gen_type),
+                (EDGE_PROBABILITY_PARAM, GenType::Decimal),
+            ],
+            variadic: false,
+            create_fn: &$create_fn_name,
+        };
+
+    };
+}
+
+make_edge_builtin!(
+    EDGE_UINT_BUILTIN,
+    create_edge_uint_builtin,
+    GenType::Uint,
+    AnyFunction::Uint,
+    AnyFunction::require_uint,
+    "edge_uint"
+);
+make_edge_builtin!(
+    EDGE_INT_BUILTIN,
+    create_edge_int_builtin,
+    GenType::Int,
+    AnyFunction::Int,
+    AnyFunction::require_int,
+    "edge_int"
+);
+make_edge_builtin!(
+    EDGE_DECIMAL_BUILTIN,
+    create_edge_decimal_builtin,
+    GenType::Decimal,
+    AnyFunction::Decimal,
+    AnyFunction::require_decimal,
+    "edge_decimal"
+);
+
+fn create_unbounded_edge_decimal(args: Arguments) -> CreateFunctionResult {
+    let edge_probability =
+        args.required_arg(EDGE_PROBABILITY_PARAM, 0, AnyFunction::require_decimal)?;
+    Ok(AnyFunction::Decimal(Rc::new(UnboundedEdgeDecimal {
+        edge_probability,
+    })))
+}
+
+pub const UNBOUNDED_EDGE_DECIMAL_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "edge_decimal",
+    description: "Generates any decimal number. With the given probability (between 0 and 1), the number will be a boundary value such as NaN, positive or negative infinity, negative zero, a subnormal number, or the largest or smallest finite value",
+    arguments: &[(EDGE_PROBABILITY_PARAM, GenType::Decimal)],
+    variadic: false,
+    create_fn: &create_unbounded_edge_decimal,
+};
+
+#[cfg(test)]
+mod test {
+    use super::{next_down, next_up, EdgeValues};
+    use crate::fun_test::{assert_runtime_error, run_program, test_program_success};
+
+    #[test]
+    fn edge_uint_always_returns_edge_values_when_probability_is_one() {
+        let program = r#"trailing_newline(to_string(edge_uint(0, 1000, 1.0)))"#;
+        let output = String::from_utf8(run_program(200, program).unwrap()).unwrap();
+        let edges = u64::edge_values(0, 1000);
+        for line in output.lines() {
+            let value: u64 = line.parse().unwrap();
+            assert!(edges.contains(&value), "{} is not an edge value", value);
+        }
+        assert!(edges.contains(&255) && edges.contains(&256) && edges.contains(&999));
+        assert!(!edges.contains(&1025));
+    }
+
+    #[test]
+    fn edge_int_includes_negative_powers_of_two() {
+        let edges = i64::edge_values(i64::MIN, i64::MAX);
+        let expected_values = &[i64::MIN, i64::MIN + 1, -129, -128, -1, 0, 127, 2147483647, i64::MAX];
+        for expected in expected_values {
+            assert!(edges.contains(expected), "missing {}", expected);
+        }
+    }
+
+    #[test]
+    fn edge_values_are_always_within_bounds() {
+        test_program_success(3, "edge_uint(7, 7, 1.0)", "777");
+        test_program_success(2, "edge_int(-3, -3, 0.5)", "-3-3");
+        test_program_success(3, "edge_decimal(1.5, 1.5, 1.0)", "1.51.51.5");
+        assert!(f64::edge_values(1.5, 1.5).iter().all(|value| *value == 1.5));
+    }
+
+    #[test]
+    fn decimal_edge_values_include_nearest_values_to_the_bounds() {
+        let edges = f64::edge_values(0.0, 1.0);
+        assert!(edges.contains(&next_up(0.0)));
+        assert!(edges.contains(&next_down(1.0)));
+        assert!(edges.iter().any(|value| *value == 0.0 && value.is_sign_negative()));
+        assert!(edges.iter().all(|value| *value >= 0.0 && *value <= 1.0));
+    }
+
+    #[test]
+    fn unbounded_edge_decimal_generates_non_finite_values() {
+        let program = r#"trailing_newline(to_string(edge_decimal(1.0)))"#;
+        let output = String::from_utf8(run_program(300, program).unwrap()).unwrap();
+        assert!(output.lines().any(|line| line == "NaN"));
+        assert!(output.lines().any(|line| line == "inf"));
+        assert!(output.lines().any(|line| line == "-inf"));
+    }
+
+    #[test]
+    fn edge_decimal_returns_an_error_for_non_finite_bounds() {
+        // the unbounded edge_decimal generates NaN and infinities, which are returned as errors rather than panicking
+        let program = "repeat(300, to_string(edge_decimal(edge_decimal(1.0), 5.0, 1.0)))";
+        assert_runtime_error(program, "both bounds must be finite");
+    }
+}
@@ -0,0 +1,570 @@
+use super::byte_order::FixedWidth;
+use super::varint::{write_protobuf_int, write_uleb128, write_zigzag_varint};
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput, DynBinFun,
+    DynFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use byteorder::LittleEndian;
+use failure::Error;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+const WIRE_TYPE_VARINT: u64 = 0;
+const WIRE_TYPE_FIXED64: u64 = 1;
+const WIRE_TYPE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_TYPE_START_GROUP: u64 = 3;
+const WIRE_TYPE_END_GROUP: u64 = 4;
+const WIRE_TYPE_FIXED32: u64 = 5;
+
+const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
+const RESERVED_FIELD_NUMBERS: std::ops::RangeInclusive<u64> = 19000..=19999;
+
+fn write_tag(field_number: u64, wire_type: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    if field_number == 0 || field_number > MAX_FIELD_NUMBER {
+        bail!(
+            "Invalid protobuf field number: {}, must be between 1 and {}",
+            field_number,
+            MAX_FIELD_NUMBER
+        );
+    }
+    if wire_type > WIRE_TYPE_FIXED32 {
+        bail!(
+            "Invalid protobuf wire type: {}, must be between 0 and 5",
+            wire_type
+        );
+    }
+    write_uleb128((field_number << 3) | wire_type, out)
+}
+
+/// Returns the field number from the tag at the start of an encoded field, if there is one
+fn read_field_number(field: &[u8]) -> Option<u64> {
+    let mut tag = 0u64;
+    for (i, byte) in field.iter().take(5).enumerate() {
+        tag |= u64::from(byte & 0x7F) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some(tag >> 3);
+        }
+    }
+    None
+}
+
+fn write_bool(value: bool, out: &mut Vec<u8>) -> Result<(), Error> {
+    out.push(value as u8);
+    Ok(())
+}
+
+fn write_fixed32<T: FixedWidth>(value: T, out: &mut Vec<u8>) -> Result<(), Error> {
+    value.write_fixed_width::<LittleEndian>(32, out)
+}
+
+fn write_fixed64<T: FixedWidth>(value: T, out: &mut Vec<u8>) -> Result<(), Error> {
+    value.write_fixed_width::<LittleEndian>(64, out)
+}
+
+fn write_length_delimited(value: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+    write_uleb128(value.len() as u64, out)?;
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+fn write_bytes(value: Vec<u8>, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_length_delimited(value.as_slice(), out)
+}
+
+fn write_string(value: IString, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_length_delimited(value.as_bytes(), out)
+}
+
+#[derive(Debug)]
+struct Tag {
+    field_number: DynUintFun,
+    wire_type: DynUintFun,
+}
+
+impl RunnableFunction<Vec<u8>> for Tag {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let field_number = self.field_number.gen_value(ctx)?;
+        let wire_type = self.wire_type.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(5);
+        write_tag(field_number, wire_type, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+#[derive(Debug)]
+struct Field<T> {
+    field_number: DynUintFun,
+    value: DynFun<T>,
+    wire_type: u64,
+    encode: fn(T, &mut Vec<u8>) -> Result<(), Error>,
+}
+
+impl<T: Debug> RunnableFunction<Vec<u8>> for Field<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let field_number = self.field_number.gen_value(ctx)?;
+        let value = self.value.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(16);
+        write_tag(field_number, self.wire_type, &mut buffer)?;
+        (self.encode)(value, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+#[derive(Debug)]
+struct PackedField {
+    field_number: DynUintFun,
+    count: DynUintFun,
+    element: DynBinFun,
+}
+
+impl RunnableFunction<Vec<u8>> for PackedField {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let field_number = self.field_number.gen_value(ctx)?;
+        let count = self.count.gen_value(ctx)?;
+        let mut elements = Vec::new();
+        for _ in 0..count {
+            let element = self.element.gen_value(ctx)?;
+            elements.extend_from_slice(element.as_slice());
+        }
+        let mut buffer = Vec::with_capacity(elements.len() + 10);
+        write_tag(field_number, WIRE_TYPE_LENGTH_DELIMITED, &mut buffer)?;
+        write_length_delimited(elements.as_slice(), &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+/// Encodings that are valid according to the protobuf spec, but that encoders never produce on their own
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Variations {
+    shuffle: bool,
+    duplicate: bool,
+    unknown: bool,
+}
+
+fn parse_variations(spec: &str) -> Result<Variations, Error> {
+    let mut variations = Variations::default();
+    for variation in spec.split(' ').filter(|part| !part.is_empty()) {
+        match variation {
+            "shuffle" => variations.shuffle = true,
+            "duplicate" => variations.duplicate = true,
+            "unknown" => variations.unknown = true,
+            other => bail!(
+                "Invalid protobuf message variation: '{}', must be one of 'shuffle', 'duplicate', or 'unknown'",
+                other
+            ),
+        }
+    }
+    Ok(variations)
+}
+
+fn unknown_field_number(ctx: &mut ProgramContext, used: &[u64]) -> u64 {
+    loop {
+        let field_number = if ctx.gen_chance(0.5) {
+            ctx.gen_range_inclusive(1, 100)
+        } else {
+            ctx.gen_range_inclusive(1, MAX_FIELD_NUMBER)
+        };
+        if !used.contains(&field_number) && !RESERVED_FIELD_NUMBERS.contains(&field_number) {
+            return field_number;
+        }
+    }
+}
+
+fn random_bytes(ctx: &mut ProgramContext, len: usize) -> Vec<u8> {
+    (0..len).map(|_| ctx.gen_value::<u8>()).collect()
+}
+
+/// Appends a field with a field number that isn't in `used`, using any of the wire types, including the deprecated
+/// groups, which decoders still have to skip over
+fn write_unknown_field(ctx: &mut ProgramContext, used: &[u64], out: &mut Vec<u8>) -> Result<(), Error> {
+    let field_number = unknown_field_number(ctx, used);
+    let wire_type = ctx.choose(&[
+        WIRE_TYPE_VARINT,
+        WIRE_TYPE_FIXED64,
+        WIRE_TYPE_LENGTH_DELIMITED,
+        WIRE_TYPE_START_GROUP,
+        WIRE_TYPE_FIXED32,
+    ]);
+    write_tag(field_number, wire_type, out)?;
+    match wire_type {
+        WIRE_TYPE_VARINT => {
+            let value = ctx.gen_value::<u64>() >> ctx.gen_range_inclusive(0, 63);
+            write_uleb128(value, out)?;
+        }
+        WIRE_TYPE_FIXED64 => out.extend(random_bytes(ctx, 8)),
+        WIRE_TYPE_FIXED32 => out.extend(random_bytes(ctx, 4)),
+        WIRE_TYPE_LENGTH_DELIMITED => {
+            let len = ctx.gen_range_inclusive(0, 16);
+            write_length_delimited(random_bytes(ctx, len).as_slice(), out)?;
+        }
+        _ => {
+            write_tag(1, WIRE_TYPE_VARINT, out)?;
+            write_uleb128(ctx.gen_range_inclusive(0, 1000), out)?;
+            write_tag(field_number, WIRE_TYPE_END_GROUP, out)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Message {
+    variations: DynStringFun,
+    fields: Vec<DynBinFun>,
+}
+
+impl RunnableFunction<Vec<u8>> for Message {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let variations = parse_variations(&self.variations.gen_value(ctx)?)?;
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in self.fields.iter() {
+            fields.push(field.gen_value(ctx)?);
+            // the last value wins for singular fields, so the duplicate is generated separately
+            if variations.duplicate && ctx.gen_chance(0.3) {
+                fields.push(field.gen_value(ctx)?);
+            }
+        }
+        if variations.unknown {
+            let used = fields
+                .iter()
+                .filter_map(|field| read_field_number(field))
+                .collect::<Vec<_>>();
+            for _ in 0..ctx.gen_range_inclusive(1, 3) {
+                let mut unknown = Vec::new();
+                write_unknown_field(ctx, &used, &mut unknown)?;
+                fields.push(unknown);
+            }
+        }
+        if variations.shuffle {
+            for i in (1..fields.len()).rev() {
+                let j = ctx.gen_range_inclusive(0, i);
+                fields.swap(i, j);
+            }
+        }
+        Ok(fields.concat())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+const FIELD_NUMBER_PARAM: &str = "field_number";
+const WIRE_TYPE_PARAM: &str = "wire_type";
+const VALUE_PARAM: &str = "value";
+const COUNT_PARAM: &str = "count";
+const ELEMENT_PARAM: &str = "element";
+const VARIATIONS_PARAM: &str = "variations";
+const FIELDS_PARAM: &str = "fields";
+
+fn create_tag(args: Arguments) -> CreateFunctionResult {
+    let (field_number, wire_type) = args.require_2_args(
+        FIELD_NUMBER_PARAM,
+        AnyFunction::require_uint,
+        WIRE_TYPE_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(Tag {
+        field_number,
+        wire_type,
+    })))
+}
+
+fn create_packed_field(args: Arguments) -> CreateFunctionResult {
+    let (field_number, count, element) = args.require_3_args(
+        FIELD_NUMBER_PARAM,
+        AnyFunction::require_uint,
+        COUNT_PARAM,
+        AnyFunction::require_uint,
+        ELEMENT_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(PackedField {
+        field_number,
+        count,
+        element,
+    })))
+}
+
+fn create_message(args: Arguments) -> CreateFunctionResult {
+    let variations = args.required_arg(VARIATIONS_PARAM, 0, AnyFunction::require_string)?;
+    let fields = args.get_required_varargs(FIELDS_PARAM, 1, AnyFunction::require_bin)?;
+    Ok(AnyFunction::Bin(Rc::new(Message { variations, fields })))
+}
+
+macro_rules! make_field_builtin {
+    ($proto_name:ident, $fun_name:expr, $gen_type:expr, $convert_input:path, $wire_type:expr, $encode_fn:path, $description:expr) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let (field_number, value) = args.require_2_args(
+                    FIELD_NUMBER_PARAM,
+                    AnyFunction::require_uint,
+                    VALUE_PARAM,
+                    $convert_input,
+                )?;
+                Ok(AnyFunction::Bin(Rc::new(Field {
+                    field_number,
+                    value,
+                    wire_type: $wire_type,
+                    encode: $encode_fn,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: $description,
+                arguments: &[(FIELD_NUMBER_PARAM, GenType::Uint), (VALUE_PARAM, $gen_type)],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_field_builtin!(
+    UINT_VARINT_FIELD_BUILTIN,
+    "protobuf_varint_field",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    WIRE_TYPE_VARINT,
+    write_uleb128,
+    "encodes a protobuf field with the varint wire type, as used for uint32, uint64, and enum fields"
+);
+make_field_builtin!(
+    INT_VARINT_FIELD_BUILTIN,
+    "protobuf_varint_field",
+    GenType::Int,
+    AnyFunction::require_int,
+    WIRE_TYPE_VARINT,
+    write_protobuf_int,
+    "encodes a protobuf field with the varint wire type, as used for int32 and int64 fields. Negative numbers always take 10 bytes"
+);
+make_field_builtin!(
+    BOOLEAN_VARINT_FIELD_BUILTIN,
+    "protobuf_varint_field",
+    GenType::Boolean,
+    AnyFunction::require_boolean,
+    WIRE_TYPE_VARINT,
+    write_bool,
+    "encodes a protobuf bool field"
+);
+make_field_builtin!(
+    SINT_FIELD_BUILTIN,
+    "protobuf_sint_field",
+    GenType::Int,
+    AnyFunction::require_int,
+    WIRE_TYPE_VARINT,
+    write_zigzag_varint,
+    "encodes a protobuf sint32 or sint64 field, using a zigzag varint"
+);
+make_field_builtin!(
+    UINT_FIXED32_FIELD_BUILTIN,
+    "protobuf_fixed32_field",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    WIRE_TYPE_FIXED32,
+    write_fixed32,
+    "encodes a protobuf fixed32 field. Returns an error if the value does not fit in 32 bits"
+);
+make_field_builtin!(
+    INT_FIXED32_FIELD_BUILTIN,
+    "protobuf_fixed32_field",
+    GenType::Int,
+    AnyFunction::require_int,
+    WIRE_TYPE_FIXED32,
+    write_fixed32,
+    "encodes a protobuf sfixed32 field. Returns an error if the value does not fit in 32 bits"
+);
+make_field_builtin!(
+    DECIMAL_FIXED32_FIELD_BUILTIN,
+    "protobuf_fixed32_field",
+    GenType::Decimal,
+    AnyFunction::require_decimal,
+    WIRE_TYPE_FIXED32,
+    write_fixed32,
+    "encodes a protobuf float field"
+);
+make_field_builtin!(
+    UINT_FIXED64_FIELD_BUILTIN,
+    "protobuf_fixed64_field",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    WIRE_TYPE_FIXED64,
+    write_fixed64,
+    "encodes a protobuf fixed64 field"
+);
+make_field_builtin!(
+    INT_FIXED64_FIELD_BUILTIN,
+    "protobuf_fixed64_field",
+    GenType::Int,
+    AnyFunction::require_int,
+    WIRE_TYPE_FIXED64,
+    write_fixed64,
+    "encodes a protobuf sfixed64 field"
+);
+make_field_builtin!(
+    DECIMAL_FIXED64_FIELD_BUILTIN,
+    "protobuf_fixed64_field",
+    GenType::Decimal,
+    AnyFunction::require_decimal,
+    WIRE_TYPE_FIXED64,
+    write_fixed64,
+    "encodes a protobuf double field"
+);
+make_field_builtin!(
+    BIN_BYTES_FIELD_BUILTIN,
+    "protobuf_bytes_field",
+    GenType::Bin,
+    AnyFunction::require_bin,
+    WIRE_TYPE_LENGTH_DELIMITED,
+    write_bytes,
+    "encodes a length delimited protobuf field, as used for bytes fields and embedded messages"
+);
+make_field_builtin!(
+    STRING_BYTES_FIELD_BUILTIN,
+    "protobuf_bytes_field",
+    GenType::String,
+    AnyFunction::require_string,
+    WIRE_TYPE_LENGTH_DELIMITED,
+    write_string,
+    "encodes a protobuf string field as utf-8"
+);
+
+pub const TAG_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "protobuf_tag",
+    description: "encodes a protobuf field tag, which is the field number (between 1 and 2^29 - 1) and the wire type (0: varint, 1: 64 bit, 2: length delimited, 3: start group, 4: end group, 5: 32 bit), for building fields that the other protobuf functions can't",
+    arguments: &[(FIELD_NUMBER_PARAM, GenType::Uint), (WIRE_TYPE_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_tag,
+};
+
+pub const PACKED_FIELD_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "protobuf_packed_field",
+    description: "encodes a packed repeated protobuf field, generating the element count times. The elements should be encoded without tags, using protobuf_varint, zigzag_varint, or little_endian with a width of 32 or 64",
+    arguments: &[
+        (FIELD_NUMBER_PARAM, GenType::Uint),
+        (COUNT_PARAM, GenType::Uint),
+        (ELEMENT_PARAM, GenType::Bin),
+    ],
+    variadic: false,
+    create_fn: &create_packed_field,
+};
+
+pub const MESSAGE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "protobuf_message",
+    description: "encodes a protobuf message from the encoded fields. The variations are space separated, and are any of 'shuffle', which writes the fields in a random order, 'duplicate', which randomly writes fields a second time with a separately generated value, and 'unknown', which adds 1 to 3 fields with unused field numbers and random wire types. An empty string writes the fields in order. Messages can be embedded in other messages using protobuf_bytes_field",
+    arguments: &[(VARIATIONS_PARAM, GenType::String), (FIELDS_PARAM, GenType::Bin)],
+    variadic: true,
+    create_fn: &create_message,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error, run_program};
+
+    /// Decodes a message into (field number, wire type, value) triples, where the value is the raw encoded value
+    /// without the tag or length prefix. Groups are returned as a single field with their encoded contents
+    fn decode_message(mut input: &[u8]) -> Vec<(u64, u64, Vec<u8>)> {
+        let mut fields = Vec::new();
+        while !input.is_empty() {
+            let (tag, rest) = decode_varint(input);
+            let (field_number, wire_type) = (tag >> 3, tag & 7);
+            let (len, rest) = match wire_type {
+                WIRE_TYPE_VARINT => (rest.iter().position(|b| b & 0x80 == 0).unwrap() + 1, rest),
+                WIRE_TYPE_FIXED64 => (8, rest),
+                WIRE_TYPE_FIXED32 => (4, rest),
+                WIRE_TYPE_LENGTH_DELIMITED => {
+                    let (len, rest) = decode_varint(rest);
+                    (len as usize, rest)
+                }
+                WIRE_TYPE_START_GROUP => {
+                    let mut end_tag = Vec::new();
+                    write_tag(field_number, WIRE_TYPE_END_GROUP, &mut end_tag).unwrap();
+                    let end = rest.windows(end_tag.len()).position(|w| w == end_tag.as_slice()).unwrap();
+                    fields.push((field_number, wire_type, rest[..end].to_vec()));
+                    input = &rest[end + end_tag.len()..];
+                    continue;
+                }
+                other => panic!("unexpected wire type: {}", other),
+            };
+            fields.push((field_number, wire_type, rest[..len].to_vec()));
+            input = &rest[len..];
+        }
+        fields
+    }
+
+    fn decode_varint(input: &[u8]) -> (u64, &[u8]) {
+        let len = input.iter().position(|b| b & 0x80 == 0).unwrap() + 1;
+        let value = input[..len]
+            .iter()
+            .rev()
+            .fold(0, |value, byte| (value << 7) | u64::from(byte & 0x7F));
+        (value, &input[len..])
+    }
+
+    #[test]
+    fn fields_are_encoded() {
+        // examples from the protobuf encoding guide
+        assert_bin_output_is_expected("protobuf_varint_field(1, 150)", &[0x08, 0x96, 0x01]);
+        assert_bin_output_is_expected("protobuf_bytes_field(2, \"testing\")", b"\x12\x07testing");
+        assert_bin_output_is_expected("protobuf_varint_field(1, true)", &[0x08, 0x01]);
+        assert_bin_output_is_expected("protobuf_sint_field(3, -2)", &[0x18, 0x03]);
+        assert_bin_output_is_expected("protobuf_fixed32_field(1, 1)", &[0x0D, 1, 0, 0, 0]);
+        assert_bin_output_is_expected("protobuf_fixed32_field(1, -1)", &[0x0D, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_bin_output_is_expected("protobuf_fixed64_field(1, 1.0)", &[0x09, 0, 0, 0, 0, 0, 0, 0xF0, 0x3F]);
+        assert_bin_output_is_expected("protobuf_tag(536870911, 4)", &[0xFC, 0xFF, 0xFF, 0xFF, 0x0F]);
+        assert_bin_output_is_expected(
+            "protobuf_packed_field(4, 3, protobuf_varint(270))",
+            &[0x22, 0x06, 0x8E, 0x02, 0x8E, 0x02, 0x8E, 0x02],
+        );
+    }
+
+    #[test]
+    fn embedded_messages_are_length_delimited() {
+        let program = "protobuf_message(\"\", protobuf_varint_field(1, 150), protobuf_bytes_field(3, protobuf_message(\"\", protobuf_varint_field(1, 150))))";
+        assert_bin_output_is_expected(program, &[0x08, 0x96, 0x01, 0x1A, 0x03, 0x08, 0x96, 0x01]);
+    }
+
+    #[test]
+    fn message_variations_are_valid_encodings() {
+        let program = "protobuf_message(\"shuffle duplicate unknown\", protobuf_varint_field(1, 150), protobuf_bytes_field(2, \"a\"), protobuf_fixed32_field(3, 7))";
+        let mut saw_out_of_order = false;
+        let mut saw_duplicate = false;
+        for _ in 0..100 {
+            let output = run_program(1, program).unwrap();
+            let fields = decode_message(&output);
+            let known = fields.iter().filter(|f| f.0 <= 3).map(|f| f.0).collect::<Vec<_>>();
+            let unknown = fields.len() - known.len();
+            assert!((1..=3).contains(&unknown), "fields: {:?}", fields);
+            for expected in 1..=3 {
+                assert!(known.contains(&expected), "fields: {:?}", fields);
+            }
+            assert!(fields.iter().all(|f| f.0 > 3 || f.1 == [0, 2, 5][f.0 as usize - 1]));
+            saw_out_of_order |= known.windows(2).any(|w| w[0] > w[1]);
+            saw_duplicate |= known.len() > 3;
+        }
+        assert!(saw_out_of_order);
+        assert!(saw_duplicate);
+    }
+
+    #[test]
+    fn invalid_fields_return_errors() {
+        assert_runtime_error("protobuf_varint_field(0, 1)", "Invalid protobuf field number: 0");
+        assert_runtime_error("protobuf_tag(1, 6)", "Invalid protobuf wire type: 6");
+        assert_runtime_error("protobuf_fixed32_field(1, 4294967296)", "does not fit in an unsigned 32 bit integer");
+        assert_runtime_error("protobuf_message(\"sorted\", [])", "Invalid protobuf message variation: 'sorted'");
+    }
+}
@@ -0,0 +1,2065 @@
+use super::network::{IPV4_BIN_BUILTIN, IPV4_STRING_BUILTIN, IPV6_BIN_BUILTIN, IPV6_STRING_BUILTIN};
+use super::select::SELECT_STRING_BUILTIN;
+use super::uuid::{UUID_STRING_BUILTIN, UUID_V4_BIN_BUILTIN};
+use crate::json::{write_json_string, JsonValue};
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, ConstString, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::rc::{Rc, Weak};
+
+type NodeId = usize;
+
+/// Past this depth, optional properties and array items beyond the minimum are omitted, so that recursive schemas
+/// still produce finite instances
+const SOFT_DEPTH_LIMIT: usize = 8;
+const MAX_DEPTH: usize = 64;
+/// The width of the range used for numbers that are missing a minimum or maximum
+const DEFAULT_NUMBER_RANGE: i64 = 1000;
+const DEFAULT_EXTRA_LENGTH: u64 = 12;
+const DEFAULT_EXTRA_ITEMS: u64 = 4;
+const MAX_PATTERN_ATTEMPTS: usize = 50;
+
+/// Keywords that constrain instances, but can't be generated yet. Schemas that use them return an error, rather than
+/// generating instances that might not be valid
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "allOf",
+    "not",
+    "if",
+    "then",
+    "else",
+    "patternProperties",
+    "propertyNames",
+    "dependencies",
+    "dependentRequired",
+    "dependentSchemas",
+    "minProperties",
+    "maxProperties",
+    "contains",
+    "minContains",
+    "maxContains",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Keywords that don't constrain instances, so they're allowed next to `$ref`, anyOf, and oneOf
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$anchor",
+    "$comment",
+    "$defs",
+    "definitions",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonType {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+const ALL_TYPES: [JsonType; 7] = [
+    JsonType::Null,
+    JsonType::Boolean,
+    JsonType::Integer,
+    JsonType::Number,
+    JsonType::String,
+    JsonType::Array,
+    JsonType::Object,
+];
+
+impl JsonType {
+    fn parse(name: &str, pointer: &str) -> Result<JsonType, Error> {
+        let json_type = match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "integer" => JsonType::Integer,
+            "number" => JsonType::Number,
+            "string" => JsonType::String,
+            "array" => JsonType::Array,
+            "object" => JsonType::Object,
+            other => bail!("Invalid json schema at '{}': unknown type '{}'", pointer, other),
+        };
+        Ok(json_type)
+    }
+
+    /// An instance of this type, used for instances that have the wrong type
+    fn example(self) -> &'static str {
+        match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "false",
+            JsonType::Integer => "42",
+            JsonType::Number => "4.5",
+            JsonType::String => "\"wrong type\"",
+            JsonType::Array => "[]",
+            JsonType::Object => "{}",
+        }
+    }
+
+    /// Whether instances of this type are also valid for the other type, since integers are also numbers
+    fn is_valid_as(self, other: JsonType) -> bool {
+        self == other || (self == JsonType::Integer && other == JsonType::Number)
+    }
+
+    fn of(value: &JsonValue) -> JsonType {
+        match value {
+            JsonValue::Null => JsonType::Null,
+            JsonValue::Boolean(_) => JsonType::Boolean,
+            JsonValue::Number(number) if !number.contains(['.', 'e', 'E']) => JsonType::Integer,
+            JsonValue::Number(_) => JsonType::Number,
+            JsonValue::String(_) => JsonType::String,
+            JsonValue::Array(_) => JsonType::Array,
+            JsonValue::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+fn types_overlap(first: &[JsonType], second: &[JsonType]) -> bool {
+    first
+        .iter()
+        .any(|a| second.iter().any(|b| a.is_valid_as(*b) || b.is_valid_as(*a)))
+}
+
+/// The ways that an instance can violate a schema. Each node only offers the violations that its keywords allow
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Violation {
+    WrongType,
+    NotInEnum,
+    BelowMinimum,
+    AboveMaximum,
+    NotMultiple,
+    TooShort,
+    TooLong,
+    PatternMismatch,
+    TooFewItems,
+    TooManyItems,
+    MissingRequired,
+    AdditionalProperty,
+    Child,
+}
+
+#[derive(Debug, Clone)]
+struct IntegerSchema {
+    min: Option<i64>,
+    max: Option<i64>,
+    multiple_of: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct NumberSchema {
+    /// the bound and whether it's exclusive
+    min: Option<(f64, bool)>,
+    max: Option<(f64, bool)>,
+    /// the multiple and the number of decimal places used to write it
+    multiple_of: Option<(f64, usize)>,
+}
+
+#[derive(Debug, Clone)]
+struct StringSchema {
+    min_length: u64,
+    max_length: Option<u64>,
+    pattern: Option<(Hir, Regex)>,
+    format: Option<Format>,
+}
+
+#[derive(Debug, Clone)]
+enum Format {
+    Date,
+    DateTime,
+    Email,
+    Function(DynStringFun),
+}
+
+#[derive(Debug)]
+struct ArraySchema {
+    prefix_items: Vec<NodeId>,
+    items: Option<NodeId>,
+    min_items: u64,
+    max_items: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ObjectSchema {
+    properties: Vec<(String, NodeId)>,
+    required: Vec<String>,
+    additional_properties: bool,
+}
+
+#[derive(Debug)]
+enum Kind {
+    Any,
+    Never,
+    Enum(Vec<JsonValue>),
+    Null,
+    Boolean,
+    Integer(IntegerSchema),
+    Number(NumberSchema),
+    String(StringSchema),
+    Array(ArraySchema),
+    Object(ObjectSchema),
+    /// a choice between subschemas, which are either the alternatives of anyOf or oneOf, or one per type when the
+    /// type keyword lists several
+    Choice(Vec<NodeId>),
+    Ref(NodeId),
+}
+
+#[derive(Debug)]
+struct Node {
+    kind: Kind,
+    /// the types allowed by the type keyword, or empty if it was missing
+    types: Vec<JsonType>,
+}
+
+/// A json schema that has been parsed into a graph of nodes, which may contain cycles through `$ref`s. The nodes are
+/// used to work out which subschemas can be violated, and then to build the functions that generate instances
+#[derive(Debug)]
+struct Schema {
+    nodes: Vec<Node>,
+    root: NodeId,
+    can_violate: Vec<bool>,
+}
+
+fn get<'a>(members: &'a [(String, JsonValue)], key: &str) -> Option<&'a JsonValue> {
+    members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Returns an error for keywords that would be ignored, since the instances might not be valid if they were
+fn check_keywords(members: &[(String, JsonValue)], pointer: &str) -> Result<(), Error> {
+    for (key, value) in members.iter() {
+        let is_supported = match key.as_str() {
+            "uniqueItems" => *value != JsonValue::Boolean(true),
+            "additionalProperties" => match value {
+                JsonValue::Boolean(_) => true,
+                JsonValue::Object(members) => members.is_empty(),
+                _ => false,
+            },
+            other => !UNSUPPORTED_KEYWORDS.contains(&other),
+        };
+        if !is_supported {
+            bail!("Invalid json schema at '{}': unsupported keyword '{}'", pointer, key);
+        }
+    }
+    // these replace the rest of the schema, so any other constraints next to them would be ignored
+    if let Some(keyword) = ["$ref", "anyOf", "oneOf"].iter().find(|keyword| get(members, keyword).is_some()) {
+        let other = members
+            .iter()
+            .map(|(key, _)| key)
+            .find(|key| key != keyword && !ANNOTATION_KEYWORDS.contains(&key.as_str()));
+        if let Some(other) = other {
+            bail!("Invalid json schema at '{}': unsupported keyword '{}' alongside '{}'", pointer, other, keyword);
+        }
+    }
+    Ok(())
+}
+
+fn get_number(members: &[(String, JsonValue)], key: &str, pointer: &str) -> Result<Option<f64>, Error> {
+    match get(members, key) {
+        None => Ok(None),
+        Some(JsonValue::Number(number)) => Ok(Some(number.parse::<f64>()?)),
+        Some(other) => bail!(
+            "Invalid json schema at '{}': '{}' must be a number, but got a {}",
+            pointer,
+            key,
+            other.type_name()
+        ),
+    }
+}
+
+fn get_count(members: &[(String, JsonValue)], key: &str, pointer: &str) -> Result<Option<u64>, Error> {
+    match get_number(members, key, pointer)? {
+        Some(count) if count < 0.0 || count.fract() != 0.0 => bail!(
+            "Invalid json schema at '{}': '{}' must be a non-negative integer",
+            pointer,
+            key
+        ),
+        other => Ok(other.map(|count| count as u64)),
+    }
+}
+
+/// Returns a bound and whether it's exclusive. Draft 4 uses a boolean exclusive keyword that modifies the inclusive
+/// one, while later drafts use a separate number
+fn get_bound(
+    members: &[(String, JsonValue)],
+    inclusive_key: &str,
+    exclusive_key: &str,
+    pointer: &str,
+    is_lower: bool,
+) -> Result<Option<(f64, bool)>, Error> {
+    let inclusive = get_number(members, inclusive_key, pointer)?;
+    match get(members, exclusive_key) {
+        Some(JsonValue::Boolean(exclusive)) => Ok(inclusive.map(|bound| (bound, *exclusive))),
+        Some(_) => {
+            let exclusive = get_number(members, exclusive_key, pointer)?.unwrap_or(0.0);
+            match inclusive {
+                Some(bound) if (is_lower && bound > exclusive) || (!is_lower && bound < exclusive) => {
+                    Ok(Some((bound, false)))
+                }
+                _ => Ok(Some((exclusive, true))),
+            }
+        }
+        None => Ok(inclusive.map(|bound| (bound, false))),
+    }
+}
+
+/// Resolves a `$ref` of the form `#/path/to/schema` against the root of the document
+fn resolve_pointer<'a>(root: &'a JsonValue, reference: &str) -> Result<&'a JsonValue, Error> {
+    let path = match reference.strip_prefix('#') {
+        Some(path) => path,
+        None => bail!(
+            "Invalid $ref '{}': only references within the same file, starting with '#', are supported",
+            reference
+        ),
+    };
+    if path.is_empty() {
+        return Ok(root);
+    }
+    if !path.starts_with('/') {
+        bail!("Invalid $ref '{}': the fragment must be a json pointer", reference);
+    }
+    let mut current = root;
+    for token in path[1..].split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let next = match current {
+            JsonValue::Object(members) => get(members, &token),
+            JsonValue::Array(items) => token.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        current = next.ok_or_else(|| format_err!("Invalid $ref '{}': the path does not exist in the schema", reference))?;
+    }
+    Ok(current)
+}
+
+/// Creates a function by calling a builtin with the given arguments, the same way the interpreter does
+fn call_builtin(prototype: &BuiltinFunctionPrototype, args: Vec<AnyFunction>) -> Result<DynStringFun, Error> {
+    (prototype.create_fn)(Arguments::new(args))?.require_string()
+}
+
+fn parse_format(name: &str) -> Result<Option<Format>, Error> {
+    let format = match name {
+        "date" => Format::Date,
+        "date-time" => Format::DateTime,
+        "email" => Format::Email,
+        "uuid" => {
+            let uuid = (UUID_V4_BIN_BUILTIN.create_fn)(Arguments::new(Vec::new()))?;
+            Format::Function(call_builtin(UUID_STRING_BUILTIN, vec![ConstString::new("canonical"), uuid])?)
+        }
+        "ipv4" => {
+            let address = (IPV4_BIN_BUILTIN.create_fn)(Arguments::new(vec![ConstString::new("0.0.0.0/0")]))?;
+            Format::Function(call_builtin(IPV4_STRING_BUILTIN, vec![ConstString::new("dotted"), address])?)
+        }
+        "ipv6" => {
+            let address = (IPV6_BIN_BUILTIN.create_fn)(Arguments::new(vec![ConstString::new("::/0")]))?;
+            Format::Function(call_builtin(IPV6_STRING_BUILTIN, vec![ConstString::new("compressed"), address])?)
+        }
+        // formats are only annotations by default, so unknown ones are ignored
+        _ => return Ok(None),
+    };
+    Ok(Some(format))
+}
+
+struct Compiler<'a> {
+    root: &'a JsonValue,
+    nodes: Vec<Node>,
+    refs: HashMap<String, NodeId>,
+}
+
+impl<'a> Compiler<'a> {
+    /// The types that instances of the node may have, or None if they may have any type
+    fn possible_types(&self, id: NodeId, depth: usize) -> Option<Vec<JsonType>> {
+        let node = &self.nodes[id];
+        if !node.types.is_empty() {
+            return Some(node.types.clone());
+        }
+        let json_type = match node.kind {
+            // references that are still being compiled, or that form a cycle, could be anything
+            Kind::Any => return None,
+            Kind::Ref(_) | Kind::Choice(_) if depth > MAX_DEPTH => return None,
+            Kind::Ref(target) => return self.possible_types(target, depth + 1),
+            Kind::Choice(ref choices) => {
+                let mut types = Vec::new();
+                for choice in choices.iter() {
+                    types.extend(self.possible_types(*choice, depth + 1)?);
+                }
+                return Some(types);
+            }
+            Kind::Never => return Some(Vec::new()),
+            Kind::Enum(ref values) => return Some(values.iter().map(JsonType::of).collect()),
+            Kind::Null => JsonType::Null,
+            Kind::Boolean => JsonType::Boolean,
+            Kind::Integer(_) => JsonType::Integer,
+            Kind::Number(_) => JsonType::Number,
+            Kind::String(_) => JsonType::String,
+            Kind::Array(_) => JsonType::Array,
+            Kind::Object(_) => JsonType::Object,
+        };
+        Some(vec![json_type])
+    }
+
+    fn push(&mut self, kind: Kind, types: Vec<JsonType>) -> NodeId {
+        self.nodes.push(Node { kind, types });
+        self.nodes.len() - 1
+    }
+
+    fn compile_ref(&mut self, reference: &JsonValue, pointer: &str) -> Result<NodeId, Error> {
+        let reference = match reference {
+            JsonValue::String(reference) => reference,
+            _ => bail!("Invalid json schema at '{}': '$ref' must be a string", pointer),
+        };
+        if let Some(&id) = self.refs.get(reference) {
+            return Ok(id);
+        }
+        // the placeholder is what recursive references will point to until the target is compiled
+        let id = self.push(Kind::Any, Vec::new());
+        self.refs.insert(reference.clone(), id);
+        let root = self.root;
+        let target = self.compile(resolve_pointer(root, reference)?, reference)?;
+        self.nodes[id].kind = Kind::Ref(target);
+        Ok(id)
+    }
+
+    fn compile_all(&mut self, schemas: &'a JsonValue, pointer: &str) -> Result<Vec<NodeId>, Error> {
+        match schemas {
+            JsonValue::Array(schemas) => schemas
+                .iter()
+                .enumerate()
+                .map(|(i, schema)| self.compile(schema, &format!("{}/{}", pointer, i)))
+                .collect(),
+            other => bail!(
+                "Invalid json schema at '{}': expected an array, but got a {}",
+                pointer,
+                other.type_name()
+            ),
+        }
+    }
+
+    fn compile(&mut self, schema: &'a JsonValue, pointer: &str) -> Result<NodeId, Error> {
+        let members = match schema {
+            JsonValue::Boolean(true) => return Ok(self.push(Kind::Any, Vec::new())),
+            JsonValue::Boolean(false) => return Ok(self.push(Kind::Never, Vec::new())),
+            JsonValue::Object(members) => members,
+            other => bail!(
+                "Invalid json schema at '{}': expected an object or a boolean, but got a {}",
+                pointer,
+                other.type_name()
+            ),
+        };
+        check_keywords(members, pointer)?;
+        if let Some(reference) = get(members, "$ref") {
+            return self.compile_ref(reference, pointer);
+        }
+        if let Some(value) = get(members, "const") {
+            return Ok(self.push(Kind::Enum(vec![value.clone()]), Vec::new()));
+        }
+        if let Some(values) = get(members, "enum") {
+            return match values {
+                JsonValue::Array(values) if !values.is_empty() => Ok(self.push(Kind::Enum(values.clone()), Vec::new())),
+                _ => bail!("Invalid json schema at '{}': 'enum' must be a non-empty array", pointer),
+            };
+        }
+        if let Some(schemas) = get(members, "anyOf") {
+            let choices = self.compile_all(schemas, &format!("{}/anyOf", pointer))?;
+            return Ok(self.push(Kind::Choice(choices), Vec::new()));
+        }
+        if let Some(schemas) = get(members, "oneOf") {
+            let choices = self.compile_all(schemas, &format!("{}/oneOf", pointer))?;
+            // an instance of one alternative must not match any of the others, which is only certain when every
+            // alternative allows different types
+            let types = choices.iter().map(|choice| self.possible_types(*choice, 0)).collect::<Vec<_>>();
+            for (i, first) in types.iter().enumerate() {
+                for second in types[i + 1..].iter() {
+                    match (first, second) {
+                        (Some(first), Some(second)) if !types_overlap(first, second) => {}
+                        _ => bail!(
+                            "Invalid json schema at '{}': 'oneOf' is only supported when each alternative allows different types",
+                            pointer
+                        ),
+                    }
+                }
+            }
+            return Ok(self.push(Kind::Choice(choices), Vec::new()));
+        }
+
+        let types = match get(members, "type") {
+            Some(JsonValue::String(name)) => vec![JsonType::parse(name, pointer)?],
+            Some(JsonValue::Array(names)) => names
+                .iter()
+                .map(|name| match name {
+                    JsonValue::String(name) => JsonType::parse(name, pointer),
+                    _ => bail!("Invalid json schema at '{}': 'type' must only contain strings", pointer),
+                })
+                .collect::<Result<Vec<_>, Error>>()?,
+            Some(_) => bail!("Invalid json schema at '{}': 'type' must be a string or an array", pointer),
+            None => Vec::new(),
+        };
+        match types.len() {
+            0 => match infer_type(members) {
+                Some(json_type) => self.compile_typed(json_type, members, Vec::new(), pointer),
+                None => Ok(self.push(Kind::Any, Vec::new())),
+            },
+            1 => self.compile_typed(types[0], members, types, pointer),
+            _ => {
+                let mut choices = Vec::with_capacity(types.len());
+                for json_type in types.iter() {
+                    choices.push(self.compile_typed(*json_type, members, types.clone(), pointer)?);
+                }
+                Ok(self.push(Kind::Choice(choices), types))
+            }
+        }
+    }
+
+    fn compile_typed(
+        &mut self,
+        json_type: JsonType,
+        members: &'a [(String, JsonValue)],
+        types: Vec<JsonType>,
+        pointer: &str,
+    ) -> Result<NodeId, Error> {
+        let kind = match json_type {
+            JsonType::Null => Kind::Null,
+            JsonType::Boolean => Kind::Boolean,
+            JsonType::Integer => Kind::Integer(compile_integer(members, pointer)?),
+            JsonType::Number => Kind::Number(compile_number(members, pointer)?),
+            JsonType::String => Kind::String(compile_string(members, pointer)?),
+            JsonType::Array => Kind::Array(self.compile_array(members, pointer)?),
+            JsonType::Object => Kind::Object(self.compile_object(members, pointer)?),
+        };
+        Ok(self.push(kind, types))
+    }
+
+    fn compile_array(&mut self, members: &'a [(String, JsonValue)], pointer: &str) -> Result<ArraySchema, Error> {
+        let (prefix_items, items) = match (get(members, "prefixItems"), get(members, "items")) {
+            (Some(prefix), items) => (self.compile_all(prefix, &format!("{}/prefixItems", pointer))?, items),
+            // before 2020-12, tuples were written as an array of schemas in items
+            (None, Some(prefix @ JsonValue::Array(_))) => (
+                self.compile_all(prefix, &format!("{}/items", pointer))?,
+                get(members, "additionalItems"),
+            ),
+            (None, items) => (Vec::new(), items),
+        };
+        let items = match items {
+            Some(schema) => Some(self.compile(schema, &format!("{}/items", pointer))?),
+            None => None,
+        };
+        let min_items = get_count(members, "minItems", pointer)?.unwrap_or(0);
+        let max_items = get_count(members, "maxItems", pointer)?;
+        if max_items.map(|max| max < min_items).unwrap_or(false) {
+            bail!("Invalid json schema at '{}': 'maxItems' is less than 'minItems'", pointer);
+        }
+        Ok(ArraySchema {
+            prefix_items,
+            items,
+            min_items,
+            max_items,
+        })
+    }
+
+    fn compile_object(&mut self, members: &'a [(String, JsonValue)], pointer: &str) -> Result<ObjectSchema, Error> {
+        let mut properties = Vec::new();
+        match get(members, "properties") {
+            Some(JsonValue::Object(schemas)) => {
+                for (key, schema) in schemas.iter() {
+                    let property_pointer = format!("{}/properties/{}", pointer, key.replace("~", "~0").replace("/", "~1"));
+                    properties.push((key.clone(), self.compile(schema, &property_pointer)?));
+                }
+            }
+            Some(_) => bail!("Invalid json schema at '{}': 'properties' must be an object", pointer),
+            None => {}
+        }
+        let required = match get(members, "required") {
+            Some(JsonValue::Array(keys)) => keys
+                .iter()
+                .map(|key| match key {
+                    JsonValue::String(key) => Ok(key.clone()),
+                    _ => bail!("Invalid json schema at '{}': 'required' must only contain strings", pointer),
+                })
+                .collect::<Result<Vec<_>, Error>>()?,
+            Some(_) => bail!("Invalid json schema at '{}': 'required' must be an array", pointer),
+            None => Vec::new(),
+        };
+        let additional_properties = get(members, "additionalProperties") != Some(&JsonValue::Boolean(false));
+        Ok(ObjectSchema {
+            properties,
+            required,
+            additional_properties,
+        })
+    }
+}
+
+/// Schemas without a type keyword can still only constrain the types that their keywords apply to
+fn infer_type(members: &[(String, JsonValue)]) -> Option<JsonType> {
+    let has_any = |keys: &[&str]| keys.iter().any(|key| get(members, key).is_some());
+    if has_any(&["properties", "required", "additionalProperties"]) {
+        Some(JsonType::Object)
+    } else if has_any(&["items", "prefixItems", "minItems", "maxItems"]) {
+        Some(JsonType::Array)
+    } else if has_any(&["minLength", "maxLength", "pattern", "format"]) {
+        Some(JsonType::String)
+    } else if has_any(&["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf"]) {
+        Some(JsonType::Number)
+    } else {
+        None
+    }
+}
+
+fn compile_integer(members: &[(String, JsonValue)], pointer: &str) -> Result<IntegerSchema, Error> {
+    let min = get_bound(members, "minimum", "exclusiveMinimum", pointer, true)?.map(|(bound, exclusive)| {
+        if exclusive {
+            bound.floor() as i64 + 1
+        } else {
+            bound.ceil() as i64
+        }
+    });
+    let max = get_bound(members, "maximum", "exclusiveMaximum", pointer, false)?.map(|(bound, exclusive)| {
+        if exclusive {
+            bound.ceil() as i64 - 1
+        } else {
+            bound.floor() as i64
+        }
+    });
+    let multiple_of = match get_number(members, "multipleOf", pointer)? {
+        Some(multiple) if multiple <= 0.0 || multiple.fract() != 0.0 => bail!(
+            "Invalid json schema at '{}': 'multipleOf' must be a positive integer for integers",
+            pointer
+        ),
+        other => other.map(|multiple| multiple as i64),
+    };
+    let schema = IntegerSchema { min, max, multiple_of };
+    let (lo, hi) = schema.multiplier_range();
+    if lo > hi {
+        bail!("Invalid json schema at '{}': no integer satisfies the minimum, maximum, and multipleOf", pointer);
+    }
+    Ok(schema)
+}
+
+fn compile_number(members: &[(String, JsonValue)], pointer: &str) -> Result<NumberSchema, Error> {
+    let min = get_bound(members, "minimum", "exclusiveMinimum", pointer, true)?;
+    let max = get_bound(members, "maximum", "exclusiveMaximum", pointer, false)?;
+    let multiple_of = match get(members, "multipleOf") {
+        Some(JsonValue::Number(text)) => {
+            let multiple = text.parse::<f64>()?;
+            if multiple <= 0.0 {
+                bail!("Invalid json schema at '{}': 'multipleOf' must be positive", pointer);
+            }
+            // the shortest representation never uses an exponent, so it works for values like `1e-2` too
+            let shortest = multiple.to_string();
+            let decimals = shortest.find('.').map(|dot| shortest.len() - dot - 1).unwrap_or(0);
+            Some((multiple, decimals))
+        }
+        Some(_) => bail!("Invalid json schema at '{}': 'multipleOf' must be a number", pointer),
+        None => None,
+    };
+    if let (Some((lo, lo_exclusive)), Some((hi, hi_exclusive))) = (min, max) {
+        if lo > hi || (lo == hi && (lo_exclusive || hi_exclusive)) {
+            bail!("Invalid json schema at '{}': no number satisfies the minimum and maximum", pointer);
+        }
+    }
+    let schema = NumberSchema { min, max, multiple_of };
+    if schema.multiple_of.is_some() {
+        let (lo, hi) = schema.multiplier_range();
+        if lo > hi {
+            bail!("Invalid json schema at '{}': no number satisfies the minimum, maximum, and multipleOf", pointer);
+        }
+    }
+    Ok(schema)
+}
+
+fn compile_string(members: &[(String, JsonValue)], pointer: &str) -> Result<StringSchema, Error> {
+    let min_length = get_count(members, "minLength", pointer)?.unwrap_or(0);
+    let max_length = get_count(members, "maxLength", pointer)?;
+    if max_length.map(|max| max < min_length).unwrap_or(false) {
+        bail!("Invalid json schema at '{}': 'maxLength' is less than 'minLength'", pointer);
+    }
+    let pattern = match get(members, "pattern") {
+        Some(JsonValue::String(pattern)) => {
+            let hir = regex_syntax::parse(pattern)
+                .map_err(|e| format_err!("Invalid json schema at '{}': invalid pattern: {}", pointer, e))?;
+            let regex = Regex::new(pattern)?;
+            Some((hir, regex))
+        }
+        Some(_) => bail!("Invalid json schema at '{}': 'pattern' must be a string", pointer),
+        None => None,
+    };
+    let format = match get(members, "format") {
+        Some(JsonValue::String(name)) => parse_format(name)?,
+        _ => None,
+    };
+    Ok(StringSchema {
+        min_length,
+        max_length,
+        pattern,
+        format,
+    })
+}
+
+impl IntegerSchema {
+    fn range(&self) -> (i64, i64) {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => (min, max),
+            (Some(min), None) => (min, min.saturating_add(DEFAULT_NUMBER_RANGE)),
+            (None, Some(max)) => (max.saturating_sub(DEFAULT_NUMBER_RANGE), max),
+            (None, None) => (-DEFAULT_NUMBER_RANGE, DEFAULT_NUMBER_RANGE),
+        }
+    }
+
+    /// The range of values that get multiplied by multipleOf to produce valid instances
+    fn multiplier_range(&self) -> (i64, i64) {
+        let (min, max) = self.range();
+        match self.multiple_of {
+            Some(multiple) => (div_ceil(min, multiple), max.div_euclid(multiple)),
+            None => (min, max),
+        }
+    }
+}
+
+fn div_ceil(value: i64, divisor: i64) -> i64 {
+    let quotient = value.div_euclid(divisor);
+    if value.rem_euclid(divisor) == 0 {
+        quotient
+    } else {
+        quotient + 1
+    }
+}
+
+impl NumberSchema {
+    fn range(&self) -> (f64, f64) {
+        let default = DEFAULT_NUMBER_RANGE as f64;
+        match (self.min, self.max) {
+            (Some((min, _)), Some((max, _))) => (min, max),
+            (Some((min, _)), None) => (min, min + default),
+            (None, Some((max, _))) => (max - default, max),
+            (None, None) => (-default, default),
+        }
+    }
+
+    /// The range of values that get multiplied by multipleOf to produce valid instances. The bounds are checked against
+    /// the multiples as they're written, so that rounding can never produce a value on an exclusive bound
+    fn multiplier_range(&self) -> (i64, i64) {
+        let (min, max) = self.range();
+        let multiple = self.multiple_of.map(|(multiple, _)| multiple).unwrap_or(1.0);
+        let mut lo = (min / multiple).ceil() as i64;
+        if !self.is_valid(self.multiple_at(lo)) {
+            lo += 1;
+        }
+        let mut hi = (max / multiple).floor() as i64;
+        if !self.is_valid(self.multiple_at(hi)) {
+            hi -= 1;
+        }
+        (lo, hi)
+    }
+
+    /// The multiple at the given index, rounded to the number of decimal places that it's written with
+    fn multiple_at(&self, index: i64) -> f64 {
+        let (multiple, decimals) = self.multiple_of.unwrap_or((1.0, 0));
+        let scale = 10f64.powi(decimals as i32);
+        (index as f64 * multiple * scale).round() / scale
+    }
+
+    fn is_valid(&self, value: f64) -> bool {
+        let above_min = match self.min {
+            Some((min, true)) => value > min,
+            Some((min, false)) => value >= min,
+            None => true,
+        };
+        let below_max = match self.max {
+            Some((max, true)) => value < max,
+            Some((max, false)) => value <= max,
+            None => true,
+        };
+        above_min && below_max
+    }
+}
+
+/// Mostly ascii letters and digits, with some characters that need to be escaped in json and some that are outside
+/// the basic multilingual plane
+fn gen_char(ctx: &mut ProgramContext) -> char {
+    const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    if ctx.gen_chance(0.9) {
+        ctx.choose(ALPHANUMERIC) as char
+    } else {
+        ctx.choose(&[' ', '"', '\\', '/', '\n', '\t', '\u{1}', '\u{e9}', '\u{65e5}', '\u{1f600}'])
+    }
+}
+
+fn gen_chars(ctx: &mut ProgramContext, len: u64, out: &mut String) {
+    for _ in 0..len {
+        out.push(gen_char(ctx));
+    }
+}
+
+/// Generates a string that matches the regular expression. Character classes are restricted to their ascii
+/// characters when they have any, since json schema patterns use ecmascript syntax, where classes like `\d` and `\w`
+/// only match ascii characters
+fn gen_pattern(hir: &Hir, ctx: &mut ProgramContext, out: &mut String) -> Result<(), Error> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(literal) => out.push_str(std::str::from_utf8(&literal.0)?),
+        HirKind::Class(Class::Unicode(class)) => {
+            let ascii = class
+                .ranges()
+                .iter()
+                .filter(|range| range.start().is_ascii())
+                .map(|range| (range.start(), range.end().min('\u{7f}')))
+                .collect::<Vec<_>>();
+            let ranges = if ascii.is_empty() {
+                class.ranges().iter().map(|range| (range.start(), range.end())).collect()
+            } else {
+                ascii
+            };
+            if ranges.is_empty() {
+                bail!("The pattern contains a character class that can never match");
+            }
+            let (start, end) = ctx.choose(&ranges);
+            let c = ctx.gen_range_inclusive(start as u32, end as u32);
+            // ranges never include surrogates, but they may span them
+            out.push(std::char::from_u32(c).unwrap_or(start));
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            let ranges = class.ranges().iter().filter(|range| range.start().is_ascii()).collect::<Vec<_>>();
+            if ranges.is_empty() {
+                bail!("The pattern contains a character class that can never match");
+            }
+            let range = ctx.choose(&ranges);
+            out.push(ctx.gen_range_inclusive(range.start(), range.end().min(0x7F)) as char);
+        }
+        HirKind::Repetition(repetition) => {
+            let max = repetition.max.unwrap_or(repetition.min + DEFAULT_EXTRA_ITEMS as u32);
+            for _ in 0..ctx.gen_range_inclusive(repetition.min, max) {
+                gen_pattern(&repetition.sub, ctx, out)?;
+            }
+        }
+        HirKind::Capture(capture) => gen_pattern(&capture.sub, ctx, out)?,
+        HirKind::Concat(hirs) => {
+            for hir in hirs.iter() {
+                gen_pattern(hir, ctx, out)?;
+            }
+        }
+        HirKind::Alternation(hirs) => {
+            let index = ctx.gen_range_exclusive(0, hirs.len());
+            gen_pattern(&hirs[index], ctx, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn gen_any(ctx: &mut ProgramContext, out: &mut String) {
+    match ctx.gen_range_inclusive(0, 3) {
+        0 => out.push_str("null"),
+        1 => out.push_str(ctx.choose(&["true", "false"])),
+        2 => {
+            let _ = write!(out, "{}", ctx.gen_range_inclusive(-DEFAULT_NUMBER_RANGE, DEFAULT_NUMBER_RANGE));
+        }
+        _ => {
+            let mut string = String::new();
+            let len = ctx.gen_range_inclusive(0, DEFAULT_EXTRA_LENGTH);
+            gen_chars(ctx, len, &mut string);
+            write_json_string(&string, out);
+        }
+    }
+}
+
+fn gen_lowercase(ctx: &mut ProgramContext, len: u64, out: &mut String) {
+    for _ in 0..len {
+        out.push(ctx.gen_range_inclusive(b'a', b'z') as char);
+    }
+}
+
+/// Writes a full-date, as defined by RFC 3339. Days are limited to 28 so that every month is valid
+fn gen_date(ctx: &mut ProgramContext, out: &mut String) {
+    let year = ctx.gen_range_inclusive(1970, 2099);
+    let month = ctx.gen_range_inclusive(1, 12);
+    let day = ctx.gen_range_inclusive(1, 28);
+    let _ = write!(out, "{:04}-{:02}-{:02}", year, month, day);
+}
+
+impl Schema {
+    fn children(&self, id: NodeId) -> Vec<NodeId> {
+        match self.nodes[id].kind {
+            Kind::Choice(ref choices) => choices.clone(),
+            Kind::Ref(target) => vec![target],
+            Kind::Array(ref array) => array.prefix_items.iter().cloned().chain(array.items).collect(),
+            Kind::Object(ref object) => object.properties.iter().map(|(_, id)| *id).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn wrong_types(&self, id: NodeId) -> Vec<JsonType> {
+        let types = &self.nodes[id].types;
+        if types.is_empty() {
+            return Vec::new();
+        }
+        ALL_TYPES
+            .iter()
+            .cloned()
+            .filter(|candidate| !types.iter().any(|allowed| candidate.is_valid_as(*allowed)))
+            .collect()
+    }
+
+    /// The violations that a node can produce by itself, without any of its children
+    fn own_violations(&self, id: NodeId) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if !self.wrong_types(id).is_empty() {
+            violations.push(Violation::WrongType);
+        }
+        match self.nodes[id].kind {
+            Kind::Never => violations.push(Violation::WrongType),
+            Kind::Enum(_) => violations.push(Violation::NotInEnum),
+            Kind::Integer(ref integer) => {
+                if integer.min.is_some() {
+                    violations.push(Violation::BelowMinimum);
+                }
+                if integer.max.is_some() {
+                    violations.push(Violation::AboveMaximum);
+                }
+                if integer.multiple_of.map(|multiple| multiple > 1).unwrap_or(false) {
+                    violations.push(Violation::NotMultiple);
+                }
+            }
+            Kind::Number(ref number) => {
+                if number.min.is_some() {
+                    violations.push(Violation::BelowMinimum);
+                }
+                if number.max.is_some() {
+                    violations.push(Violation::AboveMaximum);
+                }
+                if number.multiple_of.is_some() {
+                    violations.push(Violation::NotMultiple);
+                }
+            }
+            Kind::String(ref string) => {
+                if string.min_length > 0 {
+                    violations.push(Violation::TooShort);
+                }
+                if string.max_length.is_some() {
+                    violations.push(Violation::TooLong);
+                }
+                if string.pattern.is_some() {
+                    violations.push(Violation::PatternMismatch);
+                }
+            }
+            Kind::Array(ref array) => {
+                if array.min_items > 0 {
+                    violations.push(Violation::TooFewItems);
+                }
+                if array.max_items.is_some() {
+                    violations.push(Violation::TooManyItems);
+                }
+            }
+            Kind::Object(ref object) => {
+                if !object.required.is_empty() {
+                    violations.push(Violation::MissingRequired);
+                }
+                if !object.additional_properties {
+                    violations.push(Violation::AdditionalProperty);
+                }
+            }
+            _ => {}
+        }
+        violations
+    }
+
+    /// Whether each node can produce an invalid instance, either by itself or through one of its children. Any of
+    /// the alternatives of anyOf might accept an instance that violates another one, so they never violate. The same
+    /// goes for oneOf, where a violating instance might also match none or several of the alternatives
+    fn find_violations(&mut self) {
+        let mut can_violate = (0..self.nodes.len())
+            .map(|id| !self.own_violations(id).is_empty())
+            .collect::<Vec<_>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in 0..self.nodes.len() {
+                if !can_violate[id] && !self.is_any_of(id) && self.children(id).iter().any(|child| can_violate[*child]) {
+                    can_violate[id] = true;
+                    changed = true;
+                }
+            }
+        }
+        self.can_violate = can_violate;
+    }
+
+    /// Whether the node is a choice between the alternatives of anyOf or oneOf, rather than one per type
+    fn is_any_of(&self, id: NodeId) -> bool {
+        match self.nodes[id].kind {
+            Kind::Choice(_) => self.nodes[id].types.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn violations(&self, id: NodeId) -> Vec<Violation> {
+        let mut violations = self.own_violations(id);
+        if !self.is_any_of(id) && self.children(id).iter().any(|child| self.can_violate[*child]) {
+            violations.push(Violation::Child);
+        }
+        violations
+    }
+}
+
+/// Tracks how deeply nested the instance that's being generated is. It's shared by all of the functions of a compiled
+/// schema, so that recursive schemas stop adding optional values past some depth
+#[derive(Debug, Clone, Default)]
+struct Depth(Rc<Cell<usize>>);
+
+impl Depth {
+    fn is_past_soft_limit(&self) -> bool {
+        self.0.get() > SOFT_DEPTH_LIMIT
+    }
+
+    /// Calls the function one level deeper, or returns an error if that would be past the max depth
+    fn nested<R, F: FnOnce() -> Result<R, Error>>(&self, fun: F) -> Result<R, Error> {
+        let depth = self.0.get();
+        if depth >= MAX_DEPTH {
+            bail!("The json schema is too deeply recursive to generate an instance");
+        }
+        self.0.set(depth + 1);
+        let result = fun();
+        self.0.set(depth);
+        result
+    }
+}
+
+/// The functions that generate instances of a subschema
+#[derive(Debug, Clone)]
+struct Generators {
+    valid: DynStringFun,
+    /// generates instances that break exactly one constraint, or is None if the subschema can't be violated
+    violating: Option<DynStringFun>,
+    /// whether the subschema is `false`, which no instance is valid for
+    is_never: bool,
+}
+
+impl Generators {
+    fn get(&self, violate: bool) -> &DynStringFun {
+        match self.violating {
+            Some(ref violating) if violate => violating,
+            _ => &self.valid,
+        }
+    }
+}
+
+fn const_json(json: &str) -> Result<DynStringFun, Error> {
+    ConstString::new(json).require_string()
+}
+
+/// Randomly selects one of the functions, using the select builtin
+fn select(mut functions: Vec<DynStringFun>) -> Result<DynStringFun, Error> {
+    if functions.len() == 1 {
+        return Ok(functions.remove(0));
+    }
+    call_builtin(SELECT_STRING_BUILTIN, functions.into_iter().map(AnyFunction::String).collect())
+}
+
+#[derive(Debug)]
+struct AnyInstance;
+
+impl RunnableFunction<IString> for AnyInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let mut value = String::new();
+        gen_any(ctx, &mut value);
+        Ok(value.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct NoInstance;
+
+impl RunnableFunction<IString> for NoInstance {
+    fn gen_value(&self, _: &mut ProgramContext) -> Result<IString, Error> {
+        bail!("The json schema does not allow any value, so no instance can be generated")
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Generates the instances of the subschema that a `$ref` points to. The target is only set once the whole schema has
+/// been compiled, since references may be recursive, and it's weak so that recursive schemas aren't reference cycles
+#[derive(Debug)]
+struct SchemaRef {
+    target: RefCell<Option<Weak<dyn RunnableFunction<IString>>>>,
+    depth: Depth,
+}
+
+impl RunnableFunction<IString> for SchemaRef {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let target = self
+            .target
+            .borrow()
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| format_err!("The json schema was dropped while it was still in use"))?;
+        self.depth.nested(|| target.gen_value(ctx))
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct IntegerInstance {
+    schema: Rc<IntegerSchema>,
+    violation: Option<Violation>,
+}
+
+impl RunnableFunction<IString> for IntegerInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let integer = &self.schema;
+        let value = match self.violation {
+            Some(Violation::BelowMinimum) => {
+                let min = integer.min.unwrap_or(0);
+                min.saturating_sub(ctx.gen_range_inclusive(1, 10))
+            }
+            Some(Violation::AboveMaximum) => {
+                let max = integer.max.unwrap_or(0);
+                max.saturating_add(ctx.gen_range_inclusive(1, 10))
+            }
+            _ => {
+                let (lo, hi) = integer.multiplier_range();
+                let value = ctx.gen_range_inclusive(lo, hi);
+                let multiple = integer.multiple_of.unwrap_or(1);
+                if self.violation == Some(Violation::NotMultiple) {
+                    value.saturating_mul(multiple).saturating_add(ctx.gen_range_exclusive(1, multiple))
+                } else {
+                    value.saturating_mul(multiple)
+                }
+            }
+        };
+        Ok(value.to_string().into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct NumberInstance {
+    schema: Rc<NumberSchema>,
+    violation: Option<Violation>,
+}
+
+impl RunnableFunction<IString> for NumberInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let number = &self.schema;
+        let value = match self.violation {
+            Some(Violation::BelowMinimum) => {
+                let (min, exclusive) = number.min.unwrap_or((0.0, false));
+                let value = if exclusive { min } else { min - ctx.gen_range_inclusive(0.5, 10.0) };
+                value.to_string()
+            }
+            Some(Violation::AboveMaximum) => {
+                let (max, exclusive) = number.max.unwrap_or((0.0, false));
+                let value = if exclusive { max } else { max + ctx.gen_range_inclusive(0.5, 10.0) };
+                value.to_string()
+            }
+            _ => match number.multiple_of {
+                Some((multiple, decimals)) => {
+                    let (lo, hi) = number.multiplier_range();
+                    let value = number.multiple_at(ctx.gen_range_inclusive(lo, hi));
+                    if self.violation == Some(Violation::NotMultiple) {
+                        // half way between two multiples is never a multiple, and needs one more decimal place
+                        let above = value + multiple / 2.0;
+                        let value = if number.is_valid(above) { above } else { value - multiple / 2.0 };
+                        format!("{:.*}", decimals + 1, value)
+                    } else {
+                        format!("{:.*}", decimals, value)
+                    }
+                }
+                None => {
+                    let (lo, hi) = number.range();
+                    let mut value = ctx.gen_range_inclusive(lo, hi);
+                    if ctx.gen_chance(0.3) {
+                        value = value.round();
+                    }
+                    // the only values that can be invalid are exclusive bounds, and the middle of the range never is
+                    if !number.is_valid(value) {
+                        value = lo + (hi - lo) / 2.0;
+                    }
+                    value.to_string()
+                }
+            },
+        };
+        Ok(value.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct StringInstance {
+    schema: Rc<StringSchema>,
+    violation: Option<Violation>,
+}
+
+impl RunnableFunction<IString> for StringInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let string = &self.schema;
+        let max_length = string.max_length.unwrap_or(string.min_length + DEFAULT_EXTRA_LENGTH);
+        let mut value = String::new();
+        match self.violation {
+            Some(Violation::TooShort) => {
+                let len = ctx.gen_range_exclusive(0, string.min_length);
+                gen_chars(ctx, len, &mut value);
+            }
+            Some(Violation::TooLong) => {
+                let len = ctx.gen_range_inclusive(max_length + 1, max_length + DEFAULT_EXTRA_LENGTH);
+                gen_chars(ctx, len, &mut value);
+            }
+            Some(Violation::PatternMismatch) => {
+                let regex = &string.pattern.as_ref().unwrap().1;
+                for _ in 0..MAX_PATTERN_ATTEMPTS {
+                    value.clear();
+                    let len = ctx.gen_range_inclusive(string.min_length, max_length);
+                    gen_chars(ctx, len, &mut value);
+                    if !regex.is_match(&value) {
+                        break;
+                    }
+                }
+                if regex.is_match(&value) {
+                    bail!("Failed to generate a string that does not match the pattern '{}'", regex.as_str());
+                }
+            }
+            _ => {
+                if let Some((ref hir, ref regex)) = string.pattern {
+                    let mut attempts = 0;
+                    loop {
+                        value.clear();
+                        gen_pattern(hir, ctx, &mut value)?;
+                        let len = value.chars().count() as u64;
+                        if len >= string.min_length && len <= max_length {
+                            break;
+                        }
+                        attempts += 1;
+                        if attempts >= MAX_PATTERN_ATTEMPTS {
+                            bail!(
+                                "Failed to generate a string that matches the pattern '{}' with a length between {} and {}",
+                                regex.as_str(),
+                                string.min_length,
+                                max_length
+                            );
+                        }
+                    }
+                } else {
+                    match string.format {
+                        Some(Format::Date) => gen_date(ctx, &mut value),
+                        Some(Format::DateTime) => {
+                            gen_date(ctx, &mut value);
+                            let (hour, minute, second) = (ctx.gen_range_exclusive(0, 24), ctx.gen_range_exclusive(0, 60), ctx.gen_range_exclusive(0, 60));
+                            let _ = write!(value, "T{:02}:{:02}:{:02}Z", hour, minute, second);
+                        }
+                        Some(Format::Email) => {
+                            let local_len = ctx.gen_range_inclusive(1, 10);
+                            let domain_len = ctx.gen_range_inclusive(1, 10);
+                            gen_lowercase(ctx, local_len, &mut value);
+                            value.push('@');
+                            gen_lowercase(ctx, domain_len, &mut value);
+                            value.push_str(ctx.choose(&[".com", ".org", ".net", ".example"]));
+                        }
+                        Some(Format::Function(ref fun)) => value.push_str(&fun.gen_value(ctx)?),
+                        None => {
+                            let len = ctx.gen_range_inclusive(string.min_length, max_length);
+                            gen_chars(ctx, len, &mut value);
+                        }
+                    }
+                }
+            }
+        }
+        let mut out = String::new();
+        write_json_string(&value, &mut out);
+        Ok(out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct ArrayInstance {
+    prefix_items: Vec<Generators>,
+    items: Option<Generators>,
+    min_items: u64,
+    max_items: Option<u64>,
+    violation: Option<Violation>,
+    depth: Depth,
+}
+
+impl RunnableFunction<IString> for ArrayInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let item_at = |i: usize| self.prefix_items.get(i).or(self.items.as_ref());
+        // items after the prefix can't be generated if the schema for them is false
+        let items_allowed = self.items.as_ref().map(|items| !items.is_never).unwrap_or(true);
+        let mut max_items = self.max_items.unwrap_or(self.min_items + DEFAULT_EXTRA_ITEMS);
+        if !items_allowed {
+            max_items = max_items.min(self.prefix_items.len() as u64);
+        }
+        let mut count = if self.depth.is_past_soft_limit() {
+            self.min_items
+        } else {
+            ctx.gen_range_inclusive(self.min_items, max_items.max(self.min_items))
+        };
+        let mut violating_index = None;
+        match self.violation {
+            Some(Violation::TooFewItems) => count = ctx.gen_range_exclusive(0, self.min_items),
+            Some(Violation::TooManyItems) => {
+                let max = self.max_items.unwrap_or(0);
+                count = ctx.gen_range_inclusive(max + 1, max + DEFAULT_EXTRA_ITEMS);
+            }
+            Some(Violation::Child) => {
+                let mut candidates = (0..self.prefix_items.len())
+                    .filter(|i| self.prefix_items[*i].violating.is_some())
+                    .collect::<Vec<_>>();
+                if self.items.as_ref().map(|items| items.violating.is_some()).unwrap_or(false) {
+                    candidates.push(self.prefix_items.len() + ctx.gen_range_inclusive(0, count as usize));
+                }
+                let index = ctx.choose(&candidates);
+                count = count.max(index as u64 + 1);
+                violating_index = Some(index);
+            }
+            _ => {}
+        }
+
+        let mut out = String::from("[");
+        for i in 0..count as usize {
+            if i > 0 {
+                out.push(',');
+            }
+            match item_at(i) {
+                Some(item) => {
+                    let fun = item.get(violating_index == Some(i));
+                    out.push_str(&self.depth.nested(|| fun.gen_value(ctx))?);
+                }
+                None => gen_any(ctx, &mut out),
+            }
+        }
+        out.push(']');
+        Ok(out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct ObjectInstance {
+    properties: Vec<(String, Generators)>,
+    required: Vec<String>,
+    violation: Option<Violation>,
+    depth: Depth,
+}
+
+impl RunnableFunction<IString> for ObjectInstance {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let missing = match self.violation {
+            Some(Violation::MissingRequired) => Some(ctx.choose(&self.required.iter().collect::<Vec<_>>())),
+            _ => None,
+        };
+        let violating_property = match self.violation {
+            Some(Violation::Child) => {
+                let candidates = self
+                    .properties
+                    .iter()
+                    .filter(|(_, property)| property.violating.is_some())
+                    .map(|(key, _)| key)
+                    .collect::<Vec<_>>();
+                Some(ctx.choose(&candidates))
+            }
+            _ => None,
+        };
+
+        let mut out = String::from("{");
+        let mut first = true;
+        let mut write_key = |key: &str, out: &mut String| {
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            write_json_string(key, out);
+            out.push(':');
+        };
+        for (key, property) in self.properties.iter() {
+            let is_required = self.required.contains(key);
+            let is_violating = violating_property == Some(key);
+            let include = if missing == Some(key) {
+                false
+            } else if is_required || is_violating {
+                true
+            } else {
+                !property.is_never && !self.depth.is_past_soft_limit() && ctx.gen_chance(0.5)
+            };
+            if include {
+                write_key(key, &mut out);
+                let fun = property.get(is_violating);
+                out.push_str(&self.depth.nested(|| fun.gen_value(ctx))?);
+            }
+        }
+        // required properties that don't have a schema can have any value
+        for key in self.required.iter() {
+            if missing != Some(key) && !self.properties.iter().any(|(property, _)| property == key) {
+                write_key(key, &mut out);
+                gen_any(ctx, &mut out);
+            }
+        }
+        if self.violation == Some(Violation::AdditionalProperty) {
+            let mut key = String::from("additional_property");
+            while self.properties.iter().any(|(property, _)| *property == key) {
+                key.push('_');
+            }
+            write_key(&key, &mut out);
+            gen_any(ctx, &mut out);
+        }
+        out.push('}');
+        Ok(out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Builds the functions for each node of a schema. Every node gets a function that generates valid instances, and
+/// nodes that can be violated also get one that randomly selects one of their violations
+struct Builder<'a> {
+    schema: &'a Schema,
+    built: Vec<Option<Generators>>,
+    /// references whose targets still need to be set, and whether they point to the violating function
+    unresolved: Vec<(Rc<SchemaRef>, NodeId, bool)>,
+    depth: Depth,
+}
+
+impl<'a> Builder<'a> {
+    fn build(&mut self, id: NodeId) -> Result<Generators, Error> {
+        if let Some(ref generators) = self.built[id] {
+            return Ok(generators.clone());
+        }
+        let valid = self.build_function(id, None)?;
+        let violations = self.schema.violations(id);
+        let violating = if violations.is_empty() {
+            None
+        } else {
+            let mut functions = Vec::with_capacity(violations.len());
+            for violation in violations {
+                functions.push(self.build_function(id, Some(violation))?);
+            }
+            Some(select(functions)?)
+        };
+        let generators = Generators {
+            valid,
+            violating,
+            is_never: matches!(self.schema.nodes[id].kind, Kind::Never),
+        };
+        self.built[id] = Some(generators.clone());
+        Ok(generators)
+    }
+
+    fn build_wrong_type(&self, id: NodeId) -> Result<DynStringFun, Error> {
+        let mut wrong_types = self.schema.wrong_types(id);
+        if wrong_types.is_empty() {
+            wrong_types.push(JsonType::Null);
+        }
+        let examples = wrong_types
+            .iter()
+            .map(|json_type| const_json(json_type.example()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        select(examples)
+    }
+
+    /// Builds the function for a single violation of the node, or for valid instances if there's no violation
+    fn build_function(&mut self, id: NodeId, violation: Option<Violation>) -> Result<DynStringFun, Error> {
+        if violation == Some(Violation::WrongType) {
+            return self.build_wrong_type(id);
+        }
+        let schema = self.schema;
+        let function: DynStringFun = match schema.nodes[id].kind {
+            Kind::Any => Rc::new(AnyInstance),
+            Kind::Never => Rc::new(NoInstance),
+            Kind::Enum(ref values) => {
+                if violation.is_some() {
+                    let candidates = ["\"not an enum value\"", "-987654321", "null", "{}"];
+                    let value = candidates
+                        .iter()
+                        .find(|candidate| !values.contains(&JsonValue::parse(candidate).unwrap_or(JsonValue::Null)))
+                        .unwrap_or(&"[]");
+                    const_json(value)?
+                } else {
+                    let values = values
+                        .iter()
+                        .map(|value| const_json(&value.to_string()))
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    select(values)?
+                }
+            }
+            Kind::Null => const_json("null")?,
+            Kind::Boolean => select(vec![const_json("true")?, const_json("false")?])?,
+            Kind::Integer(ref integer) => Rc::new(IntegerInstance {
+                schema: Rc::new(integer.clone()),
+                violation,
+            }),
+            Kind::Number(ref number) => Rc::new(NumberInstance {
+                schema: Rc::new(number.clone()),
+                violation,
+            }),
+            Kind::String(ref string) => Rc::new(StringInstance {
+                schema: Rc::new(string.clone()),
+                violation,
+            }),
+            Kind::Array(ref array) => {
+                let mut prefix_items = Vec::with_capacity(array.prefix_items.len());
+                for item in array.prefix_items.iter() {
+                    prefix_items.push(self.build(*item)?);
+                }
+                let items = match array.items {
+                    Some(items) => Some(self.build(items)?),
+                    None => None,
+                };
+                Rc::new(ArrayInstance {
+                    prefix_items,
+                    items,
+                    min_items: array.min_items,
+                    max_items: array.max_items,
+                    violation,
+                    depth: self.depth.clone(),
+                })
+            }
+            Kind::Object(ref object) => {
+                let mut properties = Vec::with_capacity(object.properties.len());
+                for (key, property) in object.properties.iter() {
+                    properties.push((key.clone(), self.build(*property)?));
+                }
+                Rc::new(ObjectInstance {
+                    properties,
+                    required: object.required.clone(),
+                    violation,
+                    depth: self.depth.clone(),
+                })
+            }
+            Kind::Choice(ref choices) => {
+                let mut functions = Vec::with_capacity(choices.len());
+                for choice in choices.iter() {
+                    let generators = self.build(*choice)?;
+                    match generators.violating {
+                        Some(violating) if violation.is_some() => functions.push(violating),
+                        _ if violation.is_some() => {}
+                        _ => functions.push(generators.valid),
+                    }
+                }
+                select(functions)?
+            }
+            Kind::Ref(target) => {
+                let reference = Rc::new(SchemaRef {
+                    target: RefCell::new(None),
+                    depth: self.depth.clone(),
+                });
+                self.unresolved.push((reference.clone(), target, violation.is_some()));
+                reference
+            }
+        };
+        Ok(function)
+    }
+}
+
+/// A json schema that has been compiled into functions. Every function is kept here, since `$ref`s only hold weak
+/// references to their targets
+#[derive(Debug)]
+struct CompiledSchema {
+    root: Generators,
+    #[allow(dead_code)]
+    functions: Vec<Generators>,
+}
+
+fn build_schema(schema: &Schema) -> Result<CompiledSchema, Error> {
+    let mut builder = Builder {
+        schema,
+        built: vec![None; schema.nodes.len()],
+        unresolved: Vec::new(),
+        depth: Depth::default(),
+    };
+    let root = builder.build(schema.root)?;
+    while let Some((reference, target, violate)) = builder.unresolved.pop() {
+        let generators = builder.build(target)?;
+        *reference.target.borrow_mut() = Some(Rc::downgrade(generators.get(violate)));
+    }
+    Ok(CompiledSchema {
+        root,
+        functions: builder.built.into_iter().flatten().collect(),
+    })
+}
+
+fn compile_document(document: &JsonValue, pointer: &str) -> Result<CompiledSchema, Error> {
+    let mut compiler = Compiler {
+        root: document,
+        nodes: Vec::new(),
+        refs: HashMap::new(),
+    };
+    let root = compiler.compile_ref(&JsonValue::String(format!("#{}", pointer)), pointer)?;
+    let mut schema = Schema {
+        nodes: compiler.nodes,
+        root,
+        can_violate: Vec::new(),
+    };
+    schema.find_violations();
+    build_schema(&schema)
+}
+
+fn compile_schema(path: &str, pointer: &str) -> Result<CompiledSchema, Error> {
+    let text = fs::read_to_string(path).map_err(|e| format_err!("Failed to read json schema '{}': {}", path, e))?;
+    let document =
+        JsonValue::parse(&text).map_err(|e| format_err!("Failed to parse json schema '{}': {}", path, e))?;
+    compile_document(&document, pointer)
+}
+
+#[derive(Debug)]
+struct FromSchema {
+    violation_rate: Option<DynDecimalFun>,
+    path: DynStringFun,
+    pointer: Option<DynStringFun>,
+    schemas: RefCell<HashMap<(IString, IString), Rc<CompiledSchema>>>,
+}
+
+impl FromSchema {
+    fn get_schema(&self, path: IString, pointer: IString) -> Result<Rc<CompiledSchema>, Error> {
+        let mut schemas = self.schemas.borrow_mut();
+        let key = (path, pointer);
+        if let Some(schema) = schemas.get(&key) {
+            return Ok(schema.clone());
+        }
+        let schema = Rc::new(compile_schema(&key.0, &key.1)?);
+        schemas.insert(key, schema.clone());
+        Ok(schema)
+    }
+}
+
+impl RunnableFunction<IString> for FromSchema {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let rate = match self.violation_rate.as_ref() {
+            Some(fun) => fun.gen_value(ctx)?,
+            None => 0.0,
+        };
+        let path = self.path.gen_value(ctx)?;
+        let pointer = match self.pointer.as_ref() {
+            Some(fun) => fun.gen_value(ctx)?,
+            None => IString::from(""),
+        };
+        let schema = self.get_schema(path, pointer)?;
+        let violate = schema.root.violating.is_some() && ctx.gen_chance(rate);
+        schema.root.get(violate).gen_value(ctx)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+fn new_from_schema(
+    violation_rate: Option<DynDecimalFun>,
+    path: DynStringFun,
+    pointer: Option<DynStringFun>,
+) -> CreateFunctionResult {
+    Ok(AnyFunction::String(Rc::new(FromSchema {
+        violation_rate,
+        path,
+        pointer,
+        schemas: RefCell::new(HashMap::new()),
+    })))
+}
+
+const VIOLATION_RATE_PARAM: &str = "violation_rate";
+const PATH_PARAM: &str = "schema_path";
+const POINTER_PARAM: &str = "pointer";
+
+fn create_from_schema(args: Arguments) -> CreateFunctionResult {
+    let path = args.required_arg(PATH_PARAM, 0, AnyFunction::require_string)?;
+    new_from_schema(None, path, None)
+}
+
+pub const FROM_SCHEMA_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "from_schema",
+    description: "generates json instances of the JSON Schema in the given file. Supports type, enum, const, anyOf, oneOf where each alternative allows different types, numeric bounds and multipleOf, string lengths, pattern, and the date, date-time, email, uuid, ipv4, and ipv6 formats, items, prefixItems, minItems, maxItems, properties, required, additionalProperties, and $refs within the same file. Schemas that use any other keywords that constrain instances, such as allOf or not, return an error",
+    arguments: &[(PATH_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_from_schema,
+};
+
+fn create_from_schema_pointer(args: Arguments) -> CreateFunctionResult {
+    let (path, pointer) =
+        args.require_2_args(PATH_PARAM, AnyFunction::require_string, POINTER_PARAM, AnyFunction::require_string)?;
+    new_from_schema(None, path, Some(pointer))
+}
+
+pub const FROM_SCHEMA_POINTER_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "from_schema",
+    description: "generates json instances of the subschema at the json pointer, for example '/$defs/address', within the JSON Schema in the given file",
+    arguments: &[(PATH_PARAM, GenType::String), (POINTER_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_from_schema_pointer,
+};
+
+fn create_from_schema_violating(args: Arguments) -> CreateFunctionResult {
+    let (violation_rate, path) = args.require_2_args(
+        VIOLATION_RATE_PARAM,
+        AnyFunction::require_decimal,
+        PATH_PARAM,
+        AnyFunction::require_string,
+    )?;
+    new_from_schema(Some(violation_rate), path, None)
+}
+
+pub const FROM_SCHEMA_VIOLATING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "from_schema",
+    description: "generates json instances of the JSON Schema in the given file, where each instance violates the schema with a probability of violation_rate. A violating instance is still valid json, but breaks exactly one constraint, such as a wrong type, an out of range number, a missing required property, or an invalid nested value",
+    arguments: &[(VIOLATION_RATE_PARAM, GenType::Decimal), (PATH_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_from_schema_violating,
+};
+
+fn create_from_schema_pointer_violating(args: Arguments) -> CreateFunctionResult {
+    let (violation_rate, path, pointer) = args.require_3_args(
+        VIOLATION_RATE_PARAM,
+        AnyFunction::require_decimal,
+        PATH_PARAM,
+        AnyFunction::require_string,
+        POINTER_PARAM,
+        AnyFunction::require_string,
+    )?;
+    new_from_schema(Some(violation_rate), path, Some(pointer))
+}
+
+pub const FROM_SCHEMA_POINTER_VIOLATING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "from_schema",
+    description: "generates json instances of the subschema at the json pointer within the JSON Schema in the given file, where each instance violates the subschema with a probability of violation_rate",
+    arguments: &[
+        (VIOLATION_RATE_PARAM, GenType::Decimal),
+        (PATH_PARAM, GenType::String),
+        (POINTER_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_from_schema_pointer_violating,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_runtime_error, run_program};
+
+    const PERSON_SCHEMA: &str = "test-data/person-schema.json";
+
+    lazy_static! {
+        static ref UUID: Regex = Regex::new("^[0-9a-f]{8}(-[0-9a-f]{4}){3}-[0-9a-f]{12}$").unwrap();
+        static ref CODE: Regex = Regex::new("^[A-Z]{3}-[0-9]{4}$").unwrap();
+        static ref EMAIL: Regex = Regex::new("^[a-z]+@[a-z]+\\.[a-z]+$").unwrap();
+    }
+
+    fn run_instances(program: &str) -> Vec<JsonValue> {
+        // a nul character can never appear unescaped in json, so it's safe to use as a separator
+        let output = run_program(200, &format!("concat({}, \"\\u{{0}}\")", program)).expect("failed to run program");
+        let output = String::from_utf8(output).unwrap();
+        output
+            .split_terminator('\u{0}')
+            .map(|instance| {
+                JsonValue::parse(instance).unwrap_or_else(|e| panic!("invalid json: {}, instance: '{}'", e, instance))
+            })
+            .collect()
+    }
+
+    fn check_string(value: Option<&JsonValue>, is_valid: impl Fn(&str) -> bool) -> Result<(), String> {
+        match value {
+            Some(JsonValue::String(string)) if is_valid(string) => Ok(()),
+            other => Err(format!("invalid string: {:?}", other)),
+        }
+    }
+
+    fn check_address(value: &JsonValue) -> Result<(), String> {
+        let members = match value {
+            JsonValue::Object(members) => members,
+            other => return Err(format!("address is not an object: {}", other)),
+        };
+        check_string(get(members, "street"), |_| true)?;
+        check_string(get(members, "zip"), |zip| {
+            zip.len() == 5 && zip.bytes().all(|b| b.is_ascii_digit())
+        })
+    }
+
+    /// Checks an instance against every constraint in the person schema
+    fn check_person(value: &JsonValue) -> Result<(), String> {
+        let members = match value {
+            JsonValue::Object(members) => members,
+            other => return Err(format!("not an object: {}", other)),
+        };
+        for (key, value) in members.iter() {
+            let result = match key.as_str() {
+                "id" => check_string(Some(value), |id| UUID.is_match(id)),
+                "name" => check_string(Some(value), |name| (1..=20).contains(&name.chars().count())),
+                "age" => match value {
+                    JsonValue::Number(age) if age.parse::<i64>().map(|age| (0..150).contains(&age)).unwrap_or(false) => Ok(()),
+                    other => Err(format!("invalid age: {}", other)),
+                },
+                "score" => match value {
+                    JsonValue::Number(score) if (0.0..=1.0).contains(&score.parse::<f64>().unwrap()) => Ok(()),
+                    other => Err(format!("invalid score: {}", other)),
+                },
+                "code" => check_string(Some(value), |code| CODE.is_match(code)),
+                "role" => check_string(Some(value), |role| ["admin", "user", "guest"].contains(&role)),
+                "email" => match value {
+                    JsonValue::Null => Ok(()),
+                    _ => check_string(Some(value), |email| EMAIL.is_match(email)),
+                },
+                "tags" => match value {
+                    JsonValue::Array(tags) if (1..=3).contains(&tags.len()) => tags
+                        .iter()
+                        .try_for_each(|tag| check_string(Some(tag), |tag| tag.chars().count() <= 8)),
+                    other => Err(format!("invalid tags: {}", other)),
+                },
+                "address" => check_address(value),
+                "manager" => check_person(value),
+                other => Err(format!("additional property: {}", other)),
+            };
+            result.map_err(|e| format!("{}: {}", key, e))?;
+        }
+        for key in ["id", "name", "age", "code", "role", "tags", "address"].iter() {
+            if get(members, key).is_none() {
+                return Err(format!("missing required property: {}", key));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn instances_are_valid_for_the_schema() {
+        let instances = run_instances(&format!("from_schema(\"{}\")", PERSON_SCHEMA));
+        assert_eq!(200, instances.len());
+        for instance in instances.iter() {
+            if let Err(message) = check_person(instance) {
+                panic!("invalid instance: {}, {}", message, instance);
+            }
+        }
+        assert!(instances.iter().any(|instance| match instance {
+            JsonValue::Object(members) => get(members, "manager").is_some(),
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn violating_instances_break_the_schema() {
+        let instances = run_instances(&format!("from_schema(1.0, \"{}\")", PERSON_SCHEMA));
+        for instance in instances.iter() {
+            assert!(check_person(instance).is_err(), "instance should be invalid: {}", instance);
+        }
+        let instances = run_instances(&format!("from_schema(0.5, \"{}\")", PERSON_SCHEMA));
+        let invalid = instances.iter().filter(|instance| check_person(instance).is_err()).count();
+        assert!(invalid > 50 && invalid < 150, "invalid count: {}", invalid);
+    }
+
+    #[test]
+    fn instances_of_a_subschema_are_generated_using_a_pointer() {
+        let instances = run_instances(&format!("from_schema(\"{}\", \"/$defs/address\")", PERSON_SCHEMA));
+        for instance in instances.iter() {
+            check_address(instance).unwrap();
+        }
+        let instances = run_instances(&format!("from_schema(1.0, \"{}\", \"/$defs/address\")", PERSON_SCHEMA));
+        for instance in instances.iter() {
+            assert!(check_address(instance).is_err(), "instance should be invalid: {}", instance);
+        }
+    }
+
+    #[test]
+    fn invalid_references_return_errors() {
+        assert_runtime_error(
+            &format!("from_schema(\"{}\", \"/$defs/missing\")", PERSON_SCHEMA),
+            "Invalid $ref '#/$defs/missing': the path does not exist in the schema",
+        );
+        assert_runtime_error("from_schema(\"test-data/missing.json\")", "Failed to read json schema");
+        assert_runtime_error("from_schema(\"test-data/simple-words.txt\")", "Failed to parse json schema");
+    }
+
+    fn compile_inline(schema: &str) -> Result<CompiledSchema, Error> {
+        compile_document(&JsonValue::parse(schema)?, "")
+    }
+
+    fn generate(schema: &CompiledSchema, violate: bool, ctx: &mut ProgramContext) -> JsonValue {
+        let value = schema.root.get(violate).gen_value(ctx).unwrap();
+        JsonValue::parse(&value).unwrap()
+    }
+
+    /// Generates valid numbers and checks that each one is a multiple that's within the bounds
+    fn check_numbers(schema: &str, multiple: f64, is_in_range: impl Fn(f64) -> bool) {
+        let schema = compile_inline(schema).unwrap();
+        let mut ctx = crate::fun_test::create_context();
+        for _ in 0..300 {
+            let number = match generate(&schema, false, &mut ctx) {
+                JsonValue::Number(number) => number,
+                other => panic!("not a number: {}", other),
+            };
+            let value = number.parse::<f64>().unwrap();
+            let multiples = value / multiple;
+            assert!(is_in_range(value), "number is out of range: {}", number);
+            assert!((multiples - multiples.round()).abs() < 1e-9, "not a multiple of {}: {}", multiple, number);
+        }
+    }
+
+    #[test]
+    fn numbers_respect_multiple_of_and_exclusive_bounds() {
+        check_numbers(
+            r#"{"type": "integer", "minimum": 10, "maximum": 100, "multipleOf": 7}"#,
+            7.0,
+            |value| (10.0..=100.0).contains(&value),
+        );
+        check_numbers(
+            r#"{"type": "number", "exclusiveMinimum": 0, "maximum": 1, "multipleOf": 0.25}"#,
+            0.25,
+            |value| value > 0.0 && value <= 1.0,
+        );
+        check_numbers(
+            r#"{"type": "number", "exclusiveMinimum": 0.7, "exclusiveMaximum": 1, "multipleOf": 0.1}"#,
+            0.1,
+            |value| value > 0.7 && value < 1.0,
+        );
+        check_numbers(
+            r#"{"type": "number", "minimum": -1, "exclusiveMaximum": 0, "multipleOf": 1e-2}"#,
+            0.01,
+            |value| (-1.0..0.0).contains(&value),
+        );
+
+        let schema = compile_inline(r#"{"type": "number", "minimum": 0, "maximum": 1, "multipleOf": 1e-2}"#).unwrap();
+        let mut ctx = crate::fun_test::create_context();
+        for _ in 0..20 {
+            let number = generate(&schema, false, &mut ctx).to_string();
+            assert_eq!(Some(2), number.find('.').map(|dot| number.len() - dot - 1), "number: {}", number);
+        }
+
+        let error = compile_inline(r#"{"type": "integer", "multipleOf": 0.5}"#).unwrap_err();
+        assert!(error.to_string().contains("'multipleOf' must be a positive integer for integers"));
+        let error = compile_inline(r#"{"type": "integer", "minimum": 5, "maximum": 4}"#).unwrap_err();
+        assert!(error.to_string().contains("no integer satisfies the minimum, maximum, and multipleOf"));
+        let error =
+            compile_inline(r#"{"type": "number", "exclusiveMinimum": 0, "exclusiveMaximum": 0.25, "multipleOf": 0.25}"#)
+                .unwrap_err();
+        assert!(error.to_string().contains("no number satisfies the minimum, maximum, and multipleOf"));
+    }
+
+    #[test]
+    fn schemas_that_cannot_be_violated_always_produce_valid_instances() {
+        let schema = compile_inline(r#"{"anyOf": [{"type": "string"}, {"type": "integer", "maximum": 5}]}"#).unwrap();
+        assert!(schema.root.violating.is_none());
+        let schema = compile_inline(r#"{"type": "array", "items": {"type": "null"}, "maxItems": 2}"#).unwrap();
+        assert!(schema.root.violating.is_some());
+    }
+
+    #[test]
+    fn unsupported_keywords_return_errors() {
+        let unsupported = [
+            (r#"{"allOf": [{"required": ["zzz"]}]}"#, "unsupported keyword 'allOf'"),
+            (r#"{"type": "object", "properties": {"a": {"not": {"type": "null"}}}}"#, "unsupported keyword 'not'"),
+            (r#"{"type": "array", "uniqueItems": true}"#, "unsupported keyword 'uniqueItems'"),
+            (r#"{"patternProperties": {"^a": {}}}"#, "unsupported keyword 'patternProperties'"),
+            (r#"{"additionalProperties": {"type": "string"}}"#, "unsupported keyword 'additionalProperties'"),
+            (r##"{"$ref": "#/$defs/a", "required": ["b"], "$defs": {"a": {}}}"##, "unsupported keyword 'required' alongside '$ref'"),
+            (r#"{"anyOf": [{}], "type": "string"}"#, "unsupported keyword 'type' alongside 'anyOf'"),
+        ];
+        for (schema, expected) in unsupported.iter() {
+            let error = compile_inline(schema).unwrap_err().to_string();
+            assert!(error.contains(expected), "expected '{}', but got: {}", expected, error);
+        }
+        let supported = [
+            r#"{"type": "array", "uniqueItems": false}"#,
+            r#"{"additionalProperties": {}}"#,
+            r##"{"$ref": "#/$defs/a", "description": "a", "$defs": {"a": {}}}"##,
+        ];
+        for schema in supported.iter() {
+            compile_inline(schema).unwrap();
+        }
+    }
+
+    #[test]
+    fn one_of_is_only_supported_with_different_types() {
+        let schema = compile_inline(
+            r#"{"oneOf": [{"type": "integer"}, {"type": "string"}, {"enum": [null, true]}, {"type": ["array", "object"]}]}"#,
+        )
+        .unwrap();
+        let mut ctx = crate::fun_test::create_context();
+        for _ in 0..50 {
+            generate(&schema, false, &mut ctx);
+        }
+        let overlapping = [
+            r#"{"oneOf": [{"type": "integer"}, {"type": "number", "minimum": 0}]}"#,
+            r#"{"oneOf": [{"type": "string"}, {"maxLength": 3}]}"#,
+            r#"{"oneOf": [{"type": "string"}, {}]}"#,
+            r#"{"oneOf": [{"enum": [1, "a"]}, {"type": "string"}]}"#,
+        ];
+        for schema in overlapping.iter() {
+            let error = compile_inline(schema).unwrap_err();
+            assert!(error.to_string().contains("'oneOf' is only supported when each alternative allows different types"));
+        }
+    }
+
+    #[test]
+    fn violating_instances_never_violate_through_any_of() {
+        let schema = compile_inline(
+            r#"{"type": "object", "properties": {"a": {"anyOf": [{"type": "string"}, {"type": "integer"}]},
+                "b": {"type": "integer", "minimum": 0}}, "required": ["a", "b"]}"#,
+        )
+        .unwrap();
+        let is_valid = |instance: &JsonValue| {
+            let members = match instance {
+                JsonValue::Object(members) => members,
+                _ => return false,
+            };
+            let a_is_valid = match get(members, "a") {
+                Some(JsonValue::String(_)) => true,
+                Some(JsonValue::Number(number)) => number.parse::<i64>().is_ok(),
+                _ => false,
+            };
+            let b_is_valid = match get(members, "b") {
+                Some(JsonValue::Number(number)) => number.parse::<i64>().map(|b| b >= 0).unwrap_or(false),
+                _ => false,
+            };
+            a_is_valid && b_is_valid
+        };
+        let mut ctx = crate::fun_test::create_context();
+        for _ in 0..200 {
+            let instance = generate(&schema, true, &mut ctx);
+            assert!(!is_valid(&instance), "instance should be invalid: {}", instance);
+            let instance = generate(&schema, false, &mut ctx);
+            assert!(is_valid(&instance), "instance should be valid: {}", instance);
+        }
+    }
+
+    #[test]
+    fn required_recursion_returns_an_error() {
+        let mut ctx = crate::fun_test::create_context();
+        for schema in [r##"{"$ref": "#"}"##, r##"{"properties": {"a": {"$ref": "#"}}, "required": ["a"]}"##].iter() {
+            let schema = compile_inline(schema).unwrap();
+            let error = schema.root.valid.gen_value(&mut ctx).unwrap_err();
+            assert!(error.to_string().contains("too deeply recursive"), "unexpected error: {}", error);
+        }
+    }
+}
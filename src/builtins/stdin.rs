@@ -0,0 +1,341 @@
+use failure::Error;
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::rc::Rc;
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+
+/// Returned when a function tries to read past the end of stdin or a pipe. The runner stops the program when it sees
+/// this error, so that dgen can be used as a stage in a pipeline that runs for as long as there is input
+#[derive(Debug)]
+pub struct EndOfInput {
+    input: String,
+}
+
+impl fmt::Display for EndOfInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reached the end of {}", self.input)
+    }
+}
+
+impl ::std::error::Error for EndOfInput {}
+
+#[derive(Debug)]
+enum Input {
+    Stdin,
+    Pipe(DynStringFun),
+}
+
+impl Input {
+    fn with_reader<F, R>(&self, ctx: &mut ProgramContext, fun: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut dyn BufRead, &str) -> Result<R, Error>,
+    {
+        match *self {
+            Input::Stdin => {
+                let stdin = io::stdin();
+                let mut lock = stdin.lock();
+                fun(&mut lock, "stdin")
+            }
+            Input::Pipe(ref path_gen) => {
+                let path = path_gen.gen_value(ctx)?;
+                let reader = ctx.pipe_reader(&path)?;
+                fun(reader, &path)
+            }
+        }
+    }
+}
+
+fn end_of_input(input: &str) -> Error {
+    EndOfInput {
+        input: input.to_owned(),
+    }
+    .into()
+}
+
+/// Reads up to and including the next delimiter, and returns the value without the delimiter. The final value is
+/// not required to end with the delimiter
+fn read_record(reader: &mut dyn BufRead, input: &str, delimiter: &[u8]) -> Result<Vec<u8>, Error> {
+    if delimiter.is_empty() {
+        bail!("Cannot read records from {} using an empty delimiter", input);
+    }
+    let last_byte = delimiter[delimiter.len() - 1];
+    let mut buffer = Vec::with_capacity(128);
+    loop {
+        if reader.read_until(last_byte, &mut buffer)? == 0 {
+            if buffer.is_empty() {
+                return Err(end_of_input(input));
+            }
+            return Ok(buffer);
+        }
+        if buffer.ends_with(delimiter) {
+            let record_len = buffer.len() - delimiter.len();
+            buffer.truncate(record_len);
+            return Ok(buffer);
+        }
+    }
+}
+
+fn into_string(bytes: Vec<u8>, input: &str) -> Result<IString, Error> {
+    String::from_utf8(bytes)
+        .map(Into::into)
+        .map_err(|err| format_err!("The data read from {} is not valid utf-8: {}", input, err))
+}
+
+#[derive(Debug)]
+struct ReadLine {
+    input: Input,
+}
+
+impl RunnableFunction<IString> for ReadLine {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        self.input.with_reader(ctx, |reader, input| {
+            let mut line = read_record(reader, input, b"\n")?;
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+            into_string(line, input)
+        })
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct ReadRecord {
+    input: Input,
+    delimiter: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ReadRecord {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let delimiter = self.delimiter.gen_value(ctx)?;
+        self.input.with_reader(ctx, |reader, input| {
+            let record = read_record(reader, input, delimiter.as_bytes())?;
+            into_string(record, input)
+        })
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct ReadBytes {
+    input: Input,
+    count: DynUintFun,
+}
+
+impl RunnableFunction<Vec<u8>> for ReadBytes {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let count = self.count.gen_value(ctx)?;
+        self.input.with_reader(ctx, |reader, input| {
+            let mut buffer = Vec::with_capacity(count as usize);
+            reader.take(count).read_to_end(&mut buffer)?;
+            if buffer.is_empty() && count > 0 {
+                return Err(end_of_input(input));
+            }
+            Ok(buffer)
+        })
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write_bytes(&value)
+    }
+}
+
+const PATH_PARAM: &str = "pipe_path";
+const DELIMITER_PARAM: &str = "delimiter";
+const COUNT_PARAM: &str = "byte_count";
+
+fn create_stdin_line(_: Arguments) -> CreateFunctionResult {
+    Ok(AnyFunction::String(Rc::new(ReadLine {
+        input: Input::Stdin,
+    })))
+}
+
+fn create_pipe_line(args: Arguments) -> CreateFunctionResult {
+    let path = args.required_arg(PATH_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ReadLine {
+        input: Input::Pipe(path),
+    })))
+}
+
+fn create_stdin_record(args: Arguments) -> CreateFunctionResult {
+    let delimiter = args.required_arg(DELIMITER_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ReadRecord {
+        input: Input::Stdin,
+        delimiter,
+    })))
+}
+
+fn create_pipe_record(args: Arguments) -> CreateFunctionResult {
+    let (path, delimiter) = args.require_2_args(
+        PATH_PARAM,
+        AnyFunction::require_string,
+        DELIMITER_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(ReadRecord {
+        input: Input::Pipe(path),
+        delimiter,
+    })))
+}
+
+fn create_stdin_bytes(args: Arguments) -> CreateFunctionResult {
+    let count = args.required_arg(COUNT_PARAM, 0, AnyFunction::require_uint)?;
+    Ok(AnyFunction::Bin(Rc::new(ReadBytes {
+        input: Input::Stdin,
+        count,
+    })))
+}
+
+fn create_pipe_bytes(args: Arguments) -> CreateFunctionResult {
+    let (path, count) = args.require_2_args(
+        PATH_PARAM,
+        AnyFunction::require_string,
+        COUNT_PARAM,
+        AnyFunction::require_uint,
+    )?;
+    Ok(AnyFunction::Bin(Rc::new(ReadBytes {
+        input: Input::Pipe(path),
+        count,
+    })))
+}
+
+pub const STDIN_LINE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "stdin_line",
+    description: "Reads the next line from stdin, without the line ending. The program stops once all of stdin has been read. Cannot be used when the program itself is read from stdin",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_stdin_line,
+};
+
+pub const STDIN_RECORD_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "stdin_record",
+    description: "Reads from stdin up to the next occurrence of the delimiter, and returns everything before it. The program stops once all of stdin has been read. Cannot be used when the program itself is read from stdin",
+    arguments: &[(DELIMITER_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_stdin_record,
+};
+
+pub const STDIN_BYTES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "stdin_bytes",
+    description: "Reads the given number of bytes from stdin, or fewer if stdin ends first. The program stops once all of stdin has been read. Cannot be used when the program itself is read from stdin",
+    arguments: &[(COUNT_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_stdin_bytes,
+};
+
+pub const PIPE_LINE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "pipe_line",
+    description: "Reads the next line from the given named pipe, without the line ending. The program stops once the pipe has been closed and all of its data has been read",
+    arguments: &[(PATH_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_pipe_line,
+};
+
+pub const PIPE_RECORD_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "pipe_record",
+    description: "Reads from the given named pipe up to the next occurrence of the delimiter, and returns everything before it. The program stops once the pipe has been closed and all of its data has been read",
+    arguments: &[(PATH_PARAM, GenType::String), (DELIMITER_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_pipe_record,
+};
+
+pub const PIPE_BYTES_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "pipe_bytes",
+    description: "Reads the given number of bytes from the named pipe, or fewer if the pipe is closed first. The program stops once the pipe has been closed and all of its data has been read",
+    arguments: &[(PATH_PARAM, GenType::String), (COUNT_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_pipe_bytes,
+};
+
+const STDIN_FNS: &[&BuiltinFunctionPrototype] = &[
+    STDIN_LINE_BUILTIN,
+    STDIN_RECORD_BUILTIN,
+    STDIN_BYTES_BUILTIN,
+];
+
+const PIPE_FNS: &[&BuiltinFunctionPrototype] = &[
+    PIPE_LINE_BUILTIN,
+    PIPE_RECORD_BUILTIN,
+    PIPE_BYTES_BUILTIN,
+];
+
+fn is_one_of(function: &BuiltinFunctionPrototype, functions: &[&BuiltinFunctionPrototype]) -> bool {
+    functions
+        .iter()
+        .any(|other| other.function_name == function.function_name)
+}
+
+/// Returns true if the function reads data from stdin, which is not allowed when the program is also read from stdin
+pub fn reads_stdin(function: &BuiltinFunctionPrototype) -> bool {
+    is_one_of(function, STDIN_FNS)
+}
+
+/// Returns true if the function reads data from either stdin or a pipe, which may run out part way through an iteration
+pub fn reads_input(function: &BuiltinFunctionPrototype) -> bool {
+    reads_stdin(function) || is_one_of(function, PIPE_FNS)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_runtime_error, create_context, run_program};
+    use crate::interpreter::{Interpreter, UnreadSource};
+    use crate::writer::DataGenOutput;
+
+    #[test]
+    fn pipe_functions_read_the_input_in_order() {
+        let program = r#"concat(pipe_line("test-data/crlf-words.txt"), "|", pipe_line("test-data/crlf-words.txt"), "\n")"#;
+        // the third iteration runs out of input part way through, so none of its output is written
+        let output = run_program(3, program).unwrap();
+        assert_eq!("foo|bar\nbaz|\n", String::from_utf8(output).unwrap());
+
+        // the last chunk is shorter, and then the program stops even though more iterations were requested
+        let output = run_program(10, r#"pipe_bytes("test-data/simple-words.txt", 5)"#).unwrap();
+        assert_eq!(b"foo\nbar\nbaz\n\nqux".to_vec(), output);
+    }
+
+    #[test]
+    fn end_of_input_within_a_function_stops_the_program() {
+        let program = r#"
+            def two_lines(path: String) = concat(pipe_line(path), "|", pipe_line(path), "\n");
+            two_lines("test-data/simple-words.txt")
+        "#;
+        // each program reads the pipe from the beginning, since the readers are not shared between programs
+        for _ in 0..2 {
+            let output = run_program(10, program).unwrap();
+            assert_eq!("foo|bar\nbaz|\n", String::from_utf8(output).unwrap());
+        }
+    }
+
+    #[test]
+    fn pipe_record_errors_on_empty_delimiter() {
+        assert_runtime_error(r#"pipe_record("test-data/uri.abnf", "")"#, "empty delimiter");
+    }
+
+    #[test]
+    fn pipe_functions_cannot_read_stdin_when_it_is_the_program_source() {
+        for path in &["/dev/stdin", "/proc/self/fd/0", "-"] {
+            let mut interpreter = Interpreter::new();
+            interpreter.add_std_lib();
+            let program = format!(r#"pipe_line("{}")"#, path);
+            let function = interpreter.eval(UnreadSource::String(program)).unwrap();
+            let mut context = create_context();
+            context.set_stdin_is_source();
+            let mut out = Vec::new();
+            let error = function
+                .write_value(&mut context, &mut DataGenOutput::new(&mut out))
+                .expect_err("expected an error");
+            assert!(error.to_string().contains("the program was read from stdin"), "unexpected error: {}", error);
+        }
+    }
+}
@@ -191,6 +191,60 @@ pub const STRING_ENCODE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPro
     create_fn: &create_string_bytes,
 };
 
+#[derive(Debug)]
+struct ChangeCase {
+    uppercase: bool,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ChangeCase {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let value = self.wrapped.gen_value(ctx)?;
+        let converted = if self.uppercase {
+            value.to_uppercase()
+        } else {
+            value.to_lowercase()
+        };
+        Ok(converted.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+fn create_to_uppercase(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg("string", 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ChangeCase {
+        uppercase: true,
+        wrapped,
+    })))
+}
+
+fn create_to_lowercase(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg("string", 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ChangeCase {
+        uppercase: false,
+        wrapped,
+    })))
+}
+
+pub const TO_UPPERCASE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_uppercase",
+    description: "converts the input string to uppercase, using the unicode definition of uppercase",
+    arguments: &[("string", GenType::String)],
+    variadic: false,
+    create_fn: &create_to_uppercase,
+};
+
+pub const TO_LOWERCASE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_lowercase",
+    description: "converts the input string to lowercase, using the unicode definition of lowercase",
+    arguments: &[("string", GenType::String)],
+    variadic: false,
+    create_fn: &create_to_lowercase,
+};
+
 #[cfg(test)]
 mod test {
     use crate::fun_test::{assert_bin_output_is_expected, run_program, test_program_success};
@@ -306,6 +360,13 @@ mod test {
         test_program_success(1, program, expected);
     }
 
+    #[test]
+    fn strings_are_converted_to_upper_and_lower_case() {
+        let program = r#"concat(to_uppercase("Straße"), " ", to_lowercase("ÀB"))"#;
+        let expected = "STRASSE àb";
+        test_program_success(1, program, expected);
+    }
+
     #[test]
     fn generate_ascii_strings() {
         let expected_output = "a6OqR822C3hoTTf1";
@@ -0,0 +1,469 @@
+use crate::json::JsonValue;
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Write;
+use std::rc::Rc;
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_control(c: char) -> bool {
+    (c < ' ' && c != '\t') || c == '\u{7F}'
+}
+
+fn can_be_literal(value: &str) -> bool {
+    !value.contains('\'') && !value.chars().any(is_control)
+}
+
+fn can_be_multi_line_literal(value: &str) -> bool {
+    !value.contains('\'') && !value.chars().any(|c| is_control(c) && c != '\n')
+}
+
+/// Whether writing the members as dotted keys would write anything, since null members are omitted
+fn has_non_null_members(members: &[(String, JsonValue)]) -> bool {
+    members.iter().any(|(_, value)| *value != JsonValue::Null)
+}
+
+/// Inserts underscores between groups of digits, like `1_000_000`
+fn group_digits(digits: &str, group_size: usize) -> String {
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % group_size == 0 {
+            grouped.push('_');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// How a table or array of tables is written, relative to the table that contains it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TableStyle {
+    Inline,
+    Dotted,
+    Section,
+}
+
+struct TomlWriter<'a> {
+    ctx: &'a mut ProgramContext,
+    out: String,
+}
+
+impl<'a> TomlWriter<'a> {
+    fn maybe_comment(&mut self) {
+        if self.ctx.gen_chance(0.05) {
+            let comment = self.ctx.choose(&["#", "# note", "# TODO: check this", "#generated"]);
+            self.out.push_str(comment);
+            self.out.push('\n');
+        }
+    }
+
+    fn key(&mut self, key: &str) -> String {
+        let mut out = String::new();
+        if is_bare_key(key) && self.ctx.gen_chance(0.8) {
+            out.push_str(key);
+        } else if can_be_literal(key) && self.ctx.gen_chance(0.5) {
+            let _ = write!(out, "'{}'", key);
+        } else {
+            self.basic_string(key, &mut out);
+        }
+        out
+    }
+
+    fn dotted_key(&mut self, path: &[&str]) -> String {
+        let separator = self.ctx.choose(&[".", ".", " . "]);
+        let keys: Vec<String> = path.iter().map(|key| self.key(key)).collect();
+        keys.join(separator)
+    }
+
+    fn key_value_separator(&mut self) -> &'static str {
+        self.ctx.choose(&[" = ", " = ", "=", "  =  "])
+    }
+
+    /// Writes the header and direct members of a table, followed by any tables that need their own section. The
+    /// header may be omitted for tables that only contain other sections, since those are defined implicitly
+    fn table<'v>(
+        &mut self,
+        path: &[&'v str],
+        header: Option<(&str, &str)>,
+        members: &'v [(String, JsonValue)],
+    ) -> Result<(), Error> {
+        let mut direct = Vec::new();
+        let mut sections = Vec::new();
+        for (key, value) in members.iter() {
+            match value {
+                JsonValue::Null => {}
+                JsonValue::Object(inner) => {
+                    match self.ctx.choose(&[TableStyle::Inline, TableStyle::Dotted, TableStyle::Section]) {
+                        TableStyle::Dotted if has_non_null_members(inner) => direct.push((key.as_str(), value, TableStyle::Dotted)),
+                        TableStyle::Section => sections.push((key.as_str(), value)),
+                        _ => direct.push((key.as_str(), value, TableStyle::Inline)),
+                    }
+                }
+                JsonValue::Array(items)
+                    if !items.is_empty()
+                        && items.iter().all(|item| matches!(item, JsonValue::Object(_)))
+                        && self.ctx.gen_chance(0.7) =>
+                {
+                    sections.push((key.as_str(), value));
+                }
+                other => direct.push((key.as_str(), other, TableStyle::Inline)),
+            }
+        }
+
+        if let Some((open, close)) = header {
+            if open == "[[" || !direct.is_empty() || sections.is_empty() || self.ctx.gen_chance(0.5) {
+                self.header(open, path, close);
+            }
+        }
+        for (key, value, style) in direct {
+            match (style, value) {
+                (TableStyle::Dotted, JsonValue::Object(inner)) => self.dotted_members(&[key], inner)?,
+                _ => self.key_value(&[key], value)?,
+            }
+        }
+
+        for (key, value) in sections {
+            let mut section_path = path.to_vec();
+            section_path.push(key);
+            match value {
+                JsonValue::Object(inner) => self.table(&section_path, Some(("[", "]")), inner)?,
+                JsonValue::Array(items) => {
+                    for item in items.iter() {
+                        if let JsonValue::Object(inner) = item {
+                            self.table(&section_path, Some(("[[", "]]")), inner)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn header(&mut self, open: &str, path: &[&str], close: &str) {
+        if !self.out.is_empty() && self.ctx.gen_chance(0.7) {
+            self.out.push('\n');
+        }
+        self.maybe_comment();
+        let padding = self.ctx.choose(&["", "", " "]);
+        let key = self.dotted_key(path);
+        let _ = writeln!(self.out, "{}{}{}{}{}", open, padding, key, padding, close);
+    }
+
+    /// Writes every member of the table using dotted keys, like `a.b.c = 1`
+    fn dotted_members(&mut self, path: &[&str], members: &[(String, JsonValue)]) -> Result<(), Error> {
+        for (key, value) in members.iter() {
+            let mut member_path = path.to_vec();
+            member_path.push(key.as_str());
+            match value {
+                JsonValue::Null => {}
+                JsonValue::Object(inner) if has_non_null_members(inner) && self.ctx.gen_chance(0.5) => {
+                    self.dotted_members(&member_path, inner)?;
+                }
+                other => self.key_value(&member_path, other)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn key_value(&mut self, path: &[&str], value: &JsonValue) -> Result<(), Error> {
+        self.maybe_comment();
+        let key = self.dotted_key(path);
+        let separator = self.key_value_separator();
+        let mut formatted = String::new();
+        self.value(value, &mut formatted)?;
+        let _ = write!(self.out, "{}{}{}", key, separator, formatted);
+        if self.ctx.gen_chance(0.05) {
+            self.out.push_str(" # comment");
+        }
+        self.out.push('\n');
+        Ok(())
+    }
+
+    fn value(&mut self, value: &JsonValue, out: &mut String) -> Result<(), Error> {
+        match value {
+            JsonValue::Null => bail!("toml has no null value, so nulls can only be used as object members, where they're omitted"),
+            JsonValue::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(number) => self.number(number, out),
+            JsonValue::String(string) => self.string(string, out),
+            JsonValue::Array(items) => self.array(items, out)?,
+            JsonValue::Object(members) => {
+                let (open, separator, close) = self.ctx.choose(&[("{ ", ", ", " }"), ("{", ",", "}"), ("{ ", " , ", " }")]);
+                out.push_str(open);
+                let mut first = true;
+                for (key, value) in members.iter() {
+                    if let JsonValue::Null = value {
+                        continue;
+                    }
+                    if !first {
+                        out.push_str(separator);
+                    }
+                    first = false;
+                    let key = self.key(key);
+                    out.push_str(&key);
+                    out.push_str(self.key_value_separator());
+                    self.value(value, out)?;
+                }
+                out.push_str(close);
+            }
+        }
+        Ok(())
+    }
+
+    fn array(&mut self, items: &[JsonValue], out: &mut String) -> Result<(), Error> {
+        let multi_line = !items.is_empty() && self.ctx.gen_chance(0.3);
+        out.push('[');
+        for (i, item) in items.iter().enumerate() {
+            if multi_line {
+                out.push_str(if i == 0 { "\n    " } else { ",\n    " });
+            } else if i > 0 {
+                out.push_str(self.ctx.choose(&[", ", ","]));
+            }
+            self.value(item, out)?;
+        }
+        if multi_line {
+            out.push_str(self.ctx.choose(&[",\n", "\n", ", # trailing comma\n"]));
+        } else if !items.is_empty() && self.ctx.gen_chance(0.1) {
+            out.push(',');
+        }
+        out.push(']');
+        Ok(())
+    }
+
+    fn number(&mut self, number: &str, out: &mut String) {
+        let is_integer = !number.contains(['.', 'e', 'E']);
+        if !is_integer {
+            // toml parsers reject floats that overflow, but toml can represent infinity directly
+            match number.parse::<f64>() {
+                Ok(value) if value.is_infinite() && value > 0.0 => out.push_str(self.ctx.choose(&["inf", "+inf"])),
+                Ok(value) if value.is_infinite() => out.push_str("-inf"),
+                _ => out.push_str(number),
+            }
+            return;
+        }
+        let value = match number.parse::<i64>() {
+            Ok(value) => value,
+            Err(_) => {
+                // too large for a toml integer, which must fit in 64 bits
+                let _ = write!(out, "{}.0", number);
+                return;
+            }
+        };
+        if let Some(digits) = number.strip_prefix('-') {
+            out.push('-');
+            if digits.len() > 3 && self.ctx.gen_chance(0.3) {
+                out.push_str(&group_digits(digits, 3));
+            } else {
+                out.push_str(digits);
+            }
+            return;
+        }
+        match self.ctx.gen_range_exclusive(0, 10) {
+            0 => {
+                let _ = write!(out, "0x{:x}", value);
+            }
+            1 => {
+                let _ = write!(out, "0x{:X}", value);
+            }
+            2 => {
+                let _ = write!(out, "0o{:o}", value);
+            }
+            3 => {
+                let _ = write!(out, "0b{:b}", value);
+            }
+            4 if number.len() > 3 => out.push_str(&group_digits(number, 3)),
+            5 => {
+                let _ = write!(out, "+{}", number);
+            }
+            _ => out.push_str(number),
+        }
+    }
+
+    fn string(&mut self, value: &str, out: &mut String) {
+        let mut styles = vec![0, 1];
+        if can_be_literal(value) {
+            styles.push(2);
+        }
+        if can_be_multi_line_literal(value) && !value.contains('\r') {
+            styles.push(3);
+        }
+        match self.ctx.choose(&styles) {
+            0 => self.basic_string(value, out),
+            1 => {
+                out.push_str("\"\"\"");
+                // a line break immediately after the opening quotes is trimmed
+                if value.starts_with('\n') || self.ctx.gen_chance(0.5) {
+                    out.push('\n');
+                }
+                self.escape_basic(value, true, out);
+                out.push_str("\"\"\"");
+            }
+            2 => {
+                let _ = write!(out, "'{}'", value);
+            }
+            _ => {
+                out.push_str("'''");
+                if value.starts_with('\n') || self.ctx.gen_chance(0.5) {
+                    out.push('\n');
+                }
+                out.push_str(value);
+                out.push_str("'''");
+            }
+        }
+    }
+
+    fn basic_string(&mut self, value: &str, out: &mut String) {
+        out.push('"');
+        self.escape_basic(value, false, out);
+        out.push('"');
+    }
+
+    fn escape_basic(&mut self, value: &str, multi_line: bool, out: &mut String) {
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' if multi_line => out.push('\n'),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\u{8}' => out.push_str("\\b"),
+                '\u{c}' => out.push_str("\\f"),
+                '\t' if self.ctx.gen_chance(0.5) => out.push_str("\\t"),
+                c if is_control(c) || self.ctx.gen_chance(0.05) => {
+                    if (c as u32) < 0x10000 {
+                        let _ = write!(out, "\\u{:04X}", c as u32);
+                    } else {
+                        let _ = write!(out, "\\U{:08x}", c as u32);
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ToToml {
+    json: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ToToml {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let json = self.json.gen_value(ctx)?;
+        let value = JsonValue::parse(&json)?;
+        let members = match value {
+            JsonValue::Object(members) => members,
+            other => bail!("to_toml requires a json object, but got a {}", other.type_name()),
+        };
+        let mut writer = TomlWriter {
+            ctx,
+            out: String::new(),
+        };
+        writer.table(&[], None, &members)?;
+        Ok(writer.out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const JSON_PARAM: &str = "json";
+
+fn create_to_toml(args: Arguments) -> CreateFunctionResult {
+    let json = args.required_arg(JSON_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ToToml { json })))
+}
+
+pub const TO_TOML_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_toml",
+    description: "converts a json object into an equivalent toml document, randomly choosing the style of every value: sections, dotted keys, or inline tables, arrays of tables, bare or quoted keys, basic, literal, or multi-line strings, and integers in any base. Toml has no null, so null object members are omitted, and null array elements are an error",
+    arguments: &[(JSON_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_to_toml,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::{assert_runtime_error, run_program};
+
+    fn number_value(number: &str) -> f64 {
+        number.parse().unwrap()
+    }
+
+    fn is_equivalent(json: &JsonValue, toml: &::toml::Value) -> bool {
+        use ::toml::Value;
+        match (json, toml) {
+            (JsonValue::Boolean(a), Value::Boolean(b)) => a == b,
+            (JsonValue::Number(a), Value::Integer(b)) => number_value(a) == *b as f64,
+            (JsonValue::Number(a), Value::Float(b)) => number_value(a) == *b,
+            (JsonValue::String(a), Value::String(b)) => a == b,
+            (JsonValue::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| is_equivalent(a, b))
+            }
+            (JsonValue::Object(a), Value::Table(b)) => {
+                let members: Vec<_> = a.iter().filter(|(_, value)| *value != JsonValue::Null).collect();
+                members.len() == b.len()
+                    && members.iter().all(|(key, value)| {
+                        b.get(key.as_str())
+                            .map(|other| is_equivalent(value, other))
+                            .unwrap_or(false)
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn toml_documents_are_equivalent_to_the_json() {
+        let program = r#"json_object(json_nested(json_nested(select(json_boolean(), json_number(), json_string())))) { json ->
+            concat(json, "\u{0}", to_toml(json), "\u{0}")
+        }"#;
+        let output = String::from_utf8(run_program(300, program).unwrap()).unwrap();
+        let parts: Vec<&str> = output.split_terminator('\u{0}').collect();
+        for pair in parts.chunks(2) {
+            let json = JsonValue::parse(pair[0]).unwrap();
+            let toml = pair[1]
+                .parse::<::toml::Table>()
+                .unwrap_or_else(|err| panic!("invalid toml: {}, document:\n{}", err, pair[1]));
+            assert!(
+                is_equivalent(&json, &::toml::Value::Table(toml)),
+                "toml is not equivalent to json: {}, toml document:\n{}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn null_object_members_are_omitted() {
+        let output = run_program(20, r#"concat(to_toml("{\"a\": null, \"b\": {\"c\": null}}"), "\u{0}")"#).unwrap();
+        for document in String::from_utf8(output).unwrap().split_terminator('\u{0}') {
+            let table = document.parse::<::toml::Table>().unwrap();
+            assert_eq!(1, table.len());
+            assert_eq!(Some(&::toml::Value::Table(::toml::Table::new())), table.get("b"));
+        }
+    }
+
+    #[test]
+    fn values_that_cannot_be_represented_return_errors() {
+        assert_runtime_error(r#"to_toml("[1, 2]")"#, "to_toml requires a json object, but got a array");
+        assert_runtime_error(r#"to_toml("{\"a\": [null]}")"#, "toml has no null value");
+    }
+
+    #[test]
+    fn digits_are_grouped_from_the_right() {
+        assert_eq!("1_000_000", group_digits("1000000", 3));
+        assert_eq!("12_345", group_digits("12345", 3));
+    }
+}
@@ -0,0 +1,391 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynDecimalFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::rc::Rc;
+use unicode_normalization::UnicodeNormalization;
+
+const COMBINING_MARKS_START: u32 = 0x0300;
+const COMBINING_MARKS_END: u32 = 0x036F;
+
+#[derive(Debug)]
+struct CombiningMarks {
+    max_per_char: DynUintFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for CombiningMarks {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let max_per_char = self.max_per_char.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+        let mut result = String::with_capacity(value.len() * 2);
+        for c in value.chars() {
+            result.push(c);
+            let mark_count = ctx.gen_range_inclusive(0, max_per_char);
+            for _ in 0..mark_count {
+                let mark = ctx.gen_range_inclusive(COMBINING_MARKS_START, COMBINING_MARKS_END);
+                result.push(::std::char::from_u32(mark).unwrap());
+            }
+        }
+        Ok(result.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct Normalize {
+    form: DynStringFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for Normalize {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let form = self.form.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+        let normalized: String = match &*form {
+            "nfc" => value.nfc().collect(),
+            "nfd" => value.nfd().collect(),
+            "nfkc" => value.nfkc().collect(),
+            "nfkd" => value.nfkd().collect(),
+            other => bail!(
+                "Invalid normalization form: '{}', must be one of 'nfc', 'nfd', 'nfkc', or 'nfkd'",
+                other
+            ),
+        };
+        Ok(normalized.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const ZERO_WIDTH_CHARS: &[char] = &[
+    '\u{200B}', // zero width space
+    '\u{200C}', // zero width non-joiner
+    '\u{200D}', // zero width joiner
+    '\u{2060}', // word joiner
+    '\u{FEFF}', // zero width no-break space (byte order mark)
+];
+
+#[derive(Debug)]
+struct InsertZeroWidth {
+    rate: DynDecimalFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for InsertZeroWidth {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let rate = self.rate.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+        let mut result = String::with_capacity(value.len() * 2);
+        for c in value.chars() {
+            if ctx.gen_chance(rate) {
+                let zw = ZERO_WIDTH_CHARS[ctx.gen_range_exclusive(0, ZERO_WIDTH_CHARS.len())];
+                result.push(zw);
+            }
+            result.push(c);
+        }
+        if ctx.gen_chance(rate) {
+            let zw = ZERO_WIDTH_CHARS[ctx.gen_range_exclusive(0, ZERO_WIDTH_CHARS.len())];
+            result.push(zw);
+        }
+        Ok(result.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+/// Latin characters and digits, along with characters from other scripts that are visually confusable with them
+const HOMOGLYPHS: &[(char, &[char])] = &[
+    ('a', &['\u{0430}', '\u{0251}']),
+    ('c', &['\u{0441}', '\u{03F2}']),
+    ('d', &['\u{0501}']),
+    ('e', &['\u{0435}']),
+    ('g', &['\u{0261}']),
+    ('h', &['\u{04BB}']),
+    ('i', &['\u{0456}', '\u{0269}']),
+    ('j', &['\u{0458}']),
+    ('l', &['\u{04CF}', '\u{0399}']),
+    ('n', &['\u{0578}']),
+    ('o', &['\u{043E}', '\u{03BF}', '\u{0585}']),
+    ('p', &['\u{0440}', '\u{03C1}']),
+    ('q', &['\u{051B}']),
+    ('s', &['\u{0455}']),
+    ('u', &['\u{057D}']),
+    ('v', &['\u{03BD}', '\u{0475}']),
+    ('w', &['\u{051D}']),
+    ('x', &['\u{0445}']),
+    ('y', &['\u{0443}']),
+    ('A', &['\u{0410}', '\u{0391}']),
+    ('B', &['\u{0412}', '\u{0392}']),
+    ('C', &['\u{0421}', '\u{03F9}']),
+    ('E', &['\u{0415}', '\u{0395}']),
+    ('H', &['\u{041D}', '\u{0397}']),
+    ('I', &['\u{0406}', '\u{0399}']),
+    ('J', &['\u{0408}']),
+    ('K', &['\u{041A}', '\u{039A}']),
+    ('M', &['\u{041C}', '\u{039C}']),
+    ('N', &['\u{039D}']),
+    ('O', &['\u{041E}', '\u{039F}']),
+    ('P', &['\u{0420}', '\u{03A1}']),
+    ('S', &['\u{0405}']),
+    ('T', &['\u{0422}', '\u{03A4}']),
+    ('X', &['\u{0425}', '\u{03A7}']),
+    ('Y', &['\u{04AE}', '\u{03A5}']),
+    ('Z', &['\u{0396}']),
+    ('0', &['\u{041E}', '\u{039F}']),
+    ('1', &['\u{04CF}']),
+    ('3', &['\u{0417}']),
+];
+
+fn homoglyphs_of(c: char) -> Option<&'static [char]> {
+    HOMOGLYPHS
+        .iter()
+        .find(|&&(latin, _)| latin == c)
+        .map(|&(_, glyphs)| glyphs)
+}
+
+#[derive(Debug)]
+struct SubstituteHomoglyphs {
+    rate: DynDecimalFun,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for SubstituteHomoglyphs {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let rate = self.rate.gen_value(ctx)?;
+        let value = self.wrapped.gen_value(ctx)?;
+        let mut result = String::with_capacity(value.len() * 2);
+        for c in value.chars() {
+            match homoglyphs_of(c) {
+                Some(glyphs) if ctx.gen_chance(rate) => {
+                    result.push(glyphs[ctx.gen_range_exclusive(0, glyphs.len())]);
+                }
+                _ => result.push(c),
+            }
+        }
+        Ok(result.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const FULLWIDTH_OFFSET: u32 = 0xFEE0;
+const IDEOGRAPHIC_SPACE: char = '\u{3000}';
+
+fn to_fullwidth(c: char) -> char {
+    match c {
+        ' ' => IDEOGRAPHIC_SPACE,
+        '!'..='~' => ::std::char::from_u32(c as u32 + FULLWIDTH_OFFSET).unwrap(),
+        other => other,
+    }
+}
+
+fn to_halfwidth(c: char) -> char {
+    match c {
+        IDEOGRAPHIC_SPACE => ' ',
+        '\u{FF01}'..='\u{FF5E}' => ::std::char::from_u32(c as u32 - FULLWIDTH_OFFSET).unwrap(),
+        other => other,
+    }
+}
+
+#[derive(Debug)]
+struct ChangeWidth {
+    fullwidth: bool,
+    wrapped: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ChangeWidth {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let value = self.wrapped.gen_value(ctx)?;
+        let converted: String = if self.fullwidth {
+            value.chars().map(to_fullwidth).collect()
+        } else {
+            value.chars().map(to_halfwidth).collect()
+        };
+        Ok(converted.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const STRING_PARAM: &str = "string";
+const RATE_PARAM: &str = "rate";
+
+fn create_combining_marks(args: Arguments) -> CreateFunctionResult {
+    let (max_per_char, wrapped) = args.require_2_args(
+        "max_marks_per_char",
+        AnyFunction::require_uint,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(CombiningMarks {
+        max_per_char,
+        wrapped,
+    })))
+}
+
+fn create_normalize(args: Arguments) -> CreateFunctionResult {
+    let (form, wrapped) = args.require_2_args(
+        "form",
+        AnyFunction::require_string,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(Normalize { form, wrapped })))
+}
+
+fn create_insert_zero_width(args: Arguments) -> CreateFunctionResult {
+    let (rate, wrapped) = args.require_2_args(
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(InsertZeroWidth { rate, wrapped })))
+}
+
+fn create_homoglyphs(args: Arguments) -> CreateFunctionResult {
+    let (rate, wrapped) = args.require_2_args(
+        RATE_PARAM,
+        AnyFunction::require_decimal,
+        STRING_PARAM,
+        AnyFunction::require_string,
+    )?;
+    Ok(AnyFunction::String(Rc::new(SubstituteHomoglyphs {
+        rate,
+        wrapped,
+    })))
+}
+
+fn create_to_fullwidth(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ChangeWidth {
+        fullwidth: true,
+        wrapped,
+    })))
+}
+
+fn create_to_halfwidth(args: Arguments) -> CreateFunctionResult {
+    let wrapped = args.required_arg(STRING_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ChangeWidth {
+        fullwidth: false,
+        wrapped,
+    })))
+}
+
+pub const COMBINING_MARKS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "combining_marks",
+    description: "follows each character of the input with between 0 and max_marks_per_char (inclusive) random combining diacritical marks",
+    arguments: &[
+        ("max_marks_per_char", GenType::Uint),
+        (STRING_PARAM, GenType::String),
+    ],
+    variadic: false,
+    create_fn: &create_combining_marks,
+};
+
+pub const NORMALIZE_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "normalize",
+    description: "converts the input to the given unicode normalization form, which must be one of 'nfc', 'nfd', 'nfkc', or 'nfkd'",
+    arguments: &[("form", GenType::String), (STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_normalize,
+};
+
+pub const INSERT_ZERO_WIDTH_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "insert_zero_width",
+    description: "inserts random zero width characters (spaces, joiners, non-joiners, word joiners, and byte order marks) between the characters of the input. Each position gets one with the given probability between 0 and 1",
+    arguments: &[(RATE_PARAM, GenType::Decimal), (STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_insert_zero_width,
+};
+
+pub const HOMOGLYPHS_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "homoglyphs",
+    description: "replaces latin letters and digits in the input with visually confusable characters from other scripts (mostly Cyrillic and Greek). Each eligible character is replaced with the given probability between 0 and 1",
+    arguments: &[(RATE_PARAM, GenType::Decimal), (STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_homoglyphs,
+};
+
+pub const TO_FULLWIDTH_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_fullwidth",
+    description: "converts printable ascii characters in the input to their full-width forms",
+    arguments: &[(STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_to_fullwidth,
+};
+
+pub const TO_HALFWIDTH_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_halfwidth",
+    description: "converts full-width forms in the input to their printable ascii equivalents",
+    arguments: &[(STRING_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_to_halfwidth,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_runtime_error, run_program, test_program_success};
+
+    #[test]
+    fn combining_marks_are_added_after_each_character() {
+        let program = r#"combining_marks(3, "abc")"#;
+        let output = String::from_utf8(run_program(1, program).unwrap()).unwrap();
+        let base_chars: String = output
+            .chars()
+            .filter(|c| (*c as u32) < 0x0300 || (*c as u32) > 0x036F)
+            .collect();
+        assert_eq!("abc", base_chars);
+        assert!(output.chars().count() <= 12);
+    }
+
+    #[test]
+    fn strings_are_normalized() {
+        let program = r#"concat(normalize("nfd", "\u{E9}"), normalize("nfc", "e\u{301}"), normalize("nfkc", "\u{FB01}"))"#;
+        test_program_success(1, program, "e\u{301}\u{E9}fi");
+    }
+
+    #[test]
+    fn normalize_returns_error_for_invalid_form() {
+        assert_runtime_error(r#"normalize("nfx", "foo")"#, "Invalid normalization form: 'nfx'");
+    }
+
+    #[test]
+    fn zero_width_characters_are_inserted() {
+        test_program_success(1, r#"insert_zero_width(0.0, "abc")"#, "abc");
+        let output = run_program(1, r#"insert_zero_width(1.0, "abc")"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(7, output.chars().count());
+        let visible: String = output.chars().filter(|c| c.is_ascii()).collect();
+        assert_eq!("abc", visible);
+    }
+
+    #[test]
+    fn homoglyphs_replace_latin_characters() {
+        test_program_success(1, r#"homoglyphs(0.0, "paypal")"#, "paypal");
+        let output = run_program(1, r#"homoglyphs(1.0, "paypal")"#).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(6, output.chars().count());
+        assert!(output.chars().all(|c| !c.is_ascii()), "actual: '{}'", output);
+    }
+
+    #[test]
+    fn strings_are_converted_to_fullwidth_and_halfwidth() {
+        let program = r#"concat(to_fullwidth("Hi 42!"), "|", to_halfwidth("\u{FF28}\u{FF49}\u{3000}\u{FF14}\u{FF12}\u{FF01}"))"#;
+        test_program_success(1, program, "\u{FF28}\u{FF49}\u{3000}\u{FF14}\u{FF12}\u{FF01}|Hi 42!");
+    }
+}
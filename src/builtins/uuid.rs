@@ -0,0 +1,344 @@
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynBinFun, DynStringFun, DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Write;
+use std::rc::Rc;
+
+const UUID_LEN: usize = 16;
+const MAX_TIMESTAMP_MILLIS: u64 = (1 << 48) - 1;
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Debug)]
+enum UuidKind {
+    V4,
+    V7(DynUintFun),
+    Ulid(DynUintFun),
+    Nil,
+    Max,
+}
+
+#[derive(Debug)]
+struct UuidGen {
+    kind: UuidKind,
+}
+
+fn gen_timestamp(timestamp_gen: &DynUintFun, ctx: &mut ProgramContext) -> Result<u64, Error> {
+    let timestamp = timestamp_gen.gen_value(ctx)?;
+    if timestamp > MAX_TIMESTAMP_MILLIS {
+        bail!(
+            "Invalid timestamp: {}, the timestamp must fit into 48 bits (max: {})",
+            timestamp,
+            MAX_TIMESTAMP_MILLIS
+        );
+    }
+    Ok(timestamp)
+}
+
+fn fill_random(ctx: &mut ProgramContext, bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        *byte = ctx.gen_value();
+    }
+}
+
+fn write_timestamp(timestamp: u64, bytes: &mut [u8]) {
+    for (i, byte) in bytes[0..6].iter_mut().enumerate() {
+        *byte = (timestamp >> (40 - i * 8)) as u8;
+    }
+}
+
+fn set_version_and_variant(version: u8, bytes: &mut [u8]) {
+    bytes[6] = (bytes[6] & 0x0F) | (version << 4);
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+}
+
+impl UuidGen {
+    fn gen_uuid(&self, ctx: &mut ProgramContext) -> Result<[u8; UUID_LEN], Error> {
+        let mut bytes = [0u8; UUID_LEN];
+        match self.kind {
+            UuidKind::V4 => {
+                fill_random(ctx, &mut bytes[..]);
+                set_version_and_variant(4, &mut bytes[..]);
+            }
+            UuidKind::V7(ref timestamp_gen) => {
+                let timestamp = gen_timestamp(timestamp_gen, ctx)?;
+                write_timestamp(timestamp, &mut bytes[..]);
+                fill_random(ctx, &mut bytes[6..]);
+                set_version_and_variant(7, &mut bytes[..]);
+            }
+            UuidKind::Ulid(ref timestamp_gen) => {
+                let timestamp = gen_timestamp(timestamp_gen, ctx)?;
+                write_timestamp(timestamp, &mut bytes[..]);
+                fill_random(ctx, &mut bytes[6..]);
+            }
+            UuidKind::Nil => {}
+            UuidKind::Max => {
+                bytes = [0xFF; UUID_LEN];
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+impl RunnableFunction<Vec<u8>> for UuidGen {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        self.gen_uuid(ctx).map(|bytes| bytes.to_vec())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let bytes = self.gen_uuid(ctx)?;
+        out.write_bytes(&bytes[..])
+    }
+}
+
+fn require_uuid_length(bytes: &[u8]) -> Result<(), Error> {
+    if bytes.len() != UUID_LEN {
+        bail!(
+            "Invalid binary value with length {}, a UUID or ULID must be exactly {} bytes",
+            bytes.len(),
+            UUID_LEN
+        );
+    }
+    Ok(())
+}
+
+fn format_uuid(format: &str, bytes: &[u8]) -> Result<String, Error> {
+    require_uuid_length(bytes)?;
+    let mut hyphenated = String::with_capacity(36);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            hyphenated.push('-');
+        }
+        write!(hyphenated, "{:02x}", byte)?;
+    }
+
+    let formatted = match format {
+        "canonical" => hyphenated,
+        "uppercase" => hyphenated.to_uppercase(),
+        "braced" => format!("{{{}}}", hyphenated),
+        "urn" => format!("urn:uuid:{}", hyphenated),
+        other => bail!(
+            "Invalid uuid format: '{}', must be one of 'canonical', 'uppercase', 'braced', or 'urn'",
+            other
+        ),
+    };
+    Ok(formatted)
+}
+
+fn format_ulid(bytes: &[u8]) -> Result<String, Error> {
+    require_uuid_length(bytes)?;
+    let value = bytes
+        .iter()
+        .fold(0u128, |acc, byte| (acc << 8) | u128::from(*byte));
+    // 26 characters of 5 bits each is 130 bits, so the first character only ever encodes the top 3 bits
+    let encoded = (0..26)
+        .rev()
+        .map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect();
+    Ok(encoded)
+}
+
+#[derive(Debug)]
+struct UuidString {
+    format: DynStringFun,
+    uuid: DynBinFun,
+}
+
+impl RunnableFunction<IString> for UuidString {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let format = self.format.gen_value(ctx)?;
+        let bytes = self.uuid.gen_value(ctx)?;
+        format_uuid(&format, bytes.as_slice()).map(Into::into)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+#[derive(Debug)]
+struct UlidString {
+    ulid: DynBinFun,
+}
+
+impl RunnableFunction<IString> for UlidString {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let bytes = self.ulid.gen_value(ctx)?;
+        format_ulid(bytes.as_slice()).map(Into::into)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const TIMESTAMP_PARAM: &str = "unix_millis";
+const FORMAT_PARAM: &str = "format";
+const UUID_PARAM: &str = "uuid";
+const ULID_PARAM: &str = "ulid";
+
+fn uuid_gen(kind: UuidKind) -> CreateFunctionResult {
+    Ok(AnyFunction::Bin(Rc::new(UuidGen { kind })))
+}
+
+fn create_uuid_v4(_: Arguments) -> CreateFunctionResult {
+    uuid_gen(UuidKind::V4)
+}
+
+fn create_uuid_v7(args: Arguments) -> CreateFunctionResult {
+    let timestamp = args.required_arg(TIMESTAMP_PARAM, 0, AnyFunction::require_uint)?;
+    uuid_gen(UuidKind::V7(timestamp))
+}
+
+fn create_ulid(args: Arguments) -> CreateFunctionResult {
+    let timestamp = args.required_arg(TIMESTAMP_PARAM, 0, AnyFunction::require_uint)?;
+    uuid_gen(UuidKind::Ulid(timestamp))
+}
+
+fn create_nil_uuid(_: Arguments) -> CreateFunctionResult {
+    uuid_gen(UuidKind::Nil)
+}
+
+fn create_max_uuid(_: Arguments) -> CreateFunctionResult {
+    uuid_gen(UuidKind::Max)
+}
+
+fn create_uuid_string(args: Arguments) -> CreateFunctionResult {
+    let (format, uuid) = args.require_2_args(
+        FORMAT_PARAM,
+        AnyFunction::require_string,
+        UUID_PARAM,
+        AnyFunction::require_bin,
+    )?;
+    Ok(AnyFunction::String(Rc::new(UuidString { format, uuid })))
+}
+
+fn create_ulid_string(args: Arguments) -> CreateFunctionResult {
+    let ulid = args.required_arg(ULID_PARAM, 0, AnyFunction::require_bin)?;
+    Ok(AnyFunction::String(Rc::new(UlidString { ulid })))
+}
+
+pub const UUID_V4_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "uuid_v4_bin",
+    description: "generates a random version 4 UUID as 16 raw bytes",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_uuid_v4,
+};
+
+pub const UUID_V7_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "uuid_v7_bin",
+    description: "generates a version 7 UUID as 16 raw bytes, using the given unix timestamp in milliseconds and random bits for the remainder",
+    arguments: &[(TIMESTAMP_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_uuid_v7,
+};
+
+pub const ULID_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ulid_bin",
+    description: "generates a ULID as 16 raw bytes, using the given unix timestamp in milliseconds and 80 random bits",
+    arguments: &[(TIMESTAMP_PARAM, GenType::Uint)],
+    variadic: false,
+    create_fn: &create_ulid,
+};
+
+pub const NIL_UUID_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "nil_uuid_bin",
+    description: "always returns the nil UUID, which is 16 bytes that are all 0x00",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_nil_uuid,
+};
+
+pub const MAX_UUID_BIN_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "max_uuid_bin",
+    description: "always returns the max UUID, which is 16 bytes that are all 0xFF",
+    arguments: &[],
+    variadic: false,
+    create_fn: &create_max_uuid,
+};
+
+pub const UUID_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "uuid_string",
+    description: "formats 16 bytes as a UUID string. The format must be one of 'canonical', 'uppercase', 'braced', or 'urn'",
+    arguments: &[(FORMAT_PARAM, GenType::String), (UUID_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_uuid_string,
+};
+
+pub const ULID_STRING_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "ulid_string",
+    description: "formats 16 bytes as a 26 character ULID string using Crockford's base32 alphabet",
+    arguments: &[(ULID_PARAM, GenType::Bin)],
+    variadic: false,
+    create_fn: &create_ulid_string,
+};
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{
+        assert_bin_output_is_expected, assert_runtime_error, run_program, test_program_success,
+    };
+    use regex::Regex;
+
+    #[test]
+    fn uuid_v4_has_version_and_variant_bits_set() {
+        let output = run_program(20, r#"trailing_newline(uuid_v4())"#).expect("failed to run program");
+        let output = String::from_utf8(output).unwrap();
+        let pattern =
+            Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$").unwrap();
+        for line in output.lines() {
+            assert!(pattern.is_match(line), "invalid uuid: '{}'", line);
+        }
+    }
+
+    #[test]
+    fn uuid_v7_starts_with_the_timestamp() {
+        let output = run_program(1, r#"uuid_v7(1538352000000)"#).expect("failed to run program");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("01662cec-ac00-7"), "actual: '{}'", output);
+    }
+
+    #[test]
+    fn uuid_v7_returns_error_when_timestamp_is_too_large() {
+        assert_runtime_error(r#"uuid_v7(281474976710656)"#, "must fit into 48 bits");
+    }
+
+    #[test]
+    fn nil_and_max_uuids_are_formatted() {
+        let program = r#"concat(nil_uuid(), " ", uuid_string("braced", max_uuid_bin()))"#;
+        let expected =
+            "00000000-0000-0000-0000-000000000000 {ffffffff-ffff-ffff-ffff-ffffffffffff}";
+        test_program_success(1, program, expected);
+    }
+
+    #[test]
+    fn uuid_is_formatted_as_uppercase_and_urn() {
+        let program = r#"
+            def id() = [0x12, 0x3e, 0x45, 0x67, 0xe8, 0x9b, 0x12, 0xd3, 0xa4, 0x56, 0x42, 0x66, 0x14, 0x17, 0x40, 0x00];
+            concat(uuid_string("uppercase", id()), " ", uuid_string("urn", id()))
+        "#;
+        let expected =
+            "123E4567-E89B-12D3-A456-426614174000 urn:uuid:123e4567-e89b-12d3-a456-426614174000";
+        test_program_success(1, program, expected);
+    }
+
+    #[test]
+    fn ulid_is_formatted_as_crockford_base32() {
+        let program = r#"ulid_string(max_uuid_bin())"#;
+        test_program_success(1, program, "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+    }
+
+    #[test]
+    fn ulid_bin_starts_with_big_endian_timestamp() {
+        let output = run_program(1, r#"ulid_bin(0x010203040506)"#).expect("failed to run program");
+        assert_eq!(&output[0..6], &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(output.len(), 16);
+    }
+
+    #[test]
+    fn max_uuid_bin_is_all_ones() {
+        assert_bin_output_is_expected("max_uuid_bin()", &[0xFF; 16]);
+    }
+}
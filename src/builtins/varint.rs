@@ -0,0 +1,346 @@
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput, DynFun,
+    DynUintFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// appends the value as unsigned LEB128, which is also the encoding used for protobuf varints
+pub(crate) fn write_uleb128(mut value: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return Ok(());
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn write_sleb128(mut value: i64, out: &mut Vec<u8>) -> Result<(), Error> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let sign_bit_clear = byte & 0x40 == 0;
+        if (value == 0 && sign_bit_clear) || (value == -1 && !sign_bit_clear) {
+            out.push(byte);
+            return Ok(());
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// protobuf encodes negative int32 and int64 values as their 64 bit two's complement, which always takes 10 bytes
+pub(crate) fn write_protobuf_int(value: i64, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_uleb128(value as u64, out)
+}
+
+pub(crate) fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub(crate) fn write_zigzag_varint(value: i64, out: &mut Vec<u8>) -> Result<(), Error> {
+    write_uleb128(zigzag(value), out)
+}
+
+const QUIC_VARINT_MAX: u64 = (1 << 62) - 1;
+
+fn quic_varint_len(value: u64) -> Result<usize, Error> {
+    match value {
+        0..=63 => Ok(1),
+        64..=16383 => Ok(2),
+        16384..=1_073_741_823 => Ok(4),
+        1_073_741_824..=QUIC_VARINT_MAX => Ok(8),
+        _ => bail!(
+            "The value {} is too large for a QUIC varint, the maximum is {}",
+            value,
+            QUIC_VARINT_MAX
+        ),
+    }
+}
+
+/// Appends a QUIC variable-length integer (RFC 9000) using the given number of bytes. QUIC allows encodings that
+/// are longer than necessary, so any length that can hold the value is valid.
+fn write_quic_varint_with_len(len: usize, value: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    let prefix: u8 = match len {
+        1 => 0b00,
+        2 => 0b01,
+        4 => 0b10,
+        8 => 0b11,
+        other => bail!(
+            "Invalid QUIC varint length: {} bytes, must be one of 1, 2, 4, or 8",
+            other
+        ),
+    };
+    if quic_varint_len(value)? > len {
+        bail!(
+            "The value {} is too large for a {} byte QUIC varint",
+            value,
+            len
+        );
+    }
+    for i in (0..len).rev() {
+        let mut byte = (value >> (i * 8)) as u8;
+        if i == len - 1 {
+            byte |= prefix << 6;
+        }
+        out.push(byte);
+    }
+    Ok(())
+}
+
+pub(crate) fn write_quic_varint(value: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    let len = quic_varint_len(value)?;
+    write_quic_varint_with_len(len, value, out)
+}
+
+fn min_bytes(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize + 7) / 8
+}
+
+/// Appends an ASN.1 BER length using the long form with the given number of length bytes. DER requires the
+/// shortest possible encoding, but BER allows any number of bytes that can hold the value.
+fn write_ber_long_length(len: usize, value: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    if len == 0 || len > 8 {
+        bail!(
+            "Invalid BER length size: {} bytes, must be between 1 and 8",
+            len
+        );
+    }
+    if min_bytes(value) > len {
+        bail!("The length {} does not fit in {} bytes", value, len);
+    }
+    out.push(0x80 | len as u8);
+    for i in (0..len).rev() {
+        out.push((value >> (i * 8)) as u8);
+    }
+    Ok(())
+}
+
+/// appends the shortest encoding of an ASN.1 BER/DER definite length
+pub(crate) fn write_ber_length(value: u64, out: &mut Vec<u8>) -> Result<(), Error> {
+    if value < 0x80 {
+        out.push(value as u8);
+        Ok(())
+    } else {
+        write_ber_long_length(min_bytes(value), value, out)
+    }
+}
+
+#[derive(Debug)]
+struct VarintEncoder<T> {
+    value: DynFun<T>,
+    encode: fn(T, &mut Vec<u8>) -> Result<(), Error>,
+}
+
+impl<T: Debug> RunnableFunction<Vec<u8>> for VarintEncoder<T> {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let value = self.value.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(10);
+        (self.encode)(value, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+#[derive(Debug)]
+struct SizedVarintEncoder {
+    len: DynUintFun,
+    value: DynUintFun,
+    encode: fn(usize, u64, &mut Vec<u8>) -> Result<(), Error>,
+}
+
+impl RunnableFunction<Vec<u8>> for SizedVarintEncoder {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<Vec<u8>, Error> {
+        let len = self.len.gen_value(ctx)? as usize;
+        let value = self.value.gen_value(ctx)?;
+        let mut buffer = Vec::with_capacity(10);
+        (self.encode)(len, value, &mut buffer)?;
+        Ok(buffer)
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let buffer = self.gen_value(ctx)?;
+        out.write_bytes(&buffer)
+    }
+}
+
+const VALUE_PARAM: &str = "value";
+const LENGTH_BYTES_PARAM: &str = "length_bytes";
+
+macro_rules! make_varint_builtin {
+    ($proto_name:ident, $fun_name:expr, $gen_type:expr, $convert_input:path, $encode_fn:path, $description:expr) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let value = args.required_arg(VALUE_PARAM, 0, $convert_input)?;
+                Ok(AnyFunction::Bin(Rc::new(VarintEncoder {
+                    value,
+                    encode: $encode_fn,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: $description,
+                arguments: &[(VALUE_PARAM, $gen_type)],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+macro_rules! make_sized_varint_builtin {
+    ($proto_name:ident, $fun_name:expr, $encode_fn:path, $description:expr) => {
+        pub const $proto_name: &BuiltinFunctionPrototype = {
+            fn create(args: Arguments) -> CreateFunctionResult {
+                let (len, value) = args.require_2_args(
+                    LENGTH_BYTES_PARAM,
+                    AnyFunction::require_uint,
+                    VALUE_PARAM,
+                    AnyFunction::require_uint,
+                )?;
+                Ok(AnyFunction::Bin(Rc::new(SizedVarintEncoder {
+                    len,
+                    value,
+                    encode: $encode_fn,
+                })))
+            }
+
+            &BuiltinFunctionPrototype {
+                function_name: $fun_name,
+                description: $description,
+                arguments: &[
+                    (LENGTH_BYTES_PARAM, GenType::Uint),
+                    (VALUE_PARAM, GenType::Uint),
+                ],
+                variadic: false,
+                create_fn: &create,
+            }
+        };
+    };
+}
+
+make_varint_builtin!(
+    ULEB128_BUILTIN,
+    "uleb128",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    write_uleb128,
+    "encodes the value as unsigned LEB128"
+);
+make_varint_builtin!(
+    SLEB128_BUILTIN,
+    "sleb128",
+    GenType::Int,
+    AnyFunction::require_int,
+    write_sleb128,
+    "encodes the value as signed LEB128"
+);
+make_varint_builtin!(
+    UINT_PROTOBUF_VARINT_BUILTIN,
+    "protobuf_varint",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    write_uleb128,
+    "encodes the value as a protobuf varint, as used for uint32 and uint64 fields"
+);
+make_varint_builtin!(
+    INT_PROTOBUF_VARINT_BUILTIN,
+    "protobuf_varint",
+    GenType::Int,
+    AnyFunction::require_int,
+    write_protobuf_int,
+    "encodes the value as a protobuf varint, as used for int32 and int64 fields. Negative numbers always take 10 bytes"
+);
+make_varint_builtin!(
+    ZIGZAG_VARINT_BUILTIN,
+    "zigzag_varint",
+    GenType::Int,
+    AnyFunction::require_int,
+    write_zigzag_varint,
+    "encodes the value as a zigzag varint, as used for protobuf sint32 and sint64 fields"
+);
+make_varint_builtin!(
+    QUIC_VARINT_BUILTIN,
+    "quic_varint",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    write_quic_varint,
+    "encodes the value as the shortest QUIC/HTTP3 variable-length integer. Returns an error if the value is greater than 2^62 - 1"
+);
+make_sized_varint_builtin!(
+    SIZED_QUIC_VARINT_BUILTIN,
+    "quic_varint",
+    write_quic_varint_with_len,
+    "encodes the value as a QUIC/HTTP3 variable-length integer using the given number of bytes (1, 2, 4, or 8), which may be longer than necessary"
+);
+make_varint_builtin!(
+    BER_LENGTH_BUILTIN,
+    "ber_length",
+    GenType::Uint,
+    AnyFunction::require_uint,
+    write_ber_length,
+    "encodes the value as an ASN.1 BER definite length, using the short form for values less than 128 and the shortest long form otherwise"
+);
+make_sized_varint_builtin!(
+    SIZED_BER_LENGTH_BUILTIN,
+    "ber_length",
+    write_ber_long_length,
+    "encodes the value as an ASN.1 BER definite length, always using the long form with the given number of length bytes (between 1 and 8)"
+);
+
+#[cfg(test)]
+mod test {
+    use crate::fun_test::{assert_bin_output_is_expected, assert_runtime_error};
+
+    #[test]
+    fn leb128_values_are_encoded() {
+        assert_bin_output_is_expected("uleb128(0)", &[0x00]);
+        assert_bin_output_is_expected("uleb128(624485)", &[0xE5, 0x8E, 0x26]);
+        assert_bin_output_is_expected("sleb128(-123456)", &[0xC0, 0xBB, 0x78]);
+        assert_bin_output_is_expected("sleb128(+63)", &[0x3F]);
+        assert_bin_output_is_expected("sleb128(+64)", &[0xC0, 0x00]);
+        assert_bin_output_is_expected("sleb128(-1)", &[0x7F]);
+    }
+
+    #[test]
+    fn protobuf_varints_are_encoded() {
+        assert_bin_output_is_expected("protobuf_varint(300)", &[0xAC, 0x02]);
+        assert_bin_output_is_expected(
+            "protobuf_varint(-1)",
+            &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+        );
+        assert_bin_output_is_expected("zigzag_varint(-1)", &[0x01]);
+        assert_bin_output_is_expected("zigzag_varint(+1)", &[0x02]);
+        assert_bin_output_is_expected("zigzag_varint(-64)", &[0x7F]);
+    }
+
+    #[test]
+    fn quic_varints_are_encoded() {
+        // examples from RFC 9000 appendix A.1
+        assert_bin_output_is_expected("quic_varint(37)", &[0x25]);
+        assert_bin_output_is_expected("quic_varint(15293)", &[0x7B, 0xBD]);
+        assert_bin_output_is_expected("quic_varint(494878333)", &[0x9D, 0x7F, 0x3E, 0x7D]);
+        assert_bin_output_is_expected(
+            "quic_varint(151288809941952652)",
+            &[0xC2, 0x19, 0x7C, 0x5E, 0xFF, 0x14, 0xE8, 0x8C],
+        );
+        assert_bin_output_is_expected("quic_varint(2, 37)", &[0x40, 0x25]);
+        assert_runtime_error("quic_varint(4611686018427387904)", "too large for a QUIC varint");
+        assert_runtime_error("quic_varint(1, 64)", "too large for a 1 byte QUIC varint");
+    }
+
+    #[test]
+    fn ber_lengths_are_encoded() {
+        assert_bin_output_is_expected("ber_length(127)", &[0x7F]);
+        assert_bin_output_is_expected("ber_length(128)", &[0x81, 0x80]);
+        assert_bin_output_is_expected("ber_length(256)", &[0x82, 0x01, 0x00]);
+        assert_bin_output_is_expected("ber_length(3, 5)", &[0x83, 0x00, 0x00, 0x05]);
+        assert_runtime_error("ber_length(1, 256)", "The length 256 does not fit in 1 bytes");
+    }
+}
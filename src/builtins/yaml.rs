@@ -0,0 +1,591 @@
+use crate::json::JsonValue;
+use crate::IString;
+use crate::{
+    AnyFunction, Arguments, BuiltinFunctionPrototype, CreateFunctionResult, DataGenOutput,
+    DynStringFun, GenType, ProgramContext, RunnableFunction,
+};
+use failure::Error;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Plain scalars that would be read as something other than a string by either yaml 1.1 or 1.2 parsers
+const RESERVED_PLAIN_SCALARS: &[&str] = &[
+    "null", "~", "true", "false", "yes", "no", "on", "off", "y", "n", "=", "<<",
+];
+
+fn is_printable(c: char) -> bool {
+    match c as u32 {
+        0x9 | 0xA | 0xD | 0x20..=0x7E | 0x85 | 0xA0..=0xD7FF | 0x10000..=0x10FFFF => true,
+        0xE000..=0xFFFD => c != '\u{FEFF}',
+        _ => false,
+    }
+}
+
+/// Yaml 1.1 also treats the unicode line and paragraph separators as line breaks
+fn is_line_break(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+fn can_be_plain(value: &str) -> bool {
+    let first = match value.chars().next() {
+        Some(c) => c,
+        None => return false,
+    };
+    let starts_like_a_number = first.is_ascii_digit() || first == '+' || first == '.';
+    let lowercase = value.to_lowercase();
+    !starts_like_a_number
+        && !"-?:,[]{}#&*!|>'\"%@`".contains(first)
+        && !value.starts_with(' ')
+        && !value.ends_with(' ')
+        && value
+            .chars()
+            .all(|c| is_printable(c) && !is_line_break(c) && !":#,[]{}\t".contains(c))
+        && !RESERVED_PLAIN_SCALARS.contains(&lowercase.as_str())
+}
+
+fn can_be_single_quoted(value: &str) -> bool {
+    value.chars().all(|c| is_printable(c) && !is_line_break(c))
+}
+
+/// Whether the value can be written as a literal block scalar. The unicode line breaks would be normalized by the parser
+fn can_be_literal(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| is_printable(c) && (c == '\n' || !is_line_break(c)))
+}
+
+/// Folded scalars are only used for single lines, which may get split at spaces
+fn can_be_folded(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with(' ')
+        && !value.starts_with('\t')
+        && value.chars().all(|c| is_printable(c) && !is_line_break(c))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Position {
+    /// The root node of the document, which may follow a `---` marker
+    Root,
+    /// The value of an implicit mapping key, which follows the colon
+    AfterKey,
+    /// A sequence entry, explicit key, or explicit value, which follows the indicator and may start a compact
+    /// collection on the same line
+    AfterIndicator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    Literal,
+    Folded,
+}
+
+struct YamlWriter<'a> {
+    ctx: &'a mut ProgramContext,
+    out: String,
+    indent_width: usize,
+    anchored: Vec<(JsonValue, String)>,
+}
+
+impl<'a> YamlWriter<'a> {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn separate(&mut self) {
+        if !self.at_line_start() {
+            self.out.push(' ');
+        }
+    }
+
+    fn indent(&mut self, indent: usize) {
+        for _ in 0..indent {
+            self.out.push(' ');
+        }
+    }
+
+    fn end_line(&mut self) {
+        if !self.at_line_start() {
+            self.out.push('\n');
+        }
+    }
+
+    fn maybe_comment(&mut self, indent: usize) {
+        if self.ctx.gen_chance(0.05) {
+            self.indent(indent);
+            let words = self.ctx.choose(&["note", "TODO: check this", "generated", ""]);
+            self.out.push('#');
+            if !words.is_empty() {
+                self.out.push(' ');
+                self.out.push_str(words);
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn document(&mut self, value: &JsonValue) {
+        let start_marker = self.ctx.gen_chance(0.5);
+        if start_marker {
+            if self.ctx.gen_chance(0.3) {
+                self.out.push_str("%YAML 1.2\n");
+            }
+            self.out.push_str("---");
+        }
+        self.node(value, 0, Position::Root);
+        self.end_line();
+        if self.ctx.gen_chance(0.2) {
+            self.out.push_str("...\n");
+        }
+    }
+
+    /// Writes any node in block context, followed by a line break. The parent is the column of the key or indicator
+    /// that the node belongs to
+    fn node(&mut self, value: &JsonValue, parent: usize, position: Position) {
+        if position == Position::Root {
+            self.node_content(value, parent, position, false);
+            return;
+        }
+        if self.write_alias(value) {
+            self.out.push('\n');
+            return;
+        }
+        let anchor = self.maybe_anchor();
+        self.node_content(value, parent, position, anchor.is_some());
+        if let Some(anchor) = anchor {
+            self.anchored.push((value.clone(), anchor));
+        }
+    }
+
+    fn write_alias(&mut self, value: &JsonValue) -> bool {
+        let found = self
+            .anchored
+            .iter()
+            .find(|(anchored, _)| anchored == value)
+            .map(|(_, name)| name.clone());
+        match found {
+            Some(name) if self.ctx.gen_chance(0.5) => {
+                self.separate();
+                self.out.push('*');
+                self.out.push_str(&name);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn maybe_anchor(&mut self) -> Option<String> {
+        if self.ctx.gen_chance(0.1) {
+            let name = format!("{}{}", self.ctx.choose(&["a", "anchor", "ref_", "x-"]), self.anchored.len() + 1);
+            self.separate();
+            self.out.push('&');
+            self.out.push_str(&name);
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    fn node_content(&mut self, value: &JsonValue, parent: usize, position: Position, has_anchor: bool) {
+        let use_block = value.is_container() && self.ctx.gen_chance(0.8);
+        match value {
+            JsonValue::Array(items) if use_block && !items.is_empty() => {
+                let compact = position == Position::AfterIndicator && !has_anchor && self.ctx.gen_chance(0.5);
+                let indent = match position {
+                    Position::Root => 0,
+                    Position::AfterIndicator if compact => parent + 2,
+                    // a sequence may be at the same indentation as the key that it's the value of
+                    Position::AfterKey if self.ctx.gen_chance(0.5) => parent,
+                    _ => parent + self.indent_width,
+                };
+                if compact {
+                    self.out.push(' ');
+                } else {
+                    self.end_line();
+                }
+                self.block_sequence(items, indent, compact);
+            }
+            JsonValue::Object(members) if use_block && !members.is_empty() => {
+                let compact = position == Position::AfterIndicator && !has_anchor && self.ctx.gen_chance(0.5);
+                let indent = match position {
+                    Position::Root => 0,
+                    Position::AfterIndicator if compact => parent + 2,
+                    _ => parent + self.indent_width,
+                };
+                if compact {
+                    self.out.push(' ');
+                } else {
+                    self.end_line();
+                }
+                self.block_mapping(members, indent, compact);
+            }
+            JsonValue::String(string) => {
+                self.block_string(string, parent, position);
+            }
+            JsonValue::Null if position != Position::Root && self.ctx.gen_chance(0.2) => {
+                // an empty node is also null
+                self.out.push('\n');
+            }
+            other => {
+                self.separate();
+                self.flow_node(other);
+                self.out.push('\n');
+            }
+        }
+    }
+
+    fn block_sequence(&mut self, items: &[JsonValue], indent: usize, first_is_inline: bool) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 || !first_is_inline {
+                self.maybe_comment(indent);
+                self.indent(indent);
+            }
+            self.out.push('-');
+            self.node(item, indent, Position::AfterIndicator);
+        }
+    }
+
+    fn block_mapping(&mut self, members: &[(String, JsonValue)], indent: usize, first_is_inline: bool) {
+        for (i, (key, value)) in members.iter().enumerate() {
+            if i > 0 || !first_is_inline {
+                self.maybe_comment(indent);
+                self.indent(indent);
+            }
+            let implicit_key = self.implicit_key(key);
+            match implicit_key {
+                Some(ref key) if !self.ctx.gen_chance(0.05) => {
+                    self.out.push_str(key);
+                    self.out.push(':');
+                    self.node(value, indent, Position::AfterKey);
+                }
+                _ => {
+                    self.out.push('?');
+                    self.node(&JsonValue::String(key.clone()), indent, Position::AfterIndicator);
+                    self.indent(indent);
+                    self.out.push(':');
+                    self.node(value, indent, Position::AfterIndicator);
+                }
+            }
+        }
+    }
+
+    /// Returns the key formatted as a scalar that fits on a single line, or None if it's too long to be an implicit key
+    fn implicit_key(&mut self, key: &str) -> Option<String> {
+        let mut out = String::new();
+        self.flow_string(key, &mut out);
+        if out.chars().count() < 1024 {
+            Some(out)
+        } else {
+            None
+        }
+    }
+
+    fn block_string(&mut self, value: &str, parent: usize, position: Position) {
+        let mut styles = vec![ScalarStyle::DoubleQuoted];
+        if can_be_plain(value) {
+            styles.push(ScalarStyle::Plain);
+            styles.push(ScalarStyle::Plain);
+        }
+        if can_be_single_quoted(value) {
+            styles.push(ScalarStyle::SingleQuoted);
+        }
+        // the indentation indicator is relative to the parent node, which the root doesn't have
+        let needs_indicator = value.starts_with(' ') || value.starts_with('\n');
+        if can_be_literal(value) && !(needs_indicator && position == Position::Root) {
+            styles.push(ScalarStyle::Literal);
+        }
+        if can_be_folded(value) {
+            styles.push(ScalarStyle::Folded);
+        }
+        let style = self.ctx.choose(&styles);
+
+        self.separate();
+        let content_indent = parent + self.indent_width;
+        match style {
+            ScalarStyle::Literal => {
+                let body = value.trim_end_matches('\n');
+                let trailing_breaks = value.len() - body.len();
+                let chomping = match trailing_breaks {
+                    0 => "-",
+                    1 if !body.is_empty() => "",
+                    _ => "+",
+                };
+                self.out.push('|');
+                if needs_indicator {
+                    let _ = write!(self.out, "{}", self.indent_width);
+                }
+                self.out.push_str(chomping);
+                self.out.push('\n');
+                if !body.is_empty() {
+                    for line in body.split('\n') {
+                        if !line.is_empty() {
+                            self.indent(content_indent);
+                            self.out.push_str(line);
+                        }
+                        self.out.push('\n');
+                    }
+                }
+                let extra_breaks = if body.is_empty() {
+                    trailing_breaks
+                } else {
+                    trailing_breaks.saturating_sub(1)
+                };
+                for _ in 0..extra_breaks {
+                    self.out.push('\n');
+                }
+            }
+            ScalarStyle::Folded => {
+                self.out.push_str(">-\n");
+                self.indent(content_indent);
+                let chars: Vec<char> = value.chars().collect();
+                for (i, c) in chars.iter().enumerate() {
+                    // a single space between two other characters gets folded back into the line break
+                    let can_fold = *c == ' '
+                        && i > 0
+                        && !chars[i - 1].is_whitespace()
+                        && chars.get(i + 1).map(|next| !next.is_whitespace()).unwrap_or(false);
+                    if can_fold && self.ctx.gen_chance(0.3) {
+                        self.out.push('\n');
+                        self.indent(content_indent);
+                    } else {
+                        self.out.push(*c);
+                    }
+                }
+                self.out.push('\n');
+            }
+            other => {
+                let mut out = String::new();
+                self.write_inline_string(value, other, &mut out);
+                self.out.push_str(&out);
+                self.out.push('\n');
+            }
+        }
+    }
+
+    fn flow_string(&mut self, value: &str, out: &mut String) {
+        let mut styles = vec![ScalarStyle::DoubleQuoted];
+        if can_be_plain(value) {
+            styles.push(ScalarStyle::Plain);
+            styles.push(ScalarStyle::Plain);
+        }
+        if can_be_single_quoted(value) {
+            styles.push(ScalarStyle::SingleQuoted);
+        }
+        let style = self.ctx.choose(&styles);
+        self.write_inline_string(value, style, out);
+    }
+
+    fn write_inline_string(&mut self, value: &str, style: ScalarStyle, out: &mut String) {
+        match style {
+            ScalarStyle::Plain => out.push_str(value),
+            ScalarStyle::SingleQuoted => {
+                out.push('\'');
+                out.push_str(&value.replace('\'', "''"));
+                out.push('\'');
+            }
+            _ => self.write_double_quoted(value, out),
+        }
+    }
+
+    fn write_double_quoted(&mut self, value: &str, out: &mut String) {
+        out.push('"');
+        for c in value.chars() {
+            let must_escape = !is_printable(c) || is_line_break(c);
+            if !must_escape && !self.ctx.gen_chance(0.05) {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    c => out.push(c),
+                }
+                continue;
+            }
+            match c {
+                '\u{0}' => out.push_str("\\0"),
+                '\u{7}' => out.push_str("\\a"),
+                '\u{8}' => out.push_str("\\b"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\u{b}' => out.push_str("\\v"),
+                '\u{c}' => out.push_str("\\f"),
+                '\r' => out.push_str("\\r"),
+                '\u{1b}' => out.push_str("\\e"),
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\u{85}' => out.push_str("\\N"),
+                '\u{a0}' => out.push_str("\\_"),
+                '\u{2028}' => out.push_str("\\L"),
+                '\u{2029}' => out.push_str("\\P"),
+                c if (c as u32) < 0x100 => {
+                    let _ = write!(out, "\\x{:02X}", c as u32);
+                }
+                c if (c as u32) < 0x10000 => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                c => {
+                    let _ = write!(out, "\\U{:08X}", c as u32);
+                }
+            }
+        }
+        out.push('"');
+    }
+
+    /// Writes a node in flow style, which is also used for all scalars except strings in block context
+    fn flow_node(&mut self, value: &JsonValue) {
+        let mut out = String::new();
+        self.flow_value(value, &mut out);
+        self.out.push_str(&out);
+    }
+
+    fn flow_value(&mut self, value: &JsonValue, out: &mut String) {
+        match value {
+            // the capitalized forms are also allowed by the yaml 1.2 core schema, but many parsers don't accept them
+            JsonValue::Null => out.push_str(self.ctx.choose(&["null", "~"])),
+            JsonValue::Boolean(true) => out.push_str("true"),
+            JsonValue::Boolean(false) => out.push_str("false"),
+            JsonValue::Number(number) => out.push_str(number),
+            JsonValue::String(string) => self.flow_string(string, out),
+            JsonValue::Array(items) => {
+                let (open, separator, close) = self.ctx.choose(&[("[", ", ", "]"), ("[ ", ", ", " ]"), ("[", ",", "]")]);
+                out.push_str(open);
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(separator);
+                    }
+                    self.flow_value(item, out);
+                }
+                out.push_str(close);
+            }
+            JsonValue::Object(members) => {
+                let (open, separator, close) = self.ctx.choose(&[("{", ", ", "}"), ("{ ", ", ", " }"), ("{", ",", "}")]);
+                out.push_str(open);
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(separator);
+                    }
+                    self.flow_string(key, out);
+                    out.push_str(self.ctx.choose(&[": ", " : "]));
+                    self.flow_value(value, out);
+                }
+                out.push_str(close);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ToYaml {
+    json: DynStringFun,
+}
+
+impl RunnableFunction<IString> for ToYaml {
+    fn gen_value(&self, ctx: &mut ProgramContext) -> Result<IString, Error> {
+        let json = self.json.gen_value(ctx)?;
+        let value = JsonValue::parse(&json)?;
+        let indent_width = ctx.choose(&[2, 2, 3, 4]);
+        let mut writer = YamlWriter {
+            ctx,
+            out: String::new(),
+            indent_width,
+            anchored: Vec::new(),
+        };
+        writer.document(&value);
+        Ok(writer.out.into())
+    }
+    fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
+        let value = self.gen_value(ctx)?;
+        out.write(&value)
+    }
+}
+
+const JSON_PARAM: &str = "json";
+
+fn create_to_yaml(args: Arguments) -> CreateFunctionResult {
+    let json = args.required_arg(JSON_PARAM, 0, AnyFunction::require_string)?;
+    Ok(AnyFunction::String(Rc::new(ToYaml { json })))
+}
+
+pub const TO_YAML_BUILTIN: &BuiltinFunctionPrototype = &BuiltinFunctionPrototype {
+    function_name: "to_yaml",
+    description: "converts a json document into an equivalent yaml document, randomly choosing the style of every node: block or flow collections, plain, quoted, literal, or folded scalars, anchors and aliases for repeated values, comments, and document markers",
+    arguments: &[(JSON_PARAM, GenType::String)],
+    variadic: false,
+    create_fn: &create_to_yaml,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::run_program;
+    use yaml_rust2::{Yaml, YamlLoader};
+
+    fn number_value(number: &str) -> f64 {
+        number.parse().unwrap()
+    }
+
+    fn is_equivalent(json: &JsonValue, yaml: &Yaml) -> bool {
+        match (json, yaml) {
+            (JsonValue::Null, Yaml::Null) => true,
+            (JsonValue::Boolean(a), Yaml::Boolean(b)) => a == b,
+            (JsonValue::Number(a), Yaml::Integer(b)) => number_value(a) == *b as f64,
+            (JsonValue::Number(a), Yaml::Real(b)) => number_value(a) == number_value(b),
+            (JsonValue::String(a), Yaml::String(b)) => a == b,
+            (JsonValue::Array(a), Yaml::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| is_equivalent(a, b))
+            }
+            (JsonValue::Object(a), Yaml::Hash(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| {
+                        b.get(&Yaml::String(key.clone()))
+                            .map(|other| is_equivalent(value, other))
+                            .unwrap_or(false)
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    fn assert_equivalent_documents(json_program: &str) {
+        let program = format!(r#"{} {{ json -> concat(json, "\u{{0}}", to_yaml(json), "\u{{0}}") }}"#, json_program);
+        let output = String::from_utf8(run_program(200, &program).unwrap()).unwrap();
+        let parts: Vec<&str> = output.split_terminator('\u{0}').collect();
+        for pair in parts.chunks(2) {
+            let json = JsonValue::parse(pair[0]).unwrap();
+            let yaml = YamlLoader::load_from_str(pair[1])
+                .unwrap_or_else(|err| panic!("invalid yaml: {}, document:\n{}", err, pair[1]));
+            assert_eq!(1, yaml.len(), "expected exactly one document in:\n{}", pair[1]);
+            assert!(
+                is_equivalent(&json, &yaml[0]),
+                "yaml is not equivalent to json: {}, yaml document:\n{}\nparsed: {:?}",
+                pair[0],
+                pair[1],
+                yaml[0]
+            );
+        }
+    }
+
+    #[test]
+    fn yaml_documents_are_equivalent_to_the_json() {
+        assert_equivalent_documents("json_document()");
+        assert_equivalent_documents("json_value()");
+        assert_equivalent_documents(
+            r#"select("[\"\\n\", \" a\\n\\n\", \"a\\n \", \"\\n\\nb\", \"a  b c\", \"\\t\"]", "{\"\\n\": {\" \": \"\\n\"}}")"#,
+        );
+    }
+
+    #[test]
+    fn yaml_strings_use_many_styles() {
+        let program = r#"to_yaml("[\"multiple\\nlines\\n\", \"some words that can be folded\", \"plain\"]")"#;
+        let output = String::from_utf8(run_program(100, program).unwrap()).unwrap();
+        for style in ["|", ">-", "'plain'", "\"plain\"", "- plain"].iter() {
+            assert!(output.contains(style), "expected output to contain '{}'", style);
+        }
+    }
+
+    #[test]
+    fn strings_that_look_like_other_types_are_quoted() {
+        for value in ["true", "No", "~", "null", "1.5", "-1", ".inf", "- a", "a: b", "a #b", "", " a"].iter() {
+            assert!(!can_be_plain(value), "'{}' should not be plain", value);
+        }
+    }
+}
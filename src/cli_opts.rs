@@ -136,9 +136,22 @@ pub struct HelpOptions {
     pub module_name: Option<String>,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct InferOptions {
+    /// Sample files to analyse, which must all have the same format. Files ending in `.csv` or `.tsv` are read as
+    /// delimited files with a header row, `.json`, `.jsonl`, or `.ndjson` files as one json value per line, and anything
+    /// else as plain text lines
+    #[structopt(parse(from_os_str), raw(required = "true"))]
+    pub files: Vec<PathBuf>,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum SubCommand {
     /// Print information on available functions
     #[structopt(name = "help")]
     Help(HelpOptions),
+
+    /// Print a starter dgen program that generates data like the given sample files
+    #[structopt(name = "infer")]
+    Infer(InferOptions),
 }
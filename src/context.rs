@@ -2,8 +2,10 @@ use rand::distributions::uniform::SampleUniform;
 use rand::distributions::{Distribution, Standard};
 use rand::prng::XorShiftRng;
 use rand::{Rng, SeedableRng, FromEntropy};
+use std::collections::HashMap;
 use std::fmt;
-use std::io;
+use std::fs::File;
+use std::io::{self, BufReader};
 use crate::verbosity::Verbosity;
 use crate::interpreter::SourceRef;
 use failure::Error;
@@ -15,8 +17,13 @@ pub struct ProgramContext {
     is_unwinding: bool,
     error_output: Box<io::Write>,
     error: Option<ProgramRuntimeError>,
+    pipes: HashMap<IString, BufReader<File>>,
+    stdin_is_source: bool,
 }
 
+/// paths that refer to stdin, which can't be read as a pipe when the program itself was read from stdin
+const STDIN_PATHS: &[&str] = &["-", "/dev/stdin", "/dev/fd/0", "/proc/self/fd/0"];
+
 impl ProgramContext {
     pub fn from_seed(seed: [u8; 16], verbosity: Verbosity) -> ProgramContext {
         let rng = XorShiftRng::from_seed(seed);
@@ -35,6 +42,8 @@ impl ProgramContext {
             is_unwinding: false,
             error_output: Box::new(io::stderr()),
             error: None,
+            pipes: HashMap::new(),
+            stdin_is_source: false,
         }
     }
 
@@ -55,6 +64,24 @@ impl ProgramContext {
         self.error.take()
     }
 
+    /// returns the reader for the named pipe at the given path, opening it the first time it's used. A pipe is shared
+    /// by every function in the program that reads from it, just like stdin is
+    pub(crate) fn pipe_reader(&mut self, path: &IString) -> Result<&mut BufReader<File>, Error> {
+        if self.stdin_is_source && STDIN_PATHS.contains(&&**path) {
+            bail!("Cannot read from '{}' because the program was read from stdin", path);
+        }
+        if !self.pipes.contains_key(path) {
+            let file = File::open(&**path)
+                .map_err(|err| format_err!("Failed to open '{}': {}", path, err))?;
+            self.pipes.insert(path.clone(), BufReader::new(file));
+        }
+        Ok(self.pipes.get_mut(path).unwrap())
+    }
+
+    /// called when the program was read from stdin, so that reading stdin again as a pipe returns an error
+    pub(crate) fn set_stdin_is_source(&mut self) {
+        self.stdin_is_source = true;
+    }
 
     #[allow(dead_code)]
     pub fn gen_value<T>(&mut self) -> T
@@ -104,6 +131,17 @@ impl ProgramContext {
         distribution.sample(&mut self.rng)
     }
 
+    /// returns true with the given probability, which should be between 0 and 1
+    pub fn gen_chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.gen_range_exclusive(0.0, 1.0) < probability
+    }
+
+    /// returns a randomly selected element of the slice, which must not be empty
+    pub fn choose<T: Copy>(&mut self, options: &[T]) -> T {
+        let index = self.gen_range_exclusive(0, options.len());
+        options[index]
+    }
+
 
     pub fn error_output(&mut self, verbosity: Verbosity) -> Option<ErrorOutput> {
         if self.verbosity >= verbosity {
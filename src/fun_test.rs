@@ -1,4 +1,5 @@
 use crate::interpreter::{Interpreter, UnreadSource};
+use crate::json::JsonValue;
 use crate::program::Runner;
 use crate::writer::DataGenOutput;
 use crate::ProgramContext;
@@ -64,7 +65,13 @@ fn declare_and_use_function_with_mapper() {
 
         concat(repeat_words(count()), repeat_words(count()))
     "#;
-    let expected = "2 : w6OqR\nw6OqR\n822CZ\n822CZ\n3 : gJ42f\ngJ42f\n1aT0X\n1aT0X\n";
+    // the number printed in each header must match the number of words and repetitions that follow it
+    let expected = concat!(
+        "2 : a66IL\na66IL\n79vom\n79vom\n",
+        "5 : ggJ42\nggJ42\nggJ42\nggJ42\nggJ42\nf1aT0\nf1aT0\nf1aT0\nf1aT0\nf1aT0\n",
+        "XOFku\nXOFku\nXOFku\nXOFku\nXOFku\n7YVk2\n7YVk2\n7YVk2\n7YVk2\n7YVk2\n",
+        "gRC6c\ngRC6c\ngRC6c\ngRC6c\ngRC6c\n"
+    );
     test_program_success(1, input, expected);
 }
 
@@ -94,6 +101,125 @@ fn mapping_a_mapped_function() {
     test_program_success(1, input, expected);
 }
 
+#[test]
+fn use_std_fake_email_function() {
+    use regex::Regex;
+
+    let results = run_program(50, "trailing_newline(email())").expect("Failed to run program");
+    let as_str = String::from_utf8(results).expect("program results were not valid utf8");
+    let pattern = Regex::new(r"^[^@\s]+@[a-z0-9.-]+$").unwrap();
+    for line in as_str.lines() {
+        assert!(pattern.is_match(line), "invalid email: '{}'", line);
+    }
+}
+
+#[test]
+fn use_std_fake_us_phone_number_function() {
+    use regex::Regex;
+
+    let results = run_program(50, "trailing_newline(us_phone_number())").expect("Failed to run program");
+    let as_str = String::from_utf8(results).expect("program results were not valid utf8");
+    let digits = Regex::new(r"[^0-9]").unwrap();
+    for line in as_str.lines() {
+        let just_digits = digits.replace_all(line, "");
+        assert!(just_digits.len() == 10 || just_digits.len() == 11, "invalid phone number: '{}'", line);
+    }
+}
+
+#[test]
+fn use_std_json_document_function() {
+    // a nul character can never appear unescaped in strict json, so it's safe to use as a separator
+    let results = run_program(100, "concat(json_document(), \"\\u{0}\")").expect("Failed to run program");
+    let as_str = String::from_utf8(results).expect("program results were not valid utf8");
+    for document in as_str.split_terminator('\u{0}') {
+        if let Err(message) = JsonValue::parse(document) {
+            panic!("invalid json: {}, document: '{}'", message, document);
+        }
+    }
+}
+
+#[test]
+fn std_json_values_are_valid_json() {
+    let functions = ["json_value()", "json_number()", "json_string()", "json_nested(2, json_scalar())"];
+    for function in functions.iter() {
+        let results = run_program(100, &format!("concat({}, \"\\u{{0}}\")", function)).unwrap();
+        let as_str = String::from_utf8(results).unwrap();
+        for value in as_str.split_terminator('\u{0}') {
+            if let Err(message) = JsonValue::parse(value) {
+                panic!("invalid json from {}: {}, value: '{}'", function, message, value);
+            }
+        }
+    }
+}
+
+#[test]
+fn std_json_containers_respect_max_size() {
+    test_program_success(3, "json_array(0, json_null())", "[][][]");
+    test_program_success(3, "json_object(0, json_null())", "{}{}{}");
+    assert!(JsonValue::parse("[1, 2,]").is_err());
+    assert!(JsonValue::parse("{'a': 1}").is_err());
+}
+
+#[test]
+fn use_std_csv_file_function() {
+    let input = r#"with_bom(csv_file(csv_row("rfc4180", "id", "note"), 2, csv_row("rfc4180", "1", "x,y")))"#;
+    test_program_success(1, input, "\u{FEFF}id,note\r\n1,\"x,y\"\r\n1,\"x,y\"\r\n");
+}
+
+fn run_xml_documents(program: &str) -> Vec<String> {
+    // a nul character is never allowed in xml, and none of the malformed documents use it either
+    let results = run_program(100, &format!("concat({}, \"\\u{{0}}\")", program)).expect("Failed to run program");
+    let as_str = String::from_utf8(results).expect("program results were not valid utf8");
+    as_str.split_terminator('\u{0}').map(|document| document.to_owned()).collect()
+}
+
+#[test]
+fn use_std_xml_document_function() {
+    for document in run_xml_documents("xml_document()") {
+        if let Err(err) = roxmltree::Document::parse(&document) {
+            panic!("invalid xml: {}, document: '{}'", err, document);
+        }
+    }
+}
+
+#[test]
+fn std_xml_malformed_documents_are_rejected() {
+    let functions = ["malformed_xml_document()", "malformed_xml_element()"];
+    for function in functions.iter() {
+        for document in run_xml_documents(function) {
+            assert!(
+                roxmltree::Document::parse(&document).is_err(),
+                "expected {} to generate invalid xml, but got: '{}'",
+                function,
+                document
+            );
+        }
+    }
+}
+
+#[test]
+fn std_xml_elements_have_matching_end_tags() {
+    test_program_success(1, r#"xml_element("a", "", "text")"#, "<a>text</a>");
+    test_program_success(
+        1,
+        r#"xml_element("a", "", xml_cdata("]]>"))"#,
+        "<a><![CDATA[]]]]><![CDATA[>]]></a>",
+    );
+}
+
+#[test]
+fn use_std_config_functions() {
+    let results = run_program(50, r#"concat(toml_document(), "\u{0}", yaml_and_toml("\u{0}"), "\u{0}")"#).unwrap();
+    let as_str = String::from_utf8(results).unwrap();
+    for (i, document) in as_str.split_terminator('\u{0}').enumerate() {
+        if i % 3 == 1 {
+            assert!(yaml_rust2::YamlLoader::load_from_str(document).is_ok(), "invalid yaml: '{}'", document);
+        } else if let Err(err) = document.parse::<toml::Table>() {
+            panic!("invalid toml: {}, document: '{}'", err, document);
+        }
+    }
+}
+
 #[test]
 fn calling_a_function_with_module_name() {
     let lib1 = r##"
@@ -183,6 +309,43 @@ pub fn run_program(iterations: u64, program: &str) -> Result<Vec<u8>, Error> {
     Ok(out)
 }
 
+pub fn assert_runtime_error(program: &str, expected_message: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.add_std_lib();
+    let function = interpreter
+        .eval(UnreadSource::String(program.to_owned()))
+        .expect("failed to compile program");
+
+    let mut out = Vec::new();
+    let mut output = DataGenOutput::new(&mut out);
+    let mut context = create_context();
+    let error = function
+        .write_value(&mut context, &mut output)
+        .expect_err("expected a runtime error");
+    let message = format!("{}", error);
+    assert!(
+        message.contains(expected_message),
+        "Incorrect error message, expected it to contain: '{}', actual: '{}'",
+        expected_message,
+        message
+    );
+}
+
+pub fn assert_compile_error(program: &str, expected_message: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.add_std_lib();
+    let error = interpreter
+        .eval(UnreadSource::String(program.to_owned()))
+        .expect_err("expected a compilation error");
+    let message = format!("{}", error);
+    assert!(
+        message.contains(expected_message),
+        "Incorrect error message, expected it to contain: '{}', actual: '{}'",
+        expected_message,
+        message
+    );
+}
+
 fn run_to_string(runner: Runner) -> String {
     let mut out = Vec::new();
     {
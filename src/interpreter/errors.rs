@@ -155,6 +155,7 @@ pub enum ErrorType {
     NoSuchMethod(ErrorFunctionSignature),
     NoSuchModule(IString),
     AmbiguousFunctionCall(AmbiguousCall),
+    StdinAlreadyUsed(IString),
     InternalError(Error),
 }
 
@@ -173,6 +174,9 @@ impl Display for ErrorType {
             ErrorType::AmbiguousFunctionCall(ref call) => {
                 write!(f, "Ambiguous function call, which could refer to multiple functions:\n{}", call)
             }
+            ErrorType::StdinAlreadyUsed(ref name) => {
+                write!(f, "The function '{}' reads data from stdin, which cannot be used because the program itself was read from stdin", name)
+            }
             ErrorType::InternalError(ref err) => {
                 write!(f, "Internal Error: {}", err)
             }
@@ -218,6 +222,10 @@ impl CompileError {
         CompileError::new(source_ref, error_type)
     }
 
+    pub fn stdin_already_used(name: IString, source_ref: SourceRef) -> CompileError {
+        CompileError::new(source_ref, ErrorType::StdinAlreadyUsed(name))
+    }
+
     pub fn get_type(&self) -> &ErrorType {
         &self.error_type
     }
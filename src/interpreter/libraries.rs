@@ -11,5 +11,13 @@ const STRINGS: &'static UnreadSource = include_lib!("std.strings", "std/strings.
 const NUMBERS: &'static UnreadSource = include_lib!("std.numbers", "std/numbers.dgen");
 const BOOLEAN: &'static UnreadSource = include_lib!("std.boolean", "std/boolean.dgen");
 const REPEATS: &'static UnreadSource = include_lib!("std.repeats", "std/repeats.dgen");
+const IDS: &'static UnreadSource = include_lib!("std.ids", "std/ids.dgen");
+const FAKE: &'static UnreadSource = include_lib!("std.fake", "std/fake.dgen");
+const NETWORK: &'static UnreadSource = include_lib!("std.network", "std/network.dgen");
+const UNICODE: &'static UnreadSource = include_lib!("std.unicode", "std/unicode.dgen");
+const JSON: &'static UnreadSource = include_lib!("std.json", "std/json.dgen");
+const CSV: &'static UnreadSource = include_lib!("std.csv", "std/csv.dgen");
+const XML: &'static UnreadSource = include_lib!("std.xml", "std/xml.dgen");
+const CONFIG: &'static UnreadSource = include_lib!("std.config", "std/config.dgen");
 
-pub const STDLIBS: &[&UnreadSource] = &[STRINGS, NUMBERS, BOOLEAN, REPEATS];
+pub const STDLIBS: &[&UnreadSource] = &[STRINGS, NUMBERS, BOOLEAN, REPEATS, IDS, FAKE, NETWORK, UNICODE, JSON, CSV, XML, CONFIG];
@@ -3,23 +3,20 @@ use std::cell::RefCell;
 use std::fmt::{self, Debug};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use crate::{AnyFunction, DataGenOutput, DynFun, ProgramContext, RunnableFunction};
+use crate::{AnyFunction, DataGenOutput, DynFun, OutputType, ProgramContext, RunnableFunction};
 
 pub struct Resetter {
     value: AtomicBool,
-    bytes: AtomicBool,
 }
 impl Resetter {
     fn new() -> Resetter {
         Resetter {
             value: AtomicBool::new(true),
-            bytes: AtomicBool::new(true),
         }
     }
 
     fn reset(&self) {
         self.value.store(true, Ordering::Relaxed);
-        self.bytes.store(true, Ordering::Relaxed);
     }
 
     fn is_value_reset(&self) -> bool {
@@ -29,83 +26,55 @@ impl Resetter {
     fn value_set(&self) {
         self.value.store(false, Ordering::Relaxed)
     }
-
-    fn is_bytes_reset(&self) -> bool {
-        self.bytes.load(Ordering::Relaxed)
-    }
-
-    fn bytes_set(&self) {
-        self.bytes.store(false, Ordering::Relaxed)
-    }
-}
-
-struct MemoizedValue<T> {
-    value: Option<T>,
-    bytes: Option<Vec<u8>>,
-}
-impl<T> MemoizedValue<T> {
-    fn new() -> MemoizedValue<T> {
-        MemoizedValue {
-            value: None,
-            bytes: None,
-        }
-    }
 }
 
+/// Memoizes the value of the outer function of a mapper. Both `gen_value` and `write_value` use the same
+/// memoized value, so that functions computed over the value (like checksums) always agree with what
+/// actually gets written
 pub struct MemoizedFunction<T> {
     wrapped: DynFun<T>,
-    memoized: RefCell<MemoizedValue<T>>,
+    memoized: RefCell<Option<T>>,
     resetter: Rc<Resetter>,
 }
 
-impl<T: Clone + 'static> MemoizedFunction<T> {
+impl<T: Clone + OutputType + 'static> MemoizedFunction<T> {
     fn new(wrapped: DynFun<T>, resetter: Rc<Resetter>) -> DynFun<T> {
         Rc::new(MemoizedFunction {
             wrapped,
             resetter,
-            memoized: RefCell::new(MemoizedValue::new()),
+            memoized: RefCell::new(None),
         })
     }
 }
 
+impl<T: Clone + OutputType> MemoizedFunction<T> {
+    fn ensure_value(&self, ctx: &mut ProgramContext) -> Result<(), Error> {
+        if self.resetter.is_value_reset() {
+            let new_value = self.wrapped.gen_value(ctx)?;
+            *self.memoized.borrow_mut() = Some(new_value);
+            self.resetter.value_set();
+        }
+        Ok(())
+    }
+}
+
 impl<T> Debug for MemoizedFunction<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "memoized({:?})", self.wrapped)
     }
 }
 
-impl<T: Clone> RunnableFunction<T> for MemoizedFunction<T> {
+impl<T: Clone + OutputType> RunnableFunction<T> for MemoizedFunction<T> {
     fn gen_value(&self, ctx: &mut ProgramContext) -> Result<T, Error> {
-        if self.resetter.is_value_reset() {
-            let new_value = self.wrapped.gen_value(ctx)?;
-            let mut cell = self.memoized.borrow_mut();
-            cell.value = Some(new_value);
-            self.resetter.value_set();
-        }
+        self.ensure_value(ctx)?;
         // clone the value and return it. This is a safe unwrap because we ensure that the resetter
         // always starts out with `is_value_reset` returning true
-        Ok(self.memoized.borrow().value.as_ref().cloned().unwrap())
+        Ok(self.memoized.borrow().as_ref().cloned().unwrap())
     }
 
     fn write_value(&self, ctx: &mut ProgramContext, out: &mut DataGenOutput) -> Result<(), Error> {
-        let MemoizedFunction {
-            ref wrapped,
-            ref memoized,
-            ref resetter,
-        } = *self;
-        let mut cell = memoized.borrow_mut();
-        if resetter.is_bytes_reset() {
-            if cell.bytes.is_none() {
-                cell.bytes = Some(Vec::new());
-            }
-            let buffer = cell.bytes.as_mut().unwrap();
-            buffer.clear();
-            let mut new_out = DataGenOutput::new(buffer);
-            let _ = wrapped.write_value(ctx, &mut new_out)?;
-            resetter.bytes_set();
-        }
-        let bytes = cell.bytes.as_ref().unwrap();
-        out.write(bytes)
+        self.ensure_value(ctx)?;
+        out.write(self.memoized.borrow().as_ref().unwrap())
     }
 }
 
@@ -30,10 +30,15 @@ use crate::{
     AnyFunction, BoundArgument, ConstBin, ConstBoolean, ConstDecimal, ConstInt,
     ConstString, ConstUint, CreateFunctionResult, FunctionPrototype,
 };
+use std::cell::Cell;
 use std::sync::Arc;
 
 pub struct Compiler {
     modules: Vec<Module>,
+    /// set once any source has been read from stdin, since stdin can then no longer be used for data
+    stdin_is_source: bool,
+    /// set once any function that reads from stdin or a pipe has been compiled
+    reads_input: Cell<bool>,
 }
 
 pub type CompileResult = Result<AnyFunction, CompileError>;
@@ -42,6 +47,8 @@ impl Compiler {
     fn new() -> Compiler {
         Compiler {
             modules: Vec::new(),
+            stdin_is_source: false,
+            reads_input: Cell::new(false),
         }
     }
     fn add_module(&mut self, module: Module) -> Result<(), Error> {
@@ -129,6 +136,12 @@ impl Compiler {
 
         if resolved.is_none() {
             let function = self.find_matching_function(source_ref.clone(), name.clone(), resolved_args.as_slice())?;
+            if self.stdin_is_source && function.reads_stdin() {
+                return Err(CompileError::stdin_already_used(name, source_ref));
+            }
+            if function.reads_input() {
+                self.reads_input.set(true);
+            }
             let res = function.apply(resolved_args, self, &source_ref)?;
             resolved = Some(res);
         }
@@ -281,6 +294,11 @@ impl Interpreter {
         self.eval_any(unread_source).map(|_| ())
     }
 
+    /// Returns true if any of the functions that have been compiled so far read data from stdin or a pipe
+    pub fn reads_input(&self) -> bool {
+        self.internal.reads_input.get()
+    }
+
     pub fn has_module(&self, module_name: &str) -> bool {
         self.module_iterator().any(|module| &*module.name == module_name)
     }
@@ -295,6 +313,13 @@ impl Interpreter {
 
     pub fn eval_any(&mut self, unread_source: UnreadSource) -> Result<Option<AnyFunction>, Error> {
         let source = Source::read(unread_source)?;
+        self.eval_source(source)
+    }
+
+    fn eval_source(&mut self, source: Source) -> Result<Option<AnyFunction>, Error> {
+        if source.is_stdin() {
+            self.internal.stdin_is_source = true;
+        }
         let module_name: IString = source.module_name();
         
         let Program { assignments, expr } = {
@@ -416,6 +441,28 @@ mod test {
         assert!(error_message.contains("A module with the name 'same_module' already exists"), "wrong error message, actual: '{}'", error_message);
     }
 
+    #[test]
+    fn reading_data_from_stdin_is_an_error_when_the_program_is_read_from_stdin() {
+        use std::borrow::Cow;
+
+        let lib = r##"
+        def payload() = stdin_bytes(16);
+        "##;
+        let mut subject = Interpreter::new();
+        subject.add_module(UnreadSource::Builtin("lib", lib)).expect("failed to add module");
+        subject.eval(UnreadSource::Builtin("pass", "payload()")).expect("expected compilation to succeed");
+
+        let program = Source::new(UnreadSource::Stdin, Cow::from("stdin_line()"));
+        let error = subject.eval_source(program).expect_err("expected an error");
+        let error_message = format!("{}", error);
+        assert!(error_message.contains("The function 'stdin_line' reads data from stdin"), "wrong error message, actual: '{}'", error_message);
+
+        // once the program has been read from stdin, library functions can't read from it either
+        let error = subject.eval(UnreadSource::Builtin("fail", "lib.payload()")).expect_err("expected an error");
+        let error_message = format!("{}", error);
+        assert!(error_message.contains("The function 'stdin_bytes' reads data from stdin"), "wrong error message, actual: '{}'", error_message);
+    }
+
     fn run_function(function: &AnyFunction) -> String {
         use crate::{DataGenOutput, ProgramContext};
 
@@ -210,6 +210,20 @@ impl FunctionPrototype {
         }
     }
 
+    pub fn reads_stdin(&self) -> bool {
+        match *self {
+            FunctionPrototype::Builtin(builtin) => crate::builtins::reads_stdin(builtin),
+            FunctionPrototype::Interpreted(_) => false,
+        }
+    }
+
+    pub fn reads_input(&self) -> bool {
+        match *self {
+            FunctionPrototype::Builtin(builtin) => crate::builtins::reads_input(builtin),
+            FunctionPrototype::Interpreted(_) => false,
+        }
+    }
+
     pub fn is_same_signature(&self, other: &FunctionPrototype) -> bool {
         if self.name() != other.name() {
             return false;
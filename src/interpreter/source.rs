@@ -123,6 +123,10 @@ impl Source {
         }
     }
 
+    pub fn is_stdin(&self) -> bool {
+        self.unread == UnreadSource::Stdin
+    }
+
     pub fn text(&self) -> &str {
         &self.source_text
     }
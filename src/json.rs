@@ -0,0 +1,383 @@
+use failure::Error;
+use std::fmt::{self, Write};
+
+/// A parsed json value, used by the builtins that convert json into other formats. Numbers keep their original text so
+/// that values like `1E400` or `-0` are converted exactly as they were written, and objects keep their members in
+/// order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Boolean(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Parses strict RFC 8259 json. If an object has duplicate keys, the last value is kept, but in the position of the
+    /// first one
+    pub(crate) fn parse(input: &str) -> Result<JsonValue, Error> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        match *self {
+            JsonValue::Null => "null",
+            JsonValue::Boolean(_) => "boolean",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        }
+    }
+
+    pub(crate) fn is_container(&self) -> bool {
+        matches!(*self, JsonValue::Array(_) | JsonValue::Object(_))
+    }
+}
+
+/// Appends the value as a double quoted json string, escaping only the characters that require it
+pub(crate) fn write_json_string(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes the value as compact json, without any whitespace
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Boolean(b) => write!(f, "{}", b),
+            JsonValue::Number(ref number) => f.write_str(number),
+            JsonValue::String(ref string) => {
+                let mut quoted = String::with_capacity(string.len() + 2);
+                write_json_string(string, &mut quoted);
+                f.write_str(&quoted)
+            }
+            JsonValue::Array(ref items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(ref members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{}", JsonValue::String(key.clone()), value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        format_err!("Invalid json: {} at byte {}", message, self.pos)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(|b| b" \t\r\n".contains(&b)).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), Error> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected as char)))
+        }
+    }
+
+    fn digits(&mut self) -> Result<(), Error> {
+        let start = self.pos;
+        while self.peek().map(|b| b.is_ascii_digit()).unwrap_or(false) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            Err(self.error("expected a digit"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, Error> {
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(b'{') => self.object()?,
+            Some(b'[') => self.array()?,
+            Some(b'"') => JsonValue::String(self.string()?),
+            Some(b'-') | Some(b'0'..=b'9') => self.number()?,
+            _ => {
+                let literals = [
+                    ("true", JsonValue::Boolean(true)),
+                    ("false", JsonValue::Boolean(false)),
+                    ("null", JsonValue::Null),
+                ];
+                let (literal, value) = literals
+                    .iter()
+                    .find(|(literal, _)| self.input[self.pos..].starts_with(literal.as_bytes()))
+                    .ok_or_else(|| self.error("expected a value"))?;
+                self.pos += literal.len();
+                value.clone()
+            }
+        };
+        self.skip_whitespace();
+        Ok(value)
+    }
+
+    /// Consumes either a comma, returning true, or the end of a container, returning false
+    fn next_element(&mut self, end: u8) -> Result<bool, Error> {
+        match self.peek() {
+            Some(b',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(b) if b == end => {
+                self.pos += 1;
+                Ok(false)
+            }
+            _ => Err(self.error("expected a comma or the end of the container")),
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, Error> {
+        self.pos += 1;
+        self.skip_whitespace();
+        let mut elements = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            if !self.next_element(b']')? {
+                return Ok(JsonValue::Array(elements));
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, Error> {
+        self.pos += 1;
+        self.skip_whitespace();
+        let mut members: Vec<(String, JsonValue)> = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            let value = self.value()?;
+            match members.iter_mut().find(|(existing, _)| *existing == key) {
+                Some(member) => member.1 = value,
+                None => members.push((key, value)),
+            }
+            if !self.next_element(b'}')? {
+                return Ok(JsonValue::Object(members));
+            }
+        }
+    }
+
+    fn hex_escape(&mut self) -> Result<u32, Error> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let value = hex.iter().fold(0, |value, &digit| {
+            value * 16 + (digit as char).to_digit(16).unwrap_or(0)
+        });
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect(b'"')?;
+        let mut value = String::new();
+        let mut run_start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    value.push_str(self.run(run_start));
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some(b'\\') => {
+                    value.push_str(self.run(run_start));
+                    self.pos += 1;
+                    let escape = self.peek().ok_or_else(|| self.error("invalid escape"))?;
+                    self.pos += 1;
+                    match escape {
+                        b'u' => value.push(self.unicode_escape()?),
+                        b'"' => value.push('"'),
+                        b'\\' => value.push('\\'),
+                        b'/' => value.push('/'),
+                        b'b' => value.push('\u{8}'),
+                        b'f' => value.push('\u{c}'),
+                        b'n' => value.push('\n'),
+                        b'r' => value.push('\r'),
+                        b't' => value.push('\t'),
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("invalid escape"));
+                        }
+                    }
+                    run_start = self.pos;
+                }
+                Some(b) if b >= 0x20 => self.pos += 1,
+                _ => return Err(self.error("unterminated string or unescaped control character")),
+            }
+        }
+    }
+
+    /// Returns the unescaped text from the start position up to the current position. The input was a `&str`, and runs
+    /// always start and end next to ascii characters, so they're always valid utf-8
+    fn run(&self, start: usize) -> &'a str {
+        std::str::from_utf8(&self.input[start..self.pos]).unwrap_or("")
+    }
+
+    /// Lone surrogates are allowed by the json grammar, but can't be represented in a rust string, so they're replaced
+    /// with the unicode replacement character
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        let first = self.hex_escape()?;
+        if (0xD800..0xDC00).contains(&first) && self.input[self.pos..].starts_with(b"\\u") {
+            let start = self.pos;
+            self.pos += 2;
+            let second = self.hex_escape()?;
+            if (0xDC00..0xE000).contains(&second) {
+                let combined = 0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00);
+                return Ok(std::char::from_u32(combined).unwrap_or('\u{FFFD}'));
+            }
+            self.pos = start;
+        }
+        Ok(std::char::from_u32(first).unwrap_or('\u{FFFD}'))
+    }
+
+    fn number(&mut self) -> Result<JsonValue, Error> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else {
+            self.digits()?;
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.digits()?;
+        }
+        if self.peek() == Some(b'e') || self.peek() == Some(b'E') {
+            self.pos += 1;
+            if self.peek() == Some(b'+') || self.peek() == Some(b'-') {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        Ok(JsonValue::Number(self.run(start).to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn string(value: &str) -> JsonValue {
+        JsonValue::String(value.to_owned())
+    }
+
+    #[test]
+    fn parses_nested_values() {
+        let value = JsonValue::parse(r#" {"a": [1, -2.5e+3, true, null], "b": {}, "c": "x"} "#).unwrap();
+        let expected = JsonValue::Object(vec![
+            (
+                "a".to_owned(),
+                JsonValue::Array(vec![
+                    JsonValue::Number("1".to_owned()),
+                    JsonValue::Number("-2.5e+3".to_owned()),
+                    JsonValue::Boolean(true),
+                    JsonValue::Null,
+                ]),
+            ),
+            ("b".to_owned(), JsonValue::Object(Vec::new())),
+            ("c".to_owned(), string("x")),
+        ]);
+        assert_eq!(expected, value);
+    }
+
+    #[test]
+    fn parses_string_escapes() {
+        let value = JsonValue::parse(r#""a\"\\\/\b\f\n\r\t\u00e9\uD83D\uDE00\uD800z""#).unwrap();
+        assert_eq!(string("a\"\\/\u{8}\u{c}\n\r\t\u{e9}\u{1F600}\u{FFFD}z"), value);
+    }
+
+    #[test]
+    fn duplicate_keys_keep_the_last_value() {
+        let value = JsonValue::parse(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+        let expected = JsonValue::Object(vec![
+            ("a".to_owned(), JsonValue::Number("3".to_owned())),
+            ("b".to_owned(), JsonValue::Number("2".to_owned())),
+        ]);
+        assert_eq!(expected, value);
+    }
+
+    #[test]
+    fn values_are_written_as_compact_json() {
+        let input = r#"{"a\"\n": [1, -2.5e+3, true, null, "\u0001\u00e9"], "b": {}}"#;
+        let value = JsonValue::parse(input).unwrap();
+        let written = value.to_string();
+        assert_eq!(r#"{"a\"\n":[1,-2.5e+3,true,null,"\u0001é"],"b":{}}"#, written);
+        assert_eq!(value, JsonValue::parse(&written).unwrap());
+    }
+
+    #[test]
+    fn invalid_json_returns_errors() {
+        let invalid = ["", "[1, 2,]", "{'a': 1}", "01", "1.", "\"\t\"", "\"\\x41\"", "[1] 2", "tru", "{\"a\" 1}"];
+        for input in invalid.iter() {
+            assert!(JsonValue::parse(input).is_err(), "expected an error for: '{}'", input);
+        }
+    }
+}
@@ -7,15 +7,21 @@ extern crate byteorder;
 extern crate encoding;
 extern crate itertools;
 extern crate lalrpop_util;
+extern crate md5;
 extern crate rand;
 extern crate regex;
+extern crate regex_syntax;
 extern crate rustyline;
+extern crate sha1;
+extern crate sha2;
 extern crate string_cache;
+extern crate unicode_normalization;
 
 mod arguments;
 pub(crate) mod builtins;
 mod context;
 pub mod interpreter;
+mod json;
 pub mod program;
 pub mod repl;
 mod types;
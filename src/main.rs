@@ -5,14 +5,15 @@ extern crate dgen;
 
 mod cli_opts;
 
-use self::cli_opts::{CliOptions, SubCommand, HelpOptions};
+use self::cli_opts::{CliOptions, SubCommand, HelpOptions, InferOptions};
 use dgen::interpreter::Interpreter;
-use dgen::program::{DgenCommand, Help, Runner};
+use dgen::program::{DgenCommand, Help, Infer, Runner};
 use dgen::repl::Repl;
 use dgen::verbosity::Verbosity;
 use dgen::{DataGenOutput, ProgramContext};
 use failure::Error;
 use std::io;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 trait OrBail<T> {
@@ -56,6 +57,9 @@ fn main() {
         })) => {
             print_function_help(function_name, module_name, interpreter, verbosity);
         }
+        Some(SubCommand::Infer(InferOptions { files })) => {
+            print_inferred_program(files, verbosity);
+        }
         None => {
             let context = create_context(&args);
 
@@ -120,6 +124,14 @@ fn print_function_help(
     help.execute(&mut out).or_bail(verbosity);
 }
 
+fn print_inferred_program(files: Vec<PathBuf>, verbosity: Verbosity) {
+    let sout = io::stdout();
+    let mut lock = sout.lock();
+    let mut out = DataGenOutput::new(&mut lock);
+
+    Infer::new(files).execute(&mut out).or_bail(verbosity);
+}
+
 fn run_program(program: Runner) -> Result<(), Error> {
     let sout = std::io::stdout();
     // lock stdout once at the beginning so we don't have to keep locking/unlocking it
@@ -0,0 +1,744 @@
+use crate::builtins::parse_csv;
+use crate::interpreter::parser::parse_program;
+use crate::json::JsonValue;
+use crate::program::DgenCommand;
+use crate::DataGenOutput;
+use failure::Error;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Fields with at most this many distinct values are generated by selecting from the values that were seen
+const MAX_ENUM_VALUES: usize = 12;
+/// The number of times each distinct value must be seen on average for a field to be treated as an enumeration
+const MIN_ENUM_REPEATS: usize = 2;
+const CSV_DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// Analyses sample files and writes a dgen module that generates similar data
+pub struct Infer {
+    files: Vec<PathBuf>,
+}
+
+impl Infer {
+    pub fn new(files: Vec<PathBuf>) -> Infer {
+        Infer { files }
+    }
+}
+
+impl DgenCommand for Infer {
+    fn execute(self, out: &mut DataGenOutput) -> Result<(), Error> {
+        let mut samples = Vec::with_capacity(self.files.len());
+        for path in self.files.iter() {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format_err!("Failed to read sample file '{}': {}", path.display(), e))?;
+            samples.push((path.clone(), text));
+        }
+        let source = infer_module(&samples)?;
+        parse_program("inferred".into(), &source).map_err(|e| {
+            format_err!("Internal error: the inferred module is not a valid dgen program: {}", e)
+        })?;
+        out.write_str(&source)?;
+        out.flush().map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SampleFormat {
+    Csv,
+    JsonLines,
+    Text,
+}
+
+impl SampleFormat {
+    fn of(path: &Path) -> SampleFormat {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        match extension.as_str() {
+            "csv" | "tsv" => SampleFormat::Csv,
+            "json" | "jsonl" | "ndjson" => SampleFormat::JsonLines,
+            _ => SampleFormat::Text,
+        }
+    }
+}
+
+/// Every value that was seen for one field, grouped by the kind of value
+#[derive(Debug, Default)]
+struct Samples {
+    count: usize,
+    nulls: usize,
+    booleans: usize,
+    numbers: Vec<String>,
+    strings: Vec<String>,
+    array_lengths: Vec<usize>,
+    items: Option<Box<Samples>>,
+    objects: usize,
+    members: Vec<(String, Samples)>,
+}
+
+impl Samples {
+    fn add_json(&mut self, value: &JsonValue) {
+        self.count += 1;
+        match value {
+            JsonValue::Null => self.nulls += 1,
+            JsonValue::Boolean(_) => self.booleans += 1,
+            JsonValue::Number(number) => self.numbers.push(number.clone()),
+            JsonValue::String(string) => self.strings.push(string.clone()),
+            JsonValue::Array(items) => {
+                self.array_lengths.push(items.len());
+                let samples = self.items.get_or_insert_with(Default::default);
+                for item in items.iter() {
+                    samples.add_json(item);
+                }
+            }
+            JsonValue::Object(members) => {
+                self.objects += 1;
+                for (key, value) in members.iter() {
+                    let index = match self.members.iter().position(|(k, _)| k == key) {
+                        Some(index) => index,
+                        None => {
+                            self.members.push((key.clone(), Samples::default()));
+                            self.members.len() - 1
+                        }
+                    };
+                    self.members[index].1.add_json(value);
+                }
+            }
+        }
+    }
+
+    fn add_text(&mut self, value: &str) {
+        self.count += 1;
+        self.strings.push(value.to_owned());
+    }
+
+    fn has_containers(&self) -> bool {
+        !self.array_lengths.is_empty() || self.objects > 0
+    }
+}
+
+/// Chooses the delimiter that appears most often in the header line, which is least likely to contain quoted values
+fn sniff_delimiter(path: &Path, text: &str) -> char {
+    if path.extension().and_then(|e| e.to_str()) == Some("tsv") {
+        return '\t';
+    }
+    let header = text.lines().next().unwrap_or("");
+    CSV_DELIMITERS
+        .iter()
+        .cloned()
+        .max_by_key(|delimiter| (header.matches(*delimiter).count(), *delimiter == ','))
+        .unwrap_or(',')
+}
+
+/// Turns a column or member name into a valid dgen identifier
+fn identifier(name: &str) -> String {
+    let mut ident = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            ident.push(c.to_ascii_lowercase());
+        } else if !ident.is_empty() && !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    while ident.ends_with('_') {
+        ident.pop();
+    }
+    if ident.is_empty() {
+        ident.push_str("field");
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert_str(0, "field_");
+    }
+    ident
+}
+
+fn string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(literal, "\\u{{{:X}}}", c as u32);
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Decimal literals always need digits on both sides of the point, and never use an exponent
+fn decimal_literal(value: f64) -> String {
+    let literal = format!("{}", value);
+    if literal.contains('.') {
+        literal
+    } else {
+        literal + ".0"
+    }
+}
+
+/// Keeps comments on a single line, since a comment always ends at the next line break
+fn comment_text(value: &str) -> String {
+    value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+fn min_max<T: PartialOrd + Copy>(values: &[T]) -> (T, T) {
+    let mut min = values[0];
+    let mut max = values[0];
+    for value in values.iter() {
+        if *value < min {
+            min = *value;
+        }
+        if *value > max {
+            max = *value;
+        }
+    }
+    (min, max)
+}
+
+/// Whether a text value is a plain number. Values with leading zeros, like zip codes, are kept as strings of digits so
+/// that the zeros aren't lost
+fn looks_like_number(value: &str) -> bool {
+    let digits = value.trim_start_matches(['-', '+']);
+    let has_leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    value.parse::<f64>().is_ok() && !value.contains(char::is_alphabetic) && !has_leading_zero
+}
+
+/// Generates numbers from the same range as the samples, using integers unless any of them had a fraction or exponent
+fn number_expr(numbers: &[String]) -> (String, String) {
+    let integers = numbers.iter().map(|n| n.parse::<i64>()).collect::<Result<Vec<_>, _>>();
+    match integers {
+        Ok(ref integers) => {
+            let (min, max) = min_max(integers);
+            if min >= 0 {
+                (format!("to_string(uint({}, {}))", min, max), format!("integers from {} to {}", min, max))
+            } else {
+                (format!("to_string(int({:+}, {:+}))", min, max), format!("integers from {} to {}", min, max))
+            }
+        }
+        Err(_) => {
+            let mut decimals = numbers
+                .iter()
+                .filter_map(|n| n.parse::<f64>().ok())
+                .filter(|n| n.is_finite())
+                .collect::<Vec<_>>();
+            if decimals.is_empty() {
+                decimals.push(0.0);
+            }
+            let (min, max) = min_max(&decimals);
+            (
+                format!("to_string(decimal({}, {}))", decimal_literal(min), decimal_literal(max)),
+                format!("numbers from {} to {}", min, max),
+            )
+        }
+    }
+}
+
+/// Generates strings with the same range of lengths, using the narrowest character set that includes every character
+/// that was seen
+fn string_expr(strings: &[String]) -> (String, String) {
+    let lengths = strings.iter().map(|s| s.chars().count()).collect::<Vec<_>>();
+    let (min_len, max_len) = min_max(&lengths);
+    let length = format!("uint({}, {})", min_len, max_len);
+    let mut chars = strings.iter().flat_map(|s| s.chars()).peekable();
+    let (expr, charset) = if chars.peek().is_none() {
+        (format!("string({}, 97, 122)", length), "no characters")
+    } else if strings.iter().all(|s| s.chars().all(|c| c.is_ascii_digit())) {
+        (format!("digit_chars({})", length), "digits")
+    } else if strings.iter().all(|s| s.chars().all(|c| c.is_ascii_lowercase())) {
+        (format!("ascii_lowercase_chars({})", length), "lowercase letters")
+    } else if strings.iter().all(|s| s.chars().all(|c| c.is_ascii_uppercase())) {
+        (format!("ascii_uppercase_chars({})", length), "uppercase letters")
+    } else if strings.iter().all(|s| s.chars().all(|c| c.is_ascii_alphanumeric())) {
+        (format!("ascii_alphanumeric_chars({})", length), "letters and digits")
+    } else if strings.iter().all(|s| s.chars().all(|c| c.is_ascii_graphic())) {
+        (format!("string({}, 33, 126)", length), "printable ascii without spaces")
+    } else if strings.iter().all(|s| s.chars().all(|c| c == ' ' || c.is_ascii_graphic())) {
+        (format!("string({}, 32, 126)", length), "printable ascii")
+    } else {
+        let codepoints = strings.iter().flat_map(|s| s.chars()).map(|c| c as u32).collect::<Vec<_>>();
+        let (min, max) = min_max(&codepoints);
+        if min > 0xDFFF || max < 0xD800 {
+            (format!("string({}, {}, {})", length, min, max), "characters from the same range")
+        } else {
+            // the range would include surrogates, which aren't valid characters
+            (format!("unicode_chars({})", length), "unicode")
+        }
+    };
+    (expr, format!("{} with lengths from {} to {}", charset, min_len, max_len))
+}
+
+struct ModuleWriter {
+    format: SampleFormat,
+    defs: Vec<String>,
+    names: Names,
+}
+
+impl ModuleWriter {
+    fn def(&mut self, name: &str, description: &str, body: &str) {
+        self.defs.push(format!("# {}\ndef {}() = {};\n", comment_text(description), name, body));
+    }
+
+    /// The distinct values, formatted as they would be written, if there are few enough to use as an enumeration
+    fn enumeration(&self, samples: &Samples) -> Option<Vec<String>> {
+        if samples.has_containers() || samples.booleans > 0 {
+            return None;
+        }
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        let formatted = samples
+            .strings
+            .iter()
+            .map(|s| match self.format {
+                SampleFormat::JsonLines => JsonValue::String(s.clone()).to_string(),
+                _ => s.clone(),
+            })
+            .chain(samples.numbers.iter().cloned())
+            .chain((0..samples.nulls).map(|_| String::from("null")));
+        for value in formatted {
+            match counts.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        if counts.is_empty() || counts.len() > MAX_ENUM_VALUES || samples.count < counts.len() * MIN_ENUM_REPEATS {
+            return None;
+        }
+        // the most common values are listed first
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        Some(counts.into_iter().map(|(value, _)| value).collect())
+    }
+
+    /// Returns an expression that generates values like the samples, adding a def for every nested field
+    fn value_expr(&mut self, name: &str, samples: &Samples) -> (String, String) {
+        if let Some(values) = self.enumeration(samples) {
+            let literals = values.iter().map(|v| string_literal(v)).collect::<Vec<_>>();
+            let expr = if literals.len() == 1 {
+                literals[0].clone()
+            } else {
+                format!("select({})", literals.join(", "))
+            };
+            let description = if values.len() == 1 {
+                format!("always {}", values[0])
+            } else {
+                format!("one of {} values", values.len())
+            };
+            return (expr, description);
+        }
+
+        let mut alternatives = Vec::new();
+        let mut descriptions = Vec::new();
+        let mut strings = samples.strings.iter().filter(|s| !s.is_empty()).cloned().collect::<Vec<_>>();
+        let mut numbers = samples.numbers.clone();
+        let mut booleans = samples.booleans;
+        if self.format != SampleFormat::JsonLines {
+            // text formats have no types, so values that look like numbers or booleans are generated as such
+            if !strings.is_empty() && strings.iter().all(|s| looks_like_number(s)) {
+                numbers.append(&mut strings);
+            } else if !strings.is_empty() && strings.iter().all(|s| s == "true" || s == "false") {
+                booleans = strings.len();
+                strings.clear();
+            }
+            if strings.len() + numbers.len() + booleans < samples.strings.len() {
+                alternatives.push(String::from("\"\""));
+                descriptions.push(String::from("empty"));
+            }
+        } else if samples.strings.iter().any(|s| s.is_empty()) {
+            strings.push(String::new());
+        }
+        if samples.nulls > 0 {
+            alternatives.push(String::from("\"null\""));
+            descriptions.push(String::from("null"));
+        }
+        if booleans > 0 {
+            alternatives.push(String::from("select(\"true\", \"false\")"));
+            descriptions.push(String::from("booleans"));
+        }
+        if !numbers.is_empty() {
+            let (expr, description) = number_expr(&numbers);
+            alternatives.push(expr);
+            descriptions.push(description);
+        }
+        if !strings.is_empty() {
+            let (expr, description) = string_expr(&strings);
+            if self.format == SampleFormat::JsonLines {
+                alternatives.push(format!("json_string({})", expr));
+            } else {
+                alternatives.push(expr);
+            }
+            descriptions.push(format!("strings of {}", description));
+        }
+        if let Some(ref items) = samples.items {
+            let item_name = self.names.unique(&format!("{}_item", name));
+            let (item_expr, item_description) = self.value_expr(&item_name, items);
+            self.def(&item_name, &format!("An element of {}: {}", name, item_description), &item_expr);
+            let (min, max) = min_max(&samples.array_lengths);
+            alternatives.push(format!("repeat_delimited(uint({}, {}), \"[\", {}(), \",\", \"]\")", min, max, item_name));
+            descriptions.push(format!("arrays of {} to {} elements", min, max));
+        }
+        if samples.objects > 0 {
+            alternatives.push(self.object_expr(name, &samples.members));
+            descriptions.push(format!("objects with {} members", samples.members.len()));
+        }
+
+        let expr = match alternatives.len() {
+            0 => String::from("\"\""),
+            1 => alternatives.pop().unwrap(),
+            _ => format!("select({})", alternatives.join(", ")),
+        };
+        (expr, descriptions.join(", or "))
+    }
+
+    fn object_expr(&mut self, name: &str, members: &[(String, Samples)]) -> String {
+        let mut parts = Vec::with_capacity(members.len() * 2 + 1);
+        for (i, (key, samples)) in members.iter().enumerate() {
+            let member_name = self.names.unique(&format!("{}_{}", name, identifier(key)));
+            let (expr, description) = self.value_expr(&member_name, samples);
+            self.def(&member_name, &format!("Member '{}': {}", key, description), &expr);
+            let separator = if i == 0 { "{" } else { "," };
+            let prefix = format!("{}{}:", separator, JsonValue::String(key.clone()));
+            parts.push(string_literal(&prefix));
+            parts.push(format!("{}()", member_name));
+        }
+        parts.push(String::from(if members.is_empty() { "\"{}\"" } else { "\"}\"" }));
+        format!("concat({})", parts.join(", "))
+    }
+}
+
+/// Makes sure that each function name is only used once, since names may only differ in punctuation, and the names of
+/// nested members may collide with those of their parents
+#[derive(Default)]
+struct Names {
+    used: HashMap<String, usize>,
+}
+
+impl Names {
+    fn unique(&mut self, name: &str) -> String {
+        let mut count = self.used.get(name).cloned().unwrap_or(0);
+        let mut unique = name.to_owned();
+        // a suffixed name may already be taken by a different field, so keep counting until one is free
+        while self.used.contains_key(&unique) {
+            count += 1;
+            unique = format!("{}_{}", name, count);
+        }
+        self.used.insert(name.to_owned(), count.max(1));
+        self.used.entry(unique.clone()).or_insert(1);
+        unique
+    }
+}
+
+/// Writes a dgen module with one function per column or field of the samples, plus a `<name>_record()` function that
+/// generates a whole record. The module ends with a call to the record function, so it can be run directly
+fn infer_module(files: &[(PathBuf, String)]) -> Result<String, Error> {
+    let (first_path, first_text) = match files.first() {
+        Some(file) => file,
+        None => bail!("At least one sample file is required"),
+    };
+    let format = SampleFormat::of(first_path);
+    if let Some((path, _)) = files.iter().find(|(path, _)| SampleFormat::of(path) != format) {
+        bail!(
+            "All sample files must have the same format, but '{}' and '{}' are different",
+            first_path.display(),
+            path.display()
+        );
+    }
+    let stem = first_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = if stem.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        identifier(stem)
+    } else {
+        String::from("sample")
+    };
+    let file_names = files.iter().map(|(path, _)| path.display().to_string()).collect::<Vec<_>>();
+
+    let mut writer = ModuleWriter {
+        format,
+        defs: Vec::new(),
+        names: Names::default(),
+    };
+    let record = writer.names.unique(&format!("{}_record", name));
+    let header_name = writer.names.unique(&format!("{}_header", name));
+    let record_body = match format {
+        SampleFormat::Csv => {
+            let delimiter = sniff_delimiter(first_path, first_text);
+            let mut dialect = String::from("rfc4180");
+            match delimiter {
+                ',' => {}
+                '\t' => dialect.push_str(" delimiter=tab"),
+                other => {
+                    let _ = write!(dialect, " delimiter={}", other);
+                }
+            }
+            if !first_text.contains("\r\n") {
+                dialect.push_str(" line_ending=lf");
+            }
+
+            let mut header: Option<Vec<String>> = None;
+            let mut columns: Vec<Samples> = Vec::new();
+            for (path, text) in files.iter() {
+                let mut rows = parse_csv(text, delimiter).into_iter();
+                let file_header = rows
+                    .next()
+                    .ok_or_else(|| format_err!("The sample file '{}' is empty", path.display()))?;
+                match header {
+                    Some(ref header) if *header != file_header => bail!(
+                        "All sample files must have the same header row, but '{}' has a different one",
+                        path.display()
+                    ),
+                    Some(_) => {}
+                    None => {
+                        columns = file_header.iter().map(|_| Samples::default()).collect();
+                        header = Some(file_header);
+                    }
+                }
+                for row in rows.filter(|row| !(row.len() == 1 && row[0].is_empty())) {
+                    for (column, value) in columns.iter_mut().zip(row.iter()) {
+                        column.add_text(value);
+                    }
+                }
+            }
+            let header = header.unwrap_or_default();
+            let dialect = string_literal(&dialect);
+            let mut fields = Vec::with_capacity(header.len());
+            for (column, samples) in header.iter().zip(columns.iter()) {
+                let column_name = writer.names.unique(&format!("{}_{}", name, identifier(column)));
+                let (expr, description) = writer.value_expr(&column_name, samples);
+                writer.def(&column_name, &format!("Column '{}': {}", column, description), &expr);
+                fields.push(format!("{}()", column_name));
+            }
+            let header_fields = header.iter().map(|column| string_literal(column)).collect::<Vec<_>>();
+            writer.def(
+                &header_name,
+                "The header row",
+                &format!("csv_row({}, {})", dialect, header_fields.join(", ")),
+            );
+            format!("csv_row({}, {})", dialect, fields.join(", "))
+        }
+        SampleFormat::JsonLines => {
+            let mut samples = Samples::default();
+            for (path, text) in files.iter() {
+                // a file may contain a single array of records instead of one record per line
+                match JsonValue::parse(text) {
+                    Ok(JsonValue::Array(ref records)) => records.iter().for_each(|record| samples.add_json(record)),
+                    _ => {
+                        for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                            let value = JsonValue::parse(line).map_err(|e| {
+                                format_err!("Invalid json on line {} of '{}': {}", i + 1, path.display(), e)
+                            })?;
+                            samples.add_json(&value);
+                        }
+                    }
+                }
+            }
+            if samples.count == 0 {
+                bail!("The sample files do not contain any json values");
+            }
+            let value = writer.names.unique(&format!("{}_value", name));
+            let (expr, description) = writer.value_expr(&value, &samples);
+            writer.def(&value, &format!("A record: {}", description), &expr);
+            format!("trailing_newline({}())", value)
+        }
+        SampleFormat::Text => {
+            let lines = files
+                .iter()
+                .flat_map(|(_, text)| text.lines())
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>();
+            if lines.is_empty() {
+                bail!("The sample files do not contain any lines");
+            }
+            let tokens = lines.iter().map(|line| line.split_whitespace().collect::<Vec<_>>()).collect::<Vec<_>>();
+            let token_count = tokens[0].len();
+            let fields = if token_count > 1 && tokens.iter().all(|t| t.len() == token_count) {
+                // every line has the same number of words, so they're treated as space separated columns
+                let mut fields = Vec::with_capacity(token_count);
+                for i in 0..token_count {
+                    let mut samples = Samples::default();
+                    tokens.iter().for_each(|t| samples.add_text(t[i]));
+                    let field_name = writer.names.unique(&format!("{}_field_{}", name, i + 1));
+                    let (expr, description) = writer.value_expr(&field_name, &samples);
+                    writer.def(&field_name, &format!("Word {} of each line: {}", i + 1, description), &expr);
+                    fields.push(format!("{}()", field_name));
+                }
+                fields
+            } else {
+                let mut samples = Samples::default();
+                lines.iter().for_each(|line| samples.add_text(line));
+                let line_name = writer.names.unique(&format!("{}_line", name));
+                let (expr, description) = writer.value_expr(&line_name, &samples);
+                writer.def(&line_name, &format!("A line: {}", description), &expr);
+                vec![format!("{}()", line_name)]
+            };
+            if fields.len() == 1 {
+                format!("trailing_newline({})", fields[0])
+            } else {
+                format!("trailing_newline(concat({}))", fields.join(", \" \", "))
+            }
+        }
+    };
+    writer.def(&record, "A complete record, including the line ending", &record_body);
+    if format == SampleFormat::Csv {
+        let file = writer.names.unique(&format!("{}_file", name));
+        writer.defs.push(format!(
+            "# A complete file, with the header row followed by row_count records\ndef {}(row_count: Uint) = csv_file({}(), row_count, {}());\n",
+            file, header_name, record
+        ));
+    }
+
+    let mut source = format!(
+        "# Inferred by `dgen infer` from: {}\n# This is only a starting point, so adjust the functions to match the real format\n",
+        comment_text(&file_names.join(", "))
+    );
+    for def in writer.defs.iter() {
+        source.push('\n');
+        source.push_str(def);
+    }
+    let _ = write!(source, "\n{}()\n", record);
+    Ok(source)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fun_test::run_program;
+
+    fn infer(files: &[(&str, &str)]) -> String {
+        let files = files
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+            .collect::<Vec<_>>();
+        let source = infer_module(&files).expect("failed to infer module");
+        if let Err(e) = parse_program("inferred".into(), &source) {
+            panic!("inferred source did not parse: {}\nsource:\n{}", e, source);
+        }
+        source
+    }
+
+    fn run(source: &str, iterations: u64) -> String {
+        let output = run_program(iterations, source).unwrap_or_else(|e| panic!("{}\nsource:\n{}", e, source));
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn csv_columns_become_functions() {
+        let sample = "id,full name,age,zip,status,score\n\
+                      1,Alice,34,01234,active,0.5\n\
+                      2,Bob,-3,99999,inactive,1.25\n\
+                      3,Carol,51,12345,active,2\n\
+                      4,Dan,22,54321,active,0.75\n";
+        let source = infer(&[("people.csv", sample)]);
+        assert!(source.contains("def people_id() = to_string(uint(1, 4));"));
+        assert!(source.contains("def people_full_name() = ascii_alphanumeric_chars(uint(3, 5));"));
+        assert!(source.contains("def people_age() = to_string(int(-3, +51));"));
+        assert!(source.contains("def people_zip() = digit_chars(uint(5, 5));"));
+        assert!(source.contains("def people_status() = select(\"active\", \"inactive\");"));
+        assert!(source.contains("def people_score() = to_string(decimal(0.5, 2.0));"));
+        assert!(source.ends_with("\npeople_record()\n"));
+
+        for row in run(&source, 20).lines() {
+            let fields = row.split(',').collect::<Vec<_>>();
+            assert_eq!(6, fields.len(), "row: {}", row);
+            let age = fields[2].parse::<i64>().unwrap();
+            assert!((-3..=51).contains(&age));
+            assert!(["active", "inactive"].contains(&fields[4]));
+        }
+        let file = run(&source.replace("\npeople_record()\n", "\npeople_file(2)\n"), 1);
+        assert!(file.starts_with("id,full name,age,zip,status,score\n"));
+        assert_eq!(3, file.lines().count());
+    }
+
+    #[test]
+    fn csv_dialect_and_quoted_values_are_detected() {
+        let sample = "name;note\r\n\"Smith; John\";\"say \"\"hi\"\"\"\r\nDoe;\r\n";
+        let source = infer(&[("notes.tsv.csv", sample)]);
+        assert!(source.contains("csv_row(\"rfc4180 delimiter=;\", notes_tsv_name(), notes_tsv_note())"));
+        assert!(source.contains("def notes_tsv_note() = select(\"\", string(uint(8, 8), 32, 126));"));
+        assert!(run(&source, 10).ends_with("\r\n"));
+    }
+
+    #[test]
+    fn json_records_generate_valid_json() {
+        let sample = r#"{"id": 1, "name": "a\"b", "tags": ["x", "y"], "geo": {"lat": 1.5, "lon": -2}, "ok": true, "n": null}
+{"id": 2, "name": "c", "tags": [], "geo": {"lat": 3, "lon": 4.25}, "ok": false, "n": 5}
+"#;
+        let source = infer(&[("events.jsonl", sample)]);
+        assert!(source.contains("def events_value_geo_lat() = to_string(decimal(1.5, 3.0));"));
+        assert!(source.contains("def events_value_n() = select(\"null\", to_string(uint(5, 5)));"));
+        for line in run(&source, 50).lines() {
+            match JsonValue::parse(line) {
+                Ok(JsonValue::Object(members)) => assert_eq!(6, members.len()),
+                other => panic!("invalid record: {:?}, line: {}", other, line),
+            }
+        }
+    }
+
+    #[test]
+    fn json_arrays_of_records_and_nested_arrays_are_supported() {
+        let sample = r#"[{"a": [[1, 2], []], "a_b": "x"}, {"a": [[3]], "a_b": "yy", "b": {"c": "z"}}]"#;
+        let source = infer(&[("nested.json", sample)]);
+        for line in run(&source, 50).lines() {
+            JsonValue::parse(line).unwrap_or_else(|e| panic!("invalid json: {}, line: {}", e, line));
+        }
+    }
+
+    #[test]
+    fn text_lines_with_the_same_number_of_words_become_columns() {
+        let sample = "GET /index.html 200 1234\nPOST /api 500 12\nGET /x 200 5\n";
+        let source = infer(&[("access.log", sample)]);
+        assert!(source.contains("def access_field_3() = to_string(uint(200, 500));"));
+        for line in run(&source, 20).lines() {
+            assert_eq!(4, line.split(' ').count(), "line: {}", line);
+        }
+
+        let source = infer(&[("words.txt", "one\ntwo words\n\"quoted\" \\ \u{1}\n")]);
+        assert!(source.contains("def words_line() = "));
+        run(&source, 10);
+    }
+
+    #[test]
+    fn names_are_valid_and_unique() {
+        let sample = "1st,a-b,a b,\"\",Ünïcode\n\"x\",\"\"\"\",\\,\u{e9}\u{65e5},\u{1f600}\n";
+        let source = infer(&[("2019 data.csv", sample)]);
+        assert!(source.contains("def sample_field_1st()"));
+        assert!(source.contains("def sample_a_b()"));
+        assert!(source.contains("def sample_a_b_2()"));
+        assert!(source.contains("def sample_field()"));
+        assert!(source.contains("def sample_n_code()"));
+        run(&source, 10);
+    }
+
+    #[test]
+    fn suffixed_names_do_not_collide_with_other_fields() {
+        let source = infer(&[("s.csv", "a-b,a b,a_b_2
+1,2,3
+")]);
+        assert_eq!(1, source.matches("def s_a_b_2()").count(), "source:\n{}", source);
+        assert!(source.contains("def s_a_b_2_2()"));
+        run(&source, 5);
+
+        let source = infer(&[("s.csv", "a_b_2,a-b,a b
+1,2,3
+")]);
+        assert_eq!(1, source.matches("def s_a_b_2()").count(), "source:\n{}", source);
+        assert!(source.contains("def s_a_b_3()"));
+        run(&source, 5);
+    }
+
+    #[test]
+    fn samples_must_have_the_same_format() {
+        let files = vec![
+            (PathBuf::from("a.csv"), String::from("a\n1\n")),
+            (PathBuf::from("b.jsonl"), String::from("{}\n")),
+        ];
+        let error = infer_module(&files).unwrap_err();
+        assert!(error.to_string().contains("All sample files must have the same format"));
+
+        let files = vec![
+            (PathBuf::from("a.csv"), String::from("a\n1\n")),
+            (PathBuf::from("b.csv"), String::from("b\n1\n")),
+        ];
+        let error = infer_module(&files).unwrap_err();
+        assert!(error.to_string().contains("'b.csv' has a different one"));
+    }
+}
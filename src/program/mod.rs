@@ -1,11 +1,14 @@
 mod help;
+mod infer;
 
 use failure::Error;
+use crate::builtins::EndOfInput;
 use crate::interpreter::{Interpreter, UnreadSource};
 use crate::writer::DataGenOutput;
 use crate::ProgramContext;
 
 pub use self::help::Help;
+pub use self::infer::Infer;
 
 pub trait DgenCommand: Sized {
     fn execute(self, out: &mut DataGenOutput) -> Result<(), Error>;
@@ -48,10 +51,32 @@ impl Runner {
             ..
         } = self;
 
+        if source == UnreadSource::Stdin {
+            runtime_context.set_stdin_is_source();
+        }
         let gen = interpreter.eval(source)?;
 
+        // programs that read from stdin or a pipe write each iteration to a buffer first, so that an iteration that
+        // runs out of input part way through does not leave partial output behind
+        let buffer_iterations = interpreter.reads_input();
+        let mut iteration_buffer = Vec::new();
+
         for _ in 0..iterations {
-            let result = gen.write_value(&mut runtime_context, output);
+            let result = if buffer_iterations {
+                iteration_buffer.clear();
+                let result = gen.write_value(&mut runtime_context, &mut DataGenOutput::new(&mut iteration_buffer));
+                if !is_end_of_input(&result) {
+                    output.write_bytes(&iteration_buffer)?;
+                }
+                result
+            } else {
+                gen.write_value(&mut runtime_context, output)
+            };
+            if is_end_of_input(&result) {
+                // all of the input has been read, which is a normal way for the program to end
+                runtime_context.reset_error();
+                break;
+            }
             if let Some(err) = result.as_ref().err() {
                 handle_error(&mut runtime_context, err);
             }
@@ -69,6 +94,14 @@ impl Runner {
     }
 }
 
+fn is_end_of_input(result: &Result<(), Error>) -> bool {
+    result
+        .as_ref()
+        .err()
+        .map(|err| err.downcast_ref::<EndOfInput>().is_some())
+        .unwrap_or(false)
+}
+
 fn handle_error(context: &mut ProgramContext, error: &Error) {
     use std::fmt::Write;
 